@@ -46,6 +46,12 @@ class AttestationCreate(AttestationBase):
     pass
 
 
+class QuoteVerificationRequest(BaseModel):
+    """Schema for verifying an enclave DCAP quote."""
+    quote: str  # Hex encoded quote
+    output: Optional[Dict[str, Any]] = None  # Enclave output the quote must bind
+
+
 # Response Schemas
 
 class ProposalResponse(ProposalBase):
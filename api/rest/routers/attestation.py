@@ -7,7 +7,7 @@ from fastapi import APIRouter, HTTPException, Depends, status
 from sqlalchemy.orm import Session
 
 from api.rest.dependencies import get_db, validate_api_key
-from api.models.schemas import AttestationCreate, AttestationResponse
+from api.models.schemas import AttestationCreate, AttestationResponse, QuoteVerificationRequest
 from api.models.crud import (
     create_attestation,
     get_attestation_by_id,
@@ -15,6 +15,7 @@ from api.models.crud import (
     list_attestations
 )
 from verification.tee import phala_stub
+from verification.attestation.dcap import DcapVerifier
 
 router = APIRouter(
     prefix="/attestation",
@@ -60,6 +61,30 @@ def generate_attestation(
     return db_attestation
 
 
+@router.post("/verify-quote")
+def verify_quote(
+    request: QuoteVerificationRequest,
+    _: str = Depends(validate_api_key)
+) -> Dict[str, Any]:
+    """
+    Verify an enclave DCAP quote offline.
+    
+    Args:
+        request: Hex quote and, optionally, the output it must be bound to
+        _: API key (validated by dependency)
+        
+    Returns:
+        Verdict from the quote verifier
+    """
+    try:
+        return DcapVerifier().verify(request.quote, request.output)
+    except RuntimeError as e:
+        raise HTTPException(
+            status_code=status.HTTP_503_SERVICE_UNAVAILABLE,
+            detail=str(e)
+        )
+
+
 @router.get("/{attestation_id}", response_model=AttestationResponse)
 def get_attestation(
     attestation_id: int,
@@ -9,10 +9,12 @@ import logging
 import base64
 import hashlib
 import requests
-from typing import Dict, Any, Callable
+from typing import Dict, Any, Callable, Optional
 import pickle
 import dill
 
+from verification.attestation.dcap import DcapVerifier
+
 logger = logging.getLogger(__name__)
 
 
@@ -21,14 +23,18 @@ class SGXSecureExecutionEnvironment:
     SGX-based Secure Execution Environment.
     """
     
-    def __init__(self, enclave_url=None):
+    def __init__(self, enclave_url=None, client_id=None):
         """
         Initialize the SGX-based Secure Execution Environment.
         
         Args:
             enclave_url: URL of the SGX enclave service
+            client_id: Name under which the enclave tracks this client's
+                request nonces
         """
         self.enclave_url = enclave_url or os.environ.get("SGX_ENCLAVE_URL", "http://localhost:7000")
+        self.client_id = client_id or os.environ.get("SGX_CLIENT_ID", "chaoscore")
+        self._last_nonce = 0
         
     def run(self, func, *args, **kwargs) -> Dict[str, Any]:
         """
@@ -88,6 +94,75 @@ class SGXSecureExecutionEnvironment:
             logger.error(f"Error in SGX execution: {e}")
             raise
     
+    def run_operation(
+        self,
+        operation: str,
+        payload: Any = None,
+        version: int = 1,
+        nonce: Optional[int] = None
+    ) -> Dict[str, Any]:
+        """
+        Run a registered enclave operation.
+        
+        Unlike `run`, nothing executable is shipped to the enclave: the
+        operation is looked up by name in the enclave's registry (see
+        `list_operations`) and the output names the exact operation version
+        and code measurement that produced it. The enclave signs the output
+        with its identity and refuses any nonce this client has used before.
+        
+        Args:
+            operation: Registered operation name, e.g. "optimize_gas_parameters"
+            payload: JSON-serializable operation payload
+            version: Enclave wire protocol version
+            nonce: Request nonce, greater than any this client sent before
+                and at most 2**20 above the last one; defaults to the next
+                nonce, resuming above the enclave's record if another
+                process spent nonces under this client id
+            
+        Returns:
+            The signed enclave output: the `output` with its `operation` and
+            `result`, plus the hashes, `signer` and `signature` over them
+        """
+        resync = nonce is None
+        if nonce is None:
+            nonce = self._last_nonce + 1
+        self._last_nonce = nonce
+        body = {
+            "version": version,
+            "nonce": {"client": self.client_id, "nonce": nonce},
+            "operation": {"type": operation},
+        }
+        if payload is not None:
+            body["operation"]["payload"] = payload
+        
+        try:
+            response = requests.post(f"{self.enclave_url}/execute", json=body)
+            
+            if resync and response.status_code == 409:
+                # Continue above the nonce the enclave last saw from this id
+                self._last_nonce = response.json()["error"]["last_nonce"] + 1
+                body["nonce"]["nonce"] = self._last_nonce
+                response = requests.post(f"{self.enclave_url}/execute", json=body)
+            
+            if response.status_code != 200:
+                logger.error(f"SGX operation {operation} failed: {response.text}")
+                raise Exception(f"SGX operation {operation} failed: {response.text}")
+            
+            return response.json()
+        except Exception as e:
+            logger.error(f"Error in SGX operation {operation}: {e}")
+            raise
+    
+    def list_operations(self) -> Dict[str, Any]:
+        """
+        List the operations registered in the enclave.
+        
+        Returns:
+            Catalog with each operation's name, version, schemas and measurement
+        """
+        signed = self.run_operation("list_operations")
+        return signed["output"]["result"]["Ok"]["value"]
+    
     def get_enclave_info(self) -> Dict[str, Any]:
         """
         Get information about the SGX enclave.
@@ -107,28 +182,54 @@ class SGXSecureExecutionEnvironment:
             logger.error(f"Error getting enclave info: {e}")
             raise
     
-    def verify_attestation(self, attestation) -> bool:
+    def get_quote(self) -> Dict[str, Any]:
+        """
+        Get a DCAP quote for the enclave.
+
+        The quoted report data commits to the enclave's signing identity,
+        so together with `verify_attestation` it shows that outputs signed
+        by that identity came from the attested enclave.
+
+        Returns:
+            Dictionary with the backend, signing identity, decoded report
+            and hex quote
+        """
+        try:
+            response = requests.get(f"{self.enclave_url}/attestation")
+
+            if response.status_code != 200:
+                logger.error(f"Failed to get enclave quote: {response.text}")
+                raise Exception(f"Failed to get enclave quote: {response.text}")
+
+            return response.json()
+        except Exception as e:
+            logger.error(f"Error getting enclave quote: {e}")
+            raise
+
+    def verify_attestation(self, attestation, signed_output: Optional[Dict[str, Any]] = None) -> bool:
         """
         Verify an attestation.
         
+        The attestation must carry a hex DCAP `quote`, as returned by
+        `get_quote`. It is verified offline by `DcapVerifier`, configured
+        through `SGX_ROOT_CA_PATH` and `SGX_COLLATERAL_PATH`.
+        
         Args:
             attestation: Attestation to verify
+            signed_output: Signed output from `run_operation`; the quote must
+                bind its signer and its signature must verify
             
         Returns:
             Whether the attestation is valid
         """
         try:
-            if not attestation or not isinstance(attestation, dict):
+            if not attestation or not isinstance(attestation, dict) or "quote" not in attestation:
                 return False
             
-            # In a real implementation, this would verify the attestation
-            # with the Intel Attestation Service (IAS)
-            # For the dev container, we just check if the attestation has the required fields
-            return (
-                "hash" in attestation and
-                "enclave_hash" in attestation and
-                "timestamp" in attestation
-            )
+            verdict = DcapVerifier().verify(attestation["quote"], signed_output=signed_output)
+            if not verdict.get("verified"):
+                logger.warning(f"Attestation rejected: {verdict.get('message')}")
+            return bool(verdict.get("verified"))
         except Exception as e:
             logger.error(f"Error verifying attestation: {e}")
-            return False 
\ No newline at end of file
+            return False 
@@ -0,0 +1,471 @@
+//! Simulated DCAP quote generation.
+//!
+//! On SGX hardware, quotes are produced by Intel's quoting enclave and
+//! certified by a PCK certificate chaining to Intel's SGX root CA. Machines
+//! without SGX use [`SimulatedQuoteGenerator`] instead: it plays the role of
+//! both the quoting enclave and Intel's PKI, producing structurally genuine
+//! version 3 quotes whose PCK chain ends at a local test root:
+//!
+//! ```text
+//! test root CA -> test platform CA -> PCK certificate -> attestation key
+//! ```
+//!
+//! The PCK certificate carries the SGX extension (OID 1.2.840.113741.1.13.1)
+//! with PPID, TCB, PCE-ID and FMSPC, exactly as Intel-issued certificates do,
+//! so verifiers exercise their full code path against simulated quotes. They
+//! only need to trust [`SimulatedQuoteGenerator::root_certificate_pem`] in
+//! place of Intel's root, which no production verifier does.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{DerSignature, Signature, SigningKey};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::der::asn1::UtcTime;
+use x509_cert::der::oid::{AssociatedOid, ObjectIdentifier};
+use x509_cert::der::{self, EncodePem, Length, Writer};
+use x509_cert::ext::{AsExtension, Extension};
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::time::{Time, Validity};
+use x509_cert::Certificate;
+
+use chaoschain_enclave::attestation::{
+    AttestationReport, Attributes, CertificationData, Quote, QuoteHeader, QuoteSignature,
+    ATTESTATION_KEY_TYPE_ECDSA_P256, CERT_DATA_PCK_CERT_CHAIN, INTEL_QE_VENDOR_ID, QUOTE_VERSION,
+    TEE_TYPE_SGX,
+};
+
+use crate::error::HostError;
+
+/// OID of the SGX extension in PCK certificates
+pub const SGX_EXTENSION_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1");
+
+/// Security version of the simulated quoting enclave
+pub const SIMULATED_QE_SVN: u16 = 8;
+/// Security version of the simulated provisioning certification enclave
+pub const SIMULATED_PCE_SVN: u16 = 13;
+/// FMSPC (platform family) reported by simulated PCK certificates
+pub const SIMULATED_FMSPC: [u8; 6] = [0x00, 0x90, 0x6e, 0xa1, 0x00, 0x00];
+/// PCE ID reported by simulated PCK certificates
+pub const SIMULATED_PCE_ID: [u8; 2] = [0x00, 0x00];
+
+/// Certificates are valid from 2020-01-01 to 2049-12-31 so that fixtures
+/// never expire; UTCTime cannot express later dates.
+const NOT_BEFORE: Duration = Duration::from_secs(1_577_836_800);
+const NOT_AFTER: Duration = Duration::from_secs(2_524_607_999);
+
+const QE_AUTH_DATA: [u8; 32] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+    26, 27, 28, 29, 30, 31,
+];
+
+/// Issues quotes for simulated enclave reports, certified by a test CA
+pub struct SimulatedQuoteGenerator {
+    root: Certificate,
+    platform: Certificate,
+    pck: Certificate,
+    pck_key: SigningKey,
+    attestation_key: SigningKey,
+    cpu_svn: [u8; 16],
+}
+
+impl SimulatedQuoteGenerator {
+    /// Create a generator with fresh random keys
+    pub fn new() -> Result<Self, HostError> {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self::from_seed(seed)
+    }
+
+    /// Create a generator whose keys and certificates are derived from `seed`
+    ///
+    /// The same seed always yields the same test root, which lets fixtures
+    /// pin it.
+    pub fn from_seed(seed: [u8; 32]) -> Result<Self, HostError> {
+        let root_key = derive_key(&seed, "root")?;
+        let platform_key = derive_key(&seed, "platform")?;
+        let pck_key = derive_key(&seed, "pck")?;
+        let attestation_key = derive_key(&seed, "attestation")?;
+
+        let mut ppid = [0u8; 16];
+        ppid.copy_from_slice(&derive(&seed, "ppid")[..16]);
+        let cpu_svn = [
+            0x0c, 0x0c, 0x03, 0x03, 0xff, 0xff, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+
+        let root_name = name("CN=ChaosChain Simulated SGX Root CA,O=ChaosChain,C=US")?;
+        let platform_name = name("CN=ChaosChain Simulated SGX Platform CA,O=ChaosChain,C=US")?;
+        let pck_name = name("CN=ChaosChain Simulated SGX PCK Certificate,O=ChaosChain,C=US")?;
+
+        let root = build(
+            CertificateBuilder::new(
+                Profile::Root,
+                SerialNumber::from(1u32),
+                validity()?,
+                root_name.clone(),
+                spki(&root_key)?,
+                &root_key,
+            ),
+            &[],
+        )?;
+        let platform = build(
+            CertificateBuilder::new(
+                Profile::SubCA {
+                    issuer: root_name,
+                    path_len_constraint: Some(0),
+                },
+                SerialNumber::from(2u32),
+                validity()?,
+                platform_name.clone(),
+                spki(&platform_key)?,
+                &root_key,
+            ),
+            &[],
+        )?;
+        let pck = build(
+            CertificateBuilder::new(
+                Profile::Leaf {
+                    issuer: platform_name,
+                    enable_key_agreement: false,
+                    enable_key_encipherment: false,
+                },
+                SerialNumber::from(3u32),
+                validity()?,
+                pck_name,
+                spki(&pck_key)?,
+                &platform_key,
+            ),
+            &[SgxExtension::new(ppid, cpu_svn, SIMULATED_PCE_SVN)],
+        )?;
+
+        Ok(Self {
+            root,
+            platform,
+            pck,
+            pck_key,
+            attestation_key,
+            cpu_svn,
+        })
+    }
+
+    /// PEM of the test root CA verifiers must trust to accept these quotes
+    pub fn root_certificate_pem(&self) -> Result<String, HostError> {
+        pem(&self.root)
+    }
+
+    /// PEM chain embedded in quotes: PCK, platform CA, then root CA
+    pub fn pck_certificate_chain_pem(&self) -> Result<String, HostError> {
+        Ok([pem(&self.pck)?, pem(&self.platform)?, pem(&self.root)?].concat())
+    }
+
+    /// Quote `report`, as the quoting enclave would for a report targeted at it
+    pub fn quote(&self, report: &AttestationReport) -> Result<Quote, HostError> {
+        let header = QuoteHeader {
+            version: QUOTE_VERSION,
+            attestation_key_type: ATTESTATION_KEY_TYPE_ECDSA_P256,
+            tee_type: TEE_TYPE_SGX,
+            qe_svn: SIMULATED_QE_SVN,
+            pce_svn: SIMULATED_PCE_SVN,
+            qe_vendor_id: INTEL_QE_VENDOR_ID,
+            user_data: [0; 20],
+        };
+
+        let mut attestation_key = [0u8; 64];
+        attestation_key.copy_from_slice(
+            &self
+                .attestation_key
+                .verifying_key()
+                .to_encoded_point(false)
+                .as_bytes()[1..],
+        );
+        let qe_report = AttestationReport {
+            cpu_svn: self.cpu_svn,
+            misc_select: 0,
+            attributes: Attributes {
+                flags: Attributes::FLAG_INITTED | Attributes::FLAG_MODE64BIT,
+                xfrm: 0x3,
+            },
+            mr_enclave: derive(b"quoting-enclave", "mrenclave"),
+            mr_signer: derive(b"quoting-enclave", "mrsigner"),
+            isv_prod_id: 1,
+            isv_svn: SIMULATED_QE_SVN,
+            report_data: Quote::expected_qe_report_data(&attestation_key, &QE_AUTH_DATA),
+        };
+
+        let mut quote = Quote {
+            header,
+            report: report.clone(),
+            signature: QuoteSignature {
+                report_signature: [0; 64],
+                attestation_key,
+                qe_report_signature: sign(&self.pck_key, &qe_report.to_bytes()),
+                qe_report,
+                qe_auth_data: QE_AUTH_DATA.to_vec(),
+                certification_data: CertificationData {
+                    cert_type: CERT_DATA_PCK_CERT_CHAIN,
+                    data: self.pck_certificate_chain_pem()?.into_bytes(),
+                },
+            },
+        };
+        quote.signature.report_signature = sign(&self.attestation_key, &quote.signed_bytes());
+        Ok(quote)
+    }
+}
+
+impl fmt::Debug for SimulatedQuoteGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimulatedQuoteGenerator")
+            .field(
+                "root_subject",
+                &self.root.tbs_certificate.subject.to_string(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+/// SGX extension of a PCK certificate, DER encoded as Intel specifies:
+///
+/// ```text
+/// SEQUENCE {
+///   SEQUENCE { .1 PPID, OCTET STRING (16) }
+///   SEQUENCE { .2 TCB, SEQUENCE {
+///     SEQUENCE { .2.1 .. .2.16 SGX TCB component SVN, INTEGER } x16
+///     SEQUENCE { .2.17 PCESVN, INTEGER }
+///     SEQUENCE { .2.18 CPUSVN, OCTET STRING (16) } } }
+///   SEQUENCE { .3 PCE-ID, OCTET STRING (2) }
+///   SEQUENCE { .4 FMSPC, OCTET STRING (6) }
+///   SEQUENCE { .5 SGX type, ENUMERATED }
+/// }
+/// ```
+struct SgxExtension(Vec<u8>);
+
+impl SgxExtension {
+    fn new(ppid: [u8; 16], cpu_svn: [u8; 16], pce_svn: u16) -> Self {
+        let field = |arc: &[u32], value: Vec<u8>| {
+            let mut content = oid(arc);
+            content.extend(value);
+            tlv(0x30, &content)
+        };
+
+        let mut tcb = Vec::new();
+        for (i, svn) in cpu_svn.iter().enumerate() {
+            tcb.extend(field(&[2, i as u32 + 1], integer(u64::from(*svn))));
+        }
+        tcb.extend(field(&[2, 17], integer(u64::from(pce_svn))));
+        tcb.extend(field(&[2, 18], tlv(0x04, &cpu_svn)));
+
+        let mut content = field(&[1], tlv(0x04, &ppid));
+        content.extend(field(&[2], tlv(0x30, &tcb)));
+        content.extend(field(&[3], tlv(0x04, &SIMULATED_PCE_ID)));
+        content.extend(field(&[4], tlv(0x04, &SIMULATED_FMSPC)));
+        content.extend(field(&[5], tlv(0x0a, &[0])));
+        Self(tlv(0x30, &content))
+    }
+}
+
+impl AssociatedOid for SgxExtension {
+    const OID: ObjectIdentifier = SGX_EXTENSION_OID;
+}
+
+impl der::Encode for SgxExtension {
+    fn encoded_len(&self) -> der::Result<Length> {
+        Length::try_from(self.0.len())
+    }
+
+    fn encode(&self, writer: &mut impl Writer) -> der::Result<()> {
+        writer.write(&self.0)
+    }
+}
+
+impl AsExtension for SgxExtension {
+    fn critical(&self, _subject: &Name, _extensions: &[Extension]) -> bool {
+        false
+    }
+}
+
+/// OID `1.2.840.113741.1.13.1` followed by `arc`, as a DER TLV
+fn oid(arc: &[u32]) -> Vec<u8> {
+    let mut content = SGX_EXTENSION_OID.as_bytes().to_vec();
+    for &component in arc {
+        // Every SGX extension arc is below 128, so fits one base-128 digit
+        content.push(component as u8);
+    }
+    tlv(0x06, &content)
+}
+
+fn integer(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(7);
+    let mut content = Vec::new();
+    if bytes[start] & 0x80 != 0 {
+        content.push(0);
+    }
+    content.extend_from_slice(&bytes[start..]);
+    tlv(0x02, &content)
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let start = bytes
+            .iter()
+            .position(|&b| b != 0)
+            .unwrap_or(bytes.len() - 1);
+        out.push(0x80 | (bytes.len() - start) as u8);
+        out.extend_from_slice(&bytes[start..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn derive(seed: &[u8], label: &str) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(b"chaoschain/simulated-dcap/")
+        .chain_update(label.as_bytes())
+        .chain_update(seed)
+        .finalize()
+        .into()
+}
+
+fn derive_key(seed: &[u8; 32], label: &str) -> Result<SigningKey, HostError> {
+    SigningKey::from_slice(&derive(seed, label)).map_err(attestation_error)
+}
+
+fn sign(key: &SigningKey, message: &[u8]) -> [u8; 64] {
+    let signature: Signature = key.sign(message);
+    signature.to_bytes().into()
+}
+
+fn name(name: &str) -> Result<Name, HostError> {
+    Name::from_str(name).map_err(attestation_error)
+}
+
+fn validity() -> Result<Validity, HostError> {
+    let time = |duration| {
+        UtcTime::from_unix_duration(duration)
+            .map(Time::UtcTime)
+            .map_err(attestation_error)
+    };
+    Ok(Validity {
+        not_before: time(NOT_BEFORE)?,
+        not_after: time(NOT_AFTER)?,
+    })
+}
+
+fn spki(key: &SigningKey) -> Result<SubjectPublicKeyInfoOwned, HostError> {
+    SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).map_err(attestation_error)
+}
+
+fn build(
+    builder: Result<CertificateBuilder<'_, SigningKey>, x509_cert::builder::Error>,
+    extensions: &[SgxExtension],
+) -> Result<Certificate, HostError> {
+    let mut builder = builder.map_err(attestation_error)?;
+    for extension in extensions {
+        builder
+            .add_extension(extension)
+            .map_err(attestation_error)?;
+    }
+    builder.build::<DerSignature>().map_err(attestation_error)
+}
+
+fn pem(certificate: &Certificate) -> Result<String, HostError> {
+    certificate
+        .to_pem(der::pem::LineEnding::LF)
+        .map_err(attestation_error)
+}
+
+fn attestation_error(error: impl fmt::Display) -> HostError {
+    HostError::Attestation {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chaoschain_enclave::attestation::{report_data_for_output, simulated_report};
+    use chaoschain_enclave::{process_operation, AddPayload, EnclaveInput, Operation};
+    use p256::ecdsa::signature::Verifier;
+    use p256::ecdsa::VerifyingKey;
+
+    fn verifying_key(raw: &[u8; 64]) -> VerifyingKey {
+        let mut sec1 = vec![0x04];
+        sec1.extend_from_slice(raw);
+        VerifyingKey::from_sec1_bytes(&sec1).unwrap()
+    }
+
+    #[test]
+    fn test_quote_signatures_verify() {
+        let generator = SimulatedQuoteGenerator::from_seed([7; 32]).unwrap();
+        let output = process_operation(EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 2.into(),
+        })));
+        let quote = generator
+            .quote(&simulated_report(report_data_for_output(&output)))
+            .unwrap();
+        let quote = Quote::from_bytes(&quote.to_bytes()).unwrap();
+        let signature = &quote.signature;
+
+        assert!(quote.report.binds_output(&output));
+        let attestation_key = verifying_key(&signature.attestation_key);
+        let report_signature = Signature::from_slice(&signature.report_signature).unwrap();
+        assert!(attestation_key
+            .verify(&quote.signed_bytes(), &report_signature)
+            .is_ok());
+
+        let qe_report_signature = Signature::from_slice(&signature.qe_report_signature).unwrap();
+        assert!(generator
+            .pck_key
+            .verifying_key()
+            .verify(&signature.qe_report.to_bytes(), &qe_report_signature)
+            .is_ok());
+        assert_eq!(
+            signature.qe_report.report_data,
+            Quote::expected_qe_report_data(&signature.attestation_key, &signature.qe_auth_data)
+        );
+    }
+
+    #[test]
+    fn test_certificate_chain() {
+        let generator = SimulatedQuoteGenerator::from_seed([7; 32]).unwrap();
+        let chain = generator.pck_certificate_chain_pem().unwrap();
+
+        assert_eq!(chain.matches("-----BEGIN CERTIFICATE-----").count(), 3);
+        assert!(chain.ends_with(&generator.root_certificate_pem().unwrap()));
+        assert!(generator
+            .pck
+            .tbs_certificate
+            .extensions
+            .iter()
+            .flatten()
+            .any(|extension| extension.extn_id == SGX_EXTENSION_OID));
+        assert_eq!(
+            generator.root_certificate_pem().unwrap(),
+            SimulatedQuoteGenerator::from_seed([7; 32])
+                .unwrap()
+                .root_certificate_pem()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_der_lengths() {
+        assert_eq!(integer(0), vec![0x02, 0x01, 0x00]);
+        assert_eq!(integer(0xff), vec![0x02, 0x02, 0x00, 0xff]);
+        assert_eq!(&tlv(0x04, &[0; 200])[..3], &[0x04, 0x81, 200]);
+    }
+}
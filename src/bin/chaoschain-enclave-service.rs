@@ -0,0 +1,91 @@
+//! ChaosChain enclave service
+//!
+//! Serves the enclave's HTTP contract (see `chaoschain_governance_os::service`)
+//! on top of the default enclave backend for this build. Listens on
+//! `ENCLAVE_SERVICE_ADDR` (default `0.0.0.0:7000`, the port
+//! `SGX_ENCLAVE_URL` points at) with `ENCLAVE_SERVICE_THREADS` workers.
+//! A panic while handling a request is logged and drops that request's
+//! connection; the worker goes on serving.
+
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+
+use tiny_http::{Header, Response, Server};
+
+use chaoschain_governance_os::service::{EnclaveService, MAX_REQUEST_BYTES};
+use chaoschain_governance_os::{default_client, ServiceResponse};
+
+const DEFAULT_ADDR: &str = "0.0.0.0:7000";
+const DEFAULT_THREADS: usize = 4;
+
+fn main() {
+    let addr = std::env::var("ENCLAVE_SERVICE_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string());
+    let threads = std::env::var("ENCLAVE_SERVICE_THREADS")
+        .ok()
+        .and_then(|threads| threads.parse().ok())
+        .filter(|&threads| threads > 0)
+        .unwrap_or(DEFAULT_THREADS);
+
+    let client = default_client().unwrap_or_else(|error| {
+        eprintln!("failed to open enclave: {}", error);
+        std::process::exit(1);
+    });
+    let service = Arc::new(EnclaveService::new(client));
+    let server = Arc::new(Server::http(&addr).unwrap_or_else(|error| {
+        eprintln!("failed to listen on {}: {}", addr, error);
+        std::process::exit(1);
+    }));
+    eprintln!(
+        "enclave service listening on {} ({:?} backend, {} workers)",
+        addr,
+        service.backend(),
+        threads
+    );
+
+    let workers: Vec<_> = (0..threads)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let service = Arc::clone(&service);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let url = request.url().to_string();
+                    let handled =
+                        panic::catch_unwind(AssertUnwindSafe(|| handle(&service, request)));
+                    if handled.is_err() {
+                        eprintln!("panic while handling {}", url);
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+fn handle(service: &EnclaveService, mut request: tiny_http::Request) {
+    let mut body = Vec::new();
+    let read = request
+        .as_reader()
+        .take(MAX_REQUEST_BYTES as u64 + 1)
+        .read_to_end(&mut body);
+
+    let response = match read {
+        Ok(_) => service.handle(request.method().as_str(), request.url(), &body),
+        Err(error) => ServiceResponse {
+            status: 400,
+            body: serde_json::json!({ "error": error.to_string() }),
+        },
+    };
+
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("static header is valid");
+    let response = Response::from_data(response.body.to_string())
+        .with_status_code(response.status)
+        .with_header(content_type);
+    if let Err(error) = request.respond(response) {
+        eprintln!("failed to send response: {}", error);
+    }
+}
@@ -0,0 +1,78 @@
+//! Backend-independent enclave client interface.
+
+use serde::{Deserialize, Serialize};
+
+use chaoschain_enclave::attestation::Quote;
+use chaoschain_enclave::{
+    EnclaveError, EnclaveIdentity, EnclaveInput, EnclaveOutput, SignedEnclaveOutput,
+};
+
+use crate::error::HostError;
+
+/// Kind of backend executing enclave operations
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// Enclave code linked into the host process, without hardware isolation
+    Simulation,
+    /// Enclave running inside an Intel SGX enclave
+    Sgx,
+}
+
+/// Handle to an enclave that can process operations
+///
+/// Each client owns one running enclave state (see
+/// [`chaoschain_enclave::EnclaveState`]): its signing identity, Ethereum key
+/// and the nonces its clients have spent.
+pub trait EnclaveClient: Send + Sync {
+    /// Execute one operation in the enclave without its state
+    ///
+    /// The output is unsigned, and operations that act with the enclave's
+    /// keys fail. Failures inside the enclave, and failures reaching it, are
+    /// reported in the returned [`EnclaveOutput`].
+    fn process(&self, input: EnclaveInput) -> EnclaveOutput;
+
+    /// Execute one operation with the enclave's state and sign the output
+    ///
+    /// `input` must carry a fresh client nonce. Operation failures are part
+    /// of the signed output; an error here means nothing was signed, e.g.
+    /// because the nonce was replayed or the enclave could not be reached.
+    fn process_signed(&self, input: EnclaveInput) -> Result<SignedEnclaveOutput, EnclaveError>;
+
+    /// Identity that signs this enclave's outputs
+    fn identity(&self) -> EnclaveIdentity;
+
+    /// Which backend this client uses
+    fn backend(&self) -> BackendKind;
+
+    /// Quote a report binding the enclave's signing identity
+    ///
+    /// The report data is [`report_data_for_identity`] of
+    /// [`EnclaveClient::identity`], so the quote vouches for every output
+    /// signed by that identity. Callers cannot choose the report data.
+    ///
+    /// [`report_data_for_identity`]: chaoschain_enclave::attestation::report_data_for_identity
+    fn attest(&self) -> Result<Quote, HostError>;
+}
+
+impl<T: EnclaveClient + ?Sized> EnclaveClient for Box<T> {
+    fn process(&self, input: EnclaveInput) -> EnclaveOutput {
+        (**self).process(input)
+    }
+
+    fn process_signed(&self, input: EnclaveInput) -> Result<SignedEnclaveOutput, EnclaveError> {
+        (**self).process_signed(input)
+    }
+
+    fn identity(&self) -> EnclaveIdentity {
+        (**self).identity()
+    }
+
+    fn backend(&self) -> BackendKind {
+        (**self).backend()
+    }
+
+    fn attest(&self) -> Result<Quote, HostError> {
+        (**self).attest()
+    }
+}
@@ -0,0 +1,38 @@
+//! Errors raised by the host runtime.
+
+use std::fmt;
+
+/// Failure setting up or talking to an enclave backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostError {
+    /// The enclave image could not be loaded
+    EnclaveLoad { path: String, reason: String },
+    /// An ECALL into the enclave failed before it produced an output
+    Ecall { reason: String },
+    /// Bytes crossing the enclave boundary could not be (de)serialized
+    Serialization { reason: String },
+    /// Attestation evidence could not be produced
+    Attestation { reason: String },
+    /// Sealed enclave state could not be restored or stored
+    State { path: String, reason: String },
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostError::EnclaveLoad { path, reason } => {
+                write!(f, "failed to load enclave {}: {}", path, reason)
+            }
+            HostError::Ecall { reason } => write!(f, "ecall failed: {}", reason),
+            HostError::Serialization { reason } => {
+                write!(f, "enclave boundary serialization failed: {}", reason)
+            }
+            HostError::Attestation { reason } => write!(f, "attestation failed: {}", reason),
+            HostError::State { path, reason } => {
+                write!(f, "enclave state {}: {}", path, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HostError {}
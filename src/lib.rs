@@ -0,0 +1,124 @@
+//! ChaosChain Governance OS host runtime
+//!
+//! Untrusted host side of the ChaosChain enclave. Callers program against
+//! [`EnclaveClient`], which turns an [`EnclaveInput`] into an
+//! [`EnclaveOutput`], or into an output signed by the enclave's running
+//! state, and pick a backend at build time:
+//!
+//! - [`SimulationClient`] links `chaoschain-enclave` into the host process and
+//!   runs operations directly. It is always available and is what tests and
+//!   machines without SGX hardware use.
+//! - `SgxClient`, behind the `sgx` feature, loads a signed enclave image and
+//!   forwards every input across the ECALLs declared in `Enclave.edl`.
+//!
+//! Both backends can quote an enclave report binding the enclave's signing
+//! identity through [`EnclaveClient::attest`]; the simulation backend's quotes are certified
+//! by a local test CA, see [`attestation`].
+//!
+//! [`default_client`] returns whichever backend this build was compiled for,
+//! keeping its sealed state in a [`StateStore`], and [`service`] exposes it
+//! over HTTP through the `chaoschain-enclave-service` binary.
+
+pub mod attestation;
+pub mod client;
+pub mod error;
+pub mod service;
+#[cfg(feature = "sgx")]
+pub mod sgx;
+pub mod simulation;
+pub mod store;
+
+pub use chaoschain_enclave as enclave;
+pub use chaoschain_enclave::{EnclaveConfig, EnclaveError, EnclaveInput, EnclaveOutput};
+
+pub use attestation::SimulatedQuoteGenerator;
+pub use client::{BackendKind, EnclaveClient};
+pub use error::HostError;
+pub use service::{EnclaveService, ServiceResponse};
+#[cfg(feature = "sgx")]
+pub use sgx::SgxClient;
+pub use simulation::SimulationClient;
+pub use store::StateStore;
+
+/// Environment variable overriding the enclave image loaded by the SGX backend
+pub const ENCLAVE_PATH_ENV: &str = "CHAOSCHAIN_ENCLAVE_PATH";
+
+/// Enclave image loaded by the SGX backend when no override is set
+pub const DEFAULT_ENCLAVE_PATH: &str = "enclave.signed.so";
+
+/// Environment variable overriding the directory of the enclave's sealed state
+pub const STATE_DIR_ENV: &str = "CHAOSCHAIN_STATE_DIR";
+
+/// Directory of the enclave's sealed state when no override is set
+pub const DEFAULT_STATE_DIR: &str = "enclave-state";
+
+/// Environment variable naming the JSON [`EnclaveConfig`] a fresh enclave
+/// state is created with
+pub const ENCLAVE_CONFIG_ENV: &str = "CHAOSCHAIN_ENCLAVE_CONFIG";
+
+/// Open the backend this build targets
+///
+/// With the `sgx` feature the enclave image named by `CHAOSCHAIN_ENCLAVE_PATH`
+/// (or [`DEFAULT_ENCLAVE_PATH`]) is loaded; otherwise the in-process
+/// simulation backend is returned. Either way the enclave state is restored
+/// from, and saved to, the [`StateStore`] named by `CHAOSCHAIN_STATE_DIR` (or
+/// [`DEFAULT_STATE_DIR`]). If the store is empty, the new state takes its
+/// config from the file named by `CHAOSCHAIN_ENCLAVE_CONFIG`, or the default
+/// config if that is unset.
+pub fn default_client() -> Result<Box<dyn EnclaveClient>, HostError> {
+    let store = StateStore::open(
+        std::env::var(STATE_DIR_ENV).unwrap_or_else(|_| DEFAULT_STATE_DIR.to_string()),
+    )?;
+    let config = match std::env::var(ENCLAVE_CONFIG_ENV) {
+        Ok(path) => read_config(&path)?,
+        Err(_) => EnclaveConfig::default(),
+    };
+
+    #[cfg(feature = "sgx")]
+    {
+        let path =
+            std::env::var(ENCLAVE_PATH_ENV).unwrap_or_else(|_| DEFAULT_ENCLAVE_PATH.to_string());
+        Ok(Box::new(SgxClient::load(
+            path,
+            cfg!(debug_assertions),
+            store,
+            &config,
+        )?))
+    }
+
+    #[cfg(not(feature = "sgx"))]
+    {
+        Ok(Box::new(SimulationClient::open(store, config)?))
+    }
+}
+
+fn read_config(path: &str) -> Result<EnclaveConfig, HostError> {
+    let config_error = |reason: String| HostError::State {
+        path: path.to_string(),
+        reason,
+    };
+    let bytes = std::fs::read(path).map_err(|e| config_error(e.to_string()))?;
+    EnclaveConfig::from_json(&bytes).map_err(|e| config_error(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chaoschain_enclave::{AddPayload, Operation, OperationResult};
+
+    #[test]
+    fn test_default_client_processes_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var(STATE_DIR_ENV, dir.path());
+        let client = default_client().unwrap();
+        let output = client.process(EnclaveInput::new(Operation::Add(AddPayload {
+            a: 2.into(),
+            b: 3.into(),
+        })));
+
+        match output.result {
+            Ok(OperationResult::Add(result)) => assert_eq!(result.sum, 5.into()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! HTTP contract served by the enclave service binary.
+//!
+//! `chaoscore/core/secure_execution_sgx.py` talks to an enclave process at
+//! `SGX_ENCLAVE_URL`. This module implements that contract independently of
+//! any HTTP server library, so it can be exercised in tests without opening
+//! sockets:
+//!
+//! | Method | Path           | Body                                             |
+//! |--------|----------------|--------------------------------------------------|
+//! | POST   | `/execute`     | JSON [`EnclaveInput`] -> [`SignedEnclaveOutput`] |
+//! | GET    | `/info`        | backend, protocol version, enclave identity      |
+//! | GET    | `/attestation` | DCAP quote binding the enclave identity          |
+//! | GET    | `/health`      | liveness probe                                   |
+//!
+//! `/execute` runs the input through the enclave's running state: the input
+//! must carry a fresh client nonce, and the output is signed by the enclave
+//! identity. Operation failures are part of the signed output and are
+//! returned with status 200. Requests that get nothing signed return an
+//! `{"error": EnclaveError}` body: 400 for inputs that cannot be decoded,
+//! name an unknown operation or carry no nonce, 409 for a replayed nonce,
+//! 503 when the enclave is out of resources and 500 otherwise.
+//!
+//! `/attestation` quotes a report whose data the enclave derives from its own
+//! signing identity (see [`report_data_for_identity`]); callers cannot
+//! choose it. A caller proves that an output came from the attested enclave
+//! by verifying the quote, checking that it binds the output's signer, and
+//! checking the output's signature.
+//!
+//! [`report_data_for_identity`]: chaoschain_enclave::attestation::report_data_for_identity
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use chaoschain_enclave::attestation::{AttestationReport, Quote};
+use chaoschain_enclave::{
+    EnclaveError, EnclaveIdentity, EnclaveInput, Operation, PROTOCOL_VERSION,
+};
+
+use crate::client::{BackendKind, EnclaveClient};
+
+/// Largest request body accepted by `/execute`
+pub const MAX_REQUEST_BYTES: usize = 16 * 1024 * 1024;
+
+/// Status code and JSON body of a service response
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ServiceResponse {
+    fn json<T: Serialize>(status: u16, body: &T) -> Self {
+        let body = serde_json::to_value(body).unwrap_or_else(|e| json!({ "error": e.to_string() }));
+        Self { status, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+
+    fn enclave_error(error: EnclaveError) -> Self {
+        let status = match &error {
+            EnclaveError::UnsupportedOperation { .. }
+            | EnclaveError::InvalidPayload { .. }
+            | EnclaveError::VersionMismatch { .. } => 400,
+            EnclaveError::ReplayDetected { .. } => 409,
+            EnclaveError::ResourceExhausted { .. } => 503,
+            _ => 500,
+        };
+        Self::json(status, &json!({ "error": error }))
+    }
+}
+
+/// Description returned by `/info`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServiceInfo {
+    pub service: &'static str,
+    pub version: &'static str,
+    pub protocol_version: u32,
+    pub backend: BackendKind,
+    /// Identity that signs every `/execute` output
+    pub identity: EnclaveIdentity,
+    pub operations: &'static [&'static str],
+}
+
+/// Evidence returned by `/attestation`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AttestationResponse {
+    pub backend: BackendKind,
+    /// Identity the report data commits to
+    pub identity: EnclaveIdentity,
+    /// Decoded report body, for convenience; `quote` is authoritative
+    pub report: AttestationReport,
+    /// Hex encoded DCAP quote
+    pub quote: Quote,
+}
+
+impl AttestationResponse {
+    fn new(backend: BackendKind, identity: EnclaveIdentity, quote: Quote) -> Self {
+        Self {
+            backend,
+            identity,
+            report: quote.report.clone(),
+            quote,
+        }
+    }
+}
+
+/// Routes HTTP requests to an enclave client
+pub struct EnclaveService {
+    client: Box<dyn EnclaveClient>,
+}
+
+impl EnclaveService {
+    /// Serve requests with `client`
+    pub fn new(client: Box<dyn EnclaveClient>) -> Self {
+        Self { client }
+    }
+
+    /// Backend answering requests
+    pub fn backend(&self) -> BackendKind {
+        self.client.backend()
+    }
+
+    /// Handle one request; `path` may carry a query string
+    pub fn handle(&self, method: &str, path: &str, body: &[u8]) -> ServiceResponse {
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        match (method, path) {
+            ("POST", "/execute") => self.execute(body),
+            ("GET", "/info") => ServiceResponse::json(200, &self.info()),
+            ("GET", "/attestation") => self.attestation(),
+            ("GET", "/health") => ServiceResponse::json(
+                200,
+                &json!({ "status": "ok", "backend": self.client.backend() }),
+            ),
+            (_, "/execute" | "/info" | "/attestation" | "/health") => {
+                ServiceResponse::error(405, format!("method {} not allowed on {}", method, path))
+            }
+            _ => ServiceResponse::error(404, format!("no route for {}", path)),
+        }
+    }
+
+    /// Service description for `/info`
+    pub fn info(&self) -> ServiceInfo {
+        ServiceInfo {
+            service: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            protocol_version: PROTOCOL_VERSION,
+            backend: self.client.backend(),
+            identity: self.client.identity(),
+            operations: Operation::NAMES,
+        }
+    }
+
+    fn execute(&self, body: &[u8]) -> ServiceResponse {
+        if body.len() > MAX_REQUEST_BYTES {
+            return ServiceResponse::error(413, "request body too large");
+        }
+        match EnclaveInput::from_json(body).and_then(|input| self.client.process_signed(input)) {
+            Ok(signed) => ServiceResponse::json(200, &signed),
+            Err(error) => ServiceResponse::enclave_error(error),
+        }
+    }
+
+    fn attestation(&self) -> ServiceResponse {
+        match self.client.attest() {
+            Ok(quote) => ServiceResponse::json(
+                200,
+                &AttestationResponse::new(self.backend(), self.client.identity(), quote),
+            ),
+            Err(error) => {
+                let error = EnclaveError::AttestationFailure {
+                    reason: error.to_string(),
+                };
+                ServiceResponse::json(503, &json!({ "error": error }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimulationClient;
+    use chaoschain_enclave::agency::receipts::RecordActionPayload;
+    use chaoschain_enclave::agency::ActionType;
+    use chaoschain_enclave::endpoint::EndpointCallSpec;
+    use chaoschain_enclave::ethereum::transaction::{
+        Envelope, Fees, SignTransactionPayload, TransactionParams,
+    };
+    use chaoschain_enclave::ethereum::Address;
+    use chaoschain_enclave::{EnclaveConfig, OperationResult, SignedEnclaveOutput};
+
+    fn service() -> EnclaveService {
+        EnclaveService::new(Box::new(SimulationClient::new()))
+    }
+
+    const ADD: &[u8] = br#"{"version": 1, "nonce": {"client": "dao-a", "nonce": 1},
+        "operation": {"type": "add", "payload": {"a": 2, "b": 3}}}"#;
+
+    #[test]
+    fn test_execute_round_trip() {
+        let service = service();
+        let response = service.handle("POST", "/execute", ADD);
+
+        assert_eq!(response.status, 200);
+        let signed: SignedEnclaveOutput = serde_json::from_value(response.body).unwrap();
+        assert!(signed.verify().is_ok());
+        assert_eq!(signed.signer, service.info().identity);
+        assert_eq!(
+            serde_json::to_value(signed.output.result.unwrap()).unwrap(),
+            json!({"type": "add", "value": {"sum": "5"}})
+        );
+    }
+
+    #[test]
+    fn test_execute_refuses_replayed_nonce() {
+        let service = service();
+        assert_eq!(service.handle("POST", "/execute", ADD).status, 200);
+
+        let replayed = service.handle("POST", "/execute", ADD);
+        assert_eq!(replayed.status, 409);
+        assert_eq!(replayed.body["error"]["code"], 3003);
+    }
+
+    #[test]
+    fn test_execute_rejects_undecodable_input() {
+        let service = service();
+        let response = service.handle("POST", "/execute", b"not json");
+        assert_eq!(response.status, 400);
+        assert_eq!(response.body["error"]["code"], 1003);
+
+        let unsigned =
+            br#"{"version": 1, "operation": {"type": "add", "payload": {"a": 2, "b": 3}}}"#;
+        assert_eq!(service.handle("POST", "/execute", unsigned).status, 400);
+
+        let unknown = br#"{"version": 1, "nonce": {"client": "dao-a", "nonce": 1},
+            "operation": {"type": "multiply", "payload": {"a": 2, "b": 3}}}"#;
+        let response = service.handle("POST", "/execute", unknown);
+        assert_eq!(response.status, 400);
+        assert_eq!(response.body["error"]["code"], 1001);
+    }
+
+    #[test]
+    fn test_execute_signs_ethereum_transactions() {
+        let endpoint = Address([0x35; 20]);
+        let service = EnclaveService::new(Box::new(SimulationClient::with_config(EnclaveConfig {
+            endpoint: Some(endpoint),
+            ..EnclaveConfig::default()
+        })));
+        let execute = |input: EnclaveInput| -> SignedEnclaveOutput {
+            let response = service.handle("POST", "/execute", &serde_json::to_vec(&input).unwrap());
+            assert_eq!(response.status, 200);
+            serde_json::from_value(response.body).unwrap()
+        };
+
+        let account = execute(EnclaveInput::new(Operation::EthereumAddress).with_nonce("dao-a", 1));
+        let Ok(OperationResult::EthereumAddress(account)) = account.output.result else {
+            panic!("unexpected output {:?}", account.output);
+        };
+
+        let action = execute(
+            EnclaveInput::new(Operation::RecordAction(RecordActionPayload {
+                agent_id: "agent-1".to_string(),
+                action_id: "a1".to_string(),
+                action_type: ActionType::Execute,
+                input_hash: [1; 32],
+                output_hash: [2; 32],
+                parent: None,
+            }))
+            .with_nonce("dao-a", 2),
+        );
+        let payload = SignTransactionPayload {
+            transaction: TransactionParams {
+                nonce: 0,
+                gas_limit: 200_000,
+                fees: Fees::Legacy { gas_price: 1 },
+            },
+            call: EndpointCallSpec::AnchorAction {
+                output: action,
+                metadata_uri: "ipfs://action".to_string(),
+            },
+        };
+        let signed =
+            execute(EnclaveInput::new(Operation::SignTransaction(payload)).with_nonce("dao-a", 3));
+        let Ok(OperationResult::SignTransaction(transaction)) = signed.output.result else {
+            panic!("unexpected output {:?}", signed.output);
+        };
+        assert_eq!(transaction.from, account.address);
+        assert_eq!(transaction.to, endpoint);
+        let envelope = Envelope::decode(&transaction.raw).unwrap();
+        assert_eq!(envelope.sender().unwrap(), account.address);
+    }
+
+    #[test]
+    fn test_info_and_health() {
+        let service = service();
+
+        let info = service.handle("GET", "/info", b"");
+        assert_eq!(info.status, 200);
+        assert_eq!(info.body["backend"], "simulation");
+        assert_eq!(info.body["protocol_version"], PROTOCOL_VERSION);
+
+        let health = service.handle("GET", "/health?verbose=1", b"");
+        assert_eq!(health.body["status"], "ok");
+    }
+
+    #[test]
+    fn test_attestation_binds_identity() {
+        let service = service();
+        let signed: SignedEnclaveOutput =
+            serde_json::from_value(service.handle("POST", "/execute", ADD).body).unwrap();
+
+        let response = service.handle("GET", "/attestation?output_hash=00", b"");
+        assert_eq!(response.status, 200);
+        let quote: Quote = serde_json::from_value(response.body["quote"].clone()).unwrap();
+        assert!(quote.report.binds_identity(&signed.signer));
+        assert!(!quote.report.binds_output(&signed.output));
+        assert_eq!(response.body["report"]["attributes"]["flags"], 7);
+    }
+
+    #[test]
+    fn test_unknown_routes() {
+        let service = service();
+        assert_eq!(service.handle("GET", "/execute", b"").status, 405);
+        assert_eq!(service.handle("GET", "/nope", b"").status, 404);
+    }
+}
@@ -0,0 +1,402 @@
+//! Intel SGX backend.
+//!
+//! Inputs cross into the enclave as JSON through the ECALLs declared in
+//! `verification/tee/enclave/Enclave.edl`, and the JSON output is copied
+//! back into a host buffer. If that buffer is too small the enclave reports
+//! the size it needs and keeps the output, which is then collected with
+//! `ecall_take_output` into a buffer of that size. Calls are serialized so
+//! that the kept output is always the caller's own.
+//!
+//! Loading the enclave restores its state from a [`StateStore`], or creates
+//! it, through `ecall_init`. Signed requests go through
+//! `ecall_process_signed`, after which the state is sealed with `ecall_seal`
+//! and saved to the store, and the store's counter advanced, before the
+//! output is returned.
+//!
+//! Quotes come from Intel's DCAP quote library (`libsgx_dcap_ql`): the host
+//! asks it for the quoting enclave's target info, has the enclave create a
+//! report for that target with `ecall_create_report`, and asks the library to
+//! quote the report. The platform needs a configured quote provider library
+//! so that quotes carry their PCK certificate chain.
+
+use std::sync::Mutex;
+
+use sgx_types::*;
+use sgx_urts::SgxEnclave;
+
+use chaoschain_enclave::attestation::Quote;
+use chaoschain_enclave::state::MonotonicCounter;
+use chaoschain_enclave::{
+    EnclaveConfig, EnclaveError, EnclaveIdentity, EnclaveInput, EnclaveOutput, SealedBlob,
+    SignedEnclaveOutput,
+};
+
+use crate::client::{BackendKind, EnclaveClient};
+use crate::error::HostError;
+use crate::store::StateStore;
+
+/// Output buffer size tried before asking the enclave for the exact size
+const INITIAL_OUTPUT_CAPACITY: usize = 64 * 1024;
+
+extern "C" {
+    fn ecall_process(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        input: *const u8,
+        input_len: usize,
+        output: *mut u8,
+        output_capacity: usize,
+        output_len: *mut usize,
+    ) -> sgx_status_t;
+
+    fn ecall_init(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        sealed: *const u8,
+        sealed_len: usize,
+        counter: u64,
+        config: *const u8,
+        config_len: usize,
+        output: *mut u8,
+        output_capacity: usize,
+        output_len: *mut usize,
+    ) -> sgx_status_t;
+
+    fn ecall_process_signed(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        input: *const u8,
+        input_len: usize,
+        output: *mut u8,
+        output_capacity: usize,
+        output_len: *mut usize,
+    ) -> sgx_status_t;
+
+    fn ecall_seal(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        counter: u64,
+        output: *mut u8,
+        output_capacity: usize,
+        output_len: *mut usize,
+    ) -> sgx_status_t;
+
+    fn ecall_take_output(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        output: *mut u8,
+        output_capacity: usize,
+        output_len: *mut usize,
+    ) -> sgx_status_t;
+
+    fn ecall_create_report(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        target_info: *const sgx_target_info_t,
+        report: *mut sgx_report_t,
+    ) -> sgx_status_t;
+}
+
+#[link(name = "sgx_dcap_ql")]
+extern "C" {
+    fn sgx_qe_get_target_info(qe_target_info: *mut sgx_target_info_t) -> sgx_quote3_error_t;
+
+    fn sgx_qe_get_quote_size(quote_size: *mut u32) -> sgx_quote3_error_t;
+
+    fn sgx_qe_get_quote(
+        app_report: *const sgx_report_t,
+        quote_size: u32,
+        quote: *mut u8,
+    ) -> sgx_quote3_error_t;
+}
+
+/// Runs enclave operations inside a hardware SGX enclave
+pub struct SgxClient {
+    enclave: SgxEnclave,
+    identity: EnclaveIdentity,
+    calls: Mutex<()>,
+    /// Held from processing a signed request until its state is saved
+    store: Mutex<StateStore>,
+}
+
+impl SgxClient {
+    /// Load and initialize a signed enclave image whose state lives in
+    /// `store`
+    ///
+    /// The state sealed in `store` is restored, or a fresh one with `config`
+    /// is created and saved if there is none. A restored state keeps the
+    /// config it was created with.
+    pub fn load(
+        path: impl Into<String>,
+        debug: bool,
+        store: StateStore,
+        config: &EnclaveConfig,
+    ) -> Result<Self, HostError> {
+        let path = path.into();
+        let mut launch_token: sgx_launch_token_t = [0; 1024];
+        let mut launch_token_updated: i32 = 0;
+        let mut misc_attr = sgx_misc_attribute_t {
+            secs_attr: sgx_attributes_t { flags: 0, xfrm: 0 },
+            misc_select: 0,
+        };
+
+        let enclave = SgxEnclave::create(
+            &path,
+            debug as i32,
+            &mut launch_token,
+            &mut launch_token_updated,
+            &mut misc_attr,
+        )
+        .map_err(|status| HostError::EnclaveLoad {
+            path: path.clone(),
+            reason: status.to_string(),
+        })?;
+
+        let sealed = store.load()?.map(|blob| blob.to_bytes());
+        let config = serde_json::to_vec(config).map_err(|e| HostError::EnclaveLoad {
+            path: path.clone(),
+            reason: e.to_string(),
+        })?;
+        let counter = store.counter().read().map_err(|e| state_error(&store, e))?;
+        let eid = enclave.geteid();
+        let identity = call_buffer(eid, |retval, output, capacity, output_len| unsafe {
+            let sealed = sealed.as_deref().unwrap_or_default();
+            ecall_init(
+                eid,
+                retval,
+                sealed.as_ptr(),
+                sealed.len(),
+                counter,
+                config.as_ptr(),
+                config.len(),
+                output,
+                capacity,
+                output_len,
+            )
+        })?;
+        let identity: Result<EnclaveIdentity, EnclaveError> = decode(&identity)?;
+        let identity = identity.map_err(|e| HostError::EnclaveLoad {
+            path: path.clone(),
+            reason: e.to_string(),
+        })?;
+
+        let client = Self {
+            enclave,
+            identity,
+            calls: Mutex::new(()),
+            store: Mutex::new(store),
+        };
+        if sealed.is_none() {
+            let store = client.store.lock().expect("not shared yet");
+            client.persist(&store)?;
+        }
+        Ok(client)
+    }
+
+    /// Seal the enclave state and save it to `store`
+    ///
+    /// The blob is saved before the counter advances, so a failure at
+    /// either step leaves a blob the enclave accepts: the previous one, or
+    /// the new one sealed one ahead of the counter.
+    fn persist(&self, store: &StateStore) -> Result<(), HostError> {
+        let mut counter = store.counter();
+        let value = counter.read().map_err(|e| state_error(store, e))?;
+        let eid = self.enclave.geteid();
+        let sealed = self.call(|retval, output, capacity, output_len| unsafe {
+            ecall_seal(eid, retval, value, output, capacity, output_len)
+        })?;
+        let sealed: Result<SealedBlob, EnclaveError> = decode(&sealed)?;
+        let sealed = sealed.map_err(|e| state_error(store, e))?;
+
+        store.save(&sealed)?;
+        counter.increment().map_err(|e| state_error(store, e))?;
+        Ok(())
+    }
+
+    /// Enclave id assigned by the SGX runtime
+    pub fn enclave_id(&self) -> sgx_enclave_id_t {
+        self.enclave.geteid()
+    }
+
+    /// Run an ECALL writing into an output buffer, one call at a time
+    fn call(
+        &self,
+        ecall: impl FnOnce(*mut sgx_status_t, *mut u8, usize, *mut usize) -> sgx_status_t,
+    ) -> Result<Vec<u8>, HostError> {
+        let _serialized = self.calls.lock().map_err(|_| HostError::Ecall {
+            reason: "enclave call lock poisoned".to_string(),
+        })?;
+        call_buffer(self.enclave.geteid(), ecall)
+    }
+
+    /// Run an ECALL taking a JSON input and producing a JSON output
+    fn call_json<T: serde::de::DeserializeOwned>(
+        &self,
+        ecall: unsafe extern "C" fn(
+            sgx_enclave_id_t,
+            *mut sgx_status_t,
+            *const u8,
+            usize,
+            *mut u8,
+            usize,
+            *mut usize,
+        ) -> sgx_status_t,
+        input: &EnclaveInput,
+    ) -> Result<T, HostError> {
+        let input = serde_json::to_vec(input).map_err(|e| HostError::Serialization {
+            reason: e.to_string(),
+        })?;
+        let eid = self.enclave.geteid();
+        let output = self.call(|retval, output, capacity, output_len| unsafe {
+            ecall(
+                eid,
+                retval,
+                input.as_ptr(),
+                input.len(),
+                output,
+                capacity,
+                output_len,
+            )
+        })?;
+        decode(&output)
+    }
+}
+
+/// Run an ECALL writing into an output buffer and return what it wrote
+///
+/// `ecall` receives the return value slot, the buffer, its capacity and the
+/// output length slot.
+fn call_buffer(
+    eid: sgx_enclave_id_t,
+    ecall: impl FnOnce(*mut sgx_status_t, *mut u8, usize, *mut usize) -> sgx_status_t,
+) -> Result<Vec<u8>, HostError> {
+    let mut output = vec![0u8; INITIAL_OUTPUT_CAPACITY];
+    let mut output_len = 0usize;
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+    let status = ecall(
+        &mut retval,
+        output.as_mut_ptr(),
+        output.len(),
+        &mut output_len,
+    );
+    check(status)?;
+
+    if retval == sgx_status_t::SGX_ERROR_OUT_OF_MEMORY && output_len > output.len() {
+        output = vec![0u8; output_len];
+        let status = unsafe {
+            ecall_take_output(
+                eid,
+                &mut retval,
+                output.as_mut_ptr(),
+                output.len(),
+                &mut output_len,
+            )
+        };
+        check(status)?;
+    }
+    check(retval)?;
+
+    output.truncate(output_len);
+    Ok(output)
+}
+
+fn check(status: sgx_status_t) -> Result<(), HostError> {
+    match status {
+        sgx_status_t::SGX_SUCCESS => Ok(()),
+        status => Err(HostError::Ecall {
+            reason: status.to_string(),
+        }),
+    }
+}
+
+fn check_quote(status: sgx_quote3_error_t) -> Result<(), HostError> {
+    match status {
+        sgx_quote3_error_t::SGX_QL_SUCCESS => Ok(()),
+        status => Err(attestation_error(format!("{:?}", status))),
+    }
+}
+
+fn attestation_error(reason: impl Into<String>) -> HostError {
+    HostError::Attestation {
+        reason: reason.into(),
+    }
+}
+
+fn decode<T: serde::de::DeserializeOwned>(output: &[u8]) -> Result<T, HostError> {
+    serde_json::from_slice(output).map_err(|e| HostError::Serialization {
+        reason: e.to_string(),
+    })
+}
+
+fn state_error(store: &StateStore, error: EnclaveError) -> HostError {
+    HostError::State {
+        path: store.dir().display().to_string(),
+        reason: error.to_string(),
+    }
+}
+
+fn internal(error: HostError) -> EnclaveError {
+    EnclaveError::Internal {
+        reason: error.to_string(),
+    }
+}
+
+impl EnclaveClient for SgxClient {
+    fn process(&self, input: EnclaveInput) -> EnclaveOutput {
+        self.call_json(ecall_process, &input)
+            .unwrap_or_else(|error| EnclaveOutput::new(Err(internal(error))))
+    }
+
+    fn process_signed(&self, input: EnclaveInput) -> Result<SignedEnclaveOutput, EnclaveError> {
+        let store = self.store.lock().map_err(|_| EnclaveError::Internal {
+            reason: "enclave state store lock poisoned".to_string(),
+        })?;
+        let signed: Result<SignedEnclaveOutput, EnclaveError> = self
+            .call_json(ecall_process_signed, &input)
+            .map_err(internal)?;
+        let signed = signed?;
+        self.persist(&store)
+            .map_err(|e| EnclaveError::SealingFailure {
+                reason: e.to_string(),
+            })?;
+        Ok(signed)
+    }
+
+    fn identity(&self) -> EnclaveIdentity {
+        self.identity.clone()
+    }
+
+    fn backend(&self) -> BackendKind {
+        BackendKind::Sgx
+    }
+
+    fn attest(&self) -> Result<Quote, HostError> {
+        let mut qe_target_info = sgx_target_info_t::default();
+        check_quote(unsafe { sgx_qe_get_target_info(&mut qe_target_info) })?;
+
+        let mut report = sgx_report_t::default();
+        let mut retval = sgx_status_t::SGX_SUCCESS;
+        let status = unsafe {
+            ecall_create_report(
+                self.enclave.geteid(),
+                &mut retval,
+                &qe_target_info,
+                &mut report,
+            )
+        };
+        check(status).and_then(|()| check(retval))?;
+
+        let mut quote_size = 0u32;
+        check_quote(unsafe { sgx_qe_get_quote_size(&mut quote_size) })?;
+        let mut quote = vec![0u8; quote_size as usize];
+        check_quote(unsafe { sgx_qe_get_quote(&report, quote_size, quote.as_mut_ptr()) })?;
+
+        let quote = Quote::from_bytes(&quote).map_err(|e| attestation_error(e.to_string()))?;
+        if !quote.report.binds_identity(&self.identity) {
+            return Err(attestation_error(
+                "quoted report does not bind the enclave identity",
+            ));
+        }
+        Ok(quote)
+    }
+}
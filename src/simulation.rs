@@ -0,0 +1,266 @@
+//! In-process simulation backend.
+
+use std::sync::Mutex;
+
+use chaoschain_enclave::attestation::{report_data_for_identity, simulated_report, Quote};
+use chaoschain_enclave::{
+    process_operation, EnclaveConfig, EnclaveError, EnclaveIdentity, EnclaveInput, EnclaveOutput,
+    EnclaveSigner, EnclaveState, MonotonicCounter, SealPolicy, Sealer, SignatureScheme,
+    SignedEnclaveOutput,
+};
+
+use crate::attestation::SimulatedQuoteGenerator;
+use crate::client::{BackendKind, EnclaveClient};
+use crate::error::HostError;
+use crate::store::StateStore;
+
+/// Runs enclave operations directly in the host process
+///
+/// Outputs are identical to those of the SGX backend, but nothing is
+/// isolated from the host, so this backend is meant for development, tests
+/// and machines without SGX hardware. Quotes come from a
+/// [`SimulatedQuoteGenerator`] and report the debug attribute.
+///
+/// A client made with [`SimulationClient::open`] restores its enclave state
+/// from a [`StateStore`] and saves it there after every signed output; other
+/// clients start from a fresh key and forget it when dropped. If a save
+/// fails, the output is dropped and the client goes back to the state last
+/// saved, so the request can be sent again with the same nonce.
+#[derive(Debug)]
+pub struct SimulationClient {
+    quotes: Result<SimulatedQuoteGenerator, HostError>,
+    state: Mutex<EnclaveState>,
+    persistence: Option<(StateStore, Sealer)>,
+}
+
+impl SimulationClient {
+    /// Create a simulation backend with a freshly generated test CA and
+    /// enclave key, and the default [`EnclaveConfig`]
+    pub fn new() -> Self {
+        Self::with_config(EnclaveConfig::default())
+    }
+
+    /// Create a simulation backend whose fresh enclave state keeps `config`
+    pub fn with_config(config: EnclaveConfig) -> Self {
+        Self {
+            quotes: SimulatedQuoteGenerator::new(),
+            state: Mutex::new(fresh_state(config)),
+            persistence: None,
+        }
+    }
+
+    /// Create a simulation backend quoting with `generator`
+    pub fn with_quote_generator(generator: SimulatedQuoteGenerator) -> Self {
+        Self {
+            quotes: Ok(generator),
+            ..Self::new()
+        }
+    }
+
+    /// Create a simulation backend whose enclave state lives in `store`
+    ///
+    /// The state sealed in `store` is restored, or a fresh one with `config`
+    /// is created and saved if there is none. A restored state keeps the
+    /// config it was created with.
+    pub fn open(store: StateStore, config: EnclaveConfig) -> Result<Self, HostError> {
+        let dir = store.dir().display().to_string();
+        let state_error = |e: EnclaveError| HostError::State {
+            path: dir.clone(),
+            reason: e.to_string(),
+        };
+
+        let sealer = Sealer::open(store.sealing_key_path()).map_err(state_error)?;
+        let (state, fresh) = match store.load()? {
+            Some(blob) => (
+                EnclaveState::unseal(&sealer, &blob, &mut store.counter()).map_err(state_error)?,
+                false,
+            ),
+            None => (fresh_state(config), true),
+        };
+
+        let client = Self {
+            quotes: SimulatedQuoteGenerator::new(),
+            state: Mutex::new(state),
+            persistence: Some((store, sealer)),
+        };
+        if fresh {
+            let state = client.state.lock().expect("not shared yet");
+            client.persist(&state).map_err(state_error)?;
+        }
+        Ok(client)
+    }
+
+    /// Seal `state` into the store, if this client has one
+    ///
+    /// The blob is saved before the counter advances, see
+    /// [`EnclaveState::seal`].
+    fn persist(&self, state: &EnclaveState) -> Result<(), EnclaveError> {
+        if let Some((store, sealer)) = &self.persistence {
+            let mut counter = store.counter();
+            let blob = state.seal(sealer, SealPolicy::MrSigner, &counter)?;
+            store
+                .save(&blob)
+                .map_err(|e| EnclaveError::SealingFailure {
+                    reason: e.to_string(),
+                })?;
+            counter.increment()?;
+        }
+        Ok(())
+    }
+
+    /// State last saved to the store, if this client has one it can read
+    fn saved_state(&self) -> Option<EnclaveState> {
+        let (store, sealer) = self.persistence.as_ref()?;
+        let blob = store.load().ok()??;
+        EnclaveState::unseal(sealer, &blob, &mut store.counter()).ok()
+    }
+
+    /// Generator whose test root CA certifies this backend's quotes
+    pub fn quote_generator(&self) -> Result<&SimulatedQuoteGenerator, HostError> {
+        self.quotes.as_ref().map_err(Clone::clone)
+    }
+}
+
+impl Default for SimulationClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnclaveClient for SimulationClient {
+    fn process(&self, input: EnclaveInput) -> EnclaveOutput {
+        process_operation(input)
+    }
+
+    fn process_signed(&self, input: EnclaveInput) -> Result<SignedEnclaveOutput, EnclaveError> {
+        let mut state = self.state.lock().map_err(|_| EnclaveError::Internal {
+            reason: "enclave state lock poisoned".to_string(),
+        })?;
+        let signed = state.process(input)?;
+        if let Err(error) = self.persist(&state) {
+            if let Some(saved) = self.saved_state() {
+                *state = saved;
+            }
+            return Err(error);
+        }
+        Ok(signed)
+    }
+
+    fn identity(&self) -> EnclaveIdentity {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .signer()
+            .identity()
+    }
+
+    fn backend(&self) -> BackendKind {
+        BackendKind::Simulation
+    }
+
+    fn attest(&self) -> Result<Quote, HostError> {
+        let report_data = report_data_for_identity(&self.identity());
+        self.quote_generator()?
+            .quote(&simulated_report(report_data))
+    }
+}
+
+fn fresh_state(config: EnclaveConfig) -> EnclaveState {
+    EnclaveState::with_config(EnclaveSigner::generate(SignatureScheme::Secp256k1), config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chaoschain_enclave::{AddPayload, Operation};
+
+    #[test]
+    fn test_matches_enclave_crate() {
+        let input = EnclaveInput::new(Operation::Add(AddPayload {
+            a: 40.into(),
+            b: 2.into(),
+        }));
+        let client = SimulationClient::new();
+
+        assert_eq!(client.process(input.clone()), process_operation(input));
+        assert_eq!(client.backend(), BackendKind::Simulation);
+    }
+
+    #[test]
+    fn test_signed_outputs_use_enclave_state() {
+        let client = SimulationClient::new();
+        let input = EnclaveInput::new(Operation::EthereumAddress).with_nonce("dao-a", 1);
+
+        let signed = client.process_signed(input.clone()).unwrap();
+        assert_eq!(signed.signer, client.identity());
+        assert!(signed.verify().is_ok());
+        assert!(signed.output.result.is_ok());
+
+        let error = client.process_signed(input).unwrap_err();
+        assert_eq!(error.code(), 3003);
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = || {
+            SimulationClient::open(
+                StateStore::open(dir.path()).unwrap(),
+                EnclaveConfig::default(),
+            )
+            .unwrap()
+        };
+        let add = |nonce| {
+            EnclaveInput::new(Operation::Add(AddPayload {
+                a: 1.into(),
+                b: 2.into(),
+            }))
+            .with_nonce("dao-a", nonce)
+        };
+
+        let client = open();
+        let identity = client.identity();
+        assert_eq!(client.process_signed(add(1)).unwrap().sequence, 0);
+        drop(client);
+
+        let restarted = open();
+        assert_eq!(restarted.identity(), identity);
+        assert_eq!(restarted.process_signed(add(1)).unwrap_err().code(), 3003);
+        assert_eq!(restarted.process_signed(add(2)).unwrap().sequence, 1);
+    }
+
+    #[test]
+    fn test_failed_save_keeps_store_usable() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = || {
+            SimulationClient::open(
+                StateStore::open(dir.path()).unwrap(),
+                EnclaveConfig::default(),
+            )
+            .unwrap()
+        };
+        let add = |nonce| {
+            EnclaveInput::new(Operation::Add(AddPayload {
+                a: 1.into(),
+                b: 2.into(),
+            }))
+            .with_nonce("dao-a", nonce)
+        };
+
+        let client = open();
+        assert_eq!(client.process_signed(add(1)).unwrap().sequence, 0);
+
+        // Saving stages the blob in state.tmp, which a directory now blocks
+        let staging = dir.path().join("state.tmp");
+        std::fs::create_dir(&staging).unwrap();
+        assert_eq!(client.process_signed(add(2)).unwrap_err().code(), 3002);
+        std::fs::remove_dir(&staging).unwrap();
+
+        // The dropped output spent nothing, and the store still unseals
+        assert_eq!(client.process_signed(add(2)).unwrap().sequence, 1);
+        drop(client);
+        let restarted = open();
+        assert_eq!(restarted.process_signed(add(2)).unwrap_err().code(), 3003);
+        assert_eq!(restarted.process_signed(add(3)).unwrap().sequence, 2);
+    }
+}
@@ -0,0 +1,110 @@
+//! Host storage of the enclave's sealed state.
+//!
+//! The enclave keeps its signing key and spent client nonces in an
+//! [`EnclaveState`](chaoschain_enclave::EnclaveState) that only survives a
+//! restart as a [`SealedBlob`]. Both backends seal the state after every
+//! signed output and write the blob here before returning that output, so a
+//! restarted enclave keeps its identity and still refuses old nonces.
+//!
+//! A [`StateStore`] is a directory holding:
+//!
+//! | File            | Contents                                        |
+//! |-----------------|-------------------------------------------------|
+//! | `state.sealed`  | latest sealed state, in [`SealedBlob`] encoding |
+//! | `state.counter` | monotonic counter the blob was sealed at        |
+//! | `sealing.key`   | root sealing key of the simulation backend      |
+//!
+//! Losing `state.sealed` or `state.counter` loses the enclave identity; the
+//! next start creates a new one.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chaoschain_enclave::state::FileCounter;
+use chaoschain_enclave::SealedBlob;
+
+use crate::error::HostError;
+
+/// Directory in which the host keeps the enclave's sealed state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateStore {
+    dir: PathBuf,
+}
+
+impl StateStore {
+    /// Use `dir`, creating it if needed
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, HostError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| state_error(&dir, e))?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Counter that every seal advances
+    pub fn counter(&self) -> FileCounter {
+        FileCounter::new(self.dir.join("state.counter"))
+    }
+
+    /// Root key file of the simulation backend's sealer
+    pub fn sealing_key_path(&self) -> PathBuf {
+        self.dir.join("sealing.key")
+    }
+
+    /// Latest sealed state, if any was saved
+    pub fn load(&self) -> Result<Option<SealedBlob>, HostError> {
+        let path = self.blob_path();
+        match std::fs::read(&path) {
+            Ok(bytes) => SealedBlob::from_bytes(&bytes)
+                .map(Some)
+                .map_err(|e| state_error(&path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(state_error(&path, e)),
+        }
+    }
+
+    /// Atomically replace the saved state with `blob`
+    pub fn save(&self, blob: &SealedBlob) -> Result<(), HostError> {
+        let path = self.blob_path();
+        let staging = path.with_extension("tmp");
+        std::fs::File::create(&staging)
+            .and_then(|mut file| {
+                file.write_all(&blob.to_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&staging, &path))
+            .map_err(|e| state_error(&path, e))
+    }
+
+    fn blob_path(&self) -> PathBuf {
+        self.dir.join("state.sealed")
+    }
+}
+
+fn state_error(path: &Path, error: impl ToString) -> HostError {
+    HostError::State {
+        path: path.display().to_string(),
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chaoschain_enclave::{SealPolicy, Sealer};
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = StateStore::open(dir.path().join("state")).unwrap();
+        assert_eq!(store.load().unwrap(), None);
+
+        let blob = Sealer::from_root_key([1; 32])
+            .seal(SealPolicy::MrSigner, b"aad", b"secret")
+            .unwrap();
+        store.save(&blob).unwrap();
+        assert_eq!(store.load().unwrap(), Some(blob));
+    }
+}
@@ -0,0 +1,98 @@
+"""
+Tests for the DCAP quote verifier wrapper.
+"""
+
+import json
+import os
+import shutil
+import time
+
+import pytest
+
+from verification.attestation.dcap import DcapVerifier
+
+FIXTURES = os.path.join(
+    os.path.dirname(__file__), "..", "..", "..",
+    "verification", "tee", "verifier", "tests", "fixtures"
+)
+VERIFIER = os.environ.get("CHAOSCHAIN_VERIFIER_PATH", "chaoschain-verify")
+
+pytestmark = pytest.mark.skipif(
+    shutil.which(VERIFIER) is None, reason="chaoschain-verify is not built"
+)
+
+
+@pytest.fixture
+def quote():
+    """
+    Hex quote produced by the simulated quote generator.
+    
+    Returns:
+        Hex encoded quote
+    """
+    with open(os.path.join(FIXTURES, "simulated_quote.hex")) as f:
+        return f.read().strip()
+
+
+@pytest.fixture
+def output():
+    """
+    Enclave output the fixture quote is bound to.
+    
+    Returns:
+        Enclave output
+    """
+    with open(os.path.join(FIXTURES, "simulated_output.json")) as f:
+        return json.load(f)
+
+
+def make_verifier(policy_path=None, deployment_policy_path=None):
+    return DcapVerifier(
+        root_ca_path=os.path.join(FIXTURES, "simulated_root.pem"),
+        collateral_path=os.path.join(FIXTURES, "simulated_collateral.json"),
+        policy_path=policy_path,
+        verifier_path=VERIFIER,
+        deployment_policy_path=deployment_policy_path
+    )
+
+
+def test_default_policy_rejects_debug_enclave(quote):
+    """Test that simulated enclaves, which run in debug mode, are rejected by default."""
+    verdict = make_verifier().verify(quote)
+    
+    assert verdict["verified"] is False
+    assert verdict["error"]["kind"] == "policy_violation"
+
+
+def test_verifies_bound_output(quote, output, tmp_path):
+    """Test verifying a quote against the output it attests to."""
+    policy_path = tmp_path / "policy.json"
+    policy_path.write_text(json.dumps({"allow_debug": True}))
+    verifier = make_verifier(str(policy_path))
+    
+    verdict = verifier.verify(quote, output)
+    assert verdict["verified"] is True
+    assert verdict["quote"]["tcb_status"] == "UpToDate"
+    
+    output["version"] += 1
+    verdict = verifier.verify(quote, output)
+    assert verdict["verified"] is False
+    assert verdict["error"]["kind"] == "output_not_bound"
+
+
+
+def test_deployment_policy_traces_checks(quote, output):
+    """Test that a deployment policy reports every check it ran."""
+    verifier = make_verifier(
+        deployment_policy_path=os.path.join(FIXTURES, "simulated_policy.toml")
+    )
+    
+    verdict = verifier.verify(quote, output, attested_at=int(time.time()))
+    assert verdict["verified"] is True
+    assert verdict["policy"]["policy"] == "simulated-fixture"
+    assert all(check["passed"] for check in verdict["policy"]["checks"])
+    
+    verdict = verifier.verify(quote, output, attested_at=int(time.time()) - 7200)
+    assert verdict["verified"] is False
+    failed = [check["check"] for check in verdict["policy"]["checks"] if not check["passed"]]
+    assert failed == ["attestation_age"]
@@ -0,0 +1,119 @@
+"""
+DCAP quote verification.
+
+This module wraps the `chaoschain-verify` binary built from
+`verification/tee/verifier`, which verifies SGX DCAP quotes offline against
+a trusted root certificate, a local collateral bundle and a quote policy.
+"""
+
+import json
+import os
+import subprocess
+from typing import Any, Dict, Optional
+
+
+class DcapVerifier:
+    """
+    Offline verifier for enclave quotes.
+
+    Configuration defaults to the environment:
+
+    - `CHAOSCHAIN_VERIFIER_PATH`: verifier binary (default `chaoschain-verify`)
+    - `SGX_ROOT_CA_PATH`: PEM root certificate to trust
+    - `SGX_COLLATERAL_PATH`: JSON collateral bundle
+    - `SGX_QUOTE_POLICY_PATH`: optional JSON quote policy
+    - `SGX_DEPLOYMENT_POLICY_PATH`: optional TOML or JSON deployment policy
+    """
+
+    def __init__(
+        self,
+        root_ca_path: Optional[str] = None,
+        collateral_path: Optional[str] = None,
+        policy_path: Optional[str] = None,
+        verifier_path: Optional[str] = None,
+        deployment_policy_path: Optional[str] = None,
+        timeout: float = 10.0
+    ):
+        """
+        Initialize the verifier.
+
+        Args:
+            root_ca_path: Root certificate trusted to certify PCK chains
+            collateral_path: TCB info and QE identity bundle
+            policy_path: Quote policy; the verifier's default policy if unset
+            verifier_path: Path to the `chaoschain-verify` binary
+            deployment_policy_path: Deployment policy the quote and output
+                must also satisfy, if any
+            timeout: Seconds to wait for a verdict
+        """
+        self.root_ca_path = root_ca_path or os.environ.get("SGX_ROOT_CA_PATH")
+        self.collateral_path = collateral_path or os.environ.get("SGX_COLLATERAL_PATH")
+        self.policy_path = policy_path or os.environ.get("SGX_QUOTE_POLICY_PATH")
+        self.verifier_path = verifier_path or os.environ.get(
+            "CHAOSCHAIN_VERIFIER_PATH", "chaoschain-verify"
+        )
+        self.deployment_policy_path = deployment_policy_path or os.environ.get(
+            "SGX_DEPLOYMENT_POLICY_PATH"
+        )
+        self.timeout = timeout
+
+    def verify(
+        self,
+        quote: str,
+        output: Optional[Dict[str, Any]] = None,
+        attested_at: Optional[int] = None,
+        signed_output: Optional[Dict[str, Any]] = None
+    ) -> Dict[str, Any]:
+        """
+        Verify a quote.
+
+        Args:
+            quote: Hex encoded DCAP quote
+            output: Enclave output the quote must be bound to, if any
+            attested_at: Unix time the quote was produced, checked against
+                the deployment policy's maximum attestation age
+            signed_output: Signed enclave output whose signer the quote must
+                bind; give either this or `output`
+
+        Returns:
+            `{"verified": True, "quote": ...}` with the attested report, PCK
+            platform details and TCB status, or `{"verified": False,
+            "error": ..., "message": ...}` explaining the rejection. With a
+            deployment policy, a `"policy"` report traces each check and
+            `"verified"` is true only if all of them passed
+
+        Raises:
+            RuntimeError: If the verifier is not configured or cannot run
+        """
+        if not self.root_ca_path or not self.collateral_path:
+            raise RuntimeError("SGX_ROOT_CA_PATH and SGX_COLLATERAL_PATH must be set")
+
+        command = [
+            self.verifier_path,
+            "--root", self.root_ca_path,
+            "--collateral", self.collateral_path,
+        ]
+        if self.policy_path:
+            command += ["--policy", self.policy_path]
+        if self.deployment_policy_path:
+            command += ["--deployment-policy", self.deployment_policy_path]
+
+        request = {"quote": quote}
+        if output is not None:
+            request["output"] = output
+        if signed_output is not None:
+            request["signed_output"] = signed_output
+        if attested_at is not None:
+            request["attested_at"] = attested_at
+
+        completed = subprocess.run(
+            command,
+            input=json.dumps(request),
+            capture_output=True,
+            text=True,
+            timeout=self.timeout
+        )
+        if completed.returncode != 0:
+            raise RuntimeError(f"chaoschain-verify failed: {completed.stderr.strip()}")
+
+        return json.loads(completed.stdout)
@@ -0,0 +1,287 @@
+//! Solidity ABI encoding.
+//!
+//! Enough of the [contract ABI specification] to build calldata and event
+//! topics for the contracts the enclave's outputs are anchored in: every
+//! elementary type the endpoint uses, dynamic `bytes` and `string`, fixed
+//! and dynamic arrays, and tuples. [`encode`] is `abi.encode`, and
+//! [`encode_call`] prefixes the function selector as
+//! `abi.encodeWithSignature` does.
+//!
+//! Signatures are given in canonical form, with no spaces or parameter names,
+//! e.g. `"anchorAction(string,string,string,string,bytes)"`.
+//!
+//! [contract ABI specification]: https://docs.soliditylang.org/en/latest/abi-spec.html
+
+use crate::merkle::keccak256;
+use crate::EnclaveError;
+
+/// Size of one ABI word
+pub const WORD: usize = 32;
+
+/// A value to encode, tagged with its Solidity type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// `uintN`, as a 32-byte big-endian word
+    Uint([u8; WORD]),
+    Address([u8; 20]),
+    Bool(bool),
+    /// `bytesN`, holding 1 to 32 bytes
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+    /// `T[]`
+    Array(Vec<Token>),
+    /// `T[k]`
+    FixedArray(Vec<Token>),
+    Tuple(Vec<Token>),
+}
+
+impl Token {
+    /// `uintN` holding `value`
+    pub fn uint(value: u128) -> Self {
+        let mut word = [0u8; WORD];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        Token::Uint(word)
+    }
+
+    /// Whether the value is encoded in the tail rather than in place
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            Token::Bytes(_) | Token::String(_) | Token::Array(_) => true,
+            Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+                tokens.iter().any(Token::is_dynamic)
+            }
+            _ => false,
+        }
+    }
+
+    /// Bytes taken in place by a static value; one word for dynamic values,
+    /// which are referenced by offset
+    fn head_size(&self) -> usize {
+        match self {
+            Token::FixedArray(tokens) | Token::Tuple(tokens) if !self.is_dynamic() => {
+                tokens.iter().map(Token::head_size).sum()
+            }
+            _ => WORD,
+        }
+    }
+}
+
+/// `abi.encode(tokens...)`
+pub fn encode(tokens: &[Token]) -> Result<Vec<u8>, EnclaveError> {
+    let mut out = Vec::new();
+    encode_sequence(tokens, &mut out)?;
+    Ok(out)
+}
+
+/// Calldata calling `signature` with `tokens` as arguments
+pub fn encode_call(signature: &str, tokens: &[Token]) -> Result<Vec<u8>, EnclaveError> {
+    let mut calldata = selector(signature).to_vec();
+    encode_sequence(tokens, &mut calldata)?;
+    Ok(calldata)
+}
+
+/// First four bytes of the Keccak-256 hash of a function signature
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Topic 0 of an event, the Keccak-256 hash of its signature
+pub fn event_topic(signature: &str) -> [u8; WORD] {
+    keccak256(signature.as_bytes())
+}
+
+/// Topic of an indexed event parameter
+///
+/// Value types are their own encoding; `string`, `bytes`, arrays and tuples
+/// are indexed by the Keccak-256 hash of their in-place encoding.
+pub fn indexed_topic(token: &Token) -> Result<[u8; WORD], EnclaveError> {
+    let mut encoded = Vec::new();
+    match token {
+        Token::Bytes(bytes) => return Ok(keccak256(bytes)),
+        Token::String(string) => return Ok(keccak256(string.as_bytes())),
+        Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            for token in tokens {
+                encoded.extend_from_slice(&indexed_topic(token)?);
+            }
+            return Ok(keccak256(&encoded));
+        }
+        _ => encode_token(token, &mut encoded)?,
+    }
+    Ok(encoded.try_into().expect("value types take one word"))
+}
+
+/// Heads of `tokens` followed by the tails of the dynamic ones
+fn encode_sequence(tokens: &[Token], out: &mut Vec<u8>) -> Result<(), EnclaveError> {
+    let heads_size: usize = tokens.iter().map(Token::head_size).sum();
+    let mut heads = Vec::with_capacity(heads_size);
+    let mut tails = Vec::new();
+    for token in tokens {
+        if token.is_dynamic() {
+            heads.extend_from_slice(&word((heads_size + tails.len()) as u64));
+            encode_token(token, &mut tails)?;
+        } else {
+            encode_token(token, &mut heads)?;
+        }
+    }
+    out.extend_from_slice(&heads);
+    out.extend_from_slice(&tails);
+    Ok(())
+}
+
+fn encode_token(token: &Token, out: &mut Vec<u8>) -> Result<(), EnclaveError> {
+    match token {
+        Token::Uint(value) => out.extend_from_slice(value),
+        Token::Address(address) => {
+            out.extend_from_slice(&[0; WORD - 20]);
+            out.extend_from_slice(address);
+        }
+        Token::Bool(value) => out.extend_from_slice(&word(*value as u64)),
+        Token::FixedBytes(bytes) => {
+            if bytes.is_empty() || bytes.len() > WORD {
+                return Err(EnclaveError::invalid_payload(format!(
+                    "bytes{} is not an ABI type",
+                    bytes.len()
+                )));
+            }
+            pad_right(bytes, out);
+        }
+        Token::Bytes(bytes) => {
+            out.extend_from_slice(&word(bytes.len() as u64));
+            pad_right(bytes, out);
+        }
+        Token::String(string) => {
+            out.extend_from_slice(&word(string.len() as u64));
+            pad_right(string.as_bytes(), out);
+        }
+        Token::Array(tokens) => {
+            out.extend_from_slice(&word(tokens.len() as u64));
+            encode_sequence(tokens, out)?;
+        }
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => encode_sequence(tokens, out)?,
+    }
+    Ok(())
+}
+
+fn word(value: u64) -> [u8; WORD] {
+    let mut word = [0u8; WORD];
+    word[WORD - 8..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// `bytes` followed by zeros up to a whole number of words
+fn pad_right(bytes: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(bytes);
+    out.resize(out.len() + (WORD - bytes.len() % WORD) % WORD, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(encoded: &[u8]) -> Vec<String> {
+        encoded.chunks(WORD).map(hex::encode).collect()
+    }
+
+    #[test]
+    fn test_selectors() {
+        assert_eq!(
+            hex::encode(selector("transfer(address,uint256)")),
+            "a9059cbb"
+        );
+        assert_eq!(
+            hex::encode(event_topic("Transfer(address,address,uint256)")),
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+    }
+
+    #[test]
+    fn test_dynamic_example_from_spec() {
+        // f(uint256,uint32[],bytes10,bytes) with (0x123, [0x456, 0x789],
+        // "1234567890", "Hello, world!")
+        let encoded = encode_call(
+            "f(uint256,uint32[],bytes10,bytes)",
+            &[
+                Token::uint(0x123),
+                Token::Array(vec![Token::uint(0x456), Token::uint(0x789)]),
+                Token::FixedBytes(b"1234567890".to_vec()),
+                Token::Bytes(b"Hello, world!".to_vec()),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(hex::encode(&encoded[..4]), "8be65246");
+        let expected = [
+            "0000000000000000000000000000000000000000000000000000000000000123",
+            "0000000000000000000000000000000000000000000000000000000000000080",
+            "3132333435363738393000000000000000000000000000000000000000000000",
+            "00000000000000000000000000000000000000000000000000000000000000e0",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000456",
+            "0000000000000000000000000000000000000000000000000000000000000789",
+            "000000000000000000000000000000000000000000000000000000000000000d",
+            "48656c6c6f2c20776f726c642100000000000000000000000000000000000000",
+        ];
+        assert_eq!(words(&encoded[4..]), expected);
+    }
+
+    #[test]
+    fn test_nested_dynamic_arrays_from_spec() {
+        // g(uint256[][],string[]) with ([[1, 2], [3]], ["one", "two", "three"])
+        let encoded = encode(&[
+            Token::Array(vec![
+                Token::Array(vec![Token::uint(1), Token::uint(2)]),
+                Token::Array(vec![Token::uint(3)]),
+            ]),
+            Token::Array(
+                ["one", "two", "three"]
+                    .iter()
+                    .map(|s| Token::String(s.to_string()))
+                    .collect(),
+            ),
+        ])
+        .unwrap();
+
+        let word_of = |value: u64| hex::encode(word(value));
+        let text = |s: &str| hex::encode(format!("{:\0<32}", s));
+        let expected = vec![
+            word_of(0x40),
+            word_of(0x140),
+            word_of(2),
+            word_of(0x40),
+            word_of(0xa0),
+            word_of(2),
+            word_of(1),
+            word_of(2),
+            word_of(1),
+            word_of(3),
+            word_of(3),
+            word_of(0x60),
+            word_of(0xa0),
+            word_of(0xe0),
+            word_of(3),
+            text("one"),
+            word_of(3),
+            text("two"),
+            word_of(5),
+            text("three"),
+        ];
+        assert_eq!(words(&encoded), expected);
+    }
+
+    #[test]
+    fn test_static_tuples_are_inline() {
+        let tuple = Token::Tuple(vec![Token::Bool(true), Token::Address([0xaa; 20])]);
+        assert!(!tuple.is_dynamic());
+        let encoded = encode(&[tuple, Token::String("x".to_string())]).unwrap();
+        assert_eq!(words(&encoded).len(), 5);
+        assert_eq!(encoded[2 * WORD + 31], 0x60);
+
+        assert!(encode(&[Token::FixedBytes(vec![0; 33])]).is_err());
+        assert_eq!(
+            indexed_topic(&Token::String("a1".to_string())).unwrap(),
+            keccak256(b"a1")
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Proof-of-Agency receipts.
+//!
+//! Port of the action and outcome records kept in Python memory by
+//! `chaoscore/core/proof_of_agency`. Here every action an agent takes, and
+//! every outcome it later reaches, becomes a [`receipts::Receipt`] that
+//! commits to its predecessor, so each agent's history forms a hash chain
+//! that anyone holding the signed outputs can check. [`rewards`] then pays
+//! out for a concluded action from its receipts.
+//!
+//! The enclave remembers the head of every agent's chain in an
+//! [`AgencyLedger`], sealed with the rest of its state, so each new receipt
+//! must extend the agent's latest one and no chain can fork. The ledger also
+//! records what [`rewards`] paid each agent, so no action is paid twice and
+//! an agent's rewards always go to the same address.
+
+pub mod receipts;
+pub mod rewards;
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ethereum::Address;
+use crate::EnclaveError;
+use receipts::Receipt;
+
+/// Most agents whose chains an [`AgencyLedger`] tracks
+pub const MAX_TRACKED_AGENTS: usize = 4096;
+
+/// Kind of action an agent performed, as `ActionType` in
+/// `chaoscore/core/proof_of_agency/interfaces.py`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionType {
+    Analyze,
+    Propose,
+    Verify,
+    Execute,
+    Monitor,
+    Collaborate,
+}
+
+impl ActionType {
+    /// Name of this action type, as its Python enum value
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActionType::Analyze => "analyze",
+            ActionType::Propose => "propose",
+            ActionType::Verify => "verify",
+            ActionType::Execute => "execute",
+            ActionType::Monitor => "monitor",
+            ActionType::Collaborate => "collaborate",
+        }
+    }
+
+    /// Byte identifying this action type in receipt hashes
+    pub fn code(self) -> u8 {
+        match self {
+            ActionType::Analyze => 1,
+            ActionType::Propose => 2,
+            ActionType::Verify => 3,
+            ActionType::Execute => 4,
+            ActionType::Monitor => 5,
+            ActionType::Collaborate => 6,
+        }
+    }
+}
+
+/// What the enclave remembers of one agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AgentRecord {
+    /// Hash of the agent's latest receipt
+    head: [u8; 32],
+    /// Height of the agent's latest action that was paid for
+    paid_through: Option<u64>,
+    /// Address the agent's rewards go to, fixed by its first reward
+    payout: Option<Address>,
+}
+
+/// Latest receipt, and rewards paid, of every agent the enclave issued
+/// receipts for
+///
+/// Forgetting an agent would let it start a second chain from a new genesis
+/// receipt, so unlike the [`ReplayGuard`](crate::ReplayGuard) a full ledger
+/// refuses new agents rather than evicting old ones.
+///
+/// An agent's actions are paid for in chain order: the ledger keeps the
+/// height of the latest action paid and refuses any action at or below it,
+/// so an action passed over can no longer be paid.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AgencyLedger {
+    agents: BTreeMap<String, AgentRecord>,
+}
+
+impl AgencyLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hash of the latest receipt of `agent_id`
+    pub fn head(&self, agent_id: &str) -> Option<[u8; 32]> {
+        self.agents.get(agent_id).map(|agent| agent.head)
+    }
+
+    /// Height of the latest action of `agent_id` that was paid for
+    pub fn paid_through(&self, agent_id: &str) -> Option<u64> {
+        self.agents.get(agent_id)?.paid_through
+    }
+
+    /// Address the rewards of `agent_id` go to
+    pub fn payout_address(&self, agent_id: &str) -> Option<Address> {
+        self.agents.get(agent_id)?.payout
+    }
+
+    /// Check that a receipt of `agent_id` whose parent is `parent_hash`, or
+    /// that has no parent, would extend the agent's chain at its head
+    pub(crate) fn check_head(
+        &self,
+        agent_id: &str,
+        parent_hash: Option<&[u8; 32]>,
+    ) -> Result<(), EnclaveError> {
+        match (self.head(agent_id), parent_hash) {
+            (None, None) if self.agents.len() >= MAX_TRACKED_AGENTS => {
+                Err(EnclaveError::ResourceExhausted {
+                    resource: format!("tracked agents ({})", MAX_TRACKED_AGENTS),
+                })
+            }
+            (None, None) => Ok(()),
+            (Some(head), Some(parent_hash)) if head == *parent_hash => Ok(()),
+            (Some(_), None) => Err(EnclaveError::invalid_payload(format!(
+                "agent {} already has receipts; the parent must be its latest",
+                agent_id
+            ))),
+            _ => Err(EnclaveError::invalid_payload(format!(
+                "parent is not the latest receipt of agent {}",
+                agent_id
+            ))),
+        }
+    }
+
+    /// Make `receipt` the head of its agent's chain
+    pub(crate) fn advance(&mut self, receipt: &Receipt) {
+        self.agents
+            .entry(receipt.agent_id.clone())
+            .and_modify(|agent| agent.head = receipt.receipt_hash)
+            .or_insert(AgentRecord {
+                head: receipt.receipt_hash,
+                paid_through: None,
+                payout: None,
+            });
+    }
+
+    /// Check that `action` comes after the latest action of its agent that
+    /// was paid for
+    pub(crate) fn check_unpaid(&self, action: &Receipt) -> Result<(), EnclaveError> {
+        match self.paid_through(&action.agent_id) {
+            Some(paid) if action.height <= paid => Err(EnclaveError::invalid_payload(format!(
+                "action {} of agent {} is at or below its latest paid action, at height {}",
+                action.action_id, action.agent_id, paid
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that `agent_id` may be paid at `address`
+    pub(crate) fn check_payout(
+        &self,
+        agent_id: &str,
+        address: Address,
+    ) -> Result<(), EnclaveError> {
+        let agent = self.agents.get(agent_id).ok_or_else(|| {
+            EnclaveError::invalid_payload(format!("agent {} has no receipts", agent_id))
+        })?;
+        match agent.payout {
+            Some(payout) if payout != address => Err(EnclaveError::invalid_payload(format!(
+                "agent {} is paid at {}",
+                agent_id, payout
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record that `action` was paid for, and that each of `payouts` was
+    /// paid at its address, after [`Self::check_unpaid`] and
+    /// [`Self::check_payout`] passed
+    pub(crate) fn record_payment<'a>(
+        &mut self,
+        action: &Receipt,
+        payouts: impl IntoIterator<Item = (&'a str, Address)>,
+    ) {
+        for (agent_id, address) in payouts {
+            if let Some(agent) = self.agents.get_mut(agent_id) {
+                agent.payout = Some(address);
+            }
+        }
+        if let Some(agent) = self.agents.get_mut(&action.agent_id) {
+            agent.paid_through = Some(action.height);
+        }
+    }
+
+    /// Encoding used in sealed state: `count (u32 BE) || (id length (u16 BE)
+    /// || id || head (32) || paid flag (u8) [|| paid height (u64 BE)]
+    /// || payout flag (u8) [|| payout address (20)])*`
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = (self.agents.len() as u32).to_be_bytes().to_vec();
+        for (agent_id, agent) in &self.agents {
+            out.extend_from_slice(&(agent_id.len() as u16).to_be_bytes());
+            out.extend_from_slice(agent_id.as_bytes());
+            out.extend_from_slice(&agent.head);
+            match agent.paid_through {
+                None => out.push(0),
+                Some(height) => {
+                    out.push(1);
+                    out.extend_from_slice(&height.to_be_bytes());
+                }
+            }
+            match agent.payout {
+                None => out.push(0),
+                Some(address) => {
+                    out.push(1);
+                    out.extend_from_slice(&address.0);
+                }
+            }
+        }
+        out
+    }
+
+    pub(crate) fn from_bytes(mut bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let mut take = |len: usize| -> Result<&[u8], EnclaveError> {
+            if bytes.len() < len {
+                return Err(EnclaveError::SealingFailure {
+                    reason: "agency ledger is truncated".to_string(),
+                });
+            }
+            let (head, tail) = bytes.split_at(len);
+            bytes = tail;
+            Ok(head)
+        };
+
+        let count = u32::from_be_bytes(take(4)?.try_into().expect("length checked"));
+        let mut agents = BTreeMap::new();
+        for _ in 0..count {
+            let len = u16::from_be_bytes(take(2)?.try_into().expect("length checked"));
+            let agent_id = String::from_utf8(take(len as usize)?.to_vec()).map_err(|_| {
+                EnclaveError::SealingFailure {
+                    reason: "agency ledger holds a non UTF-8 agent id".to_string(),
+                }
+            })?;
+            let head = take(32)?.try_into().expect("length checked");
+            let paid_through = match take(1)?[0] {
+                0 => None,
+                _ => Some(u64::from_be_bytes(
+                    take(8)?.try_into().expect("length checked"),
+                )),
+            };
+            let payout = match take(1)?[0] {
+                0 => None,
+                _ => Some(Address(take(20)?.try_into().expect("length checked"))),
+            };
+            agents.insert(
+                agent_id,
+                AgentRecord {
+                    head,
+                    paid_through,
+                    payout,
+                },
+            );
+        }
+        if !bytes.is_empty() {
+            return Err(EnclaveError::SealingFailure {
+                reason: "trailing bytes after agency ledger".to_string(),
+            });
+        }
+        Ok(Self { agents })
+    }
+}
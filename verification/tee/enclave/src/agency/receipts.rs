@@ -0,0 +1,526 @@
+//! Hash-chained action and outcome receipts.
+//!
+//! `record_action` turns an agent's action (its type, and the hashes of what
+//! it consumed and produced) into a [`Receipt`]; `record_outcome` records how
+//! an earlier action turned out. Each receipt names the hash of the agent's
+//! previous receipt and sits one above it in height, so an agent's receipts
+//! form a chain from a genesis receipt at height 0.
+//!
+//! Receipts leave the enclave inside a [`SignedEnclaveOutput`], and they
+//! come back the same way: the caller passes the signed output that carried
+//! the previous receipt, and the enclave only accepts it if its own identity
+//! signed it. The receipt hash alone proves nothing, as anyone can compute
+//! it. So a chain can only be extended from a receipt this enclave issued,
+//! and both operations need the keys of a running
+//! [`EnclaveState`](crate::EnclaveState).
+//!
+//! Every new receipt must extend the agent's latest receipt, which the
+//! enclave tracks in its [`AgencyLedger`]: a receipt with no parent is only
+//! accepted for an agent the enclave has not seen, so an agent has a single
+//! chain.
+//!
+//! An outcome names the action it concludes and extends the agent's chain
+//! past it. The caller passes the signed receipts that followed the action,
+//! up to the agent's latest, and the enclave checks that they link up, so an
+//! outcome's parent always descends from the action it concludes. As the
+//! chain cannot fork, any earlier outcome of the action is among those
+//! receipts, and the enclave refuses to conclude an action twice.
+//!
+//! The receipt hash is SHA-256 over a fixed binary layout:
+//!
+//! ```text
+//! RECEIPT_DOMAIN || kind (u8) || agent id length (u16 BE) || agent id
+//!     || action id length (u16 BE) || action id || action type (u8)
+//!     || input_hash (32) || output_hash (32) || parent_hash (32)
+//!     || height (u64 BE) || outcome flag (u8)
+//!     [|| success (u8) || impact_score (int256 BE, scaled by 10^18)]
+//! ```
+//!
+//! For an outcome, `input_hash` is the hash of the action receipt it
+//! concludes and `output_hash` the hash of the results. [`verify_chain`]
+//! checks that a sequence of receipts links up.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{ActionType, AgencyLedger};
+use crate::fixed::Fixed;
+use crate::signing::{EnclaveIdentity, SignedEnclaveOutput};
+use crate::{EnclaveError, OperationResult};
+
+/// Domain separation tag prefixed to every receipt hash
+pub const RECEIPT_DOMAIN: &[u8] = b"chaoschain-enclave/agency-receipt/v2";
+
+/// Longest accepted agent or action identifier, in bytes
+pub const MAX_ID_LEN: usize = 256;
+
+/// Whether a receipt records an action or its outcome
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptKind {
+    Action,
+    Outcome,
+}
+
+/// How an action turned out
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutcomeSummary {
+    pub success: bool,
+    /// Impact from 0 to 1; recorded as given, checked where it is used
+    pub impact_score: Fixed,
+}
+
+/// One link in an agent's hash chain
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub kind: ReceiptKind,
+    pub agent_id: String,
+    pub action_id: String,
+    pub action_type: ActionType,
+    #[serde(with = "crate::serde_hex")]
+    pub input_hash: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub output_hash: [u8; 32],
+    /// Hash of the agent's previous receipt; zero for the genesis receipt
+    #[serde(with = "crate::serde_hex")]
+    pub parent_hash: [u8; 32],
+    pub height: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<OutcomeSummary>,
+    #[serde(with = "crate::serde_hex")]
+    pub receipt_hash: [u8; 32],
+}
+
+impl Receipt {
+    /// Hash of this receipt's contents, see the module documentation
+    pub fn compute_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(RECEIPT_DOMAIN);
+        hasher.update([match self.kind {
+            ReceiptKind::Action => 0,
+            ReceiptKind::Outcome => 1,
+        }]);
+        for id in [&self.agent_id, &self.action_id] {
+            hasher.update((id.len() as u16).to_be_bytes());
+            hasher.update(id.as_bytes());
+        }
+        hasher.update([self.action_type.code()]);
+        hasher.update(self.input_hash);
+        hasher.update(self.output_hash);
+        hasher.update(self.parent_hash);
+        hasher.update(self.height.to_be_bytes());
+        match self.outcome {
+            None => hasher.update([0]),
+            Some(outcome) => {
+                hasher.update([1, outcome.success as u8]);
+                hasher.update(outcome.impact_score.to_be_bytes());
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    /// Check that `receipt_hash` matches the receipt's contents
+    pub fn verify_hash(&self) -> Result<(), EnclaveError> {
+        if self.compute_hash() != self.receipt_hash {
+            return Err(EnclaveError::AttestationFailure {
+                reason: format!("receipt hash of action {} does not match", self.action_id),
+            });
+        }
+        Ok(())
+    }
+
+    fn seal(mut self) -> Self {
+        self.receipt_hash = self.compute_hash();
+        self
+    }
+}
+
+/// Payload for the `record_action` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordActionPayload {
+    pub agent_id: String,
+    pub action_id: String,
+    pub action_type: ActionType,
+    /// Hash of the data the action consumed
+    #[serde(with = "crate::serde_hex")]
+    pub input_hash: [u8; 32],
+    /// Hash of what the action produced
+    #[serde(with = "crate::serde_hex")]
+    pub output_hash: [u8; 32],
+    /// Signed output holding the agent's latest receipt; absent for its
+    /// first action
+    #[serde(default)]
+    pub parent: Option<SignedEnclaveOutput>,
+}
+
+/// Payload for the `record_outcome` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordOutcomePayload {
+    /// Signed output holding the receipt of the action being concluded
+    pub action: SignedEnclaveOutput,
+    pub success: bool,
+    /// Impact of the action from 0 to 1
+    pub impact_score: Fixed,
+    /// Hash of the action's results
+    #[serde(with = "crate::serde_hex")]
+    pub results_hash: [u8; 32],
+    /// Signed outputs holding the agent's receipts after `action`, oldest
+    /// first; the last is the agent's latest receipt. Empty if `action` is
+    /// the latest.
+    #[serde(default)]
+    pub since_action: Vec<SignedEnclaveOutput>,
+}
+
+/// Issue the receipt for a new action of the enclave `enclave`, at the head
+/// of the agent's chain in `ledger`
+pub fn record_action(
+    payload: &RecordActionPayload,
+    enclave: &EnclaveIdentity,
+    ledger: &mut AgencyLedger,
+) -> Result<Receipt, EnclaveError> {
+    check_id("agent_id", &payload.agent_id)?;
+    check_id("action_id", &payload.action_id)?;
+    let parent = payload
+        .parent
+        .as_ref()
+        .map(|parent| issued_receipt(parent, enclave))
+        .transpose()?;
+    let (parent_hash, height) = extend(&payload.agent_id, parent)?;
+    ledger.check_head(&payload.agent_id, parent.map(|parent| &parent.receipt_hash))?;
+
+    let receipt = Receipt {
+        kind: ReceiptKind::Action,
+        agent_id: payload.agent_id.clone(),
+        action_id: payload.action_id.clone(),
+        action_type: payload.action_type,
+        input_hash: payload.input_hash,
+        output_hash: payload.output_hash,
+        parent_hash,
+        height,
+        outcome: None,
+        receipt_hash: [0; 32],
+    }
+    .seal();
+    ledger.advance(&receipt);
+    Ok(receipt)
+}
+
+/// Issue the receipt concluding an earlier action of the enclave `enclave`,
+/// at the head of the agent's chain in `ledger`
+pub fn record_outcome(
+    payload: &RecordOutcomePayload,
+    enclave: &EnclaveIdentity,
+    ledger: &mut AgencyLedger,
+) -> Result<Receipt, EnclaveError> {
+    let action = issued_receipt(&payload.action, enclave)?;
+    if action.kind != ReceiptKind::Action {
+        return Err(EnclaveError::invalid_payload(
+            "outcomes conclude action receipts",
+        ));
+    }
+    let mut parent = action;
+    for signed in &payload.since_action {
+        let receipt = issued_receipt(signed, enclave)?;
+        if !follows(parent, receipt) {
+            return Err(EnclaveError::invalid_payload(
+                "receipts since the action do not descend from it",
+            ));
+        }
+        if receipt.kind == ReceiptKind::Outcome && receipt.input_hash == action.receipt_hash {
+            return Err(EnclaveError::invalid_payload(format!(
+                "action {} already has an outcome",
+                action.action_id
+            )));
+        }
+        parent = receipt;
+    }
+    let (parent_hash, height) = extend(&action.agent_id, Some(parent))?;
+    ledger.check_head(&action.agent_id, Some(&parent.receipt_hash))?;
+
+    let receipt = Receipt {
+        kind: ReceiptKind::Outcome,
+        agent_id: action.agent_id.clone(),
+        action_id: action.action_id.clone(),
+        action_type: action.action_type,
+        input_hash: action.receipt_hash,
+        output_hash: payload.results_hash,
+        parent_hash,
+        height,
+        outcome: Some(OutcomeSummary {
+            success: payload.success,
+            impact_score: payload.impact_score,
+        }),
+        receipt_hash: [0; 32],
+    }
+    .seal();
+    ledger.advance(&receipt);
+    Ok(receipt)
+}
+
+/// Receipt held by `signed`, which `enclave` must have signed
+pub fn issued_receipt<'a>(
+    signed: &'a SignedEnclaveOutput,
+    enclave: &EnclaveIdentity,
+) -> Result<&'a Receipt, EnclaveError> {
+    if signed.signer != *enclave {
+        return Err(EnclaveError::AttestationFailure {
+            reason: "receipt was issued by another enclave identity".to_string(),
+        });
+    }
+    signed.verify()?;
+    let receipt = match &signed.output.result {
+        Ok(OperationResult::RecordAction(receipt) | OperationResult::RecordOutcome(receipt)) => {
+            receipt
+        }
+        _ => {
+            return Err(EnclaveError::invalid_payload(
+                "output does not hold a receipt",
+            ))
+        }
+    };
+    receipt
+        .verify_hash()
+        .map_err(|e| EnclaveError::invalid_payload(e.to_string()))?;
+    Ok(receipt)
+}
+
+/// Check that `receipts` form one agent's chain, in order, from its genesis
+/// receipt
+///
+/// Callers must separately check each receipt's signed output envelope.
+pub fn verify_chain(receipts: &[Receipt]) -> Result<(), EnclaveError> {
+    let mut previous: Option<&Receipt> = None;
+    for receipt in receipts {
+        receipt.verify_hash()?;
+        let linked = match previous {
+            None => receipt.parent_hash == [0; 32] && receipt.height == 0,
+            Some(parent) => {
+                if parent.agent_id != receipt.agent_id {
+                    return Err(chain_broken(receipt, "belongs to another agent"));
+                }
+                follows(parent, receipt)
+            }
+        };
+        if !linked {
+            return Err(chain_broken(receipt, "does not follow its predecessor"));
+        }
+        previous = Some(receipt);
+    }
+    Ok(())
+}
+
+/// Whether `receipt` comes right after `parent` in the same agent's chain
+fn follows(parent: &Receipt, receipt: &Receipt) -> bool {
+    receipt.agent_id == parent.agent_id
+        && receipt.parent_hash == parent.receipt_hash
+        && parent.height.checked_add(1) == Some(receipt.height)
+}
+
+/// Parent hash and height of the receipt following `parent` in `agent_id`'s
+/// chain
+fn extend(agent_id: &str, parent: Option<&Receipt>) -> Result<([u8; 32], u64), EnclaveError> {
+    let Some(parent) = parent else {
+        return Ok(([0; 32], 0));
+    };
+    if parent.agent_id != agent_id {
+        return Err(EnclaveError::invalid_payload(
+            "parent receipt belongs to another agent",
+        ));
+    }
+    let height = parent
+        .height
+        .checked_add(1)
+        .ok_or_else(|| EnclaveError::ResourceExhausted {
+            resource: "receipt chain height".to_string(),
+        })?;
+    Ok((parent.receipt_hash, height))
+}
+
+fn check_id(field: &str, id: &str) -> Result<(), EnclaveError> {
+    if id.is_empty() || id.len() > MAX_ID_LEN {
+        return Err(EnclaveError::invalid_payload(format!(
+            "{} must be 1 to {} bytes",
+            field, MAX_ID_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn chain_broken(receipt: &Receipt, reason: &str) -> EnclaveError {
+    EnclaveError::AttestationFailure {
+        reason: format!(
+            "receipt at height {} for action {} {}",
+            receipt.height, receipt.action_id, reason
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EnclaveInput, EnclaveSigner, EnclaveState, Operation, SignatureScheme};
+
+    fn state() -> EnclaveState {
+        EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Ed25519))
+    }
+
+    /// Run `operation` through `state`, returning the signed output if the
+    /// operation succeeded
+    fn issue(
+        state: &mut EnclaveState,
+        operation: Operation,
+    ) -> Result<SignedEnclaveOutput, EnclaveError> {
+        let nonce = state.signer().sequence() + 1;
+        let signed = state
+            .process(EnclaveInput::new(operation).with_nonce("agency", nonce))
+            .unwrap();
+        signed.output.result.clone().map(|_| signed)
+    }
+
+    fn receipt(signed: &SignedEnclaveOutput) -> &Receipt {
+        issued_receipt(signed, &signed.signer).unwrap()
+    }
+
+    fn action(
+        state: &mut EnclaveState,
+        agent_id: &str,
+        parent: Option<&SignedEnclaveOutput>,
+    ) -> Result<SignedEnclaveOutput, EnclaveError> {
+        issue(
+            state,
+            Operation::RecordAction(RecordActionPayload {
+                agent_id: agent_id.to_string(),
+                action_id: "a1".to_string(),
+                action_type: ActionType::Analyze,
+                input_hash: [1; 32],
+                output_hash: [2; 32],
+                parent: parent.cloned(),
+            }),
+        )
+    }
+
+    fn outcome(
+        state: &mut EnclaveState,
+        action: &SignedEnclaveOutput,
+        since_action: &[SignedEnclaveOutput],
+    ) -> Result<SignedEnclaveOutput, EnclaveError> {
+        issue(
+            state,
+            Operation::RecordOutcome(RecordOutcomePayload {
+                action: action.clone(),
+                success: true,
+                impact_score: Fixed::new(85, 2),
+                results_hash: [3; 32],
+                since_action: since_action.to_vec(),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_receipts_form_a_chain() {
+        let mut state = state();
+        let first = action(&mut state, "agent-1", None).unwrap();
+        let second = action(&mut state, "agent-1", Some(&first)).unwrap();
+        let concluded = outcome(&mut state, &first, std::slice::from_ref(&second)).unwrap();
+        let (first, second, concluded) = (receipt(&first), receipt(&second), receipt(&concluded));
+
+        assert_eq!((first.height, second.height, concluded.height), (0, 1, 2));
+        assert_eq!(concluded.input_hash, first.receipt_hash);
+        assert_eq!(concluded.parent_hash, second.receipt_hash);
+        assert!(verify_chain(&[first.clone(), second.clone(), concluded.clone()]).is_ok());
+
+        assert!(verify_chain(&[first.clone(), concluded.clone()]).is_err());
+        assert!(verify_chain(&[second.clone(), concluded.clone()]).is_err());
+    }
+
+    #[test]
+    fn test_receipts_need_this_enclaves_signature() {
+        let mut state = state();
+        let first = action(&mut state, "agent-1", None).unwrap();
+
+        let mut tampered = first.clone();
+        if let Ok(OperationResult::RecordAction(receipt)) = &mut tampered.output.result {
+            receipt.action_type = ActionType::Execute;
+            receipt.receipt_hash = receipt.compute_hash();
+        }
+        let error = action(&mut state, "agent-1", Some(&tampered)).unwrap_err();
+        assert_eq!(error.code(), 3001);
+
+        let foreign = action(&mut self::state(), "agent-1", None).unwrap();
+        let error = action(&mut state, "agent-1", Some(&foreign)).unwrap_err();
+        assert_eq!(error.code(), 3001);
+
+        let error = crate::process_operation(EnclaveInput::new(Operation::RecordAction(
+            RecordActionPayload {
+                agent_id: "agent-1".to_string(),
+                action_id: "a1".to_string(),
+                action_type: ActionType::Analyze,
+                input_hash: [1; 32],
+                output_hash: [2; 32],
+                parent: None,
+            },
+        )))
+        .result
+        .unwrap_err();
+        assert_eq!(error.code(), 1001);
+    }
+
+    #[test]
+    fn test_chains_cannot_fork() {
+        let mut state = state();
+        let first = action(&mut state, "agent-1", None).unwrap();
+        let second = action(&mut state, "agent-1", Some(&first)).unwrap();
+
+        assert_eq!(
+            action(&mut state, "agent-1", None).unwrap_err().code(),
+            1003
+        );
+        assert!(action(&mut state, "agent-1", Some(&first)).is_err());
+        assert!(outcome(&mut state, &first, &[]).is_err());
+        assert_eq!(
+            state.agency_ledger().head("agent-1"),
+            Some(receipt(&second).receipt_hash)
+        );
+        assert!(action(&mut state, "agent-1", Some(&second)).is_ok());
+
+        let ledger = state.agency_ledger();
+        assert_eq!(
+            &AgencyLedger::from_bytes(&ledger.to_bytes()).unwrap(),
+            ledger
+        );
+    }
+
+    #[test]
+    fn test_outcome_rules() {
+        let mut state = state();
+        let first = action(&mut state, "agent-1", None).unwrap();
+        let second = action(&mut state, "agent-1", Some(&first)).unwrap();
+        let third = action(&mut state, "agent-1", Some(&second)).unwrap();
+
+        assert!(outcome(&mut state, &second, std::slice::from_ref(&first)).is_err());
+        // Receipts since the action must reach the head without skipping a
+        // link
+        assert!(outcome(&mut state, &first, std::slice::from_ref(&second)).is_err());
+        assert!(outcome(&mut state, &first, std::slice::from_ref(&third)).is_err());
+        let concluded = outcome(&mut state, &first, &[second.clone(), third.clone()]).unwrap();
+
+        assert!(outcome(&mut state, &concluded, &[]).is_err());
+        let error = outcome(&mut state, &first, &[second, third, concluded]).unwrap_err();
+        assert_eq!(error.code(), 1003);
+
+        let other = action(&mut state, "agent-2", None).unwrap();
+        assert!(outcome(&mut state, &first, &[other]).is_err());
+    }
+
+    #[test]
+    fn test_receipt_hash_covers_outcome() {
+        let mut state = state();
+        let first = action(&mut state, "agent-1", None).unwrap();
+        let mut concluded = receipt(&outcome(&mut state, &first, &[]).unwrap()).clone();
+        concluded.outcome = Some(OutcomeSummary {
+            success: true,
+            impact_score: Fixed::ONE,
+        });
+        assert!(concluded.verify_hash().is_err());
+    }
+}
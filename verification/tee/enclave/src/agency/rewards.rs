@@ -0,0 +1,512 @@
+//! Reward computation for concluded actions.
+//!
+//! Port of `InMemoryProofOfAgency.compute_rewards`: the acting agent earns
+//! the base reward scaled by the outcome's impact score, and by a failure
+//! multiplier if the action failed; every verifier earns a fixed share of the
+//! base reward. Amounts are integers in the token's smallest unit (wei for an
+//! 18-decimal token); the impact score and the schedule's shares are
+//! [`Fixed`] decimals, and each amount is rounded down to a whole unit once,
+//! after the last multiplication, so the same payload yields the same
+//! amounts everywhere.
+//!
+//! Success and impact are taken from the outcome receipt rather than from
+//! the caller, and the distribution names both receipts it was computed
+//! from. The verifiers are the agents of `verify` action receipts whose
+//! input hash is the rewarded action's receipt hash. Every receipt must come
+//! in the signed output this enclave issued it in, so the operation needs
+//! the identity of a running [`EnclaveState`](crate::EnclaveState), and an
+//! impact score outside 0 to 1 is refused. The schedule is the one in the
+//! enclave's [`EnclaveConfig`](crate::EnclaveConfig), not the caller's.
+//!
+//! The enclave's [`AgencyLedger`] records each payment: an action is paid
+//! at most once, and an agent's first reward fixes the address all its
+//! later rewards go to.
+//!
+//! `recipients_data` is the distribution as canonical JSON,
+//! `[{"address": "0x…", "amount": "<decimal>"}, …]`, ready to pass as
+//! `recipientsData` to `ChaosEndpoint.distributeRewards`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::receipts::{issued_receipt, ReceiptKind};
+use super::{ActionType, AgencyLedger};
+use crate::ethereum::Address;
+use crate::fixed::{Fixed, Rounding};
+use crate::signing::{EnclaveIdentity, SignedEnclaveOutput};
+use crate::EnclaveError;
+
+/// One whole token in base units, for an 18-decimal token
+pub const TOKEN: u128 = 1_000_000_000_000_000_000;
+
+/// Amounts paid out per action
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct RewardSchedule {
+    /// Reward of a successful action with an impact score of 1
+    #[serde(with = "crate::serde_decimal")]
+    pub base_reward: u128,
+    /// Scale applied to the agent's reward when the action failed
+    pub failure_multiplier: Fixed,
+    /// Reward of each verifier, as a share of `base_reward`
+    pub verifier_share: Fixed,
+}
+
+impl Default for RewardSchedule {
+    /// The schedule hard-coded in `compute_rewards`
+    fn default() -> Self {
+        Self {
+            base_reward: 100 * TOKEN,
+            failure_multiplier: Fixed::new(25, 2),
+            verifier_share: Fixed::new(1, 1),
+        }
+    }
+}
+
+/// Payload for the `compute_rewards` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComputeRewardsPayload {
+    /// Signed output holding the receipt of the rewarded action
+    pub action: SignedEnclaveOutput,
+    /// Signed output holding the receipt of its outcome
+    pub outcome: SignedEnclaveOutput,
+    /// Signed outputs holding the `verify` receipts of the agents that
+    /// verified the action
+    #[serde(default)]
+    pub verifications: Vec<SignedEnclaveOutput>,
+    /// Payout address of every rewarded agent
+    pub addresses: BTreeMap<String, String>,
+}
+
+/// Amount owed to one agent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RewardRecipient {
+    pub agent_id: String,
+    /// Lowercase `0x`-prefixed address
+    pub address: String,
+    #[serde(with = "crate::serde_decimal")]
+    pub amount: u128,
+}
+
+/// Result of the `compute_rewards` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RewardDistribution {
+    pub action_id: String,
+    #[serde(with = "crate::serde_hex")]
+    pub action_receipt_hash: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub outcome_receipt_hash: [u8; 32],
+    pub schedule: RewardSchedule,
+    /// The acting agent first, then verifiers in payload order
+    pub recipients: Vec<RewardRecipient>,
+    #[serde(with = "crate::serde_decimal")]
+    pub total: u128,
+    /// `recipients` as canonical JSON for `distributeRewards`
+    pub recipients_data: String,
+}
+
+#[derive(Serialize)]
+struct RecipientData<'a> {
+    address: &'a str,
+    #[serde(with = "crate::serde_decimal")]
+    amount: u128,
+}
+
+/// Compute the rewards owed under `schedule` for a concluded action whose
+/// receipts the enclave `enclave` issued, and record them in `ledger`
+pub fn compute(
+    payload: &ComputeRewardsPayload,
+    enclave: &EnclaveIdentity,
+    schedule: &RewardSchedule,
+    ledger: &mut AgencyLedger,
+) -> Result<RewardDistribution, EnclaveError> {
+    let action = issued_receipt(&payload.action, enclave)?;
+    let outcome = issued_receipt(&payload.outcome, enclave)?;
+    if action.kind != ReceiptKind::Action || outcome.kind != ReceiptKind::Outcome {
+        return Err(EnclaveError::invalid_payload(
+            "rewards need an action receipt and an outcome receipt",
+        ));
+    }
+    if outcome.input_hash != action.receipt_hash {
+        return Err(EnclaveError::invalid_payload(
+            "outcome receipt does not conclude the action receipt",
+        ));
+    }
+    let summary = outcome
+        .outcome
+        .ok_or_else(|| EnclaveError::invalid_payload("outcome receipt has no outcome"))?;
+    if summary.impact_score.is_negative() || summary.impact_score > Fixed::ONE {
+        return Err(EnclaveError::invalid_payload(format!(
+            "impact score {} is outside 0 to 1",
+            summary.impact_score
+        )));
+    }
+    ledger.check_unpaid(action)?;
+
+    if schedule.failure_multiplier.is_negative() || schedule.verifier_share.is_negative() {
+        return Err(EnclaveError::invalid_payload(
+            "reward shares must not be negative",
+        ));
+    }
+    let multiplier = if summary.success {
+        Fixed::ONE
+    } else {
+        schedule.failure_multiplier
+    };
+    let agent_reward = scale(schedule.base_reward, &[summary.impact_score, multiplier])?;
+    let verifier_reward = scale(schedule.base_reward, &[schedule.verifier_share])?;
+
+    let mut recipients: Vec<RewardRecipient> = Vec::new();
+    let mut payouts: Vec<(&str, Address)> = Vec::new();
+    let mut credit = |agent_id: &str, amount: u128| -> Result<(), EnclaveError> {
+        if let Some(recipient) = recipients.iter_mut().find(|r| r.agent_id == agent_id) {
+            recipient.amount = recipient.amount.checked_add(amount).ok_or_else(overflow)?;
+            return Ok(());
+        }
+        let (agent_id, address) = payload.addresses.get_key_value(agent_id).ok_or_else(|| {
+            EnclaveError::invalid_payload(format!("no payout address for agent {}", agent_id))
+        })?;
+        let address: Address = address.parse()?;
+        ledger.check_payout(agent_id, address)?;
+        payouts.push((agent_id, address));
+        recipients.push(RewardRecipient {
+            agent_id: agent_id.clone(),
+            address: address.to_string(),
+            amount,
+        });
+        Ok(())
+    };
+
+    credit(&action.agent_id, agent_reward)?;
+    let mut verifiers: Vec<&str> = Vec::new();
+    for signed in &payload.verifications {
+        let verification = issued_receipt(signed, enclave)?;
+        if verification.kind != ReceiptKind::Action
+            || verification.action_type != ActionType::Verify
+            || verification.input_hash != action.receipt_hash
+        {
+            return Err(EnclaveError::invalid_payload(
+                "verifications must be verify receipts of the rewarded action",
+            ));
+        }
+        if verifiers.contains(&verification.agent_id.as_str()) {
+            return Err(EnclaveError::invalid_payload(format!(
+                "verifier {} is listed twice",
+                verification.agent_id
+            )));
+        }
+        verifiers.push(&verification.agent_id);
+        credit(&verification.agent_id, verifier_reward)?;
+    }
+
+    let total = recipients
+        .iter()
+        .try_fold(0u128, |total, r| total.checked_add(r.amount))
+        .ok_or_else(overflow)?;
+    let data: Vec<RecipientData<'_>> = recipients
+        .iter()
+        .map(|r| RecipientData {
+            address: &r.address,
+            amount: r.amount,
+        })
+        .collect();
+    let recipients_data = String::from_utf8(crate::canonical::to_canonical_json(&data)?)
+        .expect("canonical JSON is UTF-8");
+    ledger.record_payment(action, payouts);
+
+    Ok(RewardDistribution {
+        action_id: action.action_id.clone(),
+        action_receipt_hash: action.receipt_hash,
+        outcome_receipt_hash: outcome.receipt_hash,
+        schedule: *schedule,
+        recipients,
+        total,
+        recipients_data,
+    })
+}
+
+/// `amount` times one or two factors, rounded down to a whole unit
+///
+/// `amount` is whole, so the first product is exact, and rounding the
+/// second at 18 decimals cannot move it across a whole unit: the amount is
+/// in effect floored once, at the end.
+fn scale(amount: u128, factors: &[Fixed]) -> Result<u128, EnclaveError> {
+    debug_assert!(factors.len() <= 2, "more factors would round early");
+    factors
+        .iter()
+        .try_fold(Fixed::from(amount), |product, &factor| {
+            product.mul(factor, Rounding::Down)
+        })
+        .and_then(|product| product.to_u128(Rounding::Down))
+        .map_err(|_| overflow())
+}
+
+fn overflow() -> EnclaveError {
+    EnclaveError::invalid_payload("reward amount overflows")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agency::receipts::{OutcomeSummary, RecordActionPayload, RecordOutcomePayload};
+    use crate::{
+        EnclaveConfig, EnclaveInput, EnclaveSigner, EnclaveState, Operation, OperationResult,
+        SignatureScheme,
+    };
+
+    /// Enclave state, and the latest receipt of each agent it issued
+    struct Agency {
+        state: EnclaveState,
+        latest: BTreeMap<String, SignedEnclaveOutput>,
+    }
+
+    impl Agency {
+        fn new() -> Self {
+            Self::with_schedule(RewardSchedule::default())
+        }
+
+        fn with_schedule(reward_schedule: RewardSchedule) -> Self {
+            let config = EnclaveConfig {
+                reward_schedule,
+                ..EnclaveConfig::default()
+            };
+            Self {
+                state: EnclaveState::with_config(
+                    EnclaveSigner::generate(SignatureScheme::Ed25519),
+                    config,
+                ),
+                latest: BTreeMap::new(),
+            }
+        }
+
+        fn process(&mut self, operation: Operation) -> SignedEnclaveOutput {
+            let nonce = self.state.signer().sequence() + 1;
+            self.state
+                .process(EnclaveInput::new(operation).with_nonce("agency", nonce))
+                .unwrap()
+        }
+
+        /// Record an action of `agent_id` at the head of its chain
+        fn act(
+            &mut self,
+            agent_id: &str,
+            action_type: ActionType,
+            input_hash: [u8; 32],
+        ) -> SignedEnclaveOutput {
+            let signed = self.process(Operation::RecordAction(RecordActionPayload {
+                agent_id: agent_id.to_string(),
+                action_id: "a1".to_string(),
+                action_type,
+                input_hash,
+                output_hash: [2; 32],
+                parent: self.latest.get(agent_id).cloned(),
+            }));
+            assert!(signed.output.result.is_ok(), "{:?}", signed.output);
+            self.latest.insert(agent_id.to_string(), signed.clone());
+            signed
+        }
+
+        /// Receipts of a new action of agent-1 and its outcome
+        fn receipts(
+            &mut self,
+            success: bool,
+            impact_score: Fixed,
+        ) -> (SignedEnclaveOutput, SignedEnclaveOutput) {
+            let action = self.act("agent-1", ActionType::Propose, [1; 32]);
+            let outcome = self.process(Operation::RecordOutcome(RecordOutcomePayload {
+                action: action.clone(),
+                success,
+                impact_score,
+                results_hash: [3; 32],
+                since_action: Vec::new(),
+            }));
+            assert!(outcome.output.result.is_ok(), "{:?}", outcome.output);
+            self.latest.insert("agent-1".to_string(), outcome.clone());
+            (action, outcome)
+        }
+
+        /// Receipt of `verifier` verifying the action in `action`
+        fn verify(&mut self, verifier: &str, action: &SignedEnclaveOutput) -> SignedEnclaveOutput {
+            let receipt = issued_receipt(action, &action.signer).unwrap().receipt_hash;
+            self.act(verifier, ActionType::Verify, receipt)
+        }
+
+        fn payload(&mut self, success: bool, impact_score: Fixed) -> ComputeRewardsPayload {
+            let (action, outcome) = self.receipts(success, impact_score);
+            ComputeRewardsPayload {
+                verifications: vec![
+                    self.verify("verifier-1", &action),
+                    self.verify("verifier-2", &action),
+                ],
+                action,
+                outcome,
+                addresses: [
+                    ("agent-1", "0x00000000000000000000000000000000000000A1"),
+                    ("verifier-1", "0x00000000000000000000000000000000000000b1"),
+                    ("verifier-2", "00000000000000000000000000000000000000b2"),
+                ]
+                .into_iter()
+                .map(|(agent, address)| (agent.to_string(), address.to_string()))
+                .collect(),
+            }
+        }
+
+        /// Run `compute_rewards` on `payload`
+        fn compute(
+            &mut self,
+            payload: &ComputeRewardsPayload,
+        ) -> Result<RewardDistribution, EnclaveError> {
+            let signed = self.process(Operation::ComputeRewards(payload.clone()));
+            match signed.output.result {
+                Ok(OperationResult::ComputeRewards(distribution)) => Ok(distribution),
+                Ok(other) => panic!("unexpected result {:?}", other),
+                Err(error) => Err(error),
+            }
+        }
+
+        fn distribute(
+            &mut self,
+            success: bool,
+            impact_score: Fixed,
+        ) -> Result<RewardDistribution, EnclaveError> {
+            let payload = self.payload(success, impact_score);
+            self.compute(&payload)
+        }
+    }
+
+    #[test]
+    fn test_matches_python_schedule() {
+        let mut agency = Agency::new();
+        let distribution = agency.distribute(true, Fixed::new(85, 2)).unwrap();
+        let amounts: Vec<u128> = distribution.recipients.iter().map(|r| r.amount).collect();
+
+        // 100 * 0.85 for the agent, 100 * 0.1 for each verifier
+        assert_eq!(amounts, vec![85 * TOKEN, 10 * TOKEN, 10 * TOKEN]);
+        assert_eq!(distribution.total, 105 * TOKEN);
+        assert_eq!(distribution.schedule, RewardSchedule::default());
+
+        let failed = agency.distribute(false, Fixed::new(85, 2)).unwrap();
+        assert_eq!(failed.recipients[0].amount, 21_250_000_000_000_000_000);
+    }
+
+    #[test]
+    fn test_recipients_data_is_canonical_json() {
+        let distribution = Agency::new().distribute(true, Fixed::ONE).unwrap();
+        assert_eq!(
+            distribution.recipients_data,
+            concat!(
+                r#"[{"address":"0x00000000000000000000000000000000000000a1","amount":"100000000000000000000"},"#,
+                r#"{"address":"0x00000000000000000000000000000000000000b1","amount":"10000000000000000000"},"#,
+                r#"{"address":"0x00000000000000000000000000000000000000b2","amount":"10000000000000000000"}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_agent_verifying_itself_is_credited_once() {
+        let mut agency = Agency::new();
+        let mut payload = agency.payload(true, Fixed::ONE);
+        let again = agency.verify("verifier-1", &payload.action);
+        payload.verifications.push(again);
+        assert!(agency.compute(&payload).is_err());
+
+        payload.verifications = vec![agency.verify("agent-1", &payload.action)];
+        let distribution = agency.compute(&payload).unwrap();
+        assert_eq!(distribution.recipients.len(), 1);
+        assert_eq!(distribution.recipients[0].amount, 110 * TOKEN);
+    }
+
+    #[test]
+    fn test_verifications_must_verify_the_action() {
+        let mut agency = Agency::new();
+        let mut payload = agency.payload(true, Fixed::ONE);
+
+        let elsewhere = agency.act("verifier-3", ActionType::Verify, [9; 32]);
+        payload.verifications.push(elsewhere);
+        assert_eq!(agency.compute(&payload).unwrap_err().code(), 1003);
+
+        let receipt = issued_receipt(&payload.action, &payload.action.signer).unwrap();
+        let analysis = agency.act("verifier-3", ActionType::Analyze, receipt.receipt_hash);
+        *payload.verifications.last_mut().unwrap() = analysis;
+        assert_eq!(agency.compute(&payload).unwrap_err().code(), 1003);
+
+        payload.verifications.pop();
+        assert_eq!(agency.compute(&payload).unwrap().recipients.len(), 3);
+    }
+
+    #[test]
+    fn test_actions_are_paid_once() {
+        let mut agency = Agency::new();
+        let earlier = agency.payload(true, Fixed::ONE);
+        let payload = agency.payload(true, Fixed::ONE);
+        agency.compute(&payload).unwrap();
+
+        assert_eq!(agency.compute(&payload).unwrap_err().code(), 1003);
+        // Actions are paid in chain order
+        assert!(agency.compute(&earlier).is_err());
+        let ledger = agency.state.agency_ledger();
+        let action = issued_receipt(&payload.action, &payload.action.signer).unwrap();
+        assert_eq!(ledger.paid_through("agent-1"), Some(action.height));
+        assert_eq!(
+            ledger.payout_address("verifier-1"),
+            Some(
+                "0x00000000000000000000000000000000000000b1"
+                    .parse()
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            &AgencyLedger::from_bytes(&ledger.to_bytes()).unwrap(),
+            ledger
+        );
+
+        // Later rewards go to the address of the first
+        let mut redirected = agency.payload(true, Fixed::ONE);
+        redirected
+            .addresses
+            .insert("agent-1".to_string(), format!("0x{}", "ee".repeat(20)));
+        assert_eq!(agency.compute(&redirected).unwrap_err().code(), 1003);
+    }
+
+    #[test]
+    fn test_receipts_must_match() {
+        let mut agency = Agency::new();
+        let mut mismatched = agency.payload(true, Fixed::ONE);
+        mismatched.outcome = agency.receipts(true, Fixed::ONE).0;
+        assert!(agency.compute(&mismatched).is_err());
+
+        let mut unknown = agency.payload(true, Fixed::ONE);
+        unknown.addresses.remove("verifier-2");
+        assert!(agency.compute(&unknown).is_err());
+
+        let mut overflowing = Agency::with_schedule(RewardSchedule {
+            base_reward: u128::MAX,
+            ..RewardSchedule::default()
+        });
+        assert!(overflowing.distribute(true, Fixed::ONE).is_err());
+    }
+
+    #[test]
+    fn test_receipts_must_come_from_this_enclave() {
+        let mut agency = Agency::new();
+        let payload = agency.payload(true, Fixed::ONE);
+        let error = Agency::new().compute(&payload).unwrap_err();
+        assert_eq!(error.code(), 3001);
+
+        let mut forged = payload.clone();
+        if let Ok(OperationResult::RecordOutcome(receipt)) = &mut forged.outcome.output.result {
+            receipt.outcome = Some(OutcomeSummary {
+                success: true,
+                impact_score: 100.into(),
+            });
+            receipt.receipt_hash = receipt.compute_hash();
+        }
+        assert_eq!(agency.compute(&forged).unwrap_err().code(), 3001);
+
+        let inflated = agency.distribute(true, Fixed::new(10_001, 4));
+        assert_eq!(inflated.unwrap_err().code(), 1003);
+        let negative = agency.distribute(true, Fixed::new(-1, 4));
+        assert_eq!(negative.unwrap_err().code(), 1003);
+    }
+}
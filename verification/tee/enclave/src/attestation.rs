@@ -0,0 +1,520 @@
+//! SGX attestation reports and DCAP quotes.
+//!
+//! An [`AttestationReport`] mirrors the 384-byte SGX `REPORT` body: the
+//! enclave's measurements (MRENCLAVE, MRSIGNER), product ID and security
+//! version, attributes, and 64 bytes of report data chosen by the enclave.
+//! A report binds either one output, whose canonical hash fills the report
+//! data (see [`report_data_for_output`]), or the identity that signs the
+//! enclave's outputs (see [`report_data_for_identity`]). The enclave itself
+//! chooses the report data, so a quote binding its identity shows that
+//! every [`SignedEnclaveOutput`](crate::SignedEnclaveOutput) verifying under
+//! that identity came from the attested enclave.
+//!
+//! A [`Quote`] is the version 3 ECDSA-P256 DCAP quote that the quoting
+//! enclave produces from a report. Its binary layout follows Intel's
+//! "SGX ECDSA Quote Library API" specification:
+//!
+//! ```text
+//! header (48) || report body (384) || signature data length (u32 LE)
+//!     || report signature (64) || attestation key (64)
+//!     || QE report body (384) || QE report signature (64)
+//!     || QE auth data length (u16 LE) || QE auth data
+//!     || certification data type (u16 LE) || size (u32 LE) || data
+//! ```
+//!
+//! Without SGX hardware, [`simulated_report`] produces reports with stable
+//! simulated measurements and the debug attribute set, so that verifiers
+//! configured for production reject them.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use crate::{EnclaveError, EnclaveIdentity, EnclaveOutput, SignatureScheme};
+
+/// Domain separation tag of report data binding an enclave identity
+pub const IDENTITY_REPORT_DOMAIN: &[u8] = b"chaoschain-enclave/identity-report/v1";
+
+/// Size of an encoded [`AttestationReport`]
+pub const REPORT_BODY_SIZE: usize = 384;
+/// Size of an encoded [`QuoteHeader`]
+pub const QUOTE_HEADER_SIZE: usize = 48;
+
+/// Quote format version produced and understood by this crate
+pub const QUOTE_VERSION: u16 = 3;
+/// Attestation key type for ECDSA over P-256
+pub const ATTESTATION_KEY_TYPE_ECDSA_P256: u16 = 2;
+/// TEE type of SGX quotes
+pub const TEE_TYPE_SGX: u32 = 0;
+/// Vendor ID of Intel's quoting enclave
+pub const INTEL_QE_VENDOR_ID: [u8; 16] = [
+    0x93, 0x9a, 0x72, 0x33, 0xf7, 0x9c, 0x4c, 0xa9, 0x94, 0x0a, 0x0d, 0xb3, 0x95, 0x7f, 0x06, 0x07,
+];
+/// Certification data type for a PEM encoded PCK certificate chain
+pub const CERT_DATA_PCK_CERT_CHAIN: u16 = 5;
+
+/// Security attributes of an enclave
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Attributes {
+    pub flags: u64,
+    pub xfrm: u64,
+}
+
+impl Attributes {
+    pub const FLAG_INITTED: u64 = 0x1;
+    pub const FLAG_DEBUG: u64 = 0x2;
+    pub const FLAG_MODE64BIT: u64 = 0x4;
+
+    /// Whether the enclave was launched in debug mode, exposing its memory
+    pub fn is_debug(&self) -> bool {
+        self.flags & Self::FLAG_DEBUG != 0
+    }
+}
+
+/// Body of an SGX report
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AttestationReport {
+    #[serde(with = "crate::serde_hex")]
+    pub cpu_svn: [u8; 16],
+    pub misc_select: u32,
+    pub attributes: Attributes,
+    #[serde(with = "crate::serde_hex")]
+    pub mr_enclave: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub mr_signer: [u8; 32],
+    pub isv_prod_id: u16,
+    pub isv_svn: u16,
+    #[serde(with = "crate::serde_hex")]
+    pub report_data: [u8; 64],
+}
+
+impl AttestationReport {
+    /// Encode in the SGX `sgx_report_body_t` layout
+    pub fn to_bytes(&self) -> [u8; REPORT_BODY_SIZE] {
+        let mut out = [0u8; REPORT_BODY_SIZE];
+        out[0..16].copy_from_slice(&self.cpu_svn);
+        out[16..20].copy_from_slice(&self.misc_select.to_le_bytes());
+        // 20..48 reserved
+        out[48..56].copy_from_slice(&self.attributes.flags.to_le_bytes());
+        out[56..64].copy_from_slice(&self.attributes.xfrm.to_le_bytes());
+        out[64..96].copy_from_slice(&self.mr_enclave);
+        // 96..128 reserved
+        out[128..160].copy_from_slice(&self.mr_signer);
+        // 160..256 reserved
+        out[256..258].copy_from_slice(&self.isv_prod_id.to_le_bytes());
+        out[258..260].copy_from_slice(&self.isv_svn.to_le_bytes());
+        // 260..320 reserved
+        out[320..384].copy_from_slice(&self.report_data);
+        out
+    }
+
+    /// Decode from the SGX `sgx_report_body_t` layout
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let bytes: &[u8; REPORT_BODY_SIZE] = bytes
+            .try_into()
+            .map_err(|_| malformed("report body must be 384 bytes"))?;
+
+        Ok(Self {
+            cpu_svn: array(&bytes[0..16]),
+            misc_select: u32::from_le_bytes(array(&bytes[16..20])),
+            attributes: Attributes {
+                flags: u64::from_le_bytes(array(&bytes[48..56])),
+                xfrm: u64::from_le_bytes(array(&bytes[56..64])),
+            },
+            mr_enclave: array(&bytes[64..96]),
+            mr_signer: array(&bytes[128..160]),
+            isv_prod_id: u16::from_le_bytes(array(&bytes[256..258])),
+            isv_svn: u16::from_le_bytes(array(&bytes[258..260])),
+            report_data: array(&bytes[320..384]),
+        })
+    }
+
+    /// Whether this report's data commits to `output`
+    pub fn binds_output(&self, output: &EnclaveOutput) -> bool {
+        self.report_data == report_data_for_output(output)
+    }
+
+    /// Whether this report's data commits to the signing identity `identity`
+    pub fn binds_identity(&self, identity: &EnclaveIdentity) -> bool {
+        self.report_data == report_data_for_identity(identity)
+    }
+}
+
+/// Report data committing to an output: its canonical hash followed by 32
+/// zero bytes
+pub fn report_data_for_output(output: &EnclaveOutput) -> [u8; 64] {
+    let mut report_data = [0u8; 64];
+    report_data[..32].copy_from_slice(&output.output_hash());
+    report_data
+}
+
+/// Report data committing to a signing identity:
+/// `sha256(IDENTITY_REPORT_DOMAIN || scheme (u8) || public key)` followed by
+/// 32 zero bytes, with scheme 0 for ed25519 and 1 for secp256k1
+pub fn report_data_for_identity(identity: &EnclaveIdentity) -> [u8; 64] {
+    let scheme = match identity.scheme {
+        SignatureScheme::Ed25519 => 0u8,
+        SignatureScheme::Secp256k1 => 1,
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(IDENTITY_REPORT_DOMAIN);
+    hasher.update([scheme]);
+    hasher.update(&identity.public_key);
+    let mut report_data = [0u8; 64];
+    report_data[..32].copy_from_slice(&hasher.finalize());
+    report_data
+}
+
+/// Fixed 48-byte quote header
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuoteHeader {
+    pub version: u16,
+    pub attestation_key_type: u16,
+    pub tee_type: u32,
+    pub qe_svn: u16,
+    pub pce_svn: u16,
+    #[serde(with = "crate::serde_hex")]
+    pub qe_vendor_id: [u8; 16],
+    #[serde(with = "crate::serde_hex")]
+    pub user_data: [u8; 20],
+}
+
+impl QuoteHeader {
+    pub fn to_bytes(&self) -> [u8; QUOTE_HEADER_SIZE] {
+        let mut out = [0u8; QUOTE_HEADER_SIZE];
+        out[0..2].copy_from_slice(&self.version.to_le_bytes());
+        out[2..4].copy_from_slice(&self.attestation_key_type.to_le_bytes());
+        out[4..8].copy_from_slice(&self.tee_type.to_le_bytes());
+        out[8..10].copy_from_slice(&self.qe_svn.to_le_bytes());
+        out[10..12].copy_from_slice(&self.pce_svn.to_le_bytes());
+        out[12..28].copy_from_slice(&self.qe_vendor_id);
+        out[28..48].copy_from_slice(&self.user_data);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let bytes: &[u8; QUOTE_HEADER_SIZE] = bytes
+            .try_into()
+            .map_err(|_| malformed("quote header must be 48 bytes"))?;
+
+        Ok(Self {
+            version: u16::from_le_bytes(array(&bytes[0..2])),
+            attestation_key_type: u16::from_le_bytes(array(&bytes[2..4])),
+            tee_type: u32::from_le_bytes(array(&bytes[4..8])),
+            qe_svn: u16::from_le_bytes(array(&bytes[8..10])),
+            pce_svn: u16::from_le_bytes(array(&bytes[10..12])),
+            qe_vendor_id: array(&bytes[12..28]),
+            user_data: array(&bytes[28..48]),
+        })
+    }
+}
+
+/// Data certifying the quoting enclave's attestation key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CertificationData {
+    pub cert_type: u16,
+    #[serde(with = "crate::serde_hex")]
+    pub data: Vec<u8>,
+}
+
+/// ECDSA signature section of a quote
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuoteSignature {
+    /// Attestation key signature over the header and report body, `r || s`
+    #[serde(with = "crate::serde_hex")]
+    pub report_signature: [u8; 64],
+    /// Uncompressed P-256 attestation key without the `0x04` prefix
+    #[serde(with = "crate::serde_hex")]
+    pub attestation_key: [u8; 64],
+    /// Report of the quoting enclave, whose report data commits to the
+    /// attestation key and `qe_auth_data`
+    pub qe_report: AttestationReport,
+    /// PCK signature over the QE report body, `r || s`
+    #[serde(with = "crate::serde_hex")]
+    pub qe_report_signature: [u8; 64],
+    #[serde(with = "crate::serde_hex")]
+    pub qe_auth_data: Vec<u8>,
+    pub certification_data: CertificationData,
+}
+
+/// DCAP quote over an enclave report
+///
+/// Serializes to JSON as the hex encoding of its binary form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quote {
+    pub header: QuoteHeader,
+    pub report: AttestationReport,
+    pub signature: QuoteSignature,
+}
+
+impl Quote {
+    /// Bytes covered by the attestation key signature
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(QUOTE_HEADER_SIZE + REPORT_BODY_SIZE);
+        out.extend_from_slice(&self.header.to_bytes());
+        out.extend_from_slice(&self.report.to_bytes());
+        out
+    }
+
+    /// Report data the quoting enclave must place in its own report for
+    /// `attestation_key` and `qe_auth_data`
+    pub fn expected_qe_report_data(attestation_key: &[u8; 64], qe_auth_data: &[u8]) -> [u8; 64] {
+        let mut report_data = [0u8; 64];
+        let digest = Sha256::new()
+            .chain_update(attestation_key)
+            .chain_update(qe_auth_data)
+            .finalize();
+        report_data[..32].copy_from_slice(&digest);
+        report_data
+    }
+
+    /// Encode in the DCAP binary layout
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let signature = &self.signature;
+        let certification = &signature.certification_data;
+
+        let mut signature_data = Vec::new();
+        signature_data.extend_from_slice(&signature.report_signature);
+        signature_data.extend_from_slice(&signature.attestation_key);
+        signature_data.extend_from_slice(&signature.qe_report.to_bytes());
+        signature_data.extend_from_slice(&signature.qe_report_signature);
+        signature_data.extend_from_slice(&(signature.qe_auth_data.len() as u16).to_le_bytes());
+        signature_data.extend_from_slice(&signature.qe_auth_data);
+        signature_data.extend_from_slice(&certification.cert_type.to_le_bytes());
+        signature_data.extend_from_slice(&(certification.data.len() as u32).to_le_bytes());
+        signature_data.extend_from_slice(&certification.data);
+
+        let mut out = self.signed_bytes();
+        out.extend_from_slice(&(signature_data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&signature_data);
+        out
+    }
+
+    /// Decode from the DCAP binary layout
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let mut reader = Reader(bytes);
+        let header = QuoteHeader::from_bytes(reader.take(QUOTE_HEADER_SIZE)?)?;
+        if header.version != QUOTE_VERSION {
+            return Err(malformed(format!(
+                "unsupported quote version {}",
+                header.version
+            )));
+        }
+        if header.attestation_key_type != ATTESTATION_KEY_TYPE_ECDSA_P256 {
+            return Err(malformed(format!(
+                "unsupported attestation key type {}",
+                header.attestation_key_type
+            )));
+        }
+        let report = AttestationReport::from_bytes(reader.take(REPORT_BODY_SIZE)?)?;
+
+        let signature_len = reader.u32()? as usize;
+        let signature_data = reader.take(signature_len)?;
+        if !reader.0.is_empty() {
+            return Err(malformed("trailing bytes after quote signature data"));
+        }
+
+        let mut reader = Reader(signature_data);
+        let report_signature = array(reader.take(64)?);
+        let attestation_key = array(reader.take(64)?);
+        let qe_report = AttestationReport::from_bytes(reader.take(REPORT_BODY_SIZE)?)?;
+        let qe_report_signature = array(reader.take(64)?);
+        let qe_auth_len = reader.u16()? as usize;
+        let qe_auth_data = reader.take(qe_auth_len)?.to_vec();
+        let cert_type = reader.u16()?;
+        let cert_len = reader.u32()? as usize;
+        let data = reader.take(cert_len)?.to_vec();
+        if !reader.0.is_empty() {
+            return Err(malformed("quote signature data has trailing bytes"));
+        }
+
+        Ok(Self {
+            header,
+            report,
+            signature: QuoteSignature {
+                report_signature,
+                attestation_key,
+                qe_report,
+                qe_report_signature,
+                qe_auth_data,
+                certification_data: CertificationData { cert_type, data },
+            },
+        })
+    }
+}
+
+impl Serialize for Quote {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::serde_hex::serialize(&self.to_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quote {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes: Vec<u8> = crate::serde_hex::deserialize(deserializer)?;
+        Quote::from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+/// MRENCLAVE reported by simulated reports
+///
+/// Commits to every registered operation's measurement, so it changes
+/// whenever any operation's code does, as a real MRENCLAVE would.
+pub fn simulated_mr_enclave() -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"chaoschain-enclave/simulated-mrenclave/v1");
+    for operation in crate::registry::operations() {
+        hasher.update(operation.measurement);
+    }
+    hasher.finalize().into()
+}
+
+/// MRSIGNER reported by simulated reports
+pub fn simulated_mr_signer() -> [u8; 32] {
+    Sha256::digest(b"chaoschain-enclave/simulated-mrsigner/v1").into()
+}
+
+/// Produce a report as a simulated enclave would
+///
+/// The debug attribute is always set: nothing about a simulated enclave is
+/// confidential.
+#[cfg(not(feature = "sgx"))]
+pub fn simulated_report(report_data: [u8; 64]) -> AttestationReport {
+    AttestationReport {
+        cpu_svn: [0; 16],
+        misc_select: 0,
+        attributes: Attributes {
+            flags: Attributes::FLAG_INITTED | Attributes::FLAG_DEBUG | Attributes::FLAG_MODE64BIT,
+            xfrm: 0x3,
+        },
+        mr_enclave: simulated_mr_enclave(),
+        mr_signer: simulated_mr_signer(),
+        isv_prod_id: 1,
+        isv_svn: 1,
+        report_data,
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EnclaveError> {
+        if self.0.len() < len {
+            return Err(malformed("quote is truncated"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, EnclaveError> {
+        Ok(u16::from_le_bytes(array(self.take(2)?)))
+    }
+
+    fn u32(&mut self) -> Result<u32, EnclaveError> {
+        Ok(u32::from_le_bytes(array(self.take(4)?)))
+    }
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    bytes.try_into().expect("slice length checked by caller")
+}
+
+fn malformed(reason: impl Into<String>) -> EnclaveError {
+    EnclaveError::AttestationFailure {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(all(test, not(feature = "sgx")))]
+mod tests {
+    use super::*;
+    use crate::{process_operation, AddPayload, EnclaveInput, EnclaveSigner, Operation};
+
+    fn quote() -> Quote {
+        let output = process_operation(EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 2.into(),
+        })));
+        Quote {
+            header: QuoteHeader {
+                version: QUOTE_VERSION,
+                attestation_key_type: ATTESTATION_KEY_TYPE_ECDSA_P256,
+                tee_type: TEE_TYPE_SGX,
+                qe_svn: 8,
+                pce_svn: 13,
+                qe_vendor_id: INTEL_QE_VENDOR_ID,
+                user_data: [0; 20],
+            },
+            report: simulated_report(report_data_for_output(&output)),
+            signature: QuoteSignature {
+                report_signature: [1; 64],
+                attestation_key: [2; 64],
+                qe_report: simulated_report([3; 64]),
+                qe_report_signature: [4; 64],
+                qe_auth_data: (0..32).collect(),
+                certification_data: CertificationData {
+                    cert_type: CERT_DATA_PCK_CERT_CHAIN,
+                    data: b"-----BEGIN CERTIFICATE-----".to_vec(),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_report_body_layout() {
+        let report = simulated_report([0xab; 64]);
+        let bytes = report.to_bytes();
+
+        assert_eq!(bytes[48], 0x07);
+        assert_eq!(&bytes[64..96], &report.mr_enclave);
+        assert_eq!(&bytes[256..260], &[1, 0, 1, 0]);
+        assert_eq!(AttestationReport::from_bytes(&bytes).unwrap(), report);
+        assert!(report.attributes.is_debug());
+    }
+
+    #[test]
+    fn test_quote_round_trip() {
+        let quote = quote();
+        let bytes = quote.to_bytes();
+
+        assert_eq!(&bytes[0..2], &[3, 0]);
+        assert_eq!(Quote::from_bytes(&bytes).unwrap(), quote);
+
+        let json = serde_json::to_string(&quote).unwrap();
+        assert_eq!(serde_json::from_str::<Quote>(&json).unwrap(), quote);
+    }
+
+    #[test]
+    fn test_truncated_quote_is_rejected() {
+        let bytes = quote().to_bytes();
+        for len in [0, 47, 432, bytes.len() - 1] {
+            assert!(Quote::from_bytes(&bytes[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_report_binds_output() {
+        let output = process_operation(EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 2.into(),
+        })));
+        let other = process_operation(EnclaveInput::new(Operation::Add(AddPayload {
+            a: 2.into(),
+            b: 2.into(),
+        })));
+        let report = simulated_report(report_data_for_output(&output));
+
+        assert!(report.binds_output(&output));
+        assert!(!report.binds_output(&other));
+    }
+
+    #[test]
+    fn test_report_binds_identity() {
+        let signer = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let other = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let report = simulated_report(report_data_for_identity(&signer.identity()));
+
+        assert!(report.binds_identity(&signer.identity()));
+        assert!(!report.binds_identity(&other.identity()));
+    }
+}
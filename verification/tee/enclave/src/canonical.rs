@@ -0,0 +1,337 @@
+//! Canonical serialization and hashing of enclave values.
+//!
+//! Attestations, Proof-of-Agency records and on-chain anchors all hash the
+//! same enclave values, so every party must agree on the exact bytes. This
+//! module provides two deterministic encodings of any serde value:
+//!
+//! * [`to_canonical_json`] implements the JSON Canonicalization Scheme
+//!   (RFC 8785): no insignificant whitespace, object members sorted by their
+//!   UTF-16 code units, minimal string escaping and ECMAScript number
+//!   formatting. The one deliberate deviation is that integers outside the
+//!   IEEE-754 safe range (±2^53) are written with all their digits instead
+//!   of being rounded through `f64`; payloads that must round-trip through
+//!   strict JCS implementations should carry such amounts as strings.
+//! * [`to_compact_binary`] is a tag-length-value encoding of the same data
+//!   model with identical member ordering, for callers that want smaller
+//!   payloads.
+//!
+//! [`canonical_hash`] is SHA-256 over the canonical JSON bytes and backs
+//! [`EnclaveInput::input_hash`](crate::EnclaveInput::input_hash) and
+//! [`EnclaveOutput::output_hash`](crate::EnclaveOutput::output_hash).
+
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+use sha2::{Digest, Sha256};
+
+use crate::EnclaveError;
+
+const TAG_NULL: u8 = 0x00;
+const TAG_FALSE: u8 = 0x01;
+const TAG_TRUE: u8 = 0x02;
+const TAG_UINT: u8 = 0x03;
+const TAG_NINT: u8 = 0x04;
+const TAG_FLOAT: u8 = 0x05;
+const TAG_STRING: u8 = 0x06;
+const TAG_ARRAY: u8 = 0x07;
+const TAG_OBJECT: u8 = 0x08;
+
+/// Encode a value as RFC 8785 canonical JSON
+pub fn to_canonical_json<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, EnclaveError> {
+    let value = to_value(value)?;
+    let mut out = Vec::new();
+    write_json(&value, &mut out);
+    Ok(out)
+}
+
+/// Encode a value in the compact binary form
+///
+/// Layout, with every length and integer written as unsigned LEB128:
+///
+/// | Tag    | Body                                                  |
+/// |--------|-------------------------------------------------------|
+/// | `0x00` | null                                                  |
+/// | `0x01` | false                                                 |
+/// | `0x02` | true                                                  |
+/// | `0x03` | non-negative integer `n`                              |
+/// | `0x04` | negative integer, written as `-(n + 1)`               |
+/// | `0x05` | 8-byte big-endian IEEE-754 double                     |
+/// | `0x06` | byte length, UTF-8 bytes                              |
+/// | `0x07` | item count, items                                     |
+/// | `0x08` | member count, then per member: key length, key, value |
+///
+/// Object members appear in the same order as in the canonical JSON form.
+pub fn to_compact_binary<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, EnclaveError> {
+    let value = to_value(value)?;
+    let mut out = Vec::new();
+    write_binary(&value, &mut out);
+    Ok(out)
+}
+
+/// SHA-256 over the canonical JSON encoding of a value
+pub fn canonical_hash<T: Serialize + ?Sized>(value: &T) -> Result<[u8; 32], EnclaveError> {
+    Ok(Sha256::digest(to_canonical_json(value)?).into())
+}
+
+fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, EnclaveError> {
+    serde_json::to_value(value).map_err(|e| EnclaveError::invalid_payload(e.to_string()))
+}
+
+fn sorted_members(map: &Map<String, Value>) -> Vec<(&String, &Value)> {
+    let mut members: Vec<_> = map.iter().collect();
+    members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+    members
+}
+
+fn write_json(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.extend_from_slice(b"null"),
+        Value::Bool(true) => out.extend_from_slice(b"true"),
+        Value::Bool(false) => out.extend_from_slice(b"false"),
+        Value::Number(n) => write_json_number(n, out),
+        Value::String(s) => write_json_string(s, out),
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_json(item, out);
+            }
+            out.push(b']');
+        }
+        Value::Object(map) => {
+            out.push(b'{');
+            for (i, (key, item)) in sorted_members(map).into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_json_string(key, out);
+                out.push(b':');
+                write_json(item, out);
+            }
+            out.push(b'}');
+        }
+    }
+}
+
+fn write_json_string(s: &str, out: &mut Vec<u8>) {
+    out.push(b'"');
+    for c in s.chars() {
+        match c {
+            '"' => out.extend_from_slice(b"\\\""),
+            '\\' => out.extend_from_slice(b"\\\\"),
+            '\u{08}' => out.extend_from_slice(b"\\b"),
+            '\t' => out.extend_from_slice(b"\\t"),
+            '\n' => out.extend_from_slice(b"\\n"),
+            '\u{0c}' => out.extend_from_slice(b"\\f"),
+            '\r' => out.extend_from_slice(b"\\r"),
+            c if (c as u32) < 0x20 => {
+                out.extend_from_slice(format!("\\u{:04x}", c as u32).as_bytes())
+            }
+            c => {
+                let mut buf = [0u8; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    out.push(b'"');
+}
+
+fn write_json_number(n: &Number, out: &mut Vec<u8>) {
+    if let Some(i) = n.as_u64() {
+        out.extend_from_slice(i.to_string().as_bytes());
+    } else if let Some(i) = n.as_i64() {
+        out.extend_from_slice(i.to_string().as_bytes());
+    } else if let Some(f) = n.as_f64() {
+        out.extend_from_slice(format_es_number(f).as_bytes());
+    }
+}
+
+/// Format a finite double the way ECMAScript `Number.prototype.toString` does
+fn format_es_number(f: f64) -> String {
+    if f == 0.0 {
+        return "0".to_string();
+    }
+
+    // `{:e}` yields the shortest round-tripping digits, e.g. "-1.2345e-7"
+    let sci = format!("{:e}", f);
+    let (mantissa, exponent) = sci.split_once('e').expect("`{:e}` always has an exponent");
+    let exponent: i32 = exponent.parse().expect("`{:e}` exponent is an integer");
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", mantissa),
+    };
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exponent + 1;
+
+    let body = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat((-n) as usize), digits)
+    } else {
+        let exp_sign = if n - 1 < 0 { '-' } else { '+' };
+        let exp = (n - 1).abs();
+        if k == 1 {
+            format!("{}e{}{}", digits, exp_sign, exp)
+        } else {
+            format!("{}.{}e{}{}", &digits[..1], &digits[1..], exp_sign, exp)
+        }
+    };
+    format!("{}{}", sign, body)
+}
+
+fn write_leb128(mut n: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_binary_str(s: &str, out: &mut Vec<u8>) {
+    write_leb128(s.len() as u64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_binary(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(TAG_NULL),
+        Value::Bool(false) => out.push(TAG_FALSE),
+        Value::Bool(true) => out.push(TAG_TRUE),
+        Value::Number(n) => {
+            if let Some(i) = n.as_u64() {
+                out.push(TAG_UINT);
+                write_leb128(i, out);
+            } else if let Some(i) = n.as_i64() {
+                out.push(TAG_NINT);
+                write_leb128(!(i as u64), out);
+            } else if let Some(f) = n.as_f64() {
+                out.push(TAG_FLOAT);
+                out.extend_from_slice(&f.to_be_bytes());
+            }
+        }
+        Value::String(s) => {
+            out.push(TAG_STRING);
+            write_binary_str(s, out);
+        }
+        Value::Array(items) => {
+            out.push(TAG_ARRAY);
+            write_leb128(items.len() as u64, out);
+            for item in items {
+                write_binary(item, out);
+            }
+        }
+        Value::Object(map) => {
+            out.push(TAG_OBJECT);
+            write_leb128(map.len() as u64, out);
+            for (key, item) in sorted_members(map) {
+                write_binary_str(key, out);
+                write_binary(item, out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn jcs(value: &Value) -> String {
+        String::from_utf8(to_canonical_json(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_members_sorted_by_utf16() {
+        // Sorting example from RFC 8785 section 3.2.3
+        let value: Value = serde_json::from_str(
+            r#"{"\u20ac": "Euro Sign", "\r": "Carriage Return", "\ufb33": "Hebrew Letter Dalet With Dagesh",
+                "1": "One", "\ud83d\ude00": "Emoji: Grinning Face", "\u0080": "Control", "\u00f6": "Latin Small Letter O With Diaeresis"}"#,
+        )
+        .unwrap();
+        let keys: Vec<String> = jcs(&value)
+            .split(",")
+            .map(|member| {
+                member
+                    .split(':')
+                    .next()
+                    .unwrap()
+                    .trim_start_matches('{')
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "\"\\r\"",
+                "\"1\"",
+                "\"\u{80}\"",
+                "\"\u{f6}\"",
+                "\"\u{20ac}\"",
+                "\"\u{1f600}\"",
+                "\"\u{fb33}\""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_escaping() {
+        let value = json!({"s": "\u{0}\u{8}\t\n\u{c}\r\u{1f}\"\\/é€"});
+        assert_eq!(
+            jcs(&value),
+            "{\"s\":\"\\u0000\\b\\t\\n\\f\\r\\u001f\\\"\\\\/é€\"}"
+        );
+    }
+
+    #[test]
+    fn test_number_formatting() {
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-1.5, "-1.5"),
+            (1e21, "1e+21"),
+            (1e20, "100000000000000000000"),
+            (1e-7, "1e-7"),
+            (0.000001, "0.000001"),
+            (333333333.3333333, "333333333.3333333"),
+            (1.2345678901234568e20, "123456789012345680000"),
+            (4.5e-300, "4.5e-300"),
+            (f64::MAX, "1.7976931348623157e+308"),
+        ];
+        for (f, expected) in cases {
+            assert_eq!(format_es_number(f), expected, "formatting {}", f);
+        }
+    }
+
+    #[test]
+    fn test_canonical_json_ignores_input_order() {
+        let a: Value =
+            serde_json::from_str(r#"{ "b": [1, 2, {"y": null, "x": true}], "a": "z" }"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"a":"z","b":[1,2,{"x":true,"y":null}]}"#).unwrap();
+        assert_eq!(jcs(&a), r#"{"a":"z","b":[1,2,{"x":true,"y":null}]}"#);
+        assert_eq!(canonical_hash(&a).unwrap(), canonical_hash(&b).unwrap());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_compact_binary_layout() {
+        let value = json!({"b": -1, "a": [true, null, 300], "c": "hi"});
+        assert_eq!(
+            to_compact_binary(&value).unwrap(),
+            vec![
+                TAG_OBJECT, 3,
+                1, b'a', TAG_ARRAY, 3, TAG_TRUE, TAG_NULL, TAG_UINT, 0xac, 0x02,
+                1, b'b', TAG_NINT, 0,
+                1, b'c', TAG_STRING, 2, b'h', b'i',
+            ]
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Deployment settings of a running enclave.
+//!
+//! An [`EnclaveConfig`] bounds what the enclave will do with its keys. The
+//! host supplies it once, when the [`EnclaveState`](crate::EnclaveState) is
+//! first created. From then on it is sealed with the state and cannot change
+//! for as long as the enclave identity lives. Outputs that depend on a
+//! setting repeat it, so whoever checks the signature also sees the settings
+//! the enclave signed under.
+//!
+//! The defaults suit development: no endpoint is set, so the enclave signs
+//! no transactions until a deployment names one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::agency::rewards::RewardSchedule;
+use crate::ethereum::Address;
+use crate::EnclaveError;
+
+/// Default cap on `max_fee_per_gas` and `gas_price`: 500 gwei
+pub const DEFAULT_MAX_FEE_PER_GAS: u128 = 500_000_000_000;
+
+/// Default cap on a transaction's gas limit
+pub const DEFAULT_MAX_GAS_LIMIT: u64 = 1_000_000;
+
+/// Settings fixed when the enclave state is created
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EnclaveConfig {
+    /// Chain every transaction is signed for
+    pub chain_id: u64,
+    /// Address of the `ChaosEndpoint` contract, the only account the
+    /// enclave sends transactions to; `None` disables `sign_transaction`
+    pub endpoint: Option<Address>,
+    /// Highest `max_fee_per_gas`, or legacy `gas_price`, in wei
+    #[serde(with = "crate::serde_decimal")]
+    pub max_fee_per_gas: u128,
+    /// Highest gas limit of a transaction
+    pub max_gas_limit: u64,
+    /// Amounts `compute_rewards` pays out per action
+    #[serde(default)]
+    pub reward_schedule: RewardSchedule,
+}
+
+impl Default for EnclaveConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: 1,
+            endpoint: None,
+            max_fee_per_gas: DEFAULT_MAX_FEE_PER_GAS,
+            max_gas_limit: DEFAULT_MAX_GAS_LIMIT,
+            reward_schedule: RewardSchedule::default(),
+        }
+    }
+}
+
+impl EnclaveConfig {
+    /// Decode a config from JSON, as the host supplies it
+    pub fn from_json(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        serde_json::from_slice(bytes).map_err(|e| EnclaveError::invalid_payload(e.to_string()))
+    }
+}
@@ -0,0 +1,310 @@
+//! ECALL entry points exposed to the untrusted host.
+//!
+//! Declared in `Enclave.edl`. Inputs and outputs cross the boundary as JSON
+//! so the host needs no knowledge of the enclave's in-memory types.
+//!
+//! `ecall_process` runs an operation without any enclave state. A host that
+//! serves requests calls `ecall_init` once to create the [`EnclaveState`] or
+//! restore it from its last sealed blob, then sends every request through
+//! `ecall_process_signed`, which refuses replayed nonces and signs the
+//! output with the state's keys. After each signed output the host seals
+//! the state with `ecall_seal`, persists the blob and then advances its
+//! counter, all before replying.
+//!
+//! `ecall_create_report` produces the report the host turns into a DCAP
+//! quote. The enclave fills its report data with
+//! [`report_data_for_identity`] of the state's signing identity, so the
+//! quote vouches for every output that identity signs and the host cannot
+//! choose what it binds.
+//!
+//! There is no hardware monotonic counter in this build: the host keeps the
+//! counter value and passes it to `ecall_init` and `ecall_seal`, see
+//! [`HostCounter`].
+//!
+//! Every ECALL writes its output into a host buffer of `output_capacity`
+//! bytes and always stores the encoded size in `output_len`. If the output
+//! does not fit, the call returns `SGX_ERROR_OUT_OF_MEMORY` and keeps the
+//! output, which the host then collects with `ecall_take_output` into a
+//! buffer of the reported size. Stateful calls cannot simply be repeated, as
+//! their nonce has already been spent.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use sgx_types::{sgx_report_data_t, sgx_report_t, sgx_status_t, sgx_target_info_t};
+
+use crate::attestation::report_data_for_identity;
+use crate::{
+    process_json, EnclaveConfig, EnclaveError, EnclaveInput, EnclaveSigner, EnclaveState,
+    MonotonicCounter, SealPolicy, SealedBlob, Sealer, SignatureScheme,
+};
+
+/// State created by `ecall_init`
+static STATE: Mutex<Option<EnclaveState>> = Mutex::new(None);
+
+/// Output that did not fit the host's buffer, see the module documentation
+static PENDING: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// Monotonic counter whose value the host stores
+///
+/// Like the simulation build's `FileCounter` this catches a host handing back a
+/// stale blob by mistake, but not one that rewinds both blob and counter.
+struct HostCounter(u64);
+
+impl MonotonicCounter for HostCounter {
+    fn read(&self) -> Result<u64, EnclaveError> {
+        Ok(self.0)
+    }
+
+    fn increment(&mut self) -> Result<u64, EnclaveError> {
+        self.0 = self
+            .0
+            .checked_add(1)
+            .ok_or_else(|| EnclaveError::ResourceExhausted {
+                resource: "monotonic counter".to_string(),
+            })?;
+        Ok(self.0)
+    }
+}
+
+/// Process a JSON [`crate::EnclaveInput`] and write the JSON
+/// [`crate::EnclaveOutput`] into `output`
+///
+/// # Safety
+///
+/// The pointers must be valid for the given lengths; the edger8r generated
+/// bridge guarantees this for buffers it marshals.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_process(
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> sgx_status_t {
+    if input.is_null() {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let input = std::slice::from_raw_parts(input, input_len);
+    deliver(&process_json(input), output, output_capacity, output_len)
+}
+
+/// Create the enclave state and write the JSON
+/// `Result<EnclaveIdentity, EnclaveError>` into `output`
+///
+/// With an empty `sealed` buffer the state gets a fresh signing key and the
+/// JSON [`EnclaveConfig`] in `config`. Otherwise `sealed` is a binary
+/// [`SealedBlob`] from `ecall_seal`, which must have been sealed at the host
+/// counter value `counter`, or at `counter + 1` if the host saved it but did
+/// not advance the counter; the restored state keeps its sealed config and
+/// `config` is ignored. Fails with `SGX_ERROR_INVALID_STATE` if the state
+/// already exists.
+///
+/// # Safety
+///
+/// As for [`ecall_process`].
+#[no_mangle]
+pub unsafe extern "C" fn ecall_init(
+    sealed: *const u8,
+    sealed_len: usize,
+    counter: u64,
+    config: *const u8,
+    config_len: usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> sgx_status_t {
+    if output.is_null()
+        || output_len.is_null()
+        || (sealed.is_null() && sealed_len > 0)
+        || (config.is_null() && config_len > 0)
+    {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let mut state = match STATE.lock() {
+        Ok(state) => state,
+        Err(_) => return sgx_status_t::SGX_ERROR_UNEXPECTED,
+    };
+    if state.is_some() {
+        return sgx_status_t::SGX_ERROR_INVALID_STATE;
+    }
+
+    let restored = if sealed_len == 0 {
+        let config = std::slice::from_raw_parts(config, config_len);
+        EnclaveConfig::from_json(config).map(|config| {
+            EnclaveState::with_config(EnclaveSigner::generate(SignatureScheme::Secp256k1), config)
+        })
+    } else {
+        let sealed = std::slice::from_raw_parts(sealed, sealed_len);
+        SealedBlob::from_bytes(sealed)
+            .and_then(|blob| EnclaveState::unseal(&Sealer::new(), &blob, &mut HostCounter(counter)))
+    };
+    let identity = restored.map(|restored| {
+        let identity = restored.signer().identity();
+        *state = Some(restored);
+        identity
+    });
+    deliver(&identity, output, output_capacity, output_len)
+}
+
+/// Seal the enclave state at host counter value `counter + 1` and write the
+/// JSON `Result<SealedBlob, EnclaveError>` into `output`
+///
+/// The host must save the blob, and then store `counter + 1`, before it
+/// releases the output that led to this seal.
+///
+/// # Safety
+///
+/// As for [`ecall_process`].
+#[no_mangle]
+pub unsafe extern "C" fn ecall_seal(
+    counter: u64,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> sgx_status_t {
+    let state = match STATE.lock() {
+        Ok(state) => state,
+        Err(_) => return sgx_status_t::SGX_ERROR_UNEXPECTED,
+    };
+    let state = match state.as_ref() {
+        Some(state) => state,
+        None => return sgx_status_t::SGX_ERROR_INVALID_STATE,
+    };
+
+    let sealed = state.seal(&Sealer::new(), SealPolicy::MrSigner, &HostCounter(counter));
+    deliver(&sealed, output, output_capacity, output_len)
+}
+
+/// Process a JSON [`crate::EnclaveInput`] with the enclave state and write
+/// the JSON `Result<SignedEnclaveOutput, EnclaveError>` into `output`
+///
+/// The input must carry a fresh client nonce; see [`EnclaveState::process`].
+///
+/// # Safety
+///
+/// As for [`ecall_process`].
+#[no_mangle]
+pub unsafe extern "C" fn ecall_process_signed(
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> sgx_status_t {
+    if input.is_null() {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let mut state = match STATE.lock() {
+        Ok(state) => state,
+        Err(_) => return sgx_status_t::SGX_ERROR_UNEXPECTED,
+    };
+    let state = match state.as_mut() {
+        Some(state) => state,
+        None => return sgx_status_t::SGX_ERROR_INVALID_STATE,
+    };
+
+    let input = std::slice::from_raw_parts(input, input_len);
+    let signed = EnclaveInput::from_json(input).and_then(|input| state.process(input));
+    deliver(&signed, output, output_capacity, output_len)
+}
+
+/// Copy out the output kept by the last call that returned
+/// `SGX_ERROR_OUT_OF_MEMORY`
+///
+/// # Safety
+///
+/// As for [`ecall_process`].
+#[no_mangle]
+pub unsafe extern "C" fn ecall_take_output(
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> sgx_status_t {
+    if output.is_null() || output_len.is_null() {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let mut pending = match PENDING.lock() {
+        Ok(pending) => pending,
+        Err(_) => return sgx_status_t::SGX_ERROR_UNEXPECTED,
+    };
+    let encoded = match pending.take() {
+        Some(encoded) => encoded,
+        None => return sgx_status_t::SGX_ERROR_INVALID_STATE,
+    };
+
+    *output_len = encoded.len();
+    if encoded.len() > output_capacity {
+        *pending = Some(encoded);
+        return sgx_status_t::SGX_ERROR_OUT_OF_MEMORY;
+    }
+    std::ptr::copy_nonoverlapping(encoded.as_ptr(), output, encoded.len());
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Create a report for the quoting enclave described by `target_info`,
+/// binding the identity of the enclave state
+///
+/// Fails with `SGX_ERROR_INVALID_STATE` before `ecall_init`.
+///
+/// # Safety
+///
+/// Both pointers must be valid for one structure each; the edger8r
+/// generated bridge guarantees this.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_create_report(
+    target_info: *const sgx_target_info_t,
+    report: *mut sgx_report_t,
+) -> sgx_status_t {
+    if target_info.is_null() || report.is_null() {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let identity = match STATE.lock() {
+        Ok(state) => match state.as_ref() {
+            Some(state) => state.signer().identity(),
+            None => return sgx_status_t::SGX_ERROR_INVALID_STATE,
+        },
+        Err(_) => return sgx_status_t::SGX_ERROR_UNEXPECTED,
+    };
+
+    let report_data = sgx_report_data_t {
+        d: report_data_for_identity(&identity),
+    };
+    match sgx_tse::rsgx_create_report(&*target_info, &report_data) {
+        Ok(created) => {
+            *report = created;
+            sgx_status_t::SGX_SUCCESS
+        }
+        Err(status) => status,
+    }
+}
+
+/// Write `value` as JSON into `output`, keeping it for
+/// [`ecall_take_output`] if it does not fit
+unsafe fn deliver<T: Serialize>(
+    value: &T,
+    output: *mut u8,
+    output_capacity: usize,
+    output_len: *mut usize,
+) -> sgx_status_t {
+    if output.is_null() || output_len.is_null() {
+        return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+    }
+    let encoded = match serde_json::to_vec(value) {
+        Ok(encoded) => encoded,
+        Err(_) => return sgx_status_t::SGX_ERROR_UNEXPECTED,
+    };
+
+    *output_len = encoded.len();
+    if encoded.len() > output_capacity {
+        return match PENDING.lock() {
+            Ok(mut pending) => {
+                *pending = Some(encoded);
+                sgx_status_t::SGX_ERROR_OUT_OF_MEMORY
+            }
+            Err(_) => sgx_status_t::SGX_ERROR_UNEXPECTED,
+        };
+    }
+    std::ptr::copy_nonoverlapping(encoded.as_ptr(), output, encoded.len());
+    sgx_status_t::SGX_SUCCESS
+}
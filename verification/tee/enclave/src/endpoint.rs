@@ -0,0 +1,357 @@
+//! Calldata for `ChaosEndpoint.sol`, built from signed outputs.
+//!
+//! Each builder takes the [`SignedEnclaveOutput`] a call is about, checks its
+//! signature and that it holds the result the call needs, and encodes the
+//! call from that result alone, so what lands on-chain is exactly what the
+//! enclave attested. Builders also give the topics of the event the call
+//! emits, for matching the transaction receipt afterwards.
+//!
+//! The `attestation` argument of the endpoint is [`attestation`] of the
+//! signed output:
+//!
+//! ```text
+//! abi.encode(bytes32 inputHash, bytes32 outputHash, uint64 sequence,
+//!     uint64 timestamp, uint8 scheme, bytes publicKey, bytes signature)
+//! ```
+//!
+//! with `scheme` 1 for ed25519 and 2 for secp256k1. It carries everything
+//! needed to check the signature except the output itself, whose hash it
+//! names.
+
+use serde::{Deserialize, Serialize};
+
+use crate::abi::{self, Token};
+use crate::agency::receipts::{Receipt, ReceiptKind};
+use crate::agency::rewards::RewardDistribution;
+use crate::signing::{SignatureScheme, SignedEnclaveOutput};
+use crate::{EnclaveError, OperationResult};
+
+pub const SUBMIT_PROPOSAL: &str = "submitProposal(string,bytes)";
+pub const REGISTER_AGENT: &str = "registerAgent(string,string,bytes)";
+pub const ANCHOR_ACTION: &str = "anchorAction(string,string,string,string,bytes)";
+pub const RECORD_OUTCOME: &str = "recordOutcome(string,string)";
+pub const DISTRIBUTE_REWARDS: &str = "distributeRewards(string,string)";
+
+pub const PROPOSAL_SUBMITTED: &str = "ProposalSubmitted(uint256,address,string,bytes)";
+pub const AGENT_REGISTERED: &str = "AgentRegistered(string,address,string,bytes)";
+pub const ACTION_ANCHORED: &str = "ActionAnchored(string,string,string,string,bytes)";
+pub const REWARDS_DISTRIBUTED: &str = "RewardsDistributed(string,address,string)";
+
+/// A call to the endpoint contract
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EndpointCall {
+    /// Signature of the called function
+    pub function: String,
+    #[serde(with = "crate::serde_hex")]
+    pub calldata: Vec<u8>,
+    /// Leading topics of the emitted event; empty if the call emits none
+    ///
+    /// Topics that depend on execution, such as the id of a new proposal,
+    /// are left out.
+    #[serde(with = "crate::serde_hex::list")]
+    pub event_topics: Vec<[u8; 32]>,
+}
+
+/// One of the builders below together with its arguments, so a call can be
+/// requested over the wire
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "function", rename_all = "snake_case")]
+pub enum EndpointCallSpec {
+    SubmitProposal {
+        output: SignedEnclaveOutput,
+        metadata_uri: String,
+    },
+    RegisterAgent {
+        output: SignedEnclaveOutput,
+        metadata_uri: String,
+    },
+    AnchorAction {
+        output: SignedEnclaveOutput,
+        metadata_uri: String,
+    },
+    RecordOutcome {
+        output: SignedEnclaveOutput,
+        outcome_uri: String,
+    },
+    DistributeRewards {
+        output: SignedEnclaveOutput,
+    },
+}
+
+impl EndpointCallSpec {
+    /// The signed output the call is built from
+    pub fn output(&self) -> &SignedEnclaveOutput {
+        match self {
+            EndpointCallSpec::SubmitProposal { output, .. }
+            | EndpointCallSpec::RegisterAgent { output, .. }
+            | EndpointCallSpec::AnchorAction { output, .. }
+            | EndpointCallSpec::RecordOutcome { output, .. }
+            | EndpointCallSpec::DistributeRewards { output } => output,
+        }
+    }
+
+    /// Run the builder
+    pub fn build(&self) -> Result<EndpointCall, EnclaveError> {
+        match self {
+            EndpointCallSpec::SubmitProposal {
+                output,
+                metadata_uri,
+            } => submit_proposal(output, metadata_uri),
+            EndpointCallSpec::RegisterAgent {
+                output,
+                metadata_uri,
+            } => register_agent(output, metadata_uri),
+            EndpointCallSpec::AnchorAction {
+                output,
+                metadata_uri,
+            } => anchor_action(output, metadata_uri),
+            EndpointCallSpec::RecordOutcome {
+                output,
+                outcome_uri,
+            } => record_outcome(output, outcome_uri),
+            EndpointCallSpec::DistributeRewards { output } => distribute_rewards(output),
+        }
+    }
+}
+
+/// The `attestation` argument for `signed`, see the module documentation
+pub fn attestation(signed: &SignedEnclaveOutput) -> Result<Vec<u8>, EnclaveError> {
+    let scheme = match signed.signer.scheme {
+        SignatureScheme::Ed25519 => 1,
+        SignatureScheme::Secp256k1 => 2,
+    };
+    abi::encode(&[
+        Token::FixedBytes(signed.input_hash.to_vec()),
+        Token::FixedBytes(signed.output_hash.to_vec()),
+        Token::uint(signed.sequence as u128),
+        Token::uint(signed.timestamp as u128),
+        Token::uint(scheme),
+        Token::Bytes(signed.signer.public_key.clone()),
+        Token::Bytes(signed.signature.clone()),
+    ])
+}
+
+/// `submitProposal(metadataURI, attestation)` for any successful output
+pub fn submit_proposal(
+    signed: &SignedEnclaveOutput,
+    metadata_uri: &str,
+) -> Result<EndpointCall, EnclaveError> {
+    signed.verify()?;
+    if !signed.output.is_ok() {
+        return Err(EnclaveError::invalid_payload(
+            "only successful outputs can back a proposal",
+        ));
+    }
+    call(
+        SUBMIT_PROPOSAL,
+        &[
+            Token::String(metadata_uri.to_string()),
+            Token::Bytes(attestation(signed)?),
+        ],
+        vec![abi::event_topic(PROPOSAL_SUBMITTED)],
+    )
+}
+
+/// `registerAgent(agentId, metadataURI, attestation)` for the agent whose
+/// genesis receipt `signed` holds
+pub fn register_agent(
+    signed: &SignedEnclaveOutput,
+    metadata_uri: &str,
+) -> Result<EndpointCall, EnclaveError> {
+    let receipt = receipt(signed, ReceiptKind::Action)?;
+    if receipt.height != 0 {
+        return Err(EnclaveError::invalid_payload(
+            "agents are registered from their genesis receipt",
+        ));
+    }
+    call(
+        REGISTER_AGENT,
+        &[
+            Token::String(receipt.agent_id.clone()),
+            Token::String(metadata_uri.to_string()),
+            Token::Bytes(attestation(signed)?),
+        ],
+        vec![
+            abi::event_topic(AGENT_REGISTERED),
+            abi::indexed_topic(&Token::String(receipt.agent_id.clone()))?,
+        ],
+    )
+}
+
+/// `anchorAction(actionId, agentId, actionType, metadataURI, attestation)`
+/// for the action receipt `signed` holds
+pub fn anchor_action(
+    signed: &SignedEnclaveOutput,
+    metadata_uri: &str,
+) -> Result<EndpointCall, EnclaveError> {
+    let receipt = receipt(signed, ReceiptKind::Action)?;
+    call(
+        ANCHOR_ACTION,
+        &[
+            Token::String(receipt.action_id.clone()),
+            Token::String(receipt.agent_id.clone()),
+            Token::String(receipt.action_type.as_str().to_string()),
+            Token::String(metadata_uri.to_string()),
+            Token::Bytes(attestation(signed)?),
+        ],
+        vec![
+            abi::event_topic(ACTION_ANCHORED),
+            abi::indexed_topic(&Token::String(receipt.action_id.clone()))?,
+        ],
+    )
+}
+
+/// `recordOutcome(actionId, outcomeURI)` for the outcome receipt `signed`
+/// holds
+pub fn record_outcome(
+    signed: &SignedEnclaveOutput,
+    outcome_uri: &str,
+) -> Result<EndpointCall, EnclaveError> {
+    let receipt = receipt(signed, ReceiptKind::Outcome)?;
+    call(
+        RECORD_OUTCOME,
+        &[
+            Token::String(receipt.action_id.clone()),
+            Token::String(outcome_uri.to_string()),
+        ],
+        Vec::new(),
+    )
+}
+
+/// `distributeRewards(actionId, recipientsData)` for the reward
+/// distribution `signed` holds
+pub fn distribute_rewards(signed: &SignedEnclaveOutput) -> Result<EndpointCall, EnclaveError> {
+    let distribution: &RewardDistribution = match verified_result(signed)? {
+        OperationResult::ComputeRewards(distribution) => distribution,
+        _ => return Err(wrong_result("a reward distribution")),
+    };
+    call(
+        DISTRIBUTE_REWARDS,
+        &[
+            Token::String(distribution.action_id.clone()),
+            Token::String(distribution.recipients_data.clone()),
+        ],
+        vec![
+            abi::event_topic(REWARDS_DISTRIBUTED),
+            abi::indexed_topic(&Token::String(distribution.action_id.clone()))?,
+        ],
+    )
+}
+
+fn call(
+    function: &str,
+    arguments: &[Token],
+    event_topics: Vec<[u8; 32]>,
+) -> Result<EndpointCall, EnclaveError> {
+    Ok(EndpointCall {
+        function: function.to_string(),
+        calldata: abi::encode_call(function, arguments)?,
+        event_topics,
+    })
+}
+
+fn verified_result(signed: &SignedEnclaveOutput) -> Result<&OperationResult, EnclaveError> {
+    signed.verify()?;
+    signed
+        .output
+        .result
+        .as_ref()
+        .map_err(|_| EnclaveError::invalid_payload("output holds an error"))
+}
+
+fn receipt(signed: &SignedEnclaveOutput, kind: ReceiptKind) -> Result<&Receipt, EnclaveError> {
+    match (verified_result(signed)?, kind) {
+        (OperationResult::RecordAction(receipt), ReceiptKind::Action)
+        | (OperationResult::RecordOutcome(receipt), ReceiptKind::Outcome) => Ok(receipt),
+        (_, ReceiptKind::Action) => Err(wrong_result("an action receipt")),
+        (_, ReceiptKind::Outcome) => Err(wrong_result("an outcome receipt")),
+    }
+}
+
+fn wrong_result(expected: &str) -> EnclaveError {
+    EnclaveError::invalid_payload(format!("output does not hold {}", expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agency::receipts::RecordActionPayload;
+    use crate::agency::ActionType;
+    use crate::{AddPayload, EnclaveInput, EnclaveSigner, EnclaveState, Operation};
+
+    fn signed(operation: Operation) -> SignedEnclaveOutput {
+        let mut state = EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Secp256k1));
+        state
+            .process(EnclaveInput::new(operation).with_nonce("dao-a", 1))
+            .unwrap()
+    }
+
+    fn record_action() -> SignedEnclaveOutput {
+        signed(Operation::RecordAction(RecordActionPayload {
+            agent_id: "agent-1".to_string(),
+            action_id: "a1".to_string(),
+            action_type: ActionType::Analyze,
+            input_hash: [1; 32],
+            output_hash: [2; 32],
+            parent: None,
+        }))
+    }
+
+    /// Dynamic argument `index` of `calldata` as a string
+    fn string_argument(calldata: &[u8], index: usize) -> String {
+        let args = &calldata[4..];
+        let word =
+            |at: usize| u64::from_be_bytes(args[at + 24..at + 32].try_into().unwrap()) as usize;
+        let offset = word(index * 32);
+        let len = word(offset);
+        String::from_utf8(args[offset + 32..offset + 32 + len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_anchor_action_from_receipt() {
+        let signed = record_action();
+        let call = anchor_action(&signed, "ipfs://action").unwrap();
+
+        assert_eq!(&call.calldata[..4], &abi::selector(ANCHOR_ACTION));
+        let arguments: Vec<String> = (0..4).map(|i| string_argument(&call.calldata, i)).collect();
+        assert_eq!(arguments, vec!["a1", "agent-1", "analyze", "ipfs://action"]);
+        assert_eq!(call.event_topics[1], crate::merkle::keccak256(b"a1"));
+
+        assert!(register_agent(&signed, "ipfs://agent").is_ok());
+        assert!(record_outcome(&signed, "ipfs://outcome").is_err());
+        assert!(distribute_rewards(&signed).is_err());
+    }
+
+    #[test]
+    fn test_attestation_layout() {
+        let signed = signed(Operation::Add(AddPayload {
+            a: 2.into(),
+            b: 3.into(),
+        }));
+        let encoded = attestation(&signed).unwrap();
+
+        assert_eq!(&encoded[..32], &signed.input_hash);
+        assert_eq!(&encoded[32..64], &signed.output_hash);
+        assert_eq!(encoded[4 * 32 + 31], 2);
+        assert_eq!(
+            encoded[7 * 32 + 31] as usize,
+            signed.signer.public_key.len()
+        );
+
+        let call = submit_proposal(&signed, "ipfs://proposal").unwrap();
+        assert_eq!(string_argument(&call.calldata, 0), "ipfs://proposal");
+        assert!(anchor_action(&signed, "ipfs://action").is_err());
+    }
+
+    #[test]
+    fn test_tampered_output_is_refused() {
+        let mut signed = record_action();
+        if let Ok(OperationResult::RecordAction(receipt)) = &mut signed.output.result {
+            receipt.action_id = "a2".to_string();
+        }
+        assert_eq!(
+            anchor_action(&signed, "ipfs://action").unwrap_err().code(),
+            3001
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Structured errors returned by the enclave.
+//!
+//! Each [`EnclaveError`] variant has a stable numeric code that host code,
+//! the API layer and dashboards can switch on instead of matching strings.
+//! Codes are part of the wire contract: never renumber an existing variant,
+//! only append new ones. Retired codes stay reserved.
+//!
+//! Code 1002 was `bad_arity`, for operations given the wrong number of
+//! positional parameters. Payloads are now typed, so a missing argument is a
+//! missing field and reported as `invalid_payload`.
+//!
+//! | Code | Kind                    |
+//! |------|-------------------------|
+//! | 1001 | `unsupported_operation` |
+//! | 1002 | reserved                |
+//! | 1003 | `invalid_payload`       |
+//! | 1004 | `version_mismatch`      |
+//! | 2001 | `resource_exhausted`    |
+//! | 3001 | `attestation_failure`   |
+//! | 3002 | `sealing_failure`       |
+//! | 3003 | `replay_detected`       |
+//! | 3004 | `rollback_detected`     |
+//! | 9001 | `internal`              |
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Error produced while decoding or executing an enclave operation
+///
+/// On the wire an error is an object carrying `code`, `kind`, a human
+/// readable `message` and the variant's fields, e.g.
+/// `{"code": 1004, "kind": "version_mismatch", "message": "...", "expected": 1, "actual": 2}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(remote = "Self", tag = "kind", rename_all = "snake_case")]
+pub enum EnclaveError {
+    /// The requested operation is not known to this enclave build
+    UnsupportedOperation { operation: String },
+    /// The payload could not be decoded or failed validation
+    InvalidPayload { reason: String },
+    /// The input targets a different protocol version
+    VersionMismatch { expected: u32, actual: u32 },
+    /// An enclave resource (memory, counters, key slots) ran out
+    ResourceExhausted { resource: String },
+    /// Producing or checking an attestation failed
+    AttestationFailure { reason: String },
+    /// Sealing or unsealing persistent state failed
+    SealingFailure { reason: String },
+    /// A request reuses a nonce its client already spent
+    ReplayDetected {
+        client: String,
+        nonce: u64,
+        last_nonce: u64,
+    },
+    /// Sealed state is older than the monotonic counter allows
+    RollbackDetected { sealed: u64, current: u64 },
+    /// An unexpected internal failure
+    Internal { reason: String },
+}
+
+impl EnclaveError {
+    /// Stable numeric code for this error
+    pub fn code(&self) -> u16 {
+        match self {
+            EnclaveError::UnsupportedOperation { .. } => 1001,
+            EnclaveError::InvalidPayload { .. } => 1003,
+            EnclaveError::VersionMismatch { .. } => 1004,
+            EnclaveError::ResourceExhausted { .. } => 2001,
+            EnclaveError::AttestationFailure { .. } => 3001,
+            EnclaveError::SealingFailure { .. } => 3002,
+            EnclaveError::ReplayDetected { .. } => 3003,
+            EnclaveError::RollbackDetected { .. } => 3004,
+            EnclaveError::Internal { .. } => 9001,
+        }
+    }
+
+    /// Shorthand for an [`EnclaveError::InvalidPayload`]
+    pub fn invalid_payload(reason: impl Into<String>) -> Self {
+        EnclaveError::InvalidPayload {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for EnclaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnclaveError::UnsupportedOperation { operation } => {
+                write!(f, "unsupported operation '{}'", operation)
+            }
+            EnclaveError::InvalidPayload { reason } => write!(f, "invalid payload: {}", reason),
+            EnclaveError::VersionMismatch { expected, actual } => write!(
+                f,
+                "unsupported protocol version {} (expected {})",
+                actual, expected
+            ),
+            EnclaveError::ResourceExhausted { resource } => {
+                write!(f, "resource exhausted: {}", resource)
+            }
+            EnclaveError::AttestationFailure { reason } => {
+                write!(f, "attestation failure: {}", reason)
+            }
+            EnclaveError::SealingFailure { reason } => write!(f, "sealing failure: {}", reason),
+            EnclaveError::ReplayDetected {
+                client,
+                nonce,
+                last_nonce,
+            } => write!(
+                f,
+                "replayed request: nonce {} from client '{}' is not above {}",
+                nonce, client, last_nonce
+            ),
+            EnclaveError::RollbackDetected { sealed, current } => write!(
+                f,
+                "sealed state rolled back: counter {} is behind {}",
+                sealed, current
+            ),
+            EnclaveError::Internal { reason } => write!(f, "internal error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EnclaveError {}
+
+#[derive(Serialize)]
+struct WireErrorRef<'a> {
+    code: u16,
+    message: String,
+    #[serde(flatten, serialize_with = "serialize_detail")]
+    detail: &'a EnclaveError,
+}
+
+#[derive(Deserialize)]
+struct WireError {
+    #[serde(flatten, deserialize_with = "EnclaveError::deserialize")]
+    detail: EnclaveError,
+}
+
+fn serialize_detail<S: Serializer>(
+    error: &&EnclaveError,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    EnclaveError::serialize(error, serializer)
+}
+
+impl Serialize for EnclaveError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WireErrorRef {
+            code: self.code(),
+            message: self.to_string(),
+            detail: self,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EnclaveError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        WireError::deserialize(deserializer).map(|wire| wire.detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        let reason = || "boom".to_string();
+        let cases = [
+            (
+                EnclaveError::UnsupportedOperation {
+                    operation: "multiply".to_string(),
+                },
+                1001,
+            ),
+            (EnclaveError::invalid_payload("bad"), 1003),
+            (
+                EnclaveError::VersionMismatch {
+                    expected: 1,
+                    actual: 2,
+                },
+                1004,
+            ),
+            (
+                EnclaveError::ResourceExhausted {
+                    resource: "memory".to_string(),
+                },
+                2001,
+            ),
+            (EnclaveError::AttestationFailure { reason: reason() }, 3001),
+            (EnclaveError::SealingFailure { reason: reason() }, 3002),
+            (
+                EnclaveError::ReplayDetected {
+                    client: "dao-a".to_string(),
+                    nonce: 1,
+                    last_nonce: 1,
+                },
+                3003,
+            ),
+            (
+                EnclaveError::RollbackDetected {
+                    sealed: 1,
+                    current: 2,
+                },
+                3004,
+            ),
+            (EnclaveError::Internal { reason: reason() }, 9001),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.code(), code);
+        }
+    }
+
+    #[test]
+    fn test_error_wire_format_round_trip() {
+        let error = EnclaveError::VersionMismatch {
+            expected: 1,
+            actual: 2,
+        };
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], 1004);
+        assert_eq!(json["kind"], "version_mismatch");
+        assert_eq!(json["expected"], 1);
+        assert_eq!(json["actual"], 2);
+        assert!(json["message"].as_str().unwrap().contains("version 2"));
+
+        assert_eq!(serde_json::from_value::<EnclaveError>(json).unwrap(), error);
+    }
+}
@@ -0,0 +1,504 @@
+//! Verified block context.
+//!
+//! Operations that analyse recent blocks can take their data from a
+//! [`BlockContext`] instead of plain numbers. The host supplies raw headers
+//! and a checkpoint, the hash of the newest block, which the host and the
+//! auditors of the result trust to be on the canonical chain. The enclave
+//! checks that the newest header hashes to the checkpoint and that every
+//! other header is the parent of the one after it, so the whole range is
+//! fixed by the checkpoint hash: a host that alters any field of any header
+//! breaks the chain.
+//!
+//! The chain runs backwards from the checkpoint because only ancestry is
+//! bound by hashes. Headers after a trusted block would need validator
+//! signatures to be trusted, which the enclave does not check.
+//!
+//! A context may also carry Merkle-Patricia proofs of receipts and of
+//! accounts and storage slots, checked against the roots in the verified
+//! headers. Results computed from a context report its [`BlockRange`], so an
+//! attestation states "computed over blocks N..M, the last with hash H".
+
+use serde::{Deserialize, Serialize};
+
+use super::header::BlockHeader;
+use super::receipt::Receipt;
+use super::rlp::Item;
+use super::{trie, Address};
+use crate::fixed::{Fixed, Rounding};
+use crate::merkle::keccak256;
+use crate::EnclaveError;
+
+/// Upper bound on headers in one context
+pub const MAX_CONTEXT_BLOCKS: usize = 1_024;
+
+const GWEI: u64 = 1_000_000_000;
+
+/// Newest block of a context, trusted by whoever relies on the result
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub number: u64,
+    #[serde(with = "crate::serde_hex")]
+    pub hash: [u8; 32],
+}
+
+/// Chain data for an operation to verify before using it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockContext {
+    pub checkpoint: Checkpoint,
+    /// RLP-encoded headers, oldest first, ending with the checkpoint block
+    #[serde(with = "crate::serde_hex::list")]
+    pub headers: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub receipt_proofs: Vec<ReceiptProof>,
+    #[serde(default)]
+    pub account_proofs: Vec<AccountProof>,
+}
+
+/// Proof of the receipt at `index` in block `block_number`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptProof {
+    pub block_number: u64,
+    pub index: u64,
+    #[serde(with = "crate::serde_hex::list")]
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// Proof of an account, and optionally of its storage, after block
+/// `block_number`, as returned by `eth_getProof`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountProof {
+    pub block_number: u64,
+    pub address: Address,
+    #[serde(with = "crate::serde_hex::list")]
+    pub proof: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub storage: Vec<StorageProof>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StorageProof {
+    #[serde(with = "crate::serde_hex")]
+    pub slot: [u8; 32],
+    #[serde(with = "crate::serde_hex::list")]
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// Blocks `first..=last`, the last of which has hash `hash`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRange {
+    pub first: u64,
+    pub last: u64,
+    #[serde(with = "crate::serde_hex")]
+    pub hash: [u8; 32],
+}
+
+/// Account state in the state trie
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub nonce: u64,
+    /// Balance in wei
+    #[serde(with = "crate::serde_decimal")]
+    pub balance: u128,
+    #[serde(with = "crate::serde_hex")]
+    pub storage_root: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub code_hash: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProvenReceipt {
+    pub block_number: u64,
+    pub index: u64,
+    pub receipt: Receipt,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProvenAccount {
+    pub block_number: u64,
+    pub address: Address,
+    /// `None` if the account does not exist
+    pub account: Option<Account>,
+    pub storage: Vec<ProvenSlot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProvenSlot {
+    #[serde(with = "crate::serde_hex")]
+    pub slot: [u8; 32],
+    /// Big-endian word; zero for unset slots
+    #[serde(with = "crate::serde_hex")]
+    pub value: [u8; 32],
+}
+
+/// Everything a [`BlockContext`] proves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedBlocks {
+    pub range: BlockRange,
+    /// Oldest first
+    pub headers: Vec<BlockHeader>,
+    pub receipts: Vec<ProvenReceipt>,
+    pub accounts: Vec<ProvenAccount>,
+}
+
+/// Result of the `verify_block_context` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockContextReport {
+    pub range: BlockRange,
+    pub receipts: Vec<ProvenReceipt>,
+    pub accounts: Vec<ProvenAccount>,
+}
+
+impl BlockContext {
+    /// Check the header chain and every proof
+    pub fn verify(&self) -> Result<VerifiedBlocks, EnclaveError> {
+        if self.headers.len() > MAX_CONTEXT_BLOCKS {
+            return Err(EnclaveError::ResourceExhausted {
+                resource: format!(
+                    "context blocks ({} > {})",
+                    self.headers.len(),
+                    MAX_CONTEXT_BLOCKS
+                ),
+            });
+        }
+        let headers = self
+            .headers
+            .iter()
+            .map(|raw| BlockHeader::decode(raw))
+            .collect::<Result<Vec<_>, _>>()?;
+        let newest = headers
+            .last()
+            .ok_or_else(|| EnclaveError::invalid_payload("block context has no headers"))?;
+
+        let checkpoint = self.checkpoint;
+        if newest.number != checkpoint.number
+            || keccak256(&self.headers[headers.len() - 1]) != checkpoint.hash
+        {
+            return Err(broken_chain(format!(
+                "newest header is not checkpoint block {}",
+                checkpoint.number
+            )));
+        }
+        for (pair, raw) in headers.windows(2).zip(&self.headers) {
+            let (parent, child) = (&pair[0], &pair[1]);
+            if child.parent_hash != keccak256(raw)
+                || Some(child.number) != parent.number.checked_add(1)
+            {
+                return Err(broken_chain(format!(
+                    "block {} is not the parent of block {}",
+                    parent.number, child.number
+                )));
+            }
+        }
+
+        let range = BlockRange {
+            first: headers[0].number,
+            last: checkpoint.number,
+            hash: checkpoint.hash,
+        };
+        let header = |number: u64| {
+            number
+                .checked_sub(range.first)
+                .and_then(|offset| headers.get(offset as usize))
+                .ok_or_else(|| {
+                    EnclaveError::invalid_payload(format!(
+                        "block {} is outside the context",
+                        number
+                    ))
+                })
+        };
+
+        let receipts = self
+            .receipt_proofs
+            .iter()
+            .map(|proof| {
+                let root = header(proof.block_number)?.receipts_root;
+                let encoded =
+                    trie::verify_proof(&root, &trie::index_key(proof.index), &proof.proof)?
+                        .ok_or_else(|| {
+                            EnclaveError::invalid_payload(format!(
+                                "block {} has no receipt {}",
+                                proof.block_number, proof.index
+                            ))
+                        })?;
+                Ok(ProvenReceipt {
+                    block_number: proof.block_number,
+                    index: proof.index,
+                    receipt: Receipt::decode(&encoded)?,
+                })
+            })
+            .collect::<Result<_, EnclaveError>>()?;
+
+        let accounts = self
+            .account_proofs
+            .iter()
+            .map(|proof| {
+                let root = header(proof.block_number)?.state_root;
+                let account =
+                    trie::verify_proof(&root, &keccak256(&proof.address.0), &proof.proof)?
+                        .map(|encoded| decode_account(&encoded))
+                        .transpose()?;
+                let storage_root = account
+                    .as_ref()
+                    .map_or(trie::EMPTY_ROOT, |account| account.storage_root);
+                let storage = proof
+                    .storage
+                    .iter()
+                    .map(|slot| {
+                        let value = match trie::verify_proof(
+                            &storage_root,
+                            &keccak256(&slot.slot),
+                            &slot.proof,
+                        )? {
+                            Some(encoded) => Item::decode(&encoded)?.as_uint_be()?,
+                            None => [0; 32],
+                        };
+                        Ok(ProvenSlot {
+                            slot: slot.slot,
+                            value,
+                        })
+                    })
+                    .collect::<Result<_, EnclaveError>>()?;
+                Ok(ProvenAccount {
+                    block_number: proof.block_number,
+                    address: proof.address,
+                    account,
+                    storage,
+                })
+            })
+            .collect::<Result<_, EnclaveError>>()?;
+
+        Ok(VerifiedBlocks {
+            range,
+            headers,
+            receipts,
+            accounts,
+        })
+    }
+}
+
+impl VerifiedBlocks {
+    /// The checkpoint block
+    pub fn newest(&self) -> &BlockHeader {
+        self.headers
+            .last()
+            .expect("verified contexts are not empty")
+    }
+
+    /// Gas used by each block, newest first
+    pub fn gas_used(&self) -> Vec<u64> {
+        self.headers
+            .iter()
+            .rev()
+            .map(|header| header.gas_used)
+            .collect()
+    }
+
+    /// Base fee of each block in gwei, newest first
+    pub fn base_fees_gwei(&self) -> Result<Vec<Fixed>, EnclaveError> {
+        let gwei = Fixed::from(GWEI);
+        self.headers
+            .iter()
+            .rev()
+            .map(|header| Fixed::from(base_fee(header)?).div(gwei, Rounding::Down))
+            .collect()
+    }
+
+    pub fn report(self) -> BlockContextReport {
+        BlockContextReport {
+            range: self.range,
+            receipts: self.receipts,
+            accounts: self.accounts,
+        }
+    }
+}
+
+/// Base fee of a post-London block, in wei
+pub fn base_fee(header: &BlockHeader) -> Result<u64, EnclaveError> {
+    header.base_fee_per_gas.ok_or_else(|| {
+        EnclaveError::invalid_payload(format!("block {} has no base fee", header.number))
+    })
+}
+
+fn decode_account(encoded: &[u8]) -> Result<Account, EnclaveError> {
+    let item = Item::decode(encoded)?;
+    let [nonce, balance, storage_root, code_hash] = item.as_list()? else {
+        return Err(EnclaveError::invalid_payload("account must have 4 fields"));
+    };
+    Ok(Account {
+        nonce: nonce.as_u64()?,
+        balance: balance.as_u128()?,
+        storage_root: storage_root.as_array()?,
+        code_hash: code_hash.as_array()?,
+    })
+}
+
+fn broken_chain(reason: String) -> EnclaveError {
+    EnclaveError::AttestationFailure {
+        reason: format!("unverified block context: {}", reason),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ethereum::receipt::{logs_bloom, Log};
+    use crate::ethereum::trie::tests::build;
+
+    pub(crate) const ACCOUNT: Address = Address([0xaa; 20]);
+
+    fn receipt(cumulative_gas_used: u64) -> Receipt {
+        let logs = vec![Log {
+            address: ACCOUNT,
+            topics: vec![[0x01; 32]],
+            data: Vec::new(),
+        }];
+        Receipt {
+            tx_type: 2,
+            success: true,
+            cumulative_gas_used,
+            logs_bloom: logs_bloom(&logs),
+            logs,
+        }
+    }
+
+    fn account_rlp(storage_root: [u8; 32]) -> Vec<u8> {
+        Item::List(vec![
+            Item::uint(3),
+            Item::uint(5_000_000_000_000_000_000),
+            Item::bytes(&storage_root),
+            Item::bytes(&keccak256(b"code")),
+        ])
+        .encode()
+    }
+
+    /// A context of `blocks` post-London blocks from number 100, each with
+    /// two receipts and `ACCOUNT` holding 42 in slot 1, and all trie nodes
+    pub(crate) fn context(blocks: u64) -> (BlockContext, Vec<Vec<u8>>) {
+        let (receipts_root, mut nodes) = build(&[
+            (trie::index_key(0), receipt(21_000).encode()),
+            (trie::index_key(1), receipt(90_000).encode()),
+        ]);
+        let mut slot = [0u8; 32];
+        slot[31] = 1;
+        let (storage_root, storage_nodes) =
+            build(&[(keccak256(&slot).to_vec(), Item::uint(42).encode())]);
+        let (state_root, state_nodes) = build(&[
+            (keccak256(&ACCOUNT.0).to_vec(), account_rlp(storage_root)),
+            (
+                keccak256(&[0xbb; 20]).to_vec(),
+                account_rlp(trie::EMPTY_ROOT),
+            ),
+        ]);
+        nodes.extend(storage_nodes);
+        nodes.extend(state_nodes);
+
+        let mut parent_hash = [0x99; 32];
+        let mut headers = Vec::new();
+        for offset in 0..blocks {
+            let mut header = crate::ethereum::header::tests::genesis();
+            header.parent_hash = parent_hash;
+            header.number = 100 + offset;
+            header.gas_limit = 30_000_000;
+            header.gas_used = 10_000_000 + offset * 1_000_000;
+            header.difficulty = 0;
+            header.base_fee_per_gas = Some(7 * GWEI + offset * GWEI / 2);
+            header.receipts_root = receipts_root;
+            header.state_root = state_root;
+            parent_hash = header.hash();
+            headers.push(header.encode());
+        }
+        let context = BlockContext {
+            checkpoint: Checkpoint {
+                number: 99 + blocks,
+                hash: parent_hash,
+            },
+            headers,
+            receipt_proofs: Vec::new(),
+            account_proofs: Vec::new(),
+        };
+        (context, nodes)
+    }
+
+    #[test]
+    fn test_chain_is_anchored_at_checkpoint() {
+        let (context, _) = context(4);
+        let verified = context.verify().unwrap();
+        assert_eq!(verified.range.first, 100);
+        assert_eq!(verified.range.last, 103);
+        assert_eq!(verified.range.hash, verified.newest().hash());
+        assert_eq!(verified.gas_used()[0], 13_000_000);
+        let base_fees: Vec<String> = verified
+            .base_fees_gwei()
+            .unwrap()
+            .iter()
+            .map(Fixed::to_string)
+            .collect();
+        assert_eq!(base_fees, ["8.5", "8", "7.5", "7"]);
+
+        let mut tampered = context.clone();
+        let mut header = BlockHeader::decode(&tampered.headers[1]).unwrap();
+        header.gas_used += 1;
+        tampered.headers[1] = header.encode();
+        assert_eq!(tampered.verify().unwrap_err().code(), 3001);
+
+        let mut wrong_checkpoint = context.clone();
+        wrong_checkpoint.checkpoint.hash[0] ^= 1;
+        assert_eq!(wrong_checkpoint.verify().unwrap_err().code(), 3001);
+
+        let mut reordered = context;
+        reordered.headers.swap(0, 1);
+        assert!(reordered.verify().is_err());
+    }
+
+    #[test]
+    fn test_proofs_against_verified_roots() {
+        let (mut context, nodes) = context(2);
+        let mut slot = [0u8; 32];
+        slot[31] = 1;
+        context.receipt_proofs.push(ReceiptProof {
+            block_number: 101,
+            index: 1,
+            proof: nodes.clone(),
+        });
+        context.account_proofs.push(AccountProof {
+            block_number: 100,
+            address: ACCOUNT,
+            proof: nodes.clone(),
+            storage: vec![
+                StorageProof {
+                    slot,
+                    proof: nodes.clone(),
+                },
+                StorageProof {
+                    slot: [0x77; 32],
+                    proof: nodes.clone(),
+                },
+            ],
+        });
+        context.account_proofs.push(AccountProof {
+            block_number: 100,
+            address: Address([0xcc; 20]),
+            proof: nodes.clone(),
+            storage: Vec::new(),
+        });
+
+        let report = context.verify().unwrap().report();
+        assert_eq!(report.receipts[0].receipt, receipt(90_000));
+        let account = report.accounts[0].account.as_ref().unwrap();
+        assert_eq!(account.balance, 5_000_000_000_000_000_000);
+        assert_eq!(report.accounts[0].storage[0].value[31], 42);
+        assert_eq!(report.accounts[0].storage[1].value, [0; 32]);
+        assert_eq!(report.accounts[1].account, None);
+
+        let mut outside = context.clone();
+        outside.receipt_proofs[0].block_number = 102;
+        assert_eq!(outside.verify().unwrap_err().code(), 1003);
+
+        let mut missing = context;
+        missing.receipt_proofs[0].index = 2;
+        assert!(missing.verify().is_err());
+        missing.receipt_proofs[0].index = 1;
+        missing.receipt_proofs[0].proof.clear();
+        assert_eq!(missing.verify().unwrap_err().code(), 3001);
+    }
+}
@@ -0,0 +1,217 @@
+//! Block headers.
+//!
+//! A [`BlockHeader`] holds every field of the RLP header a block hash commits
+//! to, including the fields later forks appended: the base fee (London),
+//! withdrawals root (Shanghai), blob gas fields and beacon root (Cancun) and
+//! requests hash (Prague). Appended fields are present up to the fork of the
+//! block and absent after, so a header decodes from, and re-encodes to, the
+//! exact bytes its hash was computed over.
+
+use serde::{Deserialize, Serialize};
+
+use super::rlp::Item;
+use super::Address;
+use crate::merkle::keccak256;
+use crate::EnclaveError;
+
+/// Number of fields in a header before London
+const BASE_FIELDS: usize = 15;
+
+/// Header of a block
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockHeader {
+    #[serde(with = "crate::serde_hex")]
+    pub parent_hash: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub ommers_hash: [u8; 32],
+    pub beneficiary: Address,
+    #[serde(with = "crate::serde_hex")]
+    pub state_root: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub transactions_root: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub receipts_root: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub logs_bloom: [u8; 256],
+    #[serde(with = "crate::serde_decimal")]
+    pub difficulty: u128,
+    pub number: u64,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    pub timestamp: u64,
+    #[serde(with = "crate::serde_hex")]
+    pub extra_data: Vec<u8>,
+    #[serde(with = "crate::serde_hex")]
+    pub mix_hash: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub nonce: [u8; 8],
+    #[serde(default)]
+    pub base_fee_per_gas: Option<u64>,
+    #[serde(default, with = "crate::serde_hex::option")]
+    pub withdrawals_root: Option<[u8; 32]>,
+    #[serde(default)]
+    pub blob_gas_used: Option<u64>,
+    #[serde(default)]
+    pub excess_blob_gas: Option<u64>,
+    #[serde(default, with = "crate::serde_hex::option")]
+    pub parent_beacon_block_root: Option<[u8; 32]>,
+    #[serde(default, with = "crate::serde_hex::option")]
+    pub requests_hash: Option<[u8; 32]>,
+}
+
+impl BlockHeader {
+    /// Block hash: Keccak-256 of the RLP encoding
+    pub fn hash(&self) -> [u8; 32] {
+        keccak256(&self.encode())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.to_item().encode()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        Self::from_item(&Item::decode(bytes)?)
+    }
+
+    /// Header as an RLP list
+    ///
+    /// Fork fields are written up to the first absent one.
+    pub fn to_item(&self) -> Item {
+        let mut fields = vec![
+            Item::bytes(&self.parent_hash),
+            Item::bytes(&self.ommers_hash),
+            Item::bytes(&self.beneficiary.0),
+            Item::bytes(&self.state_root),
+            Item::bytes(&self.transactions_root),
+            Item::bytes(&self.receipts_root),
+            Item::bytes(&self.logs_bloom),
+            Item::uint(self.difficulty),
+            Item::uint(self.number as u128),
+            Item::uint(self.gas_limit as u128),
+            Item::uint(self.gas_used as u128),
+            Item::uint(self.timestamp as u128),
+            Item::bytes(&self.extra_data),
+            Item::bytes(&self.mix_hash),
+            Item::bytes(&self.nonce),
+        ];
+        let fork_fields = [
+            self.base_fee_per_gas.map(|fee| Item::uint(fee as u128)),
+            self.withdrawals_root.map(|root| Item::bytes(&root)),
+            self.blob_gas_used.map(|gas| Item::uint(gas as u128)),
+            self.excess_blob_gas.map(|gas| Item::uint(gas as u128)),
+            self.parent_beacon_block_root.map(|root| Item::bytes(&root)),
+            self.requests_hash.map(|hash| Item::bytes(&hash)),
+        ];
+        fields.extend(fork_fields.into_iter().map_while(|field| field));
+        Item::List(fields)
+    }
+
+    pub fn from_item(item: &Item) -> Result<Self, EnclaveError> {
+        let fields = item.as_list()?;
+        if fields.len() < BASE_FIELDS || fields.len() > BASE_FIELDS + 6 {
+            return Err(EnclaveError::invalid_payload(format!(
+                "block header has {} fields",
+                fields.len()
+            )));
+        }
+        let fork = |index: usize| fields.get(BASE_FIELDS + index);
+
+        Ok(Self {
+            parent_hash: fields[0].as_array()?,
+            ommers_hash: fields[1].as_array()?,
+            beneficiary: Address(fields[2].as_array()?),
+            state_root: fields[3].as_array()?,
+            transactions_root: fields[4].as_array()?,
+            receipts_root: fields[5].as_array()?,
+            logs_bloom: fields[6].as_array()?,
+            difficulty: fields[7].as_u128()?,
+            number: fields[8].as_u64()?,
+            gas_limit: fields[9].as_u64()?,
+            gas_used: fields[10].as_u64()?,
+            timestamp: fields[11].as_u64()?,
+            extra_data: fields[12].as_bytes()?.to_vec(),
+            mix_hash: fields[13].as_array()?,
+            nonce: fields[14].as_array()?,
+            base_fee_per_gas: fork(0).map(Item::as_u64).transpose()?,
+            withdrawals_root: fork(1).map(Item::as_array).transpose()?,
+            blob_gas_used: fork(2).map(Item::as_u64).transpose()?,
+            excess_blob_gas: fork(3).map(Item::as_u64).transpose()?,
+            parent_beacon_block_root: fork(4).map(Item::as_array).transpose()?,
+            requests_hash: fork(5).map(Item::as_array).transpose()?,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn bytes32(hex: &str) -> [u8; 32] {
+        hex::FromHex::from_hex(hex).unwrap()
+    }
+
+    /// The mainnet genesis header
+    pub(crate) fn genesis() -> BlockHeader {
+        BlockHeader {
+            parent_hash: [0; 32],
+            ommers_hash: bytes32(
+                "1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+            ),
+            beneficiary: Address([0; 20]),
+            state_root: bytes32("d7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544"),
+            transactions_root: bytes32(
+                "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            ),
+            receipts_root: bytes32(
+                "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            ),
+            logs_bloom: [0; 256],
+            difficulty: 0x4_0000_0000,
+            number: 0,
+            gas_limit: 5000,
+            gas_used: 0,
+            timestamp: 0,
+            extra_data: bytes32("11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa")
+                .to_vec(),
+            mix_hash: [0; 32],
+            nonce: [0, 0, 0, 0, 0, 0, 0, 0x42],
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            requests_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_genesis_hash() {
+        let header = genesis();
+        assert_eq!(
+            hex::encode(header.hash()),
+            "d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3"
+        );
+        assert_eq!(BlockHeader::decode(&header.encode()).unwrap(), header);
+    }
+
+    #[test]
+    fn test_fork_fields_round_trip() {
+        let mut header = genesis();
+        header.base_fee_per_gas = Some(7);
+        header.withdrawals_root = Some([1; 32]);
+        header.blob_gas_used = Some(0);
+        header.excess_blob_gas = Some(0);
+        header.parent_beacon_block_root = Some([2; 32]);
+
+        let encoded = header.encode();
+        assert_eq!(BlockHeader::decode(&encoded).unwrap(), header);
+        assert_ne!(header.hash(), genesis().hash());
+
+        let json = serde_json::to_string(&header).unwrap();
+        assert_eq!(serde_json::from_str::<BlockHeader>(&json).unwrap(), header);
+
+        let mut fields = Item::decode(&encoded).unwrap().as_list().unwrap().to_vec();
+        fields.truncate(BASE_FIELDS - 1);
+        assert!(BlockHeader::decode(&Item::List(fields).encode()).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Ethereum account held by the enclave.
+//!
+//! The enclave derives a secp256k1 [`EthereumKey`] from its signing key, so
+//! the account survives restarts wherever the signer's sealed state does,
+//! and only ever reveals its [`Address`]. [`transaction`] signs transactions
+//! with it, and only for calldata built by [`crate::endpoint`] from outputs
+//! this enclave signed, so a compromised host cannot use the account to
+//! anchor anything the enclave did not attest.
+//!
+//! [`rlp`] encodes and decodes the chain's own data structures, typed in
+//! [`header`], [`transaction`] and [`receipt`], so the enclave can hash and
+//! check what the host reports from the chain rather than take it on trust.
+//! [`context`] builds on them, with [`trie`] proofs, to verify the block
+//! data operations compute over.
+
+pub mod context;
+pub mod header;
+pub mod receipt;
+pub mod rlp;
+pub mod transaction;
+pub mod trie;
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use crate::merkle::keccak256;
+use crate::EnclaveError;
+
+/// Domain separation tag of the Ethereum key derivation
+pub const ETHEREUM_KEY_DOMAIN: &[u8] = b"chaoschain-enclave/ethereum-key/v1";
+
+/// 20-byte account address, written as lowercase `0x`-prefixed hex
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address(pub [u8; 20]);
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for Address {
+    type Err = EnclaveError;
+
+    /// Parse 40 hex digits, with or without `0x`, in any case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        <[u8; 20] as hex::FromHex>::from_hex(digits)
+            .map(Address)
+            .map_err(|_| EnclaveError::invalid_payload(format!("{} is not an address", s)))
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// ECDSA signature with the parity of its nonce point, as Ethereum wants it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoverableSignature {
+    #[serde(with = "crate::serde_hex")]
+    pub r: [u8; 32],
+    /// In the lower half of the curve order, per EIP-2, when the enclave signs
+    #[serde(with = "crate::serde_hex")]
+    pub s: [u8; 32],
+    pub y_parity: bool,
+}
+
+/// Private key of the enclave's Ethereum account
+pub struct EthereumKey {
+    key: k256::ecdsa::SigningKey,
+}
+
+impl EthereumKey {
+    /// Derive the key from secret seed material
+    ///
+    /// The key is SHA-256 over `ETHEREUM_KEY_DOMAIN || seed || counter (u8)`
+    /// for the first counter from zero that yields a valid scalar.
+    pub(crate) fn derive(seed: &[u8]) -> Self {
+        (0..=u8::MAX)
+            .find_map(|counter| {
+                let mut hasher = Sha256::new();
+                hasher.update(ETHEREUM_KEY_DOMAIN);
+                hasher.update(seed);
+                hasher.update([counter]);
+                let candidate = zeroize::Zeroizing::new(<[u8; 32]>::from(hasher.finalize()));
+                k256::ecdsa::SigningKey::from_slice(candidate.as_ref()).ok()
+            })
+            .map(|key| Self { key })
+            .expect("a valid scalar within 256 attempts")
+    }
+
+    /// Address of this account: the last 20 bytes of the Keccak-256 hash of
+    /// the uncompressed public key
+    pub fn address(&self) -> Address {
+        address_of(self.key.verifying_key())
+    }
+
+    /// Sign a 32-byte message hash
+    pub fn sign_hash(&self, hash: &[u8; 32]) -> Result<RecoverableSignature, EnclaveError> {
+        let (signature, recovery_id) =
+            self.key
+                .sign_prehash_recoverable(hash)
+                .map_err(|e| EnclaveError::Internal {
+                    reason: format!("signing failed: {}", e),
+                })?;
+        let (r, s) = signature.split_bytes();
+        Ok(RecoverableSignature {
+            r: r.into(),
+            s: s.into(),
+            y_parity: recovery_id.is_y_odd(),
+        })
+    }
+}
+
+impl fmt::Debug for EthereumKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EthereumKey")
+            .field("address", &self.address())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Address of the account with public key `key`
+pub fn address_of(key: &k256::ecdsa::VerifyingKey) -> Address {
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    Address(hash[12..].try_into().expect("20 bytes"))
+}
+
+/// Address of the account that made `signature` over `hash`
+pub fn recover(hash: &[u8; 32], signature: &RecoverableSignature) -> Result<Address, EnclaveError> {
+    let invalid = |e: k256::ecdsa::Error| EnclaveError::AttestationFailure {
+        reason: format!("unrecoverable signature: {}", e),
+    };
+    let parsed = k256::ecdsa::Signature::from_scalars(signature.r, signature.s).map_err(invalid)?;
+    let recovery_id = k256::ecdsa::RecoveryId::new(signature.y_parity, false);
+    k256::ecdsa::VerifyingKey::recover_from_prehash(hash, &parsed, recovery_id)
+        .map(|key| address_of(&key))
+        .map_err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_of_known_key() {
+        // Private key 1, whose address is well known
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let key = EthereumKey {
+            key: k256::ecdsa::SigningKey::from_slice(&secret).unwrap(),
+        };
+        assert_eq!(
+            key.address().to_string(),
+            "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+    }
+
+    #[test]
+    fn test_signatures_recover_to_address() {
+        let key = EthereumKey::derive(b"seed");
+        assert_eq!(key.address(), EthereumKey::derive(b"seed").address());
+        assert_ne!(key.address(), EthereumKey::derive(b"other").address());
+
+        let hash = keccak256(b"message");
+        let signature = key.sign_hash(&hash).unwrap();
+        assert!(signature.s[0] < 0x80);
+        assert_eq!(recover(&hash, &signature).unwrap(), key.address());
+    }
+
+    #[test]
+    fn test_address_parsing() {
+        let address: Address = "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_string(&address).unwrap(),
+            "\"0x7e5f4552091a69125d5dfcb7b8c2659029395bdf\""
+        );
+        assert!("0x7e5f".parse::<Address>().is_err());
+    }
+}
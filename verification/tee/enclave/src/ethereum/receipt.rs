@@ -0,0 +1,191 @@
+//! Transaction receipts and their logs.
+//!
+//! Receipts are encoded as the receipts trie stores them: the RLP list
+//! `[status, cumulativeGasUsed, logsBloom, logs]`, prefixed with the
+//! transaction type byte for typed transactions. Only post-Byzantium
+//! receipts, which carry a status rather than an intermediate state root,
+//! are supported.
+
+use serde::{Deserialize, Serialize};
+
+use super::rlp::Item;
+use super::Address;
+use crate::merkle::keccak256;
+use crate::EnclaveError;
+
+/// Event emitted during a transaction
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Log {
+    pub address: Address,
+    #[serde(with = "crate::serde_hex::list")]
+    pub topics: Vec<[u8; 32]>,
+    #[serde(with = "crate::serde_hex")]
+    pub data: Vec<u8>,
+}
+
+/// Receipt of one transaction
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    /// EIP-2718 type of the transaction; 0 for legacy transactions
+    pub tx_type: u8,
+    pub success: bool,
+    pub cumulative_gas_used: u64,
+    #[serde(with = "crate::serde_hex")]
+    pub logs_bloom: [u8; 256],
+    pub logs: Vec<Log>,
+}
+
+impl Receipt {
+    /// Encoding as stored in the receipts trie
+    pub fn encode(&self) -> Vec<u8> {
+        let item = Item::List(vec![
+            Item::uint(self.success as u128),
+            Item::uint(self.cumulative_gas_used as u128),
+            Item::bytes(&self.logs_bloom),
+            Item::List(
+                self.logs
+                    .iter()
+                    .map(|log| {
+                        Item::List(vec![
+                            Item::bytes(&log.address.0),
+                            Item::List(log.topics.iter().map(|t| Item::bytes(t)).collect()),
+                            Item::bytes(&log.data),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ]);
+        match self.tx_type {
+            0 => item.encode(),
+            tx_type => [&[tx_type], item.encode().as_slice()].concat(),
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let (tx_type, body) = match bytes.first() {
+            Some(&tx_type) if tx_type < 0x80 => (tx_type, &bytes[1..]),
+            _ => (0, bytes),
+        };
+        let item = Item::decode(body)?;
+        let fields = item.as_list()?;
+        if fields.len() != 4 {
+            return Err(EnclaveError::invalid_payload(format!(
+                "receipt has {} fields",
+                fields.len()
+            )));
+        }
+        let success = match fields[0].as_bytes()? {
+            [] => false,
+            [1] => true,
+            status if status.len() == 32 => {
+                return Err(EnclaveError::invalid_payload(
+                    "pre-Byzantium receipts are not supported",
+                ))
+            }
+            _ => return Err(EnclaveError::invalid_payload("invalid receipt status")),
+        };
+        let logs = fields[3]
+            .as_list()?
+            .iter()
+            .map(|log| {
+                let [address, topics, data] = log.as_list()? else {
+                    return Err(EnclaveError::invalid_payload("log must have 3 fields"));
+                };
+                Ok(Log {
+                    address: Address(address.as_array()?),
+                    topics: topics
+                        .as_list()?
+                        .iter()
+                        .map(Item::as_array)
+                        .collect::<Result<_, _>>()?,
+                    data: data.as_bytes()?.to_vec(),
+                })
+            })
+            .collect::<Result<_, EnclaveError>>()?;
+
+        Ok(Self {
+            tx_type,
+            success,
+            cumulative_gas_used: fields[1].as_u64()?,
+            logs_bloom: fields[2].as_array()?,
+            logs,
+        })
+    }
+
+    /// Whether `logs_bloom` is the bloom filter of `logs`
+    pub fn bloom_matches(&self) -> bool {
+        logs_bloom(&self.logs) == self.logs_bloom
+    }
+}
+
+/// 2048-bit bloom filter over the addresses and topics of `logs`
+///
+/// Each value sets three bits, taken from the low 11 bits of the first three
+/// byte pairs of its Keccak-256 hash.
+pub fn logs_bloom(logs: &[Log]) -> [u8; 256] {
+    let mut bloom = [0u8; 256];
+    let values = logs.iter().flat_map(|log| {
+        std::iter::once(log.address.0.as_slice()).chain(log.topics.iter().map(|t| t.as_slice()))
+    });
+    for value in values {
+        let hash = keccak256(value);
+        for pair in hash[..6].chunks(2) {
+            let bit = (u16::from_be_bytes([pair[0], pair[1]]) & 0x7ff) as usize;
+            bloom[255 - bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    bloom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(tx_type: u8) -> Receipt {
+        let logs = vec![Log {
+            address: Address([0x11; 20]),
+            topics: vec![keccak256(b"Transfer(address,address,uint256)"), [0x22; 32]],
+            data: vec![0x33; 40],
+        }];
+        Receipt {
+            tx_type,
+            success: true,
+            cumulative_gas_used: 52_000,
+            logs_bloom: logs_bloom(&logs),
+            logs,
+        }
+    }
+
+    #[test]
+    fn test_round_trips() {
+        for tx_type in [0, 2, 3] {
+            let receipt = receipt(tx_type);
+            let encoded = receipt.encode();
+            assert_eq!(encoded[0] == tx_type, tx_type != 0);
+            assert_eq!(Receipt::decode(&encoded).unwrap(), receipt);
+        }
+    }
+
+    #[test]
+    fn test_bloom() {
+        let mut receipt = receipt(2);
+        assert!(receipt.bloom_matches());
+
+        // An address or topic sets up to three bits, and a log's bloom
+        // covers the bloom of any log with a subset of its values
+        let address_only = logs_bloom(&[Log {
+            topics: Vec::new(),
+            ..receipt.logs[0].clone()
+        }]);
+        let bits: u32 = address_only.iter().map(|b| b.count_ones()).sum();
+        assert!((1..=3).contains(&bits));
+        assert!(address_only
+            .iter()
+            .zip(&receipt.logs_bloom)
+            .all(|(part, full)| part & !full == 0));
+        assert_ne!(address_only, receipt.logs_bloom);
+        receipt.logs[0].topics.pop();
+        assert!(!receipt.bloom_matches());
+        assert_eq!(logs_bloom(&[]), [0; 256]);
+    }
+}
@@ -0,0 +1,279 @@
+//! Recursive Length Prefix encoding.
+//!
+//! The serialization Ethereum uses for headers, transactions, receipts and
+//! trie nodes, as specified in the yellow paper, appendix B. An [`Item`] is
+//! either a byte string or a list of items; integers are byte strings holding
+//! their big-endian encoding with no leading zeros, so zero is the empty
+//! string.
+//!
+//! [`Item::decode`] accepts canonical encodings only: every value has exactly
+//! one encoding, so a decoded item re-encodes to the bytes it came from and
+//! hashes of decoded data can be trusted.
+
+use crate::EnclaveError;
+
+/// Deepest nesting of lists accepted by [`Item::decode`]
+pub const MAX_DEPTH: usize = 32;
+
+/// An RLP value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Bytes(Vec<u8>),
+    List(Vec<Item>),
+}
+
+impl Item {
+    /// Canonical encoding of an unsigned integer
+    pub fn uint(value: u128) -> Self {
+        Self::uint_be(&value.to_be_bytes())
+    }
+
+    /// Canonical encoding of a big-endian unsigned integer of any width
+    pub fn uint_be(bytes: &[u8]) -> Self {
+        let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+        Item::Bytes(bytes[start..].to_vec())
+    }
+
+    pub fn bytes(bytes: &[u8]) -> Self {
+        Item::Bytes(bytes.to_vec())
+    }
+
+    /// Encoding of this item
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_to(&mut out);
+        out
+    }
+
+    /// Decode exactly one item from `bytes`
+    pub fn decode(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let (item, rest) = decode_item(bytes, 0)?;
+        if !rest.is_empty() {
+            return Err(malformed("trailing bytes after item"));
+        }
+        Ok(item)
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8], EnclaveError> {
+        match self {
+            Item::Bytes(bytes) => Ok(bytes),
+            Item::List(_) => Err(malformed("expected a byte string, found a list")),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[Item], EnclaveError> {
+        match self {
+            Item::List(items) => Ok(items),
+            Item::Bytes(_) => Err(malformed("expected a list, found a byte string")),
+        }
+    }
+
+    /// Byte string of exactly `N` bytes
+    pub fn as_array<const N: usize>(&self) -> Result<[u8; N], EnclaveError> {
+        self.as_bytes()?
+            .try_into()
+            .map_err(|_| malformed(&format!("expected {} bytes", N)))
+    }
+
+    /// Integer of at most `N` bytes, as a big-endian array
+    pub fn as_uint_be<const N: usize>(&self) -> Result<[u8; N], EnclaveError> {
+        let bytes = self.as_bytes()?;
+        if bytes.first() == Some(&0) {
+            return Err(malformed("integer has leading zeros"));
+        }
+        if bytes.len() > N {
+            return Err(malformed(&format!("integer wider than {} bytes", N)));
+        }
+        let mut out = [0u8; N];
+        out[N - bytes.len()..].copy_from_slice(bytes);
+        Ok(out)
+    }
+
+    pub fn as_u64(&self) -> Result<u64, EnclaveError> {
+        self.as_uint_be().map(u64::from_be_bytes)
+    }
+
+    pub fn as_u128(&self) -> Result<u128, EnclaveError> {
+        self.as_uint_be().map(u128::from_be_bytes)
+    }
+
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Item::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => out.push(bytes[0]),
+            Item::Bytes(bytes) => {
+                encode_length(bytes.len(), 0x80, out);
+                out.extend_from_slice(bytes);
+            }
+            Item::List(items) => {
+                let mut payload = Vec::new();
+                for item in items {
+                    item.encode_to(&mut payload);
+                }
+                encode_length(payload.len(), 0xc0, out);
+                out.extend_from_slice(&payload);
+            }
+        }
+    }
+}
+
+/// Prefix of a payload of `len` bytes: `offset + len` for payloads under 56
+/// bytes, otherwise `offset + 55 + n` followed by `len` in `n` bytes
+fn encode_length(len: usize, offset: u8, out: &mut Vec<u8>) {
+    if len < 56 {
+        out.push(offset + len as u8);
+        return;
+    }
+    let bytes = (len as u64).to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    out.push(offset + 55 + (bytes.len() - start) as u8);
+    out.extend_from_slice(&bytes[start..]);
+}
+
+/// Decode one item from the front of `bytes`, returning the rest
+fn decode_item(bytes: &[u8], depth: usize) -> Result<(Item, &[u8]), EnclaveError> {
+    let (&prefix, rest) = bytes
+        .split_first()
+        .ok_or_else(|| malformed("input is empty"))?;
+    match prefix {
+        0x00..=0x7f => Ok((Item::Bytes(vec![prefix]), rest)),
+        0x80..=0xbf => {
+            let (payload, rest) = split_payload(prefix - 0x80, rest)?;
+            if payload.len() == 1 && payload[0] < 0x80 {
+                return Err(malformed("single byte below 0x80 has a length prefix"));
+            }
+            Ok((Item::Bytes(payload.to_vec()), rest))
+        }
+        0xc0..=0xff => {
+            if depth >= MAX_DEPTH {
+                return Err(malformed("lists nested too deeply"));
+            }
+            let (mut payload, rest) = split_payload(prefix - 0xc0, rest)?;
+            let mut items = Vec::new();
+            while !payload.is_empty() {
+                let (item, remaining) = decode_item(payload, depth + 1)?;
+                items.push(item);
+                payload = remaining;
+            }
+            Ok((Item::List(items), rest))
+        }
+    }
+}
+
+/// Split the payload announced by a prefix `tag` (already less its offset)
+/// off the front of `bytes`
+fn split_payload(tag: u8, bytes: &[u8]) -> Result<(&[u8], &[u8]), EnclaveError> {
+    let (len, bytes) = if tag < 56 {
+        (tag as usize, bytes)
+    } else {
+        let width = (tag - 55) as usize;
+        if bytes.len() < width {
+            return Err(malformed("truncated length"));
+        }
+        let (len_bytes, rest) = bytes.split_at(width);
+        if len_bytes[0] == 0 {
+            return Err(malformed("length has leading zeros"));
+        }
+        if width > 8 {
+            return Err(malformed("length too large"));
+        }
+        let len = len_bytes.iter().fold(0u64, |len, &b| (len << 8) | b as u64);
+        if len < 56 {
+            return Err(malformed("short payload uses a long length"));
+        }
+        (
+            usize::try_from(len).map_err(|_| malformed("length too large"))?,
+            rest,
+        )
+    };
+    if bytes.len() < len {
+        return Err(malformed("truncated payload"));
+    }
+    Ok(bytes.split_at(len))
+}
+
+fn malformed(reason: &str) -> EnclaveError {
+    EnclaveError::invalid_payload(format!("malformed RLP: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_examples_from_spec() {
+        assert_eq!(Item::bytes(b"dog").encode(), b"\x83dog");
+        assert_eq!(
+            Item::List(vec![Item::bytes(b"cat"), Item::bytes(b"dog")]).encode(),
+            b"\xc8\x83cat\x83dog"
+        );
+        assert_eq!(Item::bytes(b"").encode(), [0x80]);
+        assert_eq!(Item::List(Vec::new()).encode(), [0xc0]);
+        assert_eq!(Item::uint(0).encode(), [0x80]);
+        assert_eq!(Item::uint(15).encode(), [0x0f]);
+        assert_eq!(Item::uint(1024).encode(), [0x82, 0x04, 0x00]);
+
+        let lorem = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        let encoded = Item::bytes(lorem).encode();
+        assert_eq!(&encoded[..2], &[0xb8, 0x38]);
+        assert_eq!(&encoded[2..], lorem);
+    }
+
+    #[test]
+    fn test_nested_lists() {
+        // The set theoretical representation of three
+        let empty = || Item::List(Vec::new());
+        let three = Item::List(vec![
+            empty(),
+            Item::List(vec![empty()]),
+            Item::List(vec![empty(), Item::List(vec![empty()])]),
+        ]);
+        assert_eq!(
+            three.encode(),
+            [0xc7, 0xc0, 0xc1, 0xc0, 0xc3, 0xc0, 0xc1, 0xc0]
+        );
+    }
+
+    #[test]
+    fn test_decode_round_trips() {
+        let item = Item::List(vec![
+            Item::uint(0),
+            Item::uint(1024),
+            Item::bytes(&[0x7f]),
+            Item::bytes(&[0x80]),
+            Item::bytes(&[0xaa; 60]),
+            Item::List(vec![Item::List(Vec::new()), Item::bytes(&[0xbb; 1000])]),
+        ]);
+        let encoded = item.encode();
+        assert_eq!(Item::decode(&encoded).unwrap(), item);
+
+        let decoded = Item::decode(&encoded).unwrap();
+        let fields = decoded.as_list().unwrap();
+        assert_eq!(fields[0].as_u64().unwrap(), 0);
+        assert_eq!(fields[1].as_u128().unwrap(), 1024);
+        assert!(fields[5].as_bytes().is_err());
+        assert!(fields[4].as_u128().is_err());
+    }
+
+    #[test]
+    fn test_non_canonical_encodings_are_rejected() {
+        for bytes in [
+            &[0x81, 0x05][..],         // single small byte with a prefix
+            &[0xb8, 0x02, 0x01, 0x02], // short string with a long length
+            &[0xb9, 0x00, 0x40],       // length with leading zeros
+            &[0x83, 0x01],             // truncated payload
+            &[0x80, 0x80],             // trailing bytes
+            &[],
+        ] {
+            assert!(Item::decode(bytes).is_err(), "{:02x?}", bytes);
+        }
+        assert!(Item::decode(&[0x82, 0x00, 0x01]).unwrap().as_u64().is_err());
+
+        let mut nested = vec![0xc0];
+        for _ in 1..MAX_DEPTH {
+            nested = [vec![0xc0 + nested.len() as u8], nested].concat();
+        }
+        assert!(Item::decode(&nested).is_ok());
+        nested = [vec![0xc0 + nested.len() as u8], nested].concat();
+        assert!(Item::decode(&nested).is_err());
+    }
+}
@@ -0,0 +1,782 @@
+//! Ethereum transactions, and signing them with the enclave's key.
+//!
+//! A [`Transaction`] is the unsigned body of any of the envelopes in use:
+//! legacy, optionally with EIP-155 replay protection, and the typed
+//! EIP-2930 (type 1), EIP-1559 (type 2) and EIP-4844 (type 3) envelopes. An
+//! [`Envelope`] adds the signature and encodes as nodes expect it:
+//!
+//! ```text
+//! legacy: rlp([nonce, gasPrice, gasLimit, to, value, data, v, r, s])
+//! typed:  type || rlp([chainId, …fields of the type…, yParity, r, s])
+//! ```
+//!
+//! where legacy `v` is `chainId * 2 + 35 + yParity` under EIP-155 and
+//! `27 + yParity` before it. The signed hash is Keccak-256 over the same
+//! encoding with no signature, except that EIP-155 appends
+//! `[chainId, 0, 0]` in its place.
+//!
+//! The `sign_transaction` operation takes the fee and nonce fields of a
+//! transaction from the host but builds its calldata itself, through one of
+//! the [`crate::endpoint`] builders, from a signed output that must carry
+//! this enclave's own signature. The chain and the endpoint it sends to come
+//! from the [`EnclaveConfig`], which also caps the fees and gas limit the
+//! host may ask for. It never transfers value, and signs either an EIP-1559
+//! or an EIP-155 transaction.
+//!
+//! Like `ethereum_address`, it needs the key of a running
+//! [`EnclaveState`](crate::EnclaveState), so hosts reach it through the
+//! signed path: `/execute` of the enclave service, or the
+//! `ecall_process_signed` ECALL. [`crate::process_operation`] rejects it.
+
+use serde::{Deserialize, Serialize};
+
+use super::rlp::Item;
+use super::{Address, EthereumKey, RecoverableSignature};
+use crate::config::EnclaveConfig;
+use crate::endpoint::{EndpointCall, EndpointCallSpec};
+use crate::merkle::keccak256;
+use crate::signing::EnclaveIdentity;
+use crate::EnclaveError;
+
+/// Addresses and storage slots a transaction declares it will touch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: Address,
+    #[serde(with = "crate::serde_hex::list")]
+    pub storage_keys: Vec<[u8; 32]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LegacyTransaction {
+    /// Chain signed for under EIP-155; `None` for pre-EIP-155 transactions
+    pub chain_id: Option<u64>,
+    pub nonce: u64,
+    #[serde(with = "crate::serde_decimal")]
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    /// `None` for contract creation
+    pub to: Option<Address>,
+    #[serde(with = "crate::serde_decimal")]
+    pub value: u128,
+    #[serde(with = "crate::serde_hex")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Eip2930Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    #[serde(with = "crate::serde_decimal")]
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    pub to: Option<Address>,
+    #[serde(with = "crate::serde_decimal")]
+    pub value: u128,
+    #[serde(with = "crate::serde_hex")]
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    #[serde(with = "crate::serde_decimal")]
+    pub max_priority_fee_per_gas: u128,
+    #[serde(with = "crate::serde_decimal")]
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    pub to: Option<Address>,
+    #[serde(with = "crate::serde_decimal")]
+    pub value: u128,
+    #[serde(with = "crate::serde_hex")]
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Eip4844Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    #[serde(with = "crate::serde_decimal")]
+    pub max_priority_fee_per_gas: u128,
+    #[serde(with = "crate::serde_decimal")]
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    /// Blob transactions cannot create contracts
+    pub to: Address,
+    #[serde(with = "crate::serde_decimal")]
+    pub value: u128,
+    #[serde(with = "crate::serde_hex")]
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+    #[serde(with = "crate::serde_decimal")]
+    pub max_fee_per_blob_gas: u128,
+    #[serde(with = "crate::serde_hex::list")]
+    pub blob_versioned_hashes: Vec<[u8; 32]>,
+}
+
+/// Unsigned transaction of any supported type
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Transaction {
+    Legacy(LegacyTransaction),
+    Eip2930(Eip2930Transaction),
+    Eip1559(Eip1559Transaction),
+    Eip4844(Eip4844Transaction),
+}
+
+/// A transaction together with its signature
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub transaction: Transaction,
+    pub signature: RecoverableSignature,
+}
+
+impl Transaction {
+    /// EIP-2718 type byte; 0 for legacy transactions
+    pub fn tx_type(&self) -> u8 {
+        match self {
+            Transaction::Legacy(_) => 0,
+            Transaction::Eip2930(_) => 1,
+            Transaction::Eip1559(_) => 2,
+            Transaction::Eip4844(_) => 3,
+        }
+    }
+
+    /// Hash the sender signs
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut fields = self.fields();
+        if let Transaction::Legacy(LegacyTransaction {
+            chain_id: Some(chain_id),
+            ..
+        }) = self
+        {
+            fields.extend([Item::uint(*chain_id as u128), Item::uint(0), Item::uint(0)]);
+        }
+        keccak256(&self.with_type(Item::List(fields).encode()))
+    }
+
+    /// Sign with `key`
+    pub fn sign(self, key: &EthereumKey) -> Result<Envelope, EnclaveError> {
+        let signature = key.sign_hash(&self.signing_hash())?;
+        Ok(Envelope {
+            transaction: self,
+            signature,
+        })
+    }
+
+    /// Fields before the signature, in envelope order
+    fn fields(&self) -> Vec<Item> {
+        match self {
+            Transaction::Legacy(tx) => vec![
+                Item::uint(tx.nonce as u128),
+                Item::uint(tx.gas_price),
+                Item::uint(tx.gas_limit as u128),
+                to_item(tx.to.as_ref()),
+                Item::uint(tx.value),
+                Item::bytes(&tx.data),
+            ],
+            Transaction::Eip2930(tx) => vec![
+                Item::uint(tx.chain_id as u128),
+                Item::uint(tx.nonce as u128),
+                Item::uint(tx.gas_price),
+                Item::uint(tx.gas_limit as u128),
+                to_item(tx.to.as_ref()),
+                Item::uint(tx.value),
+                Item::bytes(&tx.data),
+                access_list_item(&tx.access_list),
+            ],
+            Transaction::Eip1559(tx) => vec![
+                Item::uint(tx.chain_id as u128),
+                Item::uint(tx.nonce as u128),
+                Item::uint(tx.max_priority_fee_per_gas),
+                Item::uint(tx.max_fee_per_gas),
+                Item::uint(tx.gas_limit as u128),
+                to_item(tx.to.as_ref()),
+                Item::uint(tx.value),
+                Item::bytes(&tx.data),
+                access_list_item(&tx.access_list),
+            ],
+            Transaction::Eip4844(tx) => vec![
+                Item::uint(tx.chain_id as u128),
+                Item::uint(tx.nonce as u128),
+                Item::uint(tx.max_priority_fee_per_gas),
+                Item::uint(tx.max_fee_per_gas),
+                Item::uint(tx.gas_limit as u128),
+                to_item(Some(&tx.to)),
+                Item::uint(tx.value),
+                Item::bytes(&tx.data),
+                access_list_item(&tx.access_list),
+                Item::uint(tx.max_fee_per_blob_gas),
+                Item::List(
+                    tx.blob_versioned_hashes
+                        .iter()
+                        .map(|hash| Item::bytes(hash))
+                        .collect(),
+                ),
+            ],
+        }
+    }
+
+    /// `encoded` behind the type byte, unless this is a legacy transaction
+    fn with_type(&self, encoded: Vec<u8>) -> Vec<u8> {
+        match self.tx_type() {
+            0 => encoded,
+            tx_type => [&[tx_type], encoded.as_slice()].concat(),
+        }
+    }
+}
+
+impl Envelope {
+    /// Encoding as sent to nodes and stored in the transactions trie
+    pub fn encode(&self) -> Vec<u8> {
+        let signature = &self.signature;
+        let v = match &self.transaction {
+            Transaction::Legacy(tx) => match tx.chain_id {
+                Some(chain_id) => chain_id as u128 * 2 + 35 + signature.y_parity as u128,
+                None => 27 + signature.y_parity as u128,
+            },
+            _ => signature.y_parity as u128,
+        };
+        let mut fields = self.transaction.fields();
+        fields.extend([
+            Item::uint(v),
+            Item::uint_be(&signature.r),
+            Item::uint_be(&signature.s),
+        ]);
+        self.transaction.with_type(Item::List(fields).encode())
+    }
+
+    /// Transaction hash: Keccak-256 of the encoding
+    pub fn hash(&self) -> [u8; 32] {
+        keccak256(&self.encode())
+    }
+
+    /// Address that signed the transaction
+    pub fn sender(&self) -> Result<Address, EnclaveError> {
+        super::recover(&self.transaction.signing_hash(), &self.signature)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let (tx_type, body) = match bytes.first() {
+            Some(&tx_type) if tx_type < 0x80 => (tx_type, &bytes[1..]),
+            _ => (0, bytes),
+        };
+        let item = Item::decode(body)?;
+        let fields = item.as_list()?;
+        let expected = match tx_type {
+            0 => 9,
+            1 => 11,
+            2 => 12,
+            3 => 14,
+            _ => {
+                return Err(EnclaveError::invalid_payload(format!(
+                    "unsupported transaction type {}",
+                    tx_type
+                )))
+            }
+        };
+        if fields.len() != expected {
+            return Err(EnclaveError::invalid_payload(format!(
+                "type {} transaction has {} fields",
+                tx_type,
+                fields.len()
+            )));
+        }
+        let (body, [v, r, s]) = fields.split_at(expected - 3) else {
+            unreachable!("length checked")
+        };
+
+        let (transaction, y_parity) = match tx_type {
+            0 => {
+                let (chain_id, y_parity) = match v.as_u64()? {
+                    v @ (27 | 28) => (None, v == 28),
+                    v if v >= 35 => (Some((v - 35) / 2), (v - 35) % 2 == 1),
+                    v => {
+                        return Err(EnclaveError::invalid_payload(format!(
+                            "invalid legacy v {}",
+                            v
+                        )))
+                    }
+                };
+                let tx = LegacyTransaction {
+                    chain_id,
+                    nonce: body[0].as_u64()?,
+                    gas_price: body[1].as_u128()?,
+                    gas_limit: body[2].as_u64()?,
+                    to: to_from_item(&body[3])?,
+                    value: body[4].as_u128()?,
+                    data: body[5].as_bytes()?.to_vec(),
+                };
+                (Transaction::Legacy(tx), y_parity)
+            }
+            1 => {
+                let tx = Eip2930Transaction {
+                    chain_id: body[0].as_u64()?,
+                    nonce: body[1].as_u64()?,
+                    gas_price: body[2].as_u128()?,
+                    gas_limit: body[3].as_u64()?,
+                    to: to_from_item(&body[4])?,
+                    value: body[5].as_u128()?,
+                    data: body[6].as_bytes()?.to_vec(),
+                    access_list: access_list_from_item(&body[7])?,
+                };
+                (Transaction::Eip2930(tx), y_parity(v)?)
+            }
+            2 => {
+                let tx = Eip1559Transaction {
+                    chain_id: body[0].as_u64()?,
+                    nonce: body[1].as_u64()?,
+                    max_priority_fee_per_gas: body[2].as_u128()?,
+                    max_fee_per_gas: body[3].as_u128()?,
+                    gas_limit: body[4].as_u64()?,
+                    to: to_from_item(&body[5])?,
+                    value: body[6].as_u128()?,
+                    data: body[7].as_bytes()?.to_vec(),
+                    access_list: access_list_from_item(&body[8])?,
+                };
+                (Transaction::Eip1559(tx), y_parity(v)?)
+            }
+            _ => {
+                let tx = Eip4844Transaction {
+                    chain_id: body[0].as_u64()?,
+                    nonce: body[1].as_u64()?,
+                    max_priority_fee_per_gas: body[2].as_u128()?,
+                    max_fee_per_gas: body[3].as_u128()?,
+                    gas_limit: body[4].as_u64()?,
+                    to: Address(body[5].as_array()?),
+                    value: body[6].as_u128()?,
+                    data: body[7].as_bytes()?.to_vec(),
+                    access_list: access_list_from_item(&body[8])?,
+                    max_fee_per_blob_gas: body[9].as_u128()?,
+                    blob_versioned_hashes: body[10]
+                        .as_list()?
+                        .iter()
+                        .map(Item::as_array)
+                        .collect::<Result<_, _>>()?,
+                };
+                (Transaction::Eip4844(tx), y_parity(v)?)
+            }
+        };
+
+        Ok(Self {
+            transaction,
+            signature: RecoverableSignature {
+                r: r.as_uint_be()?,
+                s: s.as_uint_be()?,
+                y_parity,
+            },
+        })
+    }
+}
+
+fn to_item(to: Option<&Address>) -> Item {
+    match to {
+        Some(address) => Item::bytes(&address.0),
+        None => Item::Bytes(Vec::new()),
+    }
+}
+
+fn to_from_item(item: &Item) -> Result<Option<Address>, EnclaveError> {
+    match item.as_bytes()? {
+        [] => Ok(None),
+        _ => item.as_array().map(|address| Some(Address(address))),
+    }
+}
+
+fn access_list_item(access_list: &[AccessListItem]) -> Item {
+    Item::List(
+        access_list
+            .iter()
+            .map(|entry| {
+                Item::List(vec![
+                    Item::bytes(&entry.address.0),
+                    Item::List(entry.storage_keys.iter().map(|k| Item::bytes(k)).collect()),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn access_list_from_item(item: &Item) -> Result<Vec<AccessListItem>, EnclaveError> {
+    item.as_list()?
+        .iter()
+        .map(|entry| {
+            let [address, storage_keys] = entry.as_list()? else {
+                return Err(EnclaveError::invalid_payload(
+                    "access list entry must have 2 fields",
+                ));
+            };
+            Ok(AccessListItem {
+                address: Address(address.as_array()?),
+                storage_keys: storage_keys
+                    .as_list()?
+                    .iter()
+                    .map(Item::as_array)
+                    .collect::<Result<_, _>>()?,
+            })
+        })
+        .collect()
+}
+
+fn y_parity(v: &Item) -> Result<bool, EnclaveError> {
+    match v.as_u64()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        v => Err(EnclaveError::invalid_payload(format!(
+            "invalid y parity {}",
+            v
+        ))),
+    }
+}
+
+/// Fee fields of a transaction, which also select its envelope
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Fees {
+    Eip1559 {
+        #[serde(with = "crate::serde_decimal")]
+        max_priority_fee_per_gas: u128,
+        #[serde(with = "crate::serde_decimal")]
+        max_fee_per_gas: u128,
+    },
+    Legacy {
+        #[serde(with = "crate::serde_decimal")]
+        gas_price: u128,
+    },
+}
+
+/// Every transaction field the host chooses
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionParams {
+    pub nonce: u64,
+    pub gas_limit: u64,
+    pub fees: Fees,
+}
+
+/// Payload for the `sign_transaction` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignTransactionPayload {
+    pub transaction: TransactionParams,
+    pub call: EndpointCallSpec,
+}
+
+/// Result of the `sign_transaction` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction {
+    /// Chain of the enclave config
+    pub chain_id: u64,
+    /// Endpoint of the enclave config
+    pub to: Address,
+    pub transaction: TransactionParams,
+    /// The call, whose calldata is the transaction's data
+    pub call: EndpointCall,
+    pub from: Address,
+    /// Keccak-256 of `raw`, as reported by nodes
+    #[serde(with = "crate::serde_hex")]
+    pub hash: [u8; 32],
+    /// Signed envelope, ready for `eth_sendRawTransaction`
+    #[serde(with = "crate::serde_hex")]
+    pub raw: Vec<u8>,
+}
+
+/// Result of the `ethereum_address` operation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthereumAccount {
+    pub address: Address,
+}
+
+impl TransactionParams {
+    /// Transaction carrying `data` to the endpoint at `to` on `chain_id`
+    pub fn transaction(&self, chain_id: u64, to: Address, data: Vec<u8>) -> Transaction {
+        match self.fees {
+            Fees::Eip1559 {
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+            } => Transaction::Eip1559(Eip1559Transaction {
+                chain_id,
+                nonce: self.nonce,
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+                gas_limit: self.gas_limit,
+                to: Some(to),
+                value: 0,
+                data,
+                access_list: Vec::new(),
+            }),
+            Fees::Legacy { gas_price } => Transaction::Legacy(LegacyTransaction {
+                chain_id: Some(chain_id),
+                nonce: self.nonce,
+                gas_price,
+                gas_limit: self.gas_limit,
+                to: Some(to),
+                value: 0,
+                data,
+            }),
+        }
+    }
+}
+
+/// Build the requested call and sign a transaction carrying it to the
+/// endpoint of `config`
+///
+/// The call's output must have been signed by `enclave`, the identity of the
+/// enclave holding `key`, and the fees and gas limit must be within the caps
+/// of `config`.
+pub fn sign(
+    key: &EthereumKey,
+    enclave: &EnclaveIdentity,
+    config: &EnclaveConfig,
+    payload: &SignTransactionPayload,
+) -> Result<SignedTransaction, EnclaveError> {
+    let to = config
+        .endpoint
+        .ok_or_else(|| EnclaveError::UnsupportedOperation {
+            operation: "sign_transaction (no endpoint configured)".to_string(),
+        })?;
+    if payload.call.output().signer != *enclave {
+        return Err(EnclaveError::AttestationFailure {
+            reason: "call is built from an output this enclave did not sign".to_string(),
+        });
+    }
+    let call = payload.call.build()?;
+    let transaction = payload.transaction;
+    if transaction.gas_limit > config.max_gas_limit {
+        return Err(EnclaveError::invalid_payload(format!(
+            "gas_limit {} exceeds the configured {}",
+            transaction.gas_limit, config.max_gas_limit
+        )));
+    }
+    let fee_cap = match transaction.fees {
+        Fees::Eip1559 {
+            max_priority_fee_per_gas,
+            max_fee_per_gas,
+        } => {
+            if max_priority_fee_per_gas > max_fee_per_gas {
+                return Err(EnclaveError::invalid_payload(
+                    "max_priority_fee_per_gas exceeds max_fee_per_gas",
+                ));
+            }
+            max_fee_per_gas
+        }
+        Fees::Legacy { gas_price } => gas_price,
+    };
+    if fee_cap > config.max_fee_per_gas {
+        return Err(EnclaveError::invalid_payload(format!(
+            "fee per gas {} exceeds the configured {}",
+            fee_cap, config.max_fee_per_gas
+        )));
+    }
+
+    let raw = transaction
+        .transaction(config.chain_id, to, call.calldata.clone())
+        .sign(key)?
+        .encode();
+    Ok(SignedTransaction {
+        chain_id: config.chain_id,
+        to,
+        transaction,
+        from: key.address(),
+        hash: keccak256(&raw),
+        raw,
+        call,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agency::receipts::RecordActionPayload;
+    use crate::agency::ActionType;
+    use crate::{EnclaveInput, EnclaveSigner, EnclaveState, Operation, SignatureScheme};
+
+    fn params(fees: Fees) -> TransactionParams {
+        TransactionParams {
+            nonce: 9,
+            gas_limit: 21_000,
+            fees,
+        }
+    }
+
+    fn config() -> EnclaveConfig {
+        EnclaveConfig {
+            endpoint: Some(Address([0x35; 20])),
+            ..EnclaveConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_eip155_example() {
+        // The example transaction from EIP-155
+        let transaction = Transaction::Legacy(LegacyTransaction {
+            chain_id: Some(1),
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 21_000,
+            to: Some(Address([0x35; 20])),
+            value: 1_000_000_000_000_000_000,
+            data: Vec::new(),
+        });
+        assert_eq!(
+            hex::encode(transaction.signing_hash()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+
+        let key = EthereumKey {
+            key: k256::ecdsa::SigningKey::from_slice(&[0x46; 32]).unwrap(),
+        };
+        let envelope = transaction.sign(&key).unwrap();
+        let encoded = envelope.encode();
+        assert_eq!(
+            hex::encode(&encoded),
+            concat!(
+                "f86c098504a817c800825208943535353535353535353535353535353535353535880de0",
+                "b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e15906",
+                "20aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b",
+                "6d83"
+            )
+        );
+        assert_eq!(Envelope::decode(&encoded).unwrap(), envelope);
+        assert_eq!(
+            envelope.sender().unwrap().to_string(),
+            "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+        );
+    }
+
+    #[test]
+    fn test_typed_envelopes_round_trip() {
+        let key = EthereumKey::derive(b"seed");
+        let access_list = vec![AccessListItem {
+            address: Address([0x44; 20]),
+            storage_keys: vec![[0x55; 32], [0; 32]],
+        }];
+        let transactions = [
+            Transaction::Legacy(LegacyTransaction {
+                chain_id: None,
+                nonce: 0,
+                gas_price: 1,
+                gas_limit: 53_000,
+                to: None,
+                value: 0,
+                data: vec![0x60; 100],
+            }),
+            Transaction::Eip2930(Eip2930Transaction {
+                chain_id: 5,
+                nonce: 1,
+                gas_price: 2,
+                gas_limit: 30_000,
+                to: Some(Address([0x35; 20])),
+                value: 3,
+                data: Vec::new(),
+                access_list: access_list.clone(),
+            }),
+            Transaction::Eip4844(Eip4844Transaction {
+                chain_id: 1,
+                nonce: 2,
+                max_priority_fee_per_gas: 1,
+                max_fee_per_gas: 10,
+                gas_limit: 21_000,
+                to: Address([0x35; 20]),
+                value: 0,
+                data: Vec::new(),
+                access_list,
+                max_fee_per_blob_gas: 7,
+                blob_versioned_hashes: vec![[0x01; 32]],
+            }),
+        ];
+        for transaction in transactions {
+            let envelope = transaction.sign(&key).unwrap();
+            let encoded = envelope.encode();
+            let decoded = Envelope::decode(&encoded).unwrap();
+            assert_eq!(decoded, envelope);
+            assert_eq!(decoded.hash(), keccak256(&encoded));
+            assert_eq!(decoded.sender().unwrap(), key.address());
+        }
+        assert!(Envelope::decode(&[0x04, 0xc0]).is_err());
+    }
+
+    fn anchor_request(state: &mut EnclaveState, fees: Fees) -> SignTransactionPayload {
+        let input = EnclaveInput::new(Operation::RecordAction(RecordActionPayload {
+            agent_id: "agent-1".to_string(),
+            action_id: "a1".to_string(),
+            action_type: ActionType::Execute,
+            input_hash: [1; 32],
+            output_hash: [2; 32],
+            parent: None,
+        }));
+        SignTransactionPayload {
+            transaction: params(fees),
+            call: EndpointCallSpec::AnchorAction {
+                output: state.process(input.with_nonce("dao-a", 1)).unwrap(),
+                metadata_uri: "ipfs://action".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_signs_endpoint_calls_of_this_enclave_only() {
+        let mut state = EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Ed25519));
+        let key = EthereumKey::derive(b"seed");
+        let payload = anchor_request(
+            &mut state,
+            Fees::Eip1559 {
+                max_priority_fee_per_gas: 1_000_000_000,
+                max_fee_per_gas: 30_000_000_000,
+            },
+        );
+
+        let signed = sign(&key, &state.signer().identity(), &config(), &payload).unwrap();
+        let envelope = Envelope::decode(&signed.raw).unwrap();
+        assert_eq!(envelope.transaction.tx_type(), 2);
+        assert_eq!(envelope.sender().unwrap(), key.address());
+        assert_eq!(signed.from, key.address());
+        match envelope.transaction {
+            Transaction::Eip1559(tx) => {
+                assert_eq!(tx.data, signed.call.calldata);
+                assert_eq!((tx.chain_id, tx.to), (1, Some(Address([0x35; 20]))));
+            }
+            other => panic!("unexpected transaction {:?}", other),
+        }
+
+        let stranger = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let error = sign(&key, &stranger.identity(), &config(), &payload).unwrap_err();
+        assert_eq!(error.code(), 3001);
+
+        let unconfigured = EnclaveConfig::default();
+        let error = sign(&key, &state.signer().identity(), &unconfigured, &payload).unwrap_err();
+        assert_eq!(error.code(), 1001);
+    }
+
+    #[test]
+    fn test_fee_fields_are_checked() {
+        let mut state = EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Secp256k1));
+        let key = EthereumKey::derive(b"seed");
+        let fees = Fees::Eip1559 {
+            max_priority_fee_per_gas: 2,
+            max_fee_per_gas: 1,
+        };
+        let identity = state.signer().identity();
+        let mut payload = anchor_request(&mut state, fees);
+        assert!(sign(&key, &identity, &config(), &payload).is_err());
+
+        payload.transaction.fees = Fees::Legacy { gas_price: 1 };
+        let signed = sign(&key, &identity, &config(), &payload).unwrap();
+        assert_eq!(signed.raw[0], 0xf9);
+        assert_eq!(signed.hash, keccak256(&signed.raw));
+
+        // The host cannot go past the caps of the config
+        let cap = config().max_fee_per_gas;
+        payload.transaction.fees = Fees::Legacy { gas_price: cap + 1 };
+        let error = sign(&key, &identity, &config(), &payload).unwrap_err();
+        assert_eq!(error.code(), 1003);
+        payload.transaction.fees = Fees::Eip1559 {
+            max_priority_fee_per_gas: 1,
+            max_fee_per_gas: cap + 1,
+        };
+        assert!(sign(&key, &identity, &config(), &payload).is_err());
+        payload.transaction.fees = Fees::Legacy { gas_price: cap };
+        payload.transaction.gas_limit = config().max_gas_limit + 1;
+        assert!(sign(&key, &identity, &config(), &payload).is_err());
+    }
+}
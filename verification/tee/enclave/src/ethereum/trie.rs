@@ -0,0 +1,283 @@
+//! Merkle-Patricia trie proofs.
+//!
+//! Ethereum commits to its state, and to the transactions and receipts of
+//! each block, with the roots of hexary Merkle-Patricia tries. A proof is the
+//! set of trie nodes on the path from the root to a key, as returned by
+//! `eth_getProof` or assembled from a block's receipts. [`verify_proof`]
+//! walks that path from a trusted root, so the value it returns, or its
+//! absence, is exactly what the root commits to.
+//!
+//! Nodes are RLP lists: a branch has sixteen children and a value, a leaf or
+//! extension has a hex-prefix encoded path segment and a value or child. A
+//! child whose encoding is shorter than 32 bytes is embedded in its parent;
+//! longer ones are referenced by their Keccak-256 hash.
+
+use std::collections::HashMap;
+
+use super::rlp::Item;
+use crate::merkle::keccak256;
+use crate::EnclaveError;
+
+/// Root of the empty trie: Keccak-256 of the RLP empty string
+pub const EMPTY_ROOT: [u8; 32] = [
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+];
+
+/// Value stored under `key` in the trie with root `root`
+///
+/// `proof` holds the encoded nodes on the path to `key`, in any order; extra
+/// nodes are ignored. Returns `None` when the proof shows `key` is absent, and
+/// an error when it does not reach far enough to tell.
+pub fn verify_proof(
+    root: &[u8; 32],
+    key: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<Option<Vec<u8>>, EnclaveError> {
+    if *root == EMPTY_ROOT {
+        return Ok(None);
+    }
+    let nodes: HashMap<[u8; 32], &[u8]> = proof
+        .iter()
+        .map(|node| (keccak256(node), node.as_slice()))
+        .collect();
+    let nibbles: Vec<u8> = key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect();
+
+    let mut path = nibbles.as_slice();
+    let mut node = lookup(&nodes, root)?;
+    loop {
+        let fields = node.as_list()?;
+        let child = match fields {
+            [children @ .., value] if children.len() == 16 => match path.split_first() {
+                None => return Ok(non_empty(value.as_bytes()?)),
+                Some((&nibble, rest)) => {
+                    path = rest;
+                    &children[nibble as usize]
+                }
+            },
+            [segment, next] => {
+                let (is_leaf, segment) = decode_path(segment.as_bytes()?)?;
+                if is_leaf {
+                    let value = next.as_bytes()?;
+                    return Ok(if path == segment.as_slice() {
+                        non_empty(value)
+                    } else {
+                        None
+                    });
+                }
+                match path.strip_prefix(segment.as_slice()) {
+                    Some(rest) => path = rest,
+                    None => return Ok(None),
+                }
+                next
+            }
+            _ => return Err(invalid_proof(&format!("node has {} fields", fields.len()))),
+        };
+        node = match child {
+            Item::Bytes(hash) if hash.is_empty() => return Ok(None),
+            Item::Bytes(hash) => lookup(
+                &nodes,
+                hash.as_slice()
+                    .try_into()
+                    .map_err(|_| invalid_proof("child reference is not a hash"))?,
+            )?,
+            Item::List(_) => child.clone(),
+        };
+    }
+}
+
+/// Trie key of the transaction or receipt at `index` in its block
+pub fn index_key(index: u64) -> Vec<u8> {
+    Item::uint(index as u128).encode()
+}
+
+fn lookup(nodes: &HashMap<[u8; 32], &[u8]>, hash: &[u8; 32]) -> Result<Item, EnclaveError> {
+    let node = nodes
+        .get(hash)
+        .ok_or_else(|| invalid_proof(&format!("missing node {}", hex::encode(hash))))?;
+    Item::decode(node)
+}
+
+/// Split a hex-prefix encoded path into its leaf flag and nibbles
+fn decode_path(encoded: &[u8]) -> Result<(bool, Vec<u8>), EnclaveError> {
+    let (&first, rest) = encoded
+        .split_first()
+        .ok_or_else(|| invalid_proof("empty path"))?;
+    let flag = first >> 4;
+    if flag > 3 || (flag & 1 == 0 && first & 0x0f != 0) {
+        return Err(invalid_proof("invalid path prefix"));
+    }
+    let mut nibbles = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        nibbles.push(first & 0x0f);
+    }
+    nibbles.extend(rest.iter().flat_map(|b| [b >> 4, b & 0x0f]));
+    Ok((flag >= 2, nibbles))
+}
+
+fn non_empty(value: &[u8]) -> Option<Vec<u8>> {
+    (!value.is_empty()).then(|| value.to_vec())
+}
+
+fn invalid_proof(reason: &str) -> EnclaveError {
+    EnclaveError::AttestationFailure {
+        reason: format!("invalid Merkle-Patricia proof: {}", reason),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Root of a trie holding `entries`, and every node of it
+    pub(crate) fn build(entries: &[(Vec<u8>, Vec<u8>)]) -> ([u8; 32], Vec<Vec<u8>>) {
+        if entries.is_empty() {
+            return (EMPTY_ROOT, Vec::new());
+        }
+        let entries: Vec<(Vec<u8>, &[u8])> = entries
+            .iter()
+            .map(|(key, value)| {
+                let nibbles = key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect();
+                (nibbles, value.as_slice())
+            })
+            .collect();
+        let mut nodes = Vec::new();
+        let root = node(&entries, &mut nodes).encode();
+        let hash = keccak256(&root);
+        nodes.push(root);
+        (hash, nodes)
+    }
+
+    fn node(entries: &[(Vec<u8>, &[u8])], nodes: &mut Vec<Vec<u8>>) -> Item {
+        if let [(path, value)] = entries {
+            return Item::List(vec![encode_path(path, true), Item::bytes(value)]);
+        }
+        let first = &entries[0].0;
+        let common = entries.iter().fold(first.len(), |len, (path, _)| {
+            len.min(path.iter().zip(first).take_while(|(a, b)| a == b).count())
+        });
+        if common > 0 {
+            let rest: Vec<_> = entries
+                .iter()
+                .map(|(path, value)| (path[common..].to_vec(), *value))
+                .collect();
+            let child = reference(node(&rest, nodes), nodes);
+            return Item::List(vec![encode_path(&first[..common], false), child]);
+        }
+
+        let mut fields: Vec<Item> = (0..16u8)
+            .map(|nibble| {
+                let group: Vec<_> = entries
+                    .iter()
+                    .filter(|(path, _)| path.first() == Some(&nibble))
+                    .map(|(path, value)| (path[1..].to_vec(), *value))
+                    .collect();
+                if group.is_empty() {
+                    Item::Bytes(Vec::new())
+                } else {
+                    reference(node(&group, nodes), nodes)
+                }
+            })
+            .collect();
+        let value = entries.iter().find(|(path, _)| path.is_empty());
+        fields.push(Item::bytes(value.map_or(&[][..], |(_, value)| value)));
+        Item::List(fields)
+    }
+
+    fn reference(node: Item, nodes: &mut Vec<Vec<u8>>) -> Item {
+        let encoded = node.encode();
+        if encoded.len() < 32 {
+            return node;
+        }
+        let hash = keccak256(&encoded);
+        nodes.push(encoded);
+        Item::bytes(&hash)
+    }
+
+    fn encode_path(nibbles: &[u8], is_leaf: bool) -> Item {
+        let flag = (is_leaf as u8) << 1 | (nibbles.len() % 2) as u8;
+        let mut bytes = vec![flag << 4];
+        let mut rest = nibbles;
+        if nibbles.len() % 2 == 1 {
+            bytes[0] |= nibbles[0];
+            rest = &nibbles[1..];
+        }
+        bytes.extend(rest.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+        Item::Bytes(bytes)
+    }
+
+    fn entries(pairs: &[(&str, &str)]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_roots_match_reference_tries() {
+        assert_eq!(EMPTY_ROOT, keccak256(&[0x80]));
+        let (root, _) = build(&entries(&[
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ]));
+        assert_eq!(
+            hex::encode(root),
+            "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+        );
+        let (root, _) = build(&entries(&[
+            ("do", "verb"),
+            ("horse", "stallion"),
+            ("doge", "coin"),
+            ("dog", "puppy"),
+        ]));
+        assert_eq!(
+            hex::encode(root),
+            "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+    }
+
+    #[test]
+    fn test_inclusion_and_exclusion() {
+        let pairs = entries(&[
+            ("do", "verb"),
+            ("horse", "stallion"),
+            ("doge", "coin"),
+            ("dog", "puppy"),
+        ]);
+        let (root, nodes) = build(&pairs);
+        for (key, value) in &pairs {
+            assert_eq!(
+                verify_proof(&root, key, &nodes).unwrap().as_ref(),
+                Some(value)
+            );
+        }
+        for key in [&b"d"[..], b"dogs", b"cat", b"horses", b""] {
+            assert_eq!(verify_proof(&root, key, &nodes).unwrap(), None);
+        }
+        assert_eq!(verify_proof(&EMPTY_ROOT, b"dog", &[]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_incomplete_or_foreign_proofs_fail() {
+        let pairs: Vec<_> = (0..64u64)
+            .map(|index| (index_key(index), vec![index as u8; 40]))
+            .collect();
+        let (root, nodes) = build(&pairs);
+        assert_eq!(
+            verify_proof(&root, &index_key(7), &nodes).unwrap(),
+            Some(vec![7; 40])
+        );
+
+        let without_root = &nodes[..nodes.len() - 1];
+        let error = verify_proof(&root, &index_key(7), without_root).unwrap_err();
+        assert_eq!(error.code(), 3001);
+
+        let (other_root, other_nodes) = build(&pairs[1..]);
+        assert!(verify_proof(&root, &index_key(7), &other_nodes).is_err());
+        assert_eq!(
+            verify_proof(&other_root, &index_key(0), &other_nodes).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,651 @@
+//! Fixed-point decimal arithmetic.
+//!
+//! Payload quantities that are not whole units, such as gas prices in gwei
+//! or multipliers like 1.2, are [`Fixed`] values: signed 256-bit integers
+//! scaled by 10^18, the precision of ether amounts in wei. Arithmetic is
+//! checked, and the operations that can lose digits, multiplication,
+//! division, square roots and rounding to fewer decimals, take an explicit
+//! [`Rounding`] mode and compute from the exact 512-bit intermediate, so a
+//! result never depends on the platform's floating point.
+//!
+//! On the wire a value is a decimal string such as `"21.5"` with at most 18
+//! fractional digits; plain JSON integers are accepted on input, floats are
+//! rejected.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Neg;
+use std::str::FromStr;
+
+use ethnum::{I256, U256};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::EnclaveError;
+
+/// Number of fractional decimal digits
+pub const DECIMALS: u32 = 18;
+
+const SCALE: U256 = U256::new(1_000_000_000_000_000_000);
+
+/// Signed decimal with 18 fractional digits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(I256);
+
+/// How to round a result that does not fit the target precision
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// Towards zero
+    #[default]
+    Down,
+    /// Away from zero
+    Up,
+    /// Towards negative infinity
+    Floor,
+    /// Towards positive infinity
+    Ceil,
+    /// To nearest, ties away from zero
+    HalfUp,
+    /// To nearest, ties to even
+    HalfEven,
+}
+
+impl Fixed {
+    pub const ZERO: Self = Fixed(I256::ZERO);
+    pub const ONE: Self = Fixed(I256::new(1_000_000_000_000_000_000));
+    pub const MAX: Self = Fixed(I256::MAX);
+    pub const MIN: Self = Fixed(I256::from_words(i128::MIN, 1));
+
+    /// `mantissa * 10^-decimals`, e.g. `Fixed::new(15, 2)` is 0.15
+    ///
+    /// Panics if `decimals` exceeds 18 or the scaled mantissa overflows
+    /// `i128`, so it suits constants rather than untrusted input.
+    pub const fn new(mantissa: i128, decimals: u32) -> Self {
+        assert!(decimals <= DECIMALS, "too many decimals");
+        Fixed(I256::new(mantissa * 10i128.pow(DECIMALS - decimals)))
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0.is_negative()
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == I256::ZERO
+    }
+
+    pub fn abs(self) -> Self {
+        Fixed(self.0.abs())
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Result<Self, EnclaveError> {
+        from_raw(self.0.checked_add(rhs.0))
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Result<Self, EnclaveError> {
+        from_raw(self.0.checked_sub(rhs.0))
+    }
+
+    /// `self * rhs`, rounded to 18 decimals
+    pub fn mul(self, rhs: Self, rounding: Rounding) -> Result<Self, EnclaveError> {
+        let negative = self.is_negative() != rhs.is_negative();
+        scaled(self.magnitude(), rhs.magnitude(), SCALE, negative, rounding)
+    }
+
+    /// `self / rhs`, rounded to 18 decimals
+    pub fn div(self, rhs: Self, rounding: Rounding) -> Result<Self, EnclaveError> {
+        if rhs.is_zero() {
+            return Err(EnclaveError::invalid_payload("division by zero"));
+        }
+        let negative = self.is_negative() != rhs.is_negative();
+        scaled(self.magnitude(), SCALE, rhs.magnitude(), negative, rounding)
+    }
+
+    /// `self` rounded to `decimals` fractional digits
+    pub fn round(self, decimals: u32, rounding: Rounding) -> Result<Self, EnclaveError> {
+        if decimals >= DECIMALS {
+            return Ok(self);
+        }
+        let unit = U256::from(10u8).pow(DECIMALS - decimals);
+        let units = scaled(
+            self.magnitude(),
+            U256::ONE,
+            unit,
+            self.is_negative(),
+            rounding,
+        )?;
+        scaled(
+            units.magnitude(),
+            unit,
+            U256::ONE,
+            self.is_negative(),
+            rounding,
+        )
+    }
+
+    /// Integer value of `self` after rounding
+    pub fn to_u128(self, rounding: Rounding) -> Result<u128, EnclaveError> {
+        let whole = scaled(
+            self.magnitude(),
+            U256::ONE,
+            SCALE,
+            self.is_negative(),
+            rounding,
+        )?;
+        if whole.is_negative() {
+            return Err(EnclaveError::invalid_payload(format!(
+                "{} is negative",
+                self
+            )));
+        }
+        u128::try_from(whole.magnitude()).map_err(|_| overflow())
+    }
+
+    pub fn to_u64(self, rounding: Rounding) -> Result<u64, EnclaveError> {
+        u64::try_from(self.to_u128(rounding)?).map_err(|_| overflow())
+    }
+
+    /// Square root, rounded to 18 decimals
+    pub fn sqrt(self, rounding: Rounding) -> Result<Self, EnclaveError> {
+        if self.is_negative() {
+            return Err(EnclaveError::invalid_payload(format!(
+                "square root of negative {}",
+                self
+            )));
+        }
+        if self.is_zero() {
+            return Ok(Self::ZERO);
+        }
+        // Newton's method on the raw value r = sqrt(x * 10^18), from a start
+        // above the root so the iterates decrease to floor(r)
+        let x = self.0.as_u256();
+        let mut root = x.max(SCALE);
+        loop {
+            let (quotient, _) = mul_div(x, SCALE, root).ok_or_else(overflow)?;
+            let next = (root + quotient) >> 1;
+            if next >= root {
+                break;
+            }
+            root = next;
+        }
+        // x * 10^18 = q * root + rem with q >= root; the square is exact
+        // when q == root and rem == 0, and above (root + 1/2)^2 exactly
+        // when (q - root) * root + rem > root
+        let (quotient, remainder) = mul_div(x, SCALE, root).ok_or_else(overflow)?;
+        let excess = quotient - root;
+        let round_up = match rounding {
+            Rounding::Down | Rounding::Floor => false,
+            Rounding::Up | Rounding::Ceil => excess > 0 || remainder > 0,
+            Rounding::HalfUp | Rounding::HalfEven => excess > 1 || (excess == 1 && remainder > 0),
+        };
+        from_magnitude(root + U256::from(round_up), false)
+    }
+
+    /// Raw value scaled by 10^18, as the 32 big-endian bytes of a Solidity
+    /// `int256`
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        self.0.to_be_bytes()
+    }
+
+    fn magnitude(self) -> U256 {
+        self.0.unsigned_abs()
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        // MIN is -MAX, so negation cannot overflow
+        Fixed(-self.0)
+    }
+}
+
+/// `a * b / d` from the exact 512-bit product, rounded per `rounding` and
+/// negated if `negative`
+fn scaled(
+    a: U256,
+    b: U256,
+    d: U256,
+    negative: bool,
+    rounding: Rounding,
+) -> Result<Fixed, EnclaveError> {
+    let (quotient, remainder) = mul_div(a, b, d).ok_or_else(overflow)?;
+    let away_from_zero = remainder > 0
+        && match rounding {
+            Rounding::Down => false,
+            Rounding::Up => true,
+            Rounding::Floor => negative,
+            Rounding::Ceil => !negative,
+            Rounding::HalfUp => remainder >= d - remainder,
+            Rounding::HalfEven => match remainder.cmp(&(d - remainder)) {
+                Ordering::Less => false,
+                Ordering::Equal => quotient & 1u128 == 1,
+                Ordering::Greater => true,
+            },
+        };
+    let magnitude = quotient
+        .checked_add(U256::from(away_from_zero))
+        .ok_or_else(overflow)?;
+    from_magnitude(magnitude, negative)
+}
+
+fn from_magnitude(magnitude: U256, negative: bool) -> Result<Fixed, EnclaveError> {
+    if magnitude > I256::MAX.as_u256() {
+        return Err(overflow());
+    }
+    let value = Fixed(magnitude.as_i256());
+    Ok(if negative { -value } else { value })
+}
+
+/// Quotient and remainder of `a * b / d`, or `None` if the quotient does
+/// not fit 256 bits
+fn mul_div(a: U256, b: U256, d: U256) -> Option<(U256, U256)> {
+    let (high, low) = widening_mul(a, b);
+    if high >= d {
+        return None;
+    }
+    // Long division of the 512-bit product, one bit at a time
+    let mut remainder = high;
+    let mut quotient = U256::ZERO;
+    for bit in (0..256).rev() {
+        let carry = remainder >> 255u32 == 1;
+        remainder = (remainder << 1u32) | ((low >> bit) & 1u128);
+        if carry || remainder >= d {
+            remainder = remainder.wrapping_sub(d);
+            quotient |= U256::ONE << bit;
+        }
+    }
+    Some((quotient, remainder))
+}
+
+/// 512-bit product of `a` and `b` as (high, low) halves
+fn widening_mul(a: U256, b: U256) -> (U256, U256) {
+    let (a_high, a_low) = a.into_words();
+    let (b_high, b_low) = b.into_words();
+    let product = |x: u128, y: u128| U256::from(x) * U256::from(y);
+
+    let low = product(a_low, b_low);
+    let middle_a = product(a_high, b_low);
+    let middle_b = product(a_low, b_high);
+    let high = product(a_high, b_high);
+
+    let (middle, middle_carry) = middle_a.overflowing_add(middle_b);
+    let (low, low_carry) = low.overflowing_add(middle << 128u32);
+    let high =
+        high + (middle >> 128u32) + (U256::from(middle_carry) << 128u32) + U256::from(low_carry);
+    (high, low)
+}
+
+/// Checked raw value, excluding `I256::MIN`, which has no positive
+/// counterpart
+fn from_raw(raw: Option<I256>) -> Result<Fixed, EnclaveError> {
+    match raw {
+        Some(raw) if raw != I256::MIN => Ok(Fixed(raw)),
+        _ => Err(overflow()),
+    }
+}
+
+fn overflow() -> EnclaveError {
+    EnclaveError::invalid_payload("fixed-point arithmetic overflow")
+}
+
+macro_rules! impl_from_int {
+    ($($int:ty),*) => {$(
+        impl From<$int> for Fixed {
+            fn from(value: $int) -> Self {
+                // Any 128-bit integer times 10^18 fits in 256 bits
+                Fixed(I256::from(value) * SCALE.as_i256())
+            }
+        }
+    )*};
+}
+
+impl_from_int!(i32, i64, i128, u32, u64, u128);
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let magnitude = self.magnitude();
+        if self.is_negative() {
+            f.write_str("-")?;
+        }
+        write!(f, "{}", magnitude / SCALE)?;
+        let fraction = magnitude % SCALE;
+        if fraction > 0 {
+            let digits = format!("{:018}", fraction.as_u128());
+            write!(f, ".{}", digits.trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Fixed {
+    type Err = EnclaveError;
+
+    /// Parse `[-]digits[.digits]` with at most 18 fractional digits
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || EnclaveError::invalid_payload(format!("invalid decimal {:?}", s));
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty()
+            || !is_digits(whole)
+            || !is_digits(fraction)
+            || (digits.contains('.') && fraction.is_empty())
+            || fraction.len() > DECIMALS as usize
+        {
+            return Err(invalid());
+        }
+
+        let whole = U256::from_str_radix(whole, 10).map_err(|_| overflow())?;
+        let fraction = match fraction {
+            "" => U256::ZERO,
+            fraction => {
+                U256::from_str_radix(fraction, 10).map_err(|_| invalid())?
+                    * U256::from(10u8).pow(DECIMALS - fraction.len() as u32)
+            }
+        };
+        let magnitude = whole
+            .checked_mul(SCALE)
+            .and_then(|scaled| scaled.checked_add(fraction))
+            .ok_or_else(overflow)?;
+        from_magnitude(magnitude, negative)
+    }
+}
+
+impl Serialize for Fixed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fixed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FixedVisitor;
+
+        impl Visitor<'_> for FixedVisitor {
+            type Value = Fixed;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a decimal string or an integer")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Fixed, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Fixed, E> {
+                Ok(value.into())
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Fixed, E> {
+                Ok(value.into())
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Fixed, E> {
+                Err(E::custom(format!(
+                    "{} is a float; write fractional values as decimal strings",
+                    value
+                )))
+            }
+        }
+
+        deserializer.deserialize_any(FixedVisitor)
+    }
+}
+
+/// Sum of `values`
+pub fn sum(values: &[Fixed]) -> Result<Fixed, EnclaveError> {
+    values
+        .iter()
+        .try_fold(Fixed::ZERO, |acc, &value| acc.checked_add(value))
+}
+
+/// Arithmetic mean of `values`
+pub fn mean(values: &[Fixed], rounding: Rounding) -> Result<Fixed, EnclaveError> {
+    non_empty(values)?;
+    sum(values)?.div(Fixed::from(values.len() as u64), rounding)
+}
+
+/// Middle value of `values`, or the mean of the two middle values
+pub fn median(values: &[Fixed], rounding: Rounding) -> Result<Fixed, EnclaveError> {
+    let sorted = sorted(values)?;
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        return Ok(sorted[middle]);
+    }
+    mean(&sorted[middle - 1..=middle], rounding)
+}
+
+/// Value at `percentile` (0-100) of `values`
+///
+/// Matches the agent tasks' nearest-rank rule: the sorted value at index
+/// `floor(n * percentile / 100)`, clamped to the last one.
+pub fn percentile(values: &[Fixed], percentile: u8) -> Result<Fixed, EnclaveError> {
+    if percentile > 100 {
+        return Err(EnclaveError::invalid_payload(
+            "percentile must be between 0 and 100",
+        ));
+    }
+    let sorted = sorted(values)?;
+    let index = (sorted.len() * percentile as usize / 100).min(sorted.len() - 1);
+    Ok(sorted[index])
+}
+
+/// Sample variance of `values`, which needs at least two of them
+pub fn variance(values: &[Fixed], rounding: Rounding) -> Result<Fixed, EnclaveError> {
+    if values.len() < 2 {
+        return Err(EnclaveError::invalid_payload(
+            "variance needs at least two values",
+        ));
+    }
+    let mean = mean(values, Rounding::HalfEven)?;
+    let squares = values
+        .iter()
+        .map(|&value| {
+            let deviation = value.checked_sub(mean)?;
+            deviation.mul(deviation, Rounding::HalfEven)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    sum(&squares)?.div(Fixed::from(values.len() as u64 - 1), rounding)
+}
+
+/// Sample standard deviation of `values`
+pub fn stdev(values: &[Fixed], rounding: Rounding) -> Result<Fixed, EnclaveError> {
+    variance(values, Rounding::HalfEven)?.sqrt(rounding)
+}
+
+fn non_empty(values: &[Fixed]) -> Result<(), EnclaveError> {
+    if values.is_empty() {
+        return Err(EnclaveError::invalid_payload("no values to aggregate"));
+    }
+    Ok(())
+}
+
+fn sorted(values: &[Fixed]) -> Result<Vec<Fixed>, EnclaveError> {
+    non_empty(values)?;
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    Ok(sorted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(s: &str) -> Fixed {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in ["0", "1", "-1", "21.5", "0.000000000000000001", "-7.25"] {
+            assert_eq!(fixed(s).to_string(), s);
+        }
+        assert_eq!(fixed("1.50"), Fixed::new(15, 1));
+        assert_eq!(fixed("-0"), Fixed::ZERO);
+        assert_eq!(Fixed::from(-3), fixed("-3"));
+        assert_eq!(
+            Fixed::from(u128::MAX).to_u128(Rounding::Down).unwrap(),
+            u128::MAX
+        );
+        for s in [
+            "",
+            "-",
+            "1.",
+            ".5",
+            "1e3",
+            "+1",
+            "1.0000000000000000001",
+            " 1",
+        ] {
+            assert_eq!(s.parse::<Fixed>().unwrap_err().code(), 1003, "{:?}", s);
+        }
+        assert_eq!(fixed(&Fixed::MAX.to_string()), Fixed::MAX);
+        assert_eq!(fixed(&Fixed::MIN.to_string()), Fixed::MIN);
+        assert!(format!("{}0", Fixed::MAX).parse::<Fixed>().is_err());
+    }
+
+    #[test]
+    fn test_serde_rejects_floats() {
+        let value: Fixed = serde_json::from_str("\"0.15\"").unwrap();
+        assert_eq!(value, Fixed::new(15, 2));
+        assert_eq!(serde_json::to_string(&value).unwrap(), "\"0.15\"");
+        assert_eq!(
+            serde_json::from_str::<Fixed>("-42").unwrap(),
+            Fixed::from(-42)
+        );
+        assert!(serde_json::from_str::<Fixed>("0.15").is_err());
+        assert!(serde_json::from_str::<Fixed>("\"0.1e1\"").is_err());
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(
+            fixed("0.1").checked_add(fixed("0.2")).unwrap(),
+            fixed("0.3")
+        );
+        assert_eq!(fixed("1").checked_sub(fixed("2.5")).unwrap(), fixed("-1.5"));
+        assert!(Fixed::MAX.checked_add(Fixed::new(1, 18)).is_err());
+        assert!(Fixed::MIN.checked_sub(Fixed::new(1, 18)).is_err());
+        assert_eq!(-Fixed::MIN, Fixed::MAX);
+        assert_eq!(
+            Fixed::ONE.to_be_bytes()[24..],
+            1_000_000_000_000_000_000u64.to_be_bytes()
+        );
+        assert_eq!(fixed("-1").to_be_bytes()[0], 0xff);
+
+        let product = fixed("1.5").mul(fixed("-2.25"), Rounding::Down).unwrap();
+        assert_eq!(product, fixed("-3.375"));
+        assert!(Fixed::MAX.mul(fixed("2"), Rounding::Down).is_err());
+        // The intermediate product exceeds 256 bits but the result does not
+        let big = fixed("10000000000000000000000000");
+        assert_eq!(
+            big.mul(big, Rounding::Down)
+                .unwrap()
+                .div(big, Rounding::Down)
+                .unwrap(),
+            big
+        );
+        assert_eq!(
+            fixed("1")
+                .div(Fixed::ZERO, Rounding::Down)
+                .unwrap_err()
+                .code(),
+            1003
+        );
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let third = fixed("1").div(fixed("3"), Rounding::Down).unwrap();
+        assert_eq!(third.to_string(), "0.333333333333333333");
+        let cases = [
+            // value, Down, Up, Floor, Ceil, HalfUp, HalfEven
+            ("2.5", ["2", "3", "2", "3", "3", "2"]),
+            ("3.5", ["3", "4", "3", "4", "4", "4"]),
+            ("-2.5", ["-2", "-3", "-3", "-2", "-3", "-2"]),
+            ("2.4", ["2", "3", "2", "3", "2", "2"]),
+            ("-2.6", ["-2", "-3", "-3", "-2", "-3", "-3"]),
+            ("7", ["7", "7", "7", "7", "7", "7"]),
+        ];
+        let modes = [
+            Rounding::Down,
+            Rounding::Up,
+            Rounding::Floor,
+            Rounding::Ceil,
+            Rounding::HalfUp,
+            Rounding::HalfEven,
+        ];
+        for (value, expected) in cases {
+            for (rounding, expected) in modes.iter().zip(expected) {
+                assert_eq!(
+                    fixed(value).round(0, *rounding).unwrap(),
+                    fixed(expected),
+                    "{} {:?}",
+                    value,
+                    rounding
+                );
+            }
+        }
+        assert_eq!(
+            fixed("1.23456").round(3, Rounding::HalfEven).unwrap(),
+            fixed("1.235")
+        );
+        assert_eq!(fixed("2.9").to_u64(Rounding::Down).unwrap(), 2);
+        assert_eq!(fixed("2.1").to_u64(Rounding::Ceil).unwrap(), 3);
+        assert!(fixed("-1").to_u64(Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn test_sqrt() {
+        assert_eq!(fixed("2.25").sqrt(Rounding::Down).unwrap(), fixed("1.5"));
+        assert_eq!(
+            fixed("2").sqrt(Rounding::Down).unwrap().to_string(),
+            "1.414213562373095048"
+        );
+        assert_eq!(
+            fixed("2").sqrt(Rounding::HalfEven).unwrap().to_string(),
+            "1.414213562373095049"
+        );
+        assert_eq!(
+            fixed("2").sqrt(Rounding::Up).unwrap().to_string(),
+            "1.414213562373095049"
+        );
+        assert_eq!(fixed("0.0001").sqrt(Rounding::Down).unwrap(), fixed("0.01"));
+        let max_root = Fixed::MAX.sqrt(Rounding::Down).unwrap();
+        assert!(max_root.mul(max_root, Rounding::Down).unwrap() <= Fixed::MAX);
+        assert!(fixed("-1").sqrt(Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn test_statistics() {
+        let values: Vec<Fixed> = ["2", "4", "4", "4", "5", "5", "7", "9"]
+            .iter()
+            .map(|s| fixed(s))
+            .collect();
+        assert_eq!(sum(&values).unwrap(), fixed("40"));
+        assert_eq!(mean(&values, Rounding::Down).unwrap(), fixed("5"));
+        assert_eq!(median(&values, Rounding::Down).unwrap(), fixed("4.5"));
+        assert_eq!(median(&values[..3], Rounding::Down).unwrap(), fixed("4"));
+        assert_eq!(percentile(&values, 75).unwrap(), fixed("7"));
+        assert_eq!(percentile(&values, 100).unwrap(), fixed("9"));
+        assert_eq!(percentile(&values, 0).unwrap(), fixed("2"));
+        // Sample variance 32 / 7
+        assert_eq!(
+            variance(&values, Rounding::Down).unwrap().to_string(),
+            "4.571428571428571428"
+        );
+        assert_eq!(
+            stdev(&values, Rounding::Down).unwrap().to_string(),
+            "2.138089935299395077"
+        );
+
+        assert_eq!(mean(&[], Rounding::Down).unwrap_err().code(), 1003);
+        assert!(percentile(&values, 101).is_err());
+        assert!(variance(&values[..1], Rounding::Down).is_err());
+    }
+}
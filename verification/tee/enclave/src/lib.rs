@@ -1,9 +1,24 @@
-/**
- * ChaosChain SGX Enclave
- * 
- * Minimal enclave implementation for Intel SGX integration.
- * This is a placeholder for Sprint-0 and will be expanded in Sprint-1.
- */
+//! ChaosChain SGX Enclave
+//!
+//! Dispatcher of versioned, measured operations run inside an Intel SGX
+//! enclave, or in a simulated one without the `sgx` feature.
+//!
+//! Requests arrive as a versioned [`EnclaveInput`] wrapping a typed
+//! [`Operation`], and results leave as an [`EnclaveOutput`] carrying either a
+//! typed [`OperationResult`] or a structured [`EnclaveError`]. Every
+//! operation is listed in the [`registry`] with its version, schemas and
+//! code measurement, and its output names the entry that produced it.
+//!
+//! Outputs can be signed by the enclave's key via [`process_signed`].
+//! [`EnclaveState`] ties that key to per-client request nonces and persists
+//! it through [`sealing`], guarding the sealed form against rollback so no
+//! attested decision can be replayed. Signed outputs are anchored on-chain
+//! in batches through [`merkle`], and [`endpoint`] encodes the
+//! `ChaosEndpoint` calls that carry them. Chain data that operations compute
+//! over can be verified against a trusted block hash through
+//! [`ethereum::context`]. Fractional quantities in payloads are [`Fixed`]
+//! decimals, never floats, so results do not depend on floating point
+//! behaviour.
 
 // Conditional compilation for SGX environment
 #![cfg_attr(feature = "sgx", no_std)]
@@ -11,52 +26,295 @@
 #[cfg(feature = "sgx")]
 extern crate sgx_tstd as std;
 
+pub mod abi;
+pub mod agency;
+pub mod attestation;
+pub mod canonical;
+pub mod config;
+#[cfg(feature = "sgx")]
+pub mod ecall;
+pub mod endpoint;
+pub mod error;
+pub mod ethereum;
+pub mod fixed;
+pub mod merkle;
+pub mod operation;
+pub mod registry;
+pub mod replay;
+pub mod sealing;
+mod serde_decimal;
+mod serde_hex;
+pub mod signing;
+pub mod simulation;
+pub mod state;
+pub mod tasks;
+
 use serde::{Deserialize, Serialize};
 
+pub use attestation::{AttestationReport, Quote};
+pub use config::EnclaveConfig;
+pub use error::EnclaveError;
+pub use fixed::{Fixed, Rounding};
+pub use merkle::{MerkleBatch, MerkleProof, SignedMerkleRoot};
+pub use operation::{AddPayload, AddResult, Operation, OperationResult};
+pub use registry::{OperationDescriptor, OperationRef};
+pub use replay::{ReplayGuard, RequestNonce};
+pub use sealing::{SealPolicy, SealedBlob, Sealer};
+pub use signing::{EnclaveIdentity, EnclaveSigner, SignatureScheme, SignedEnclaveOutput};
+pub use state::{EnclaveState, MonotonicCounter};
+
+/// Version of the host <-> enclave wire protocol understood by this build
+pub const PROTOCOL_VERSION: u32 = 1;
+
 /// Input for an enclave operation
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct EnclaveInput {
-    pub operation: String,
-    pub parameters: Vec<i32>,
+    pub version: u32,
+    /// Client nonce making this request unique, see [`ReplayGuard`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<RequestNonce>,
+    pub operation: Operation,
+}
+
+impl EnclaveInput {
+    /// Wrap an operation in an input for the current protocol version
+    pub fn new(operation: Operation) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            nonce: None,
+            operation,
+        }
+    }
+
+    /// Attach nonce `nonce` of client `client` to this input
+    pub fn with_nonce(mut self, client: impl Into<String>, nonce: u64) -> Self {
+        self.nonce = Some(RequestNonce {
+            client: client.into(),
+            nonce,
+        });
+        self
+    }
+
+    /// SHA-256 over the canonical JSON encoding of this input
+    pub fn input_hash(&self) -> [u8; 32] {
+        canonical::canonical_hash(self).expect("enclave inputs always serialize")
+    }
+
+    /// Decode an input from JSON, classifying failures as enclave errors
+    ///
+    /// An operation `type` this build does not know yields
+    /// [`EnclaveError::UnsupportedOperation`]; any other decoding failure
+    /// yields [`EnclaveError::InvalidPayload`].
+    pub fn from_json(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let value: serde_json::Value = serde_json::from_slice(bytes)
+            .map_err(|e| EnclaveError::invalid_payload(e.to_string()))?;
+
+        if let Some(name) = value
+            .get("operation")
+            .and_then(|op| op.get("type"))
+            .and_then(|name| name.as_str())
+        {
+            if !Operation::NAMES.contains(&name) {
+                return Err(EnclaveError::UnsupportedOperation {
+                    operation: name.to_string(),
+                });
+            }
+        }
+
+        serde_json::from_value(value).map_err(|e| EnclaveError::invalid_payload(e.to_string()))
+    }
 }
 
 /// Result of an enclave operation
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+///
+/// `result` serializes as `{"Ok": <OperationResult>}` on success and
+/// `{"Err": <EnclaveError>}` on failure. `operation` names the registered
+/// operation that ran, and is `None` only when the input could not be
+/// decoded far enough to tell.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct EnclaveOutput {
-    pub result: i32,
-    pub status: String,
+    pub version: u32,
+    pub operation: Option<OperationRef>,
+    pub result: Result<OperationResult, EnclaveError>,
 }
 
-/// Add two numbers within the enclave
-/// 
-/// This is a minimal demonstration function for Sprint-0.
-/// In Sprint-1, this will be replaced with actual verification logic.
-pub fn add(a: i32, b: i32) -> i32 {
-    a + b
+impl EnclaveOutput {
+    /// Wrap an outcome not attributable to any operation
+    pub fn new(result: Result<OperationResult, EnclaveError>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            operation: None,
+            result,
+        }
+    }
+
+    /// Wrap the outcome of the registered operation `operation`
+    pub fn for_operation(
+        operation: &OperationDescriptor,
+        result: Result<OperationResult, EnclaveError>,
+    ) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            operation: Some(operation.reference()),
+            result,
+        }
+    }
+
+    /// SHA-256 over the canonical JSON encoding of this output
+    pub fn output_hash(&self) -> [u8; 32] {
+        canonical::canonical_hash(self).expect("enclave outputs always serialize")
+    }
+
+    /// Whether the operation succeeded
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Add two numbers within the enclave, as the `add` operation does
+pub fn add(a: Fixed, b: Fixed) -> Result<Fixed, EnclaveError> {
+    a.checked_add(b)
 }
 
 /// Process an operation in the enclave
+///
+/// Operations that act with the enclave's keys or identity,
+/// `ethereum_address`, `sign_transaction`, `sign_merkle_root` and the
+/// Proof-of-Agency operations, are unsupported here; they run through
+/// [`EnclaveState::process`].
 pub fn process_operation(input: EnclaveInput) -> EnclaveOutput {
-    match input.operation.as_str() {
-        "add" => {
-            if input.parameters.len() != 2 {
-                return EnclaveOutput {
-                    result: 0,
-                    status: "ERROR: add operation requires exactly 2 parameters".to_string(),
-                };
-            }
-            
-            let result = add(input.parameters[0], input.parameters[1]);
-            
-            EnclaveOutput {
-                result,
-                status: "SUCCESS".to_string(),
-            }
-        },
-        _ => EnclaveOutput {
-            result: 0,
-            status: format!("ERROR: Unsupported operation '{}'", input.operation),
+    run(input, None)
+}
+
+/// Keys, config and records of a running enclave, for the operations that
+/// act with them
+pub(crate) struct EnclaveContext<'a> {
+    pub signer: &'a EnclaveSigner,
+    pub ethereum: &'a ethereum::EthereumKey,
+    pub config: &'a EnclaveConfig,
+    /// Last epoch whose Merkle root the enclave signed
+    pub last_epoch: &'a mut Option<u64>,
+    pub agency: &'a mut agency::AgencyLedger,
+}
+
+pub(crate) fn run(input: EnclaveInput, keys: Option<&mut EnclaveContext<'_>>) -> EnclaveOutput {
+    match registry::describe_operation(&input.operation) {
+        Some(descriptor) => EnclaveOutput::for_operation(descriptor, execute(input, keys)),
+        None => EnclaveOutput::new(execute(input, keys)),
+    }
+}
+
+/// Decode a JSON input and process it, reporting decode failures in the output
+pub fn process_json(bytes: &[u8]) -> EnclaveOutput {
+    match EnclaveInput::from_json(bytes) {
+        Ok(input) => process_operation(input),
+        Err(error) => EnclaveOutput::new(Err(error)),
+    }
+}
+
+/// Process an operation and sign the output with the enclave key
+///
+/// The signature binds the output to the hash of `input`, the signer's next
+/// sequence number and the current time.
+pub fn process_signed(
+    signer: &mut EnclaveSigner,
+    input: EnclaveInput,
+) -> Result<SignedEnclaveOutput, EnclaveError> {
+    let output = process_operation(input.clone());
+    signer.sign_output(&input, output, unix_time())
+}
+
+/// Seconds since the Unix epoch as reported by the platform clock
+pub fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+fn execute(
+    input: EnclaveInput,
+    keys: Option<&mut EnclaveContext<'_>>,
+) -> Result<OperationResult, EnclaveError> {
+    if input.version != PROTOCOL_VERSION {
+        return Err(EnclaveError::VersionMismatch {
+            expected: PROTOCOL_VERSION,
+            actual: input.version,
+        });
+    }
+
+    match input.operation {
+        Operation::Add(AddPayload { a, b }) => {
+            Ok(OperationResult::Add(AddResult { sum: add(a, b)? }))
+        }
+        Operation::OptimizeGasParameters(payload) => {
+            tasks::gas_optimizer::optimize(&payload).map(OperationResult::OptimizeGasParameters)
+        }
+        Operation::ScanProposal(payload) => {
+            tasks::proposal_scanner::scan(&payload).map(OperationResult::ScanProposal)
+        }
+        Operation::EstimateMevCost(payload) => {
+            tasks::mev_estimator::estimate(&payload).map(OperationResult::EstimateMevCost)
+        }
+        Operation::SimulateFork(payload) => {
+            simulation::fork::simulate(&payload).map(OperationResult::SimulateFork)
+        }
+        Operation::SimulateBaseFee(payload) => {
+            simulation::base_fee::simulate(&payload).map(OperationResult::SimulateBaseFee)
+        }
+        Operation::RecordAction(payload) => {
+            let keys = keys.ok_or_else(|| needs_keys("record_action"))?;
+            agency::receipts::record_action(&payload, &keys.signer.identity(), keys.agency)
+                .map(OperationResult::RecordAction)
+        }
+        Operation::RecordOutcome(payload) => {
+            let keys = keys.ok_or_else(|| needs_keys("record_outcome"))?;
+            agency::receipts::record_outcome(&payload, &keys.signer.identity(), keys.agency)
+                .map(OperationResult::RecordOutcome)
         }
+        Operation::ComputeRewards(payload) => {
+            let keys = keys.ok_or_else(|| needs_keys("compute_rewards"))?;
+            agency::rewards::compute(
+                &payload,
+                &keys.signer.identity(),
+                &keys.config.reward_schedule,
+                keys.agency,
+            )
+            .map(OperationResult::ComputeRewards)
+        }
+        Operation::EthereumAddress => {
+            let keys = keys.ok_or_else(|| needs_keys("ethereum_address"))?;
+            Ok(OperationResult::EthereumAddress(
+                ethereum::transaction::EthereumAccount {
+                    address: keys.ethereum.address(),
+                },
+            ))
+        }
+        Operation::SignTransaction(payload) => {
+            let keys = keys.ok_or_else(|| needs_keys("sign_transaction"))?;
+            ethereum::transaction::sign(
+                keys.ethereum,
+                &keys.signer.identity(),
+                keys.config,
+                &payload,
+            )
+            .map(OperationResult::SignTransaction)
+        }
+        Operation::VerifyBlockContext(context) => context
+            .verify()
+            .map(|verified| OperationResult::VerifyBlockContext(verified.report())),
+        Operation::SignMerkleRoot(payload) => {
+            let keys = keys.ok_or_else(|| needs_keys("sign_merkle_root"))?;
+            merkle::sign(keys.signer, keys.last_epoch, &payload)
+                .map(OperationResult::SignMerkleRoot)
+        }
+        Operation::ListOperations => Ok(OperationResult::ListOperations(registry::catalog())),
+    }
+}
+
+fn needs_keys(operation: &str) -> EnclaveError {
+    EnclaveError::UnsupportedOperation {
+        operation: format!("{} (needs a running enclave state)", operation),
     }
 }
 
@@ -66,35 +324,113 @@ mod tests {
 
     #[test]
     fn test_add() {
-        assert_eq!(add(2, 3), 5);
-        assert_eq!(add(-1, 1), 0);
-        assert_eq!(add(100, 200), 300);
+        assert_eq!(add(2.into(), 3.into()).unwrap(), 5.into());
+        assert_eq!(add((-1).into(), 1.into()).unwrap(), Fixed::ZERO);
+        assert_eq!(
+            add(Fixed::new(1, 1), Fixed::new(2, 1)).unwrap(),
+            Fixed::new(3, 1)
+        );
+        assert_eq!(add(Fixed::MAX, Fixed::ONE).unwrap_err().code(), 1003);
     }
 
     #[test]
     fn test_process_operation_add() {
-        let input = EnclaveInput {
-            operation: "add".to_string(),
-            parameters: vec![10, 20],
-        };
-        
+        let input = EnclaveInput::new(Operation::Add(AddPayload {
+            a: 10.into(),
+            b: 20.into(),
+        }));
+
         let expected_output = EnclaveOutput {
-            result: 30,
-            status: "SUCCESS".to_string(),
+            version: PROTOCOL_VERSION,
+            operation: Some(registry::lookup("add").unwrap().reference()),
+            result: Ok(OperationResult::Add(AddResult { sum: 30.into() })),
         };
-        
+
         assert_eq!(process_operation(input), expected_output);
     }
 
+    #[test]
+    fn test_process_operation_add_overflow() {
+        let output = process_operation(EnclaveInput::new(Operation::Add(AddPayload {
+            a: Fixed::MAX,
+            b: Fixed::MAX,
+        })));
+        assert_eq!(output.result.unwrap_err().code(), 1003);
+    }
+
     #[test]
     fn test_process_operation_invalid() {
-        let input = EnclaveInput {
-            operation: "multiply".to_string(),
-            parameters: vec![10, 20],
-        };
-        
+        let json =
+            br#"{"version": 1, "operation": {"type": "multiply", "payload": {"a": 10, "b": 20}}}"#;
+
+        let output = process_json(json);
+        let error = output.result.unwrap_err();
+        assert_eq!(error.code(), 1001);
+        assert_eq!(
+            error,
+            EnclaveError::UnsupportedOperation {
+                operation: "multiply".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_process_operation_malformed_payload() {
+        let json = br#"{"version": 1, "operation": {"type": "add", "payload": {"a": 10}}}"#;
+
+        let error = process_json(json).result.unwrap_err();
+        assert!(matches!(error, EnclaveError::InvalidPayload { .. }));
+    }
+
+    #[test]
+    fn test_process_operation_version_mismatch() {
+        let mut input = EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 2.into(),
+        }));
+        input.version = PROTOCOL_VERSION + 1;
+
         let output = process_operation(input);
-        assert_eq!(output.result, 0);
-        assert!(output.status.starts_with("ERROR"));
+        assert_eq!(
+            output.result,
+            Err(EnclaveError::VersionMismatch {
+                expected: PROTOCOL_VERSION,
+                actual: PROTOCOL_VERSION + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_input_hash_matches_canonical_json() {
+        let input = EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 2.into(),
+        }));
+        let canonical = canonical::to_canonical_json(&input).unwrap();
+        assert_eq!(
+            canonical,
+            br#"{"operation":{"payload":{"a":"1","b":"2"},"type":"add"},"version":1}"#
+        );
+
+        let reordered =
+            br#"{"operation": {"type": "add", "payload": {"b": "2", "a": "1"}}, "version": 1}"#;
+        assert_eq!(
+            EnclaveInput::from_json(reordered).unwrap().input_hash(),
+            input.input_hash()
+        );
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_output_result_envelope() {
+        let output = process_operation(EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 2.into(),
+        })));
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["result"]["Ok"]["value"]["sum"], "3");
+        assert_eq!(
+            serde_json::from_value::<EnclaveOutput>(json).unwrap(),
+            output
+        );
+    }
+}
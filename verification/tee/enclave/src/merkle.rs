@@ -0,0 +1,382 @@
+//! Merkle batching of signed outputs for on-chain anchoring.
+//!
+//! Instead of anchoring every output with its own attestation, the host
+//! collects an epoch's [`SignedEnclaveOutput`]s in a [`MerkleBatch`], anchors
+//! the single root the enclave signs for it, and hands each output's
+//! [`MerkleProof`] to whoever needs to show that output was anchored.
+//!
+//! A batch belongs to one enclave identity: it only accepts outputs that
+//! identity signed, and only that enclave signs its root, through the
+//! `sign_merkle_root` operation. The enclave keeps the last epoch it signed
+//! in its sealed state and refuses any epoch not above it, so each epoch has
+//! at most one signed root.
+//!
+//! Hashing follows the conventions of OpenZeppelin's `MerkleProof` so proofs
+//! verify on-chain unchanged:
+//!
+//! ```text
+//! leaf = keccak256(bytes.concat(keccak256(abi.encode(
+//!            bytes32 signerKeyHash, bytes32 inputHash, bytes32 outputHash,
+//!            uint64 sequence, uint64 timestamp))))
+//! node = keccak256(abi.encodePacked(min(a, b), max(a, b)))
+//! ```
+//!
+//! where `signerKeyHash` is the keccak256 of the signer's public key, so a
+//! contract checks inclusion with `MerkleProof.verify(proof, root, leaf)`.
+//! Leaves are hashed twice so that no leaf can be mistaken for an inner
+//! node. When a level has an odd number of nodes the last one is carried up
+//! unhashed, and its proof has no sibling at that level.
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::signing::{EnclaveIdentity, EnclaveSigner, SignedEnclaveOutput};
+use crate::EnclaveError;
+
+/// Domain separation tag prefixed to every signed Merkle root message
+pub const MERKLE_ROOT_DOMAIN: &[u8] = b"chaoschain-enclave/merkle-root/v1";
+
+/// Most outputs one `sign_merkle_root` request may batch
+pub const MAX_BATCH_OUTPUTS: usize = 4_096;
+
+/// Keccak-256, as Solidity's `keccak256`
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// Leaf committing to a signed output, see the module documentation
+pub fn leaf_hash(output: &SignedEnclaveOutput) -> [u8; 32] {
+    let mut encoded = [0u8; 160];
+    encoded[0..32].copy_from_slice(&keccak256(&output.signer.public_key));
+    encoded[32..64].copy_from_slice(&output.input_hash);
+    encoded[64..96].copy_from_slice(&output.output_hash);
+    encoded[120..128].copy_from_slice(&output.sequence.to_be_bytes());
+    encoded[152..160].copy_from_slice(&output.timestamp.to_be_bytes());
+    keccak256(&keccak256(&encoded))
+}
+
+/// Parent of two nodes, independent of their order
+pub fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    let mut packed = [0u8; 64];
+    packed[..32].copy_from_slice(low);
+    packed[32..].copy_from_slice(high);
+    keccak256(&packed)
+}
+
+/// Root reached from `leaf` through `proof`, as `MerkleProof.processProof`
+pub fn process_proof(leaf: [u8; 32], proof: &[[u8; 32]]) -> [u8; 32] {
+    proof
+        .iter()
+        .fold(leaf, |node, sibling| hash_pair(&node, sibling))
+}
+
+/// Proof that one leaf is included under a root
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub leaf_index: u64,
+    #[serde(with = "crate::serde_hex")]
+    pub leaf: [u8; 32],
+    /// Siblings from the leaf level up
+    #[serde(with = "crate::serde_hex::list")]
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl MerkleProof {
+    /// Whether this proof leads to `root`
+    pub fn verify(&self, root: &[u8; 32]) -> bool {
+        process_proof(self.leaf, &self.siblings) == *root
+    }
+}
+
+/// Merkle root of one epoch, signed by the enclave
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignedMerkleRoot {
+    #[serde(with = "crate::serde_hex")]
+    pub root: [u8; 32],
+    pub epoch: u64,
+    pub leaf_count: u64,
+    pub signer: EnclaveIdentity,
+    #[serde(with = "crate::serde_hex")]
+    pub signature: Vec<u8>,
+}
+
+impl SignedMerkleRoot {
+    /// Bytes covered by the signature:
+    /// `MERKLE_ROOT_DOMAIN || root (32) || epoch (u64 BE) || leaf_count (u64 BE)`
+    pub fn signing_message(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(MERKLE_ROOT_DOMAIN.len() + 48);
+        message.extend_from_slice(MERKLE_ROOT_DOMAIN);
+        message.extend_from_slice(&self.root);
+        message.extend_from_slice(&self.epoch.to_be_bytes());
+        message.extend_from_slice(&self.leaf_count.to_be_bytes());
+        message
+    }
+
+    /// Check the signature against the embedded signer
+    ///
+    /// Callers must separately check that `signer` is an identity they trust.
+    pub fn verify(&self) -> Result<(), EnclaveError> {
+        self.signer.verify(&self.signing_message(), &self.signature)
+    }
+}
+
+/// Payload for the `sign_merkle_root` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignMerkleRootPayload {
+    pub epoch: u64,
+    /// Outputs of the epoch, in leaf order
+    pub outputs: Vec<SignedEnclaveOutput>,
+}
+
+/// Accumulates the signed outputs of one enclave over one epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleBatch {
+    signer: EnclaveIdentity,
+    leaves: Vec<[u8; 32]>,
+}
+
+impl MerkleBatch {
+    /// Empty batch of outputs signed by `signer`
+    pub fn new(signer: EnclaveIdentity) -> Self {
+        Self {
+            signer,
+            leaves: Vec::new(),
+        }
+    }
+
+    pub fn signer(&self) -> &EnclaveIdentity {
+        &self.signer
+    }
+
+    /// Add an output this batch's enclave signed, returning its leaf index
+    pub fn push(&mut self, output: &SignedEnclaveOutput) -> Result<u64, EnclaveError> {
+        if output.signer != self.signer {
+            return Err(EnclaveError::AttestationFailure {
+                reason: "output was signed by another enclave identity".to_string(),
+            });
+        }
+        output.verify()?;
+        self.leaves.push(leaf_hash(output));
+        Ok(self.leaves.len() as u64 - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        &self.leaves
+    }
+
+    /// Root over every leaf pushed so far
+    pub fn root(&self) -> Result<[u8; 32], EnclaveError> {
+        let mut level = self.leaves.clone();
+        if level.is_empty() {
+            return Err(EnclaveError::invalid_payload("Merkle batch is empty"));
+        }
+        while level.len() > 1 {
+            level = next_level(&level);
+        }
+        Ok(level[0])
+    }
+
+    /// Inclusion proof of the leaf at `index`
+    pub fn proof(&self, index: u64) -> Result<MerkleProof, EnclaveError> {
+        let leaf = *self
+            .leaves
+            .get(index as usize)
+            .ok_or_else(|| EnclaveError::invalid_payload(format!("no leaf at index {}", index)))?;
+
+        let mut siblings = Vec::new();
+        let mut level = self.leaves.clone();
+        let mut position = index as usize;
+        while level.len() > 1 {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(*sibling);
+            }
+            level = next_level(&level);
+            position /= 2;
+        }
+
+        Ok(MerkleProof {
+            leaf_index: index,
+            leaf,
+            siblings,
+        })
+    }
+
+    /// Sign the root of this batch as epoch `epoch`
+    pub(crate) fn sign_root(
+        &self,
+        signer: &EnclaveSigner,
+        epoch: u64,
+    ) -> Result<SignedMerkleRoot, EnclaveError> {
+        if signer.identity() != self.signer {
+            return Err(EnclaveError::AttestationFailure {
+                reason: "batch belongs to another enclave identity".to_string(),
+            });
+        }
+        let mut signed = SignedMerkleRoot {
+            root: self.root()?,
+            epoch,
+            leaf_count: self.leaves.len() as u64,
+            signer: signer.identity(),
+            signature: Vec::new(),
+        };
+        signed.signature = signer.sign(&signed.signing_message());
+        Ok(signed)
+    }
+}
+
+/// Batch the outputs of `payload`, which `signer` must have signed, and sign
+/// their root as `payload.epoch`
+///
+/// The epoch must be above `last_epoch`, the last one `signer` signed, which
+/// then becomes `payload.epoch`.
+pub(crate) fn sign(
+    signer: &EnclaveSigner,
+    last_epoch: &mut Option<u64>,
+    payload: &SignMerkleRootPayload,
+) -> Result<SignedMerkleRoot, EnclaveError> {
+    if let Some(last) = *last_epoch {
+        if payload.epoch <= last {
+            return Err(EnclaveError::invalid_payload(format!(
+                "epoch {} is not above the last signed epoch {}",
+                payload.epoch, last
+            )));
+        }
+    }
+    if payload.outputs.len() > MAX_BATCH_OUTPUTS {
+        return Err(EnclaveError::ResourceExhausted {
+            resource: format!(
+                "batch outputs ({} > {})",
+                payload.outputs.len(),
+                MAX_BATCH_OUTPUTS
+            ),
+        });
+    }
+
+    let mut batch = MerkleBatch::new(signer.identity());
+    for output in &payload.outputs {
+        batch.push(output)?;
+    }
+    let signed = batch.sign_root(signer, payload.epoch)?;
+    *last_epoch = Some(payload.epoch);
+    Ok(signed)
+}
+
+/// Parents of `level`, carrying an unpaired last node up unhashed
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [a, b] => hash_pair(a, b),
+            [a] => *a,
+            _ => unreachable!("chunks of at most two"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_operation, AddPayload, EnclaveInput, Operation, SignatureScheme};
+
+    fn batch(signer: &mut EnclaveSigner, size: i32) -> (MerkleBatch, Vec<SignedEnclaveOutput>) {
+        let mut batch = MerkleBatch::new(signer.identity());
+        let outputs: Vec<SignedEnclaveOutput> = (0..size)
+            .map(|a| {
+                let input = EnclaveInput::new(Operation::Add(AddPayload {
+                    a: a.into(),
+                    b: 1.into(),
+                }));
+                let output = process_operation(input.clone());
+                signer.sign_output(&input, output, 1_700_000_000).unwrap()
+            })
+            .collect();
+        for output in &outputs {
+            batch.push(output).unwrap();
+        }
+        (batch, outputs)
+    }
+
+    #[test]
+    fn test_keccak256_matches_solidity() {
+        assert_eq!(
+            hex::encode(keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
+    #[test]
+    fn test_every_proof_verifies() {
+        let mut signer = EnclaveSigner::generate(SignatureScheme::Secp256k1);
+        for size in [1, 2, 3, 5, 8] {
+            let (batch, outputs) = batch(&mut signer, size);
+            let root = batch.root().unwrap();
+            for (index, output) in outputs.iter().enumerate() {
+                let proof = batch.proof(index as u64).unwrap();
+                assert_eq!(proof.leaf, leaf_hash(output));
+                assert!(proof.verify(&root), "size {} index {}", size, index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrong_leaf_does_not_verify() {
+        let mut signer = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let (batch, _) = batch(&mut signer, 4);
+        let root = batch.root().unwrap();
+
+        let mut proof = batch.proof(1).unwrap();
+        proof.leaf = batch.leaves()[2];
+        assert!(!proof.verify(&root));
+        assert!(batch.proof(4).is_err());
+        assert!(MerkleBatch::new(signer.identity()).root().is_err());
+    }
+
+    #[test]
+    fn test_signed_root() {
+        let mut signer = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let (batch, mut outputs) = batch(&mut signer, 3);
+        let signed = batch.sign_root(&signer, 7).unwrap();
+
+        assert_eq!(signed.leaf_count, 3);
+        assert!(signed.verify().is_ok());
+        let mut forged = signed.clone();
+        forged.epoch = 8;
+        assert!(forged.verify().is_err());
+
+        let other = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        assert!(batch.sign_root(&other, 7).is_err());
+
+        outputs[0].timestamp += 1;
+        assert!(MerkleBatch::new(signer.identity())
+            .push(&outputs[0])
+            .is_err());
+    }
+
+    #[test]
+    fn test_batch_is_bound_to_its_signer() {
+        let mut signer = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let mut other = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let (_, outputs) = batch(&mut signer, 1);
+        let (_, foreign) = batch(&mut other, 1);
+
+        let mut batch = MerkleBatch::new(signer.identity());
+        assert_eq!(batch.push(&foreign[0]).unwrap_err().code(), 3001);
+        assert_eq!(batch.push(&outputs[0]).unwrap(), 0);
+
+        // Same hashes, sequence and timestamp from another signer give
+        // another leaf
+        let mut relabeled = foreign[0].clone();
+        relabeled.signer = outputs[0].signer.clone();
+        assert_eq!(relabeled.input_hash, outputs[0].input_hash);
+        assert_ne!(leaf_hash(&foreign[0]), leaf_hash(&outputs[0]));
+    }
+}
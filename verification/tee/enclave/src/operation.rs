@@ -0,0 +1,162 @@
+//! Typed enclave operations and their results.
+//!
+//! Every request the host can make of the enclave is a variant of
+//! [`Operation`], carrying its own payload struct. Results come back as the
+//! matching variant of [`OperationResult`]. Both enums are serde adjacently
+//! tagged so the JSON wire format reads as
+//! `{"type": "add", "payload": {"a": "1", "b": "2.5"}}`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::agency::receipts::{Receipt, RecordActionPayload, RecordOutcomePayload};
+use crate::agency::rewards::{ComputeRewardsPayload, RewardDistribution};
+use crate::ethereum::context::{BlockContext, BlockContextReport};
+use crate::ethereum::transaction::{EthereumAccount, SignTransactionPayload, SignedTransaction};
+use crate::fixed::Fixed;
+use crate::merkle::{SignMerkleRootPayload, SignedMerkleRoot};
+use crate::registry::OperationCatalog;
+use crate::simulation::base_fee::{BaseFeeSimulationPayload, BaseFeeSimulationReport};
+use crate::simulation::fork::{ForkSimulationPayload, ForkSimulationReport};
+use crate::tasks::gas_optimizer::{GasOptimizationPayload, GasRecommendation};
+use crate::tasks::mev_estimator::{MevEstimate, MevEstimationPayload};
+use crate::tasks::proposal_scanner::{ProposalScanPayload, ProposalScanReport};
+
+/// Payload for the `add` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AddPayload {
+    pub a: Fixed,
+    pub b: Fixed,
+}
+
+/// Result of the `add` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AddResult {
+    pub sum: Fixed,
+}
+
+/// An operation the enclave knows how to execute
+///
+/// Inputs are decoded once per request, so variants hold their payloads
+/// inline rather than boxed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum Operation {
+    Add(AddPayload),
+    OptimizeGasParameters(GasOptimizationPayload),
+    ScanProposal(ProposalScanPayload),
+    EstimateMevCost(MevEstimationPayload),
+    SimulateFork(ForkSimulationPayload),
+    SimulateBaseFee(BaseFeeSimulationPayload),
+    RecordAction(RecordActionPayload),
+    RecordOutcome(RecordOutcomePayload),
+    ComputeRewards(ComputeRewardsPayload),
+    /// Report the enclave's Ethereum address; carries no payload
+    EthereumAddress,
+    SignTransaction(SignTransactionPayload),
+    VerifyBlockContext(BlockContext),
+    SignMerkleRoot(SignMerkleRootPayload),
+    /// Describe every registered operation; carries no payload
+    ListOperations,
+}
+
+impl Operation {
+    /// Wire names of every supported operation, in declaration order
+    pub const NAMES: &'static [&'static str] = &[
+        "add",
+        "optimize_gas_parameters",
+        "scan_proposal",
+        "estimate_mev_cost",
+        "simulate_fork",
+        "simulate_base_fee",
+        "record_action",
+        "record_outcome",
+        "compute_rewards",
+        "ethereum_address",
+        "sign_transaction",
+        "verify_block_context",
+        "sign_merkle_root",
+        "list_operations",
+    ];
+
+    /// Wire name of this operation
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Add(_) => "add",
+            Operation::OptimizeGasParameters(_) => "optimize_gas_parameters",
+            Operation::ScanProposal(_) => "scan_proposal",
+            Operation::EstimateMevCost(_) => "estimate_mev_cost",
+            Operation::SimulateFork(_) => "simulate_fork",
+            Operation::SimulateBaseFee(_) => "simulate_base_fee",
+            Operation::RecordAction(_) => "record_action",
+            Operation::RecordOutcome(_) => "record_outcome",
+            Operation::ComputeRewards(_) => "compute_rewards",
+            Operation::EthereumAddress => "ethereum_address",
+            Operation::SignTransaction(_) => "sign_transaction",
+            Operation::VerifyBlockContext(_) => "verify_block_context",
+            Operation::SignMerkleRoot(_) => "sign_merkle_root",
+            Operation::ListOperations => "list_operations",
+        }
+    }
+}
+
+/// Typed result of an enclave operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum OperationResult {
+    Add(AddResult),
+    OptimizeGasParameters(GasRecommendation),
+    ScanProposal(ProposalScanReport),
+    EstimateMevCost(MevEstimate),
+    SimulateFork(ForkSimulationReport),
+    SimulateBaseFee(BaseFeeSimulationReport),
+    RecordAction(Receipt),
+    RecordOutcome(Receipt),
+    ComputeRewards(RewardDistribution),
+    EthereumAddress(EthereumAccount),
+    SignTransaction(SignedTransaction),
+    VerifyBlockContext(BlockContextReport),
+    SignMerkleRoot(SignedMerkleRoot),
+    ListOperations(OperationCatalog),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_wire_format() {
+        let op = Operation::Add(AddPayload {
+            a: 1.into(),
+            b: "2.5".parse().unwrap(),
+        });
+        let json = serde_json::to_value(&op).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"type": "add", "payload": {"a": "1", "b": "2.5"}})
+        );
+        assert_eq!(serde_json::from_value::<Operation>(json).unwrap(), op);
+
+        // Integers are accepted on input, floats are not
+        let integers = serde_json::json!({"type": "add", "payload": {"a": 1, "b": 2}});
+        assert!(serde_json::from_value::<Operation>(integers).is_ok());
+        let floats = serde_json::json!({"type": "add", "payload": {"a": 1, "b": 2.5}});
+        assert!(serde_json::from_value::<Operation>(floats).is_err());
+    }
+
+    #[test]
+    fn test_unit_operation_needs_no_payload() {
+        let op: Operation = serde_json::from_str(r#"{"type": "list_operations"}"#).unwrap();
+        assert_eq!(op, Operation::ListOperations);
+    }
+
+    #[test]
+    fn test_operation_names_cover_variants() {
+        let op = Operation::Add(AddPayload {
+            a: 0.into(),
+            b: 0.into(),
+        });
+        assert!(Operation::NAMES.contains(&op.name()));
+    }
+}
@@ -0,0 +1,642 @@
+//! Registry of the operations this enclave build will execute.
+//!
+//! Only operations listed here can run; there is no way to ship code into
+//! the enclave. Each entry carries a version, JSON Schema descriptions of its
+//! payload and result (top-level fields only), and a measurement: SHA-256
+//! over the operation's identity, the [`SHARED_SOURCES`] every operation
+//! runs through and the source files implementing it, as compiled into this
+//! build. Every [`crate::EnclaveOutput`] names the
+//! [`OperationRef`] that produced it, so a signature over an output attests
+//! to "operation X version Y with measurement Z" rather than to opaque bytes.
+//!
+//! Operations start at version 1. Once an operation has been released, bump
+//! its version whenever its payload, result or semantics change; the
+//! measurement changes on its own whenever its code does.
+
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{Operation, PROTOCOL_VERSION};
+
+/// Domain separation tag prefixed to every measured operation
+pub const MEASUREMENT_DOMAIN: &[u8] = b"chaoschain-enclave/operation-measurement/v1";
+
+/// Public description of a registered operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OperationDescriptor {
+    pub name: String,
+    pub version: u32,
+    pub description: String,
+    pub input_schema: Value,
+    pub output_schema: Value,
+    #[serde(with = "crate::serde_hex")]
+    pub measurement: [u8; 32],
+}
+
+impl OperationDescriptor {
+    /// Compact reference to this operation for embedding in outputs
+    pub fn reference(&self) -> OperationRef {
+        OperationRef {
+            name: self.name.clone(),
+            version: self.version,
+            measurement: self.measurement,
+        }
+    }
+}
+
+/// Identifies the exact operation implementation that produced an output
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OperationRef {
+    pub name: String,
+    pub version: u32,
+    #[serde(with = "crate::serde_hex")]
+    pub measurement: [u8; 32],
+}
+
+/// Result of the `list_operations` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OperationCatalog {
+    pub protocol_version: u32,
+    pub operations: Vec<OperationDescriptor>,
+}
+
+struct Entry {
+    name: &'static str,
+    version: u32,
+    description: &'static str,
+    sources: &'static [&'static [u8]],
+    input_schema: fn() -> Value,
+    output_schema: fn() -> Value,
+}
+
+/// Sources of the dispatch, wire types, canonical encoding, signing and
+/// decimal arithmetic that shape every operation's signed output
+pub const SHARED_SOURCES: &[&[u8]] = &[
+    include_bytes!("lib.rs"),
+    include_bytes!("operation.rs"),
+    include_bytes!("error.rs"),
+    include_bytes!("canonical.rs"),
+    include_bytes!("signing.rs"),
+    include_bytes!("fixed.rs"),
+    include_bytes!("serde_decimal.rs"),
+    include_bytes!("serde_hex.rs"),
+];
+
+const TASKS: &[u8] = include_bytes!("tasks/mod.rs");
+const RNG: &[u8] = include_bytes!("simulation/rng.rs");
+const AGENCY: &[u8] = include_bytes!("agency/mod.rs");
+const RECEIPTS: &[u8] = include_bytes!("agency/receipts.rs");
+const REWARDS: &[u8] = include_bytes!("agency/rewards.rs");
+const ABI: &[u8] = include_bytes!("abi.rs");
+const ENDPOINT: &[u8] = include_bytes!("endpoint.rs");
+const ETHEREUM: &[u8] = include_bytes!("ethereum/mod.rs");
+const RLP: &[u8] = include_bytes!("ethereum/rlp.rs");
+const TRANSACTION: &[u8] = include_bytes!("ethereum/transaction.rs");
+const CONFIG: &[u8] = include_bytes!("config.rs");
+const HEADER: &[u8] = include_bytes!("ethereum/header.rs");
+const ETHEREUM_RECEIPT: &[u8] = include_bytes!("ethereum/receipt.rs");
+const TRIE: &[u8] = include_bytes!("ethereum/trie.rs");
+const CONTEXT: &[u8] = include_bytes!("ethereum/context.rs");
+
+const ENTRIES: &[Entry] = &[
+    Entry {
+        name: "add",
+        version: 1,
+        description: "Add two fixed-point decimals",
+        sources: &[],
+        input_schema: || object(&[("a", fixed()), ("b", fixed())], &[]),
+        output_schema: || object(&[("sum", fixed())], &[]),
+    },
+    Entry {
+        name: "optimize_gas_parameters",
+        version: 1,
+        description: "Recommend gas price and limit from recent block history",
+        sources: &[
+            TASKS,
+            include_bytes!("tasks/gas_optimizer.rs"),
+            RLP,
+            HEADER,
+            ETHEREUM_RECEIPT,
+            TRIE,
+            CONTEXT,
+        ],
+        input_schema: || {
+            object(
+                &[],
+                &[
+                    ("gas_used", array(integer())),
+                    ("gas_prices_gwei", array(fixed())),
+                    ("block_context", any_object()),
+                    ("proposal_type", string()),
+                    ("network_congestion", fixed()),
+                    ("config", any_object()),
+                ],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("recommended_gas_price_gwei", fixed()),
+                    ("max_gas_price_gwei", fixed()),
+                    ("gas_limit", integer()),
+                    ("estimated_cost_gwei", fixed()),
+                    ("priority_fee_gwei", fixed()),
+                    ("recommendation_quality", string()),
+                    ("proposal_type", string()),
+                    ("validity_blocks", integer()),
+                    ("analyzed_blocks", integer()),
+                ],
+                &[("block_range", any_object())],
+            )
+        },
+    },
+    Entry {
+        name: "scan_proposal",
+        version: 1,
+        description: "Scan a proposal for parameter, code and history risks",
+        sources: &[TASKS, include_bytes!("tasks/proposal_scanner.rs")],
+        input_schema: || {
+            object(
+                &[("proposal", any_object())],
+                &[
+                    ("protocol_parameters", any_object()),
+                    ("known_vulnerabilities", array(any_object())),
+                    ("account_history", any_object()),
+                    ("contract_bytecode", any_object()),
+                    ("config", any_object()),
+                ],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("proposal_id", string()),
+                    ("risk_level", string()),
+                    ("risk_score", fixed()),
+                    ("checks", array(any_object())),
+                    ("checks_passed", integer()),
+                    ("checks_failed", integer()),
+                    ("recommendations", array(string())),
+                ],
+                &[],
+            )
+        },
+    },
+    Entry {
+        name: "estimate_mev_cost",
+        version: 1,
+        description: "Estimate MEV exposure created by a parameter change",
+        sources: &[TASKS, include_bytes!("tasks/mev_estimator.rs")],
+        input_schema: || {
+            object(
+                &[],
+                &[
+                    ("proposal_id", string()),
+                    ("parameter_changes", any_object()),
+                    ("trading_pairs", array(any_object())),
+                    ("mempool", any_object()),
+                    ("gas_prices_gwei", array(fixed())),
+                    ("active_bots", array(any_object())),
+                    ("lending", any_object()),
+                    ("config", any_object()),
+                ],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("proposal_id", string()),
+                    ("risk_level", string()),
+                    ("risk_score", fixed()),
+                    ("estimated_total_mev_cost", fixed()),
+                    ("estimated_cost_per_block", fixed()),
+                    ("sandwich_attacks", any_object()),
+                    ("frontrunning", any_object()),
+                    ("liquidations", any_object()),
+                    ("arbitrage", any_object()),
+                    ("mitigations", array(string())),
+                    ("estimation_horizon_blocks", integer()),
+                ],
+                &[],
+            )
+        },
+    },
+    Entry {
+        name: "simulate_fork",
+        version: 1,
+        description: "Simulate a seeded block of transactions under proposed gas and fee changes",
+        sources: &[
+            RNG,
+            include_bytes!("simulation/fork.rs"),
+            RLP,
+            HEADER,
+            ETHEREUM_RECEIPT,
+            TRIE,
+            CONTEXT,
+        ],
+        input_schema: || {
+            object(
+                &[("seed", integer())],
+                &[
+                    ("proposal_id", string()),
+                    ("transaction_count", integer()),
+                    ("block", any_object()),
+                    ("adjustments", any_object()),
+                    ("include_transactions", boolean()),
+                    ("block_context", any_object()),
+                ],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("proposal_id", string()),
+                    ("seed", integer()),
+                    ("transaction_count", integer()),
+                    ("block", any_object()),
+                    ("adjustments", any_object()),
+                    ("total_original_gas", integer()),
+                    ("total_adjusted_gas", integer()),
+                    ("avg_original_gas", integer()),
+                    ("avg_adjusted_gas", integer()),
+                    ("total_original_cost", decimal()),
+                    ("total_adjusted_cost", decimal()),
+                    ("avg_original_cost", decimal()),
+                    ("avg_adjusted_cost", decimal()),
+                    ("cost_difference", decimal()),
+                    ("relative_cost_difference", fixed()),
+                    ("gas_delta", fixed()),
+                    ("fee_growth", fixed()),
+                    ("transactions_hash", string()),
+                    ("transactions", array(any_object())),
+                ],
+                &[("block_range", any_object())],
+            )
+        },
+    },
+    Entry {
+        name: "simulate_base_fee",
+        version: 1,
+        description: "Simulate EIP-1559 base fee dynamics under baseline and proposed parameters",
+        sources: &[
+            RNG,
+            include_bytes!("simulation/base_fee.rs"),
+            RLP,
+            HEADER,
+            ETHEREUM_RECEIPT,
+            TRIE,
+            CONTEXT,
+        ],
+        input_schema: || {
+            object(
+                &[("seed", integer())],
+                &[
+                    ("proposal_id", string()),
+                    ("blocks", integer()),
+                    ("demand", any_object()),
+                    ("baseline", any_object()),
+                    ("proposed", any_object()),
+                    ("include_blocks", boolean()),
+                    ("block_context", any_object()),
+                ],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("proposal_id", string()),
+                    ("seed", integer()),
+                    ("blocks", integer()),
+                    ("demand", any_object()),
+                    ("baseline", any_object()),
+                    ("proposed", any_object()),
+                    ("burned_delta", fixed()),
+                    ("volatility_delta", fixed()),
+                    ("fullness_delta", fixed()),
+                ],
+                &[("block_range", any_object())],
+            )
+        },
+    },
+    Entry {
+        name: "record_action",
+        version: 1,
+        description: "Issue a hash-chained Proof-of-Agency receipt for an agent action",
+        sources: &[AGENCY, RECEIPTS],
+        input_schema: || {
+            object(
+                &[
+                    ("agent_id", string()),
+                    ("action_id", string()),
+                    ("action_type", string()),
+                    ("input_hash", string()),
+                    ("output_hash", string()),
+                ],
+                &[("parent", any_object())],
+            )
+        },
+        output_schema: receipt_schema,
+    },
+    Entry {
+        name: "record_outcome",
+        version: 1,
+        description: "Issue a hash-chained Proof-of-Agency receipt for an action's outcome",
+        sources: &[AGENCY, RECEIPTS],
+        input_schema: || {
+            object(
+                &[
+                    ("action", any_object()),
+                    ("success", boolean()),
+                    ("impact_score", fixed()),
+                    ("results_hash", string()),
+                ],
+                &[("since_action", array(any_object()))],
+            )
+        },
+        output_schema: receipt_schema,
+    },
+    Entry {
+        name: "compute_rewards",
+        version: 1,
+        description: "Compute token rewards for a concluded action from its receipts",
+        sources: &[AGENCY, RECEIPTS, REWARDS, CONFIG],
+        input_schema: || {
+            object(
+                &[
+                    ("action", any_object()),
+                    ("outcome", any_object()),
+                    ("addresses", any_object()),
+                ],
+                &[("verifications", array(any_object()))],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("action_id", string()),
+                    ("action_receipt_hash", string()),
+                    ("outcome_receipt_hash", string()),
+                    ("schedule", any_object()),
+                    ("recipients", array(any_object())),
+                    ("total", decimal()),
+                    ("recipients_data", string()),
+                ],
+                &[],
+            )
+        },
+    },
+    Entry {
+        name: "ethereum_address",
+        version: 1,
+        description: "Report the address of the enclave's Ethereum account",
+        sources: &[ETHEREUM],
+        input_schema: || json!({ "type": "null" }),
+        output_schema: || object(&[("address", string())], &[]),
+    },
+    Entry {
+        name: "sign_transaction",
+        version: 1,
+        description: "Sign a ChaosEndpoint transaction built from an output of this enclave",
+        sources: &[ABI, ENDPOINT, ETHEREUM, RLP, TRANSACTION, CONFIG],
+        input_schema: || {
+            object(
+                &[("transaction", any_object()), ("call", any_object())],
+                &[],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("chain_id", integer()),
+                    ("to", string()),
+                    ("transaction", any_object()),
+                    ("call", any_object()),
+                    ("from", string()),
+                    ("hash", string()),
+                    ("raw", string()),
+                ],
+                &[],
+            )
+        },
+    },
+    Entry {
+        name: "verify_block_context",
+        version: 1,
+        description:
+            "Verify a header chain to a checkpoint and receipt and state proofs against it",
+        sources: &[RLP, HEADER, ETHEREUM_RECEIPT, TRIE, CONTEXT],
+        input_schema: || {
+            object(
+                &[("checkpoint", any_object()), ("headers", array(string()))],
+                &[
+                    ("receipt_proofs", array(any_object())),
+                    ("account_proofs", array(any_object())),
+                ],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("range", any_object()),
+                    ("receipts", array(any_object())),
+                    ("accounts", array(any_object())),
+                ],
+                &[],
+            )
+        },
+    },
+    Entry {
+        name: "sign_merkle_root",
+        version: 1,
+        description: "Sign the Merkle root of an epoch's outputs of this enclave, once per epoch",
+        sources: &[include_bytes!("merkle.rs"), include_bytes!("state.rs")],
+        input_schema: || {
+            object(
+                &[("epoch", integer()), ("outputs", array(any_object()))],
+                &[],
+            )
+        },
+        output_schema: || {
+            object(
+                &[
+                    ("root", string()),
+                    ("epoch", integer()),
+                    ("leaf_count", integer()),
+                    ("signer", any_object()),
+                    ("signature", string()),
+                ],
+                &[],
+            )
+        },
+    },
+    Entry {
+        name: "list_operations",
+        version: 1,
+        description: "List the operations this enclave build executes",
+        sources: &[include_bytes!("registry.rs")],
+        input_schema: || json!({ "type": "null" }),
+        output_schema: || {
+            object(
+                &[
+                    ("protocol_version", integer()),
+                    ("operations", array(any_object())),
+                ],
+                &[],
+            )
+        },
+    },
+];
+
+/// Every registered operation, in [`Operation::NAMES`] order
+pub fn operations() -> &'static [OperationDescriptor] {
+    static DESCRIPTORS: OnceLock<Vec<OperationDescriptor>> = OnceLock::new();
+    DESCRIPTORS.get_or_init(|| ENTRIES.iter().map(describe).collect())
+}
+
+/// Descriptor of the operation with wire name `name`
+pub fn lookup(name: &str) -> Option<&'static OperationDescriptor> {
+    operations()
+        .iter()
+        .find(|descriptor| descriptor.name == name)
+}
+
+/// Descriptor of the operation `operation` will run
+pub fn describe_operation(operation: &Operation) -> Option<&'static OperationDescriptor> {
+    lookup(operation.name())
+}
+
+/// Catalog returned by `list_operations`
+pub fn catalog() -> OperationCatalog {
+    OperationCatalog {
+        protocol_version: PROTOCOL_VERSION,
+        operations: operations().to_vec(),
+    }
+}
+
+fn describe(entry: &Entry) -> OperationDescriptor {
+    let mut hasher = Sha256::new();
+    hasher.update(MEASUREMENT_DOMAIN);
+    hasher.update((entry.name.len() as u64).to_be_bytes());
+    hasher.update(entry.name.as_bytes());
+    hasher.update(entry.version.to_be_bytes());
+    for source in SHARED_SOURCES.iter().chain(entry.sources) {
+        hasher.update((source.len() as u64).to_be_bytes());
+        hasher.update(source);
+    }
+
+    OperationDescriptor {
+        name: entry.name.to_string(),
+        version: entry.version,
+        description: entry.description.to_string(),
+        input_schema: (entry.input_schema)(),
+        output_schema: (entry.output_schema)(),
+        measurement: hasher.finalize().into(),
+    }
+}
+
+fn receipt_schema() -> Value {
+    object(
+        &[
+            ("kind", string()),
+            ("agent_id", string()),
+            ("action_id", string()),
+            ("action_type", string()),
+            ("input_hash", string()),
+            ("output_hash", string()),
+            ("parent_hash", string()),
+            ("height", integer()),
+            ("receipt_hash", string()),
+        ],
+        &[("outcome", any_object())],
+    )
+}
+
+fn object(required: &[(&str, Value)], optional: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = required
+        .iter()
+        .chain(optional)
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    let required: Vec<&str> = required.iter().map(|(name, _)| *name).collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn any_object() -> Value {
+    json!({ "type": "object" })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+/// Integer too wide for JSON numbers, carried as a decimal string
+fn decimal() -> Value {
+    json!({ "type": "string", "pattern": "^-?[0-9]+$" })
+}
+
+/// A [`crate::fixed::Fixed`]: a decimal string, or an integer on input
+fn fixed() -> Value {
+    json!({
+        "type": ["string", "integer"],
+        "pattern": "^-?[0-9]+(\\.[0-9]{1,18})?$"
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_operation, AddPayload, EnclaveInput, OperationResult};
+
+    #[test]
+    fn test_registry_matches_operation_names() {
+        let names: Vec<&str> = operations().iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, Operation::NAMES);
+    }
+
+    #[test]
+    fn test_measurements_are_distinct() {
+        let mut measurements: Vec<[u8; 32]> = operations().iter().map(|d| d.measurement).collect();
+        measurements.sort();
+        measurements.dedup();
+        assert_eq!(measurements.len(), operations().len());
+    }
+
+    #[test]
+    fn test_outputs_name_their_operation() {
+        let output = process_operation(EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 1.into(),
+        })));
+        assert_eq!(output.operation, Some(lookup("add").unwrap().reference()));
+    }
+
+    #[test]
+    fn test_list_operations() {
+        let output = process_operation(EnclaveInput::new(Operation::ListOperations));
+        match output.result {
+            Ok(OperationResult::ListOperations(catalog)) => {
+                assert_eq!(catalog, super::catalog());
+                assert_eq!(
+                    catalog.operations[0].input_schema["required"],
+                    json!(["a", "b"])
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
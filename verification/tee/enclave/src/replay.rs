@@ -0,0 +1,278 @@
+//! Per-client request nonces.
+//!
+//! Every request a client sends carries a [`RequestNonce`]: the client's
+//! identifier and a nonce strictly greater than any the client used before.
+//! The nonce is part of the [`EnclaveInput`] and so of its input hash, which
+//! the signed output commits to. A [`ReplayGuard`] remembers the last nonce
+//! seen from each client and refuses anything not above it, so the host
+//! cannot have the enclave attest the same request twice.
+//!
+//! Client ids are not authenticated, so anyone can make up new ones. To keep
+//! its sealed state bounded the guard tracks at most [`MAX_TRACKED_CLIENTS`]
+//! clients; when a new client arrives at a full guard, the client with the
+//! lowest last nonce is forgotten and that nonce becomes the guard's floor.
+//! A client the guard does not track must send a nonce above the floor, so
+//! forgetting a client never lets its old requests through again.
+//!
+//! For the same reason anyone can spend nonces in another client's name.
+//! A nonce may therefore be at most [`MAX_NONCE_STEP`] above the client's
+//! last nonce, or above the floor for a client the guard does not track, so
+//! nobody can push a client or the floor to the end of the nonce range.
+//! Clients count their nonces up from 1; a client whose nonce was spent by
+//! someone else resumes above the `last_nonce` of the
+//! [`EnclaveError::ReplayDetected`] it gets back.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EnclaveError, EnclaveInput};
+
+/// Most clients a [`ReplayGuard`] tracks at once
+pub const MAX_TRACKED_CLIENTS: usize = 4096;
+
+/// Largest amount by which a nonce may exceed its client's last nonce
+pub const MAX_NONCE_STEP: u64 = 1 << 20;
+
+/// Longest accepted client identifier, in bytes
+pub const MAX_CLIENT_ID_LEN: usize = 256;
+
+/// Nonce identifying one request of one client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestNonce {
+    pub client: String,
+    pub nonce: u64,
+}
+
+/// Last nonce accepted from each client
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayGuard {
+    last: BTreeMap<String, u64>,
+    /// Highest last nonce of any client forgotten so far
+    floor: u64,
+}
+
+impl ReplayGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Last nonce accepted from `client`
+    pub fn last_nonce(&self, client: &str) -> Option<u64> {
+        self.last.get(client).copied()
+    }
+
+    /// Nonce that clients this guard does not track must exceed
+    pub fn floor(&self) -> u64 {
+        self.floor
+    }
+
+    /// Check that `input` carries a fresh nonce, without recording it
+    pub fn check<'a>(&self, input: &'a EnclaveInput) -> Result<&'a RequestNonce, EnclaveError> {
+        let nonce = input
+            .nonce
+            .as_ref()
+            .ok_or_else(|| EnclaveError::invalid_payload("request carries no client nonce"))?;
+        if nonce.client.is_empty() || nonce.client.len() > MAX_CLIENT_ID_LEN {
+            return Err(EnclaveError::invalid_payload(format!(
+                "client id must be 1 to {} bytes",
+                MAX_CLIENT_ID_LEN
+            )));
+        }
+
+        let last_nonce = self.last.get(&nonce.client).copied().unwrap_or(self.floor);
+        if nonce.nonce <= last_nonce {
+            return Err(EnclaveError::ReplayDetected {
+                client: nonce.client.clone(),
+                nonce: nonce.nonce,
+                last_nonce,
+            });
+        }
+        if nonce.nonce - last_nonce > MAX_NONCE_STEP {
+            return Err(EnclaveError::invalid_payload(format!(
+                "nonce {} of client '{}' is more than {} above its last nonce {}",
+                nonce.nonce, nonce.client, MAX_NONCE_STEP, last_nonce
+            )));
+        }
+        Ok(nonce)
+    }
+
+    /// Check the nonce of `input` and record it as spent, forgetting the
+    /// client with the lowest nonce if a new client arrives at a full guard
+    pub fn accept(&mut self, input: &EnclaveInput) -> Result<(), EnclaveError> {
+        let nonce = self.check(input)?;
+        if !self.last.contains_key(&nonce.client) && self.last.len() >= MAX_TRACKED_CLIENTS {
+            self.evict_lowest();
+        }
+        self.last.insert(nonce.client.clone(), nonce.nonce);
+        Ok(())
+    }
+
+    fn evict_lowest(&mut self) {
+        let lowest = self
+            .last
+            .iter()
+            .min_by_key(|(_, &nonce)| nonce)
+            .map(|(client, &nonce)| (client.clone(), nonce));
+        if let Some((client, nonce)) = lowest {
+            self.last.remove(&client);
+            self.floor = self.floor.max(nonce);
+        }
+    }
+
+    /// Encoding used in sealed state: `floor (u64 BE) || count (u32 BE)
+    /// || (id length (u16 BE) || id || nonce (u64 BE))*`
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.floor.to_be_bytes().to_vec();
+        out.extend_from_slice(&(self.last.len() as u32).to_be_bytes());
+        for (client, nonce) in &self.last {
+            out.extend_from_slice(&(client.len() as u16).to_be_bytes());
+            out.extend_from_slice(client.as_bytes());
+            out.extend_from_slice(&nonce.to_be_bytes());
+        }
+        out
+    }
+
+    pub(crate) fn from_bytes(mut bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let mut take = |len: usize| -> Result<&[u8], EnclaveError> {
+            if bytes.len() < len {
+                return Err(EnclaveError::SealingFailure {
+                    reason: "replay state is truncated".to_string(),
+                });
+            }
+            let (head, tail) = bytes.split_at(len);
+            bytes = tail;
+            Ok(head)
+        };
+
+        let floor = u64::from_be_bytes(take(8)?.try_into().expect("length checked"));
+        let count = u32::from_be_bytes(take(4)?.try_into().expect("length checked"));
+        let mut last = BTreeMap::new();
+        for _ in 0..count {
+            let len = u16::from_be_bytes(take(2)?.try_into().expect("length checked"));
+            let client = String::from_utf8(take(len as usize)?.to_vec()).map_err(|_| {
+                EnclaveError::SealingFailure {
+                    reason: "replay state holds a non UTF-8 client id".to_string(),
+                }
+            })?;
+            let nonce = u64::from_be_bytes(take(8)?.try_into().expect("length checked"));
+            last.insert(client, nonce);
+        }
+        if !bytes.is_empty() {
+            return Err(EnclaveError::SealingFailure {
+                reason: "trailing bytes after replay state".to_string(),
+            });
+        }
+        Ok(Self { last, floor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AddPayload, Operation};
+
+    fn input(client: &str, nonce: u64) -> EnclaveInput {
+        EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 2.into(),
+        }))
+        .with_nonce(client, nonce)
+    }
+
+    #[test]
+    fn test_nonces_must_increase_per_client() {
+        let mut guard = ReplayGuard::new();
+        guard.accept(&input("dao-a", 1)).unwrap();
+        guard.accept(&input("dao-a", 5)).unwrap();
+        guard.accept(&input("dao-b", 1)).unwrap();
+
+        let error = guard.accept(&input("dao-a", 5)).unwrap_err();
+        assert_eq!(error.code(), 3003);
+        assert_eq!(
+            error,
+            EnclaveError::ReplayDetected {
+                client: "dao-a".to_string(),
+                nonce: 5,
+                last_nonce: 5,
+            }
+        );
+        assert!(guard.accept(&input("dao-a", 2)).is_err());
+        assert_eq!(guard.last_nonce("dao-a"), Some(5));
+    }
+
+    #[test]
+    fn test_missing_nonce_is_rejected() {
+        let bare = EnclaveInput::new(Operation::Add(AddPayload {
+            a: 1.into(),
+            b: 2.into(),
+        }));
+        assert!(matches!(
+            ReplayGuard::new().check(&bare),
+            Err(EnclaveError::InvalidPayload { .. })
+        ));
+    }
+
+    #[test]
+    fn test_nonce_is_covered_by_input_hash() {
+        assert_ne!(
+            input("dao-a", 1).input_hash(),
+            input("dao-a", 2).input_hash()
+        );
+        assert_ne!(
+            input("dao-a", 1).input_hash(),
+            EnclaveInput::new(Operation::Add(AddPayload {
+                a: 1.into(),
+                b: 2.into()
+            }))
+            .input_hash()
+        );
+    }
+
+    #[test]
+    fn test_nonces_cannot_jump_ahead() {
+        let mut guard = ReplayGuard::new();
+        guard.accept(&input("dao-a", 5)).unwrap();
+
+        // Nobody can spend the end of the range in dao-a's name
+        let error = guard.accept(&input("dao-a", u64::MAX)).unwrap_err();
+        assert_eq!(error.code(), 1003);
+        assert!(guard.accept(&input("dao-b", MAX_NONCE_STEP + 1)).is_err());
+        assert_eq!(guard.last_nonce("dao-a"), Some(5));
+
+        guard.accept(&input("dao-a", 5 + MAX_NONCE_STEP)).unwrap();
+        guard.accept(&input("dao-b", MAX_NONCE_STEP)).unwrap();
+    }
+
+    #[test]
+    fn test_full_guard_forgets_lowest_client() {
+        let mut guard = ReplayGuard::new();
+        for client in 0..MAX_TRACKED_CLIENTS as u64 {
+            guard
+                .accept(&input(&format!("client-{}", client), client + 10))
+                .unwrap();
+        }
+
+        guard.accept(&input("newcomer", 100)).unwrap();
+        assert_eq!(guard.last_nonce("client-0"), None);
+        assert_eq!(guard.floor(), 10);
+
+        // The forgotten client cannot replay, but can continue above the floor
+        let error = guard.accept(&input("client-0", 10)).unwrap_err();
+        assert_eq!(error.code(), 3003);
+        assert!(guard.accept(&input("other-newcomer", 5)).is_err());
+        guard.accept(&input("client-0", 11)).unwrap();
+        assert_eq!(guard.last_nonce("client-1"), None);
+        assert_eq!(guard.floor(), 11);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut guard = ReplayGuard::new();
+        guard.accept(&input("dao-a", 3)).unwrap();
+        guard.accept(&input("dao-b", 9)).unwrap();
+
+        assert_eq!(ReplayGuard::from_bytes(&guard.to_bytes()).unwrap(), guard);
+        assert!(ReplayGuard::from_bytes(&guard.to_bytes()[..10]).is_err());
+    }
+}
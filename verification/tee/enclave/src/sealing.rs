@@ -0,0 +1,535 @@
+//! Sealing of enclave secrets for persistent storage.
+//!
+//! A [`Sealer`] encrypts data so that only an enclave matching a
+//! [`SealPolicy`] can recover it: with [`SealPolicy::MrEnclave`] only the
+//! same enclave build, with [`SealPolicy::MrSigner`] any build signed by the
+//! same key (and so also later, upgraded builds). The host stores the
+//! resulting [`SealedBlob`] and hands it back after a restart; it never sees
+//! the plaintext.
+//!
+//! Every blob carries caller-chosen additional data, which is authenticated
+//! but not encrypted, and a versioned header:
+//!
+//! ```text
+//! SEALED_BLOB_MAGIC (4) || version (u16 BE) || backend (u8)
+//!     || key policy (u16 BE) || additional data length (u32 BE)
+//!     || additional data || payload length (u32 BE) || payload
+//! ```
+//!
+//! Everything up to the payload is authenticated together with it. In the
+//! `sgx` build the payload is an `sgx_sealed_data_t` from `sgx_tseal`, keyed
+//! by the CPU. Otherwise it is `nonce (12) || AES-256-GCM ciphertext || tag`
+//! under a key derived from a root key kept in a file, see [`Sealer::open`],
+//! and the simulated MRENCLAVE or MRSIGNER. A simulated sealer protects
+//! nothing from whoever can read that file.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroizing;
+
+use crate::EnclaveError;
+
+/// Leading bytes of every encoded [`SealedBlob`]
+pub const SEALED_BLOB_MAGIC: &[u8; 4] = b"CCSB";
+
+/// Version of the sealed blob layout produced by this build
+pub const SEALED_BLOB_VERSION: u16 = 1;
+
+/// Which enclave identity a sealing key is bound to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SealPolicy {
+    /// Only this exact enclave build can unseal
+    MrEnclave,
+    /// Any enclave build from the same signer can unseal
+    MrSigner,
+}
+
+impl SealPolicy {
+    /// SGX `KEYPOLICY` bit selecting this policy
+    pub fn key_policy(self) -> u16 {
+        match self {
+            SealPolicy::MrEnclave => 0x0001,
+            SealPolicy::MrSigner => 0x0002,
+        }
+    }
+
+    fn from_key_policy(key_policy: u16) -> Result<Self, EnclaveError> {
+        match key_policy {
+            0x0001 => Ok(SealPolicy::MrEnclave),
+            0x0002 => Ok(SealPolicy::MrSigner),
+            other => Err(sealing_failure(format!(
+                "unknown key policy {:#06x}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Sealing implementation that produced a blob
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SealingBackend {
+    /// AES-GCM under a file-backed root key
+    Simulation,
+    /// `sgx_tseal` under the CPU's sealing key
+    Sgx,
+}
+
+impl SealingBackend {
+    /// Backend of this build
+    pub fn current() -> Self {
+        if cfg!(feature = "sgx") {
+            SealingBackend::Sgx
+        } else {
+            SealingBackend::Simulation
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            SealingBackend::Simulation => 0,
+            SealingBackend::Sgx => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, EnclaveError> {
+        match byte {
+            0 => Ok(SealingBackend::Simulation),
+            1 => Ok(SealingBackend::Sgx),
+            other => Err(sealing_failure(format!(
+                "unknown sealing backend {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Encrypted, authenticated data that only a matching enclave can unseal
+///
+/// Serializes as a hex string of its binary encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedBlob {
+    pub version: u16,
+    pub backend: SealingBackend,
+    pub policy: SealPolicy,
+    /// Authenticated, unencrypted data supplied when sealing
+    pub aad: Vec<u8>,
+    payload: Vec<u8>,
+}
+
+impl SealedBlob {
+    /// Header bytes authenticated together with the payload
+    fn authenticated_header(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(15 + self.aad.len());
+        out.extend_from_slice(SEALED_BLOB_MAGIC);
+        out.extend_from_slice(&self.version.to_be_bytes());
+        out.push(self.backend.to_byte());
+        out.extend_from_slice(&self.policy.key_policy().to_be_bytes());
+        out.extend_from_slice(&(self.aad.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.aad);
+        out
+    }
+
+    /// Binary encoding, see the module documentation
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.authenticated_header();
+        out.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.payload);
+        out
+    }
+
+    /// Decode a blob, rejecting unknown versions and trailing bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let mut reader = Reader(bytes);
+        if reader.take(SEALED_BLOB_MAGIC.len())? != SEALED_BLOB_MAGIC {
+            return Err(sealing_failure("not a sealed blob"));
+        }
+        let version = u16::from_be_bytes(array(reader.take(2)?));
+        if version != SEALED_BLOB_VERSION {
+            return Err(sealing_failure(format!(
+                "unsupported sealed blob version {}",
+                version
+            )));
+        }
+        let backend = SealingBackend::from_byte(reader.take(1)?[0])?;
+        let policy = SealPolicy::from_key_policy(u16::from_be_bytes(array(reader.take(2)?)))?;
+        let aad = reader.take_prefixed()?.to_vec();
+        let payload = reader.take_prefixed()?.to_vec();
+        if !reader.0.is_empty() {
+            return Err(sealing_failure("trailing bytes after sealed blob"));
+        }
+
+        Ok(Self {
+            version,
+            backend,
+            policy,
+            aad,
+            payload,
+        })
+    }
+}
+
+impl Serialize for SealedBlob {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::serde_hex::serialize(&self.to_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SealedBlob {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes: Vec<u8> = crate::serde_hex::deserialize(deserializer)?;
+        SealedBlob::from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Seals and unseals data for this enclave
+pub struct Sealer {
+    #[cfg(not(feature = "sgx"))]
+    root_key: Zeroizing<[u8; 32]>,
+}
+
+#[cfg(feature = "sgx")]
+impl Sealer {
+    /// Seal with the CPU's sealing keys
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[cfg(feature = "sgx")]
+impl Default for Sealer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(feature = "sgx"))]
+impl Sealer {
+    /// Seal with keys derived from `root_key`
+    pub fn from_root_key(root_key: [u8; 32]) -> Self {
+        Self {
+            root_key: Zeroizing::new(root_key),
+        }
+    }
+
+    /// Seal with the root key stored at `path`, creating it if absent
+    ///
+    /// A new key is drawn from the platform RNG and written with owner-only
+    /// permissions. The file stands in for the CPU's fused sealing secret, so
+    /// losing it makes every blob sealed under it unrecoverable.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, EnclaveError> {
+        use std::io::Write;
+
+        let path = path.as_ref();
+        let io_error = |e: std::io::Error| sealing_failure(format!("{}: {}", path.display(), e));
+
+        match std::fs::read(path) {
+            Ok(bytes) => {
+                let bytes = Zeroizing::new(bytes);
+                let root_key: [u8; 32] = bytes.as_slice().try_into().map_err(|_| {
+                    sealing_failure(format!("{}: root key must be 32 bytes", path.display()))
+                })?;
+                Ok(Self::from_root_key(root_key))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut root_key = Zeroizing::new([0u8; 32]);
+                rand_core::RngCore::fill_bytes(&mut rand_core::OsRng, root_key.as_mut());
+
+                let mut options = std::fs::OpenOptions::new();
+                options.write(true).create_new(true);
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+                options
+                    .open(path)
+                    .and_then(|mut file| file.write_all(root_key.as_ref()))
+                    .map_err(io_error)?;
+
+                Ok(Self::from_root_key(*root_key))
+            }
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    /// AES-256 key for `policy`, bound to the simulated enclave identity
+    fn sealing_key(&self, policy: SealPolicy) -> Zeroizing<[u8; 32]> {
+        use sha2::{Digest, Sha256};
+
+        let identity = match policy {
+            SealPolicy::MrEnclave => crate::attestation::simulated_mr_enclave(),
+            SealPolicy::MrSigner => crate::attestation::simulated_mr_signer(),
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(b"chaoschain-enclave/simulated-seal-key/v1");
+        hasher.update(self.root_key.as_ref());
+        hasher.update(policy.key_policy().to_be_bytes());
+        hasher.update(identity);
+        Zeroizing::new(hasher.finalize().into())
+    }
+
+    fn seal_payload(
+        &self,
+        policy: SealPolicy,
+        header: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EnclaveError> {
+        use aes_gcm::aead::{Aead, KeyInit, Payload};
+        use aes_gcm::{Aes256Gcm, Nonce};
+
+        let mut nonce = [0u8; 12];
+        rand_core::RngCore::fill_bytes(&mut rand_core::OsRng, &mut nonce);
+        let cipher = Aes256Gcm::new(self.sealing_key(policy).as_ref().into());
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| sealing_failure("encryption failed"))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        Ok(payload)
+    }
+
+    fn unseal_payload(
+        &self,
+        policy: SealPolicy,
+        header: &[u8],
+        payload: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, EnclaveError> {
+        use aes_gcm::aead::{Aead, KeyInit, Payload};
+        use aes_gcm::{Aes256Gcm, Nonce};
+
+        if payload.len() < 12 {
+            return Err(sealing_failure("sealed payload is truncated"));
+        }
+        let (nonce, ciphertext) = payload.split_at(12);
+        let cipher = Aes256Gcm::new(self.sealing_key(policy).as_ref().into());
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| sealing_failure("blob was not sealed for this enclave or was modified"))
+    }
+}
+
+#[cfg(feature = "sgx")]
+impl Sealer {
+    fn seal_payload(
+        &self,
+        policy: SealPolicy,
+        header: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EnclaveError> {
+        use sgx_tseal::SgxSealedData;
+        use sgx_types::{
+            sgx_attributes_t, sgx_sealed_data_t, TSEAL_DEFAULT_FLAGSMASK, TSEAL_DEFAULT_MISCMASK,
+        };
+
+        let attribute_mask = sgx_attributes_t {
+            flags: TSEAL_DEFAULT_FLAGSMASK,
+            xfrm: 0,
+        };
+        let sealed = SgxSealedData::<[u8]>::seal_data_ex(
+            policy.key_policy(),
+            attribute_mask,
+            TSEAL_DEFAULT_MISCMASK,
+            header,
+            plaintext,
+        )
+        .map_err(|status| sealing_failure(format!("sgx_seal_data_ex: {}", status)))?;
+
+        let size = SgxSealedData::<[u8]>::calc_raw_sealed_data_size(
+            header.len() as u32,
+            plaintext.len() as u32,
+        );
+        if size == u32::MAX {
+            return Err(sealing_failure("sealed data is too large"));
+        }
+        let mut payload = vec![0u8; size as usize];
+        // SAFETY: `payload` is exactly the size sgx_tseal computed for this
+        // sealed data.
+        unsafe {
+            sealed.to_raw_sealed_data_t(payload.as_mut_ptr() as *mut sgx_sealed_data_t, size)
+        }
+        .ok_or_else(|| sealing_failure("failed to encode sealed data"))?;
+        Ok(payload)
+    }
+
+    fn unseal_payload(
+        &self,
+        _policy: SealPolicy,
+        header: &[u8],
+        payload: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, EnclaveError> {
+        use sgx_tseal::SgxSealedData;
+        use sgx_types::sgx_sealed_data_t;
+
+        let mut payload = payload.to_vec();
+        // SAFETY: sgx_tseal checks the embedded lengths against `payload`'s
+        // size before reading past the fixed header.
+        let sealed = unsafe {
+            SgxSealedData::<[u8]>::from_raw_sealed_data_t(
+                payload.as_mut_ptr() as *mut sgx_sealed_data_t,
+                payload.len() as u32,
+            )
+        }
+        .ok_or_else(|| sealing_failure("sealed payload is malformed"))?;
+        let unsealed = sealed
+            .unseal_data()
+            .map_err(|status| sealing_failure(format!("sgx_unseal_data: {}", status)))?;
+
+        if unsealed.get_additional_txt() != header {
+            return Err(sealing_failure("sealed payload does not match its header"));
+        }
+        Ok(Zeroizing::new(unsealed.get_decrypt_txt().to_vec()))
+    }
+}
+
+impl Sealer {
+    /// Seal `plaintext` under `policy`, authenticating `aad` alongside it
+    pub fn seal(
+        &self,
+        policy: SealPolicy,
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<SealedBlob, EnclaveError> {
+        let mut blob = SealedBlob {
+            version: SEALED_BLOB_VERSION,
+            backend: SealingBackend::current(),
+            policy,
+            aad: aad.to_vec(),
+            payload: Vec::new(),
+        };
+        blob.payload = self.seal_payload(policy, &blob.authenticated_header(), plaintext)?;
+        Ok(blob)
+    }
+
+    /// Recover the plaintext of `blob`
+    ///
+    /// Fails if the blob was sealed by another backend, for another enclave
+    /// identity, or was modified in any way, including its additional data.
+    pub fn unseal(&self, blob: &SealedBlob) -> Result<Zeroizing<Vec<u8>>, EnclaveError> {
+        if blob.backend != SealingBackend::current() {
+            return Err(sealing_failure(format!(
+                "blob was sealed by the {:?} backend",
+                blob.backend
+            )));
+        }
+        self.unseal_payload(blob.policy, &blob.authenticated_header(), &blob.payload)
+    }
+}
+
+impl std::fmt::Debug for Sealer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sealer")
+            .field("backend", &SealingBackend::current())
+            .finish_non_exhaustive()
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EnclaveError> {
+        if self.0.len() < len {
+            return Err(sealing_failure("sealed blob is truncated"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn take_prefixed(&mut self) -> Result<&'a [u8], EnclaveError> {
+        let len = u32::from_be_bytes(array(self.take(4)?));
+        self.take(len as usize)
+    }
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    bytes.try_into().expect("slice length checked by caller")
+}
+
+fn sealing_failure(reason: impl Into<String>) -> EnclaveError {
+    EnclaveError::SealingFailure {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(all(test, not(feature = "sgx")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let sealer = Sealer::from_root_key([1; 32]);
+        for policy in [SealPolicy::MrEnclave, SealPolicy::MrSigner] {
+            let blob = sealer.seal(policy, b"label", b"secret").unwrap();
+            let decoded = SealedBlob::from_bytes(&blob.to_bytes()).unwrap();
+
+            assert_eq!(decoded, blob);
+            assert_eq!(decoded.aad, b"label");
+            assert_eq!(sealer.unseal(&decoded).unwrap().as_slice(), b"secret");
+        }
+    }
+
+    #[test]
+    fn test_unseal_rejects_tampering_and_other_keys() {
+        let sealer = Sealer::from_root_key([1; 32]);
+        let blob = sealer
+            .seal(SealPolicy::MrSigner, b"label", b"secret")
+            .unwrap();
+
+        let mut relabelled = blob.clone();
+        relabelled.aad = b"other".to_vec();
+        assert!(sealer.unseal(&relabelled).is_err());
+
+        let mut repolicied = blob.clone();
+        repolicied.policy = SealPolicy::MrEnclave;
+        assert!(sealer.unseal(&repolicied).is_err());
+
+        let mut bytes = blob.to_bytes();
+        *bytes.last_mut().unwrap() ^= 1;
+        let error = sealer
+            .unseal(&SealedBlob::from_bytes(&bytes).unwrap())
+            .unwrap_err();
+        assert_eq!(error.code(), 3002);
+
+        assert!(Sealer::from_root_key([2; 32]).unseal(&blob).is_err());
+    }
+
+    #[test]
+    fn test_blob_versions_are_checked() {
+        let blob = Sealer::from_root_key([1; 32])
+            .seal(SealPolicy::MrEnclave, b"", b"secret")
+            .unwrap();
+        let mut bytes = blob.to_bytes();
+        bytes[5] = 2;
+
+        assert!(SealedBlob::from_bytes(&bytes).is_err());
+        let json = serde_json::to_string(&blob).unwrap();
+        assert_eq!(serde_json::from_str::<SealedBlob>(&json).unwrap(), blob);
+    }
+
+    #[test]
+    fn test_open_persists_root_key() {
+        let path = std::env::temp_dir().join(format!("chaoschain-seal-{}.key", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let blob = Sealer::open(&path)
+            .unwrap()
+            .seal(SealPolicy::MrSigner, b"", b"secret")
+            .unwrap();
+        let reopened = Sealer::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reopened.unseal(&blob).unwrap().as_slice(), b"secret");
+    }
+}
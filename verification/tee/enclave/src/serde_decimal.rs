@@ -0,0 +1,28 @@
+//! Serde helpers encoding wide integers as decimal strings.
+//!
+//! JSON numbers cannot carry `u128`/`i128` wei amounts without losing
+//! precision in most consumers, so such fields are written as strings. Use
+//! with `#[serde(with = "crate::serde_decimal")]`.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Display,
+    S: Serializer,
+{
+    serializer.collect_str(value)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromStr,
+    <T as FromStr>::Err: Display,
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
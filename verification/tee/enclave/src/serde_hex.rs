@@ -0,0 +1,85 @@
+//! Serde helpers encoding byte strings as lowercase hex.
+//!
+//! Use with `#[serde(with = "crate::serde_hex")]` on any field whose type is
+//! `Vec<u8>` or a fixed-size byte array, [`list`] on a `Vec` of them and
+//! [`option`] on an `Option` of one.
+
+use hex::FromHex;
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]>,
+    S: Serializer,
+{
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromHex,
+    <T as FromHex>::Error: std::fmt::Display,
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    decode(&s).map_err(serde::de::Error::custom)
+}
+
+fn decode<T: FromHex>(s: &str) -> Result<T, T::Error> {
+    T::from_hex(s.strip_prefix("0x").unwrap_or(s))
+}
+
+pub mod list {
+    use super::*;
+    use serde::ser::SerializeSeq;
+
+    pub fn serialize<T, S>(items: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for item in items {
+            seq.serialize_element(&hex::encode(item))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: FromHex,
+        <T as FromHex>::Error: std::fmt::Display,
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| decode(s).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        match value {
+            Some(bytes) => serializer.serialize_some(&hex::encode(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromHex,
+        <T as FromHex>::Error: std::fmt::Display,
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| decode(&s).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
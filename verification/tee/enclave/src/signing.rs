@@ -0,0 +1,412 @@
+//! Enclave signing identity and signed output envelopes.
+//!
+//! The enclave holds a private key that never leaves it and signs every
+//! [`EnclaveOutput`] it produces. A [`SignedEnclaveOutput`] binds together
+//! the hash of the input, the hash of the output, a monotonic sequence
+//! number and a timestamp. Hashes are SHA-256 over the canonical JSON form
+//! from [`crate::canonical`]. The signed message is a fixed binary layout so
+//! that verifiers in other languages can rebuild it without a JSON library:
+//!
+//! ```text
+//! OUTPUT_SIGNATURE_DOMAIN || input_hash (32) || output_hash (32)
+//!     || sequence (u64 big-endian) || timestamp (u64 big-endian)
+//! ```
+//!
+//! Both ed25519 and secp256k1 (ECDSA over SHA-256) keys are supported and
+//! behave identically in the simulation and `sgx` builds.
+//!
+//! [`EnclaveSigner::seal`] persists the key together with its sequence
+//! number as a [`SealedBlob`], so a restarted enclave keeps its identity and
+//! does not reuse sequence numbers.
+
+use ed25519_dalek::{Signer as _, Verifier as _};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::ethereum::EthereumKey;
+use crate::sealing::{SealPolicy, SealedBlob, Sealer};
+use crate::{EnclaveError, EnclaveInput, EnclaveOutput};
+
+/// Domain separation tag prefixed to every signed output message
+pub const OUTPUT_SIGNATURE_DOMAIN: &[u8] = b"chaoschain-enclave/signed-output/v1";
+
+/// Additional data of sealed signer state, so other blobs cannot stand in
+pub const SIGNER_STATE_AAD: &[u8] = b"chaoschain-enclave/signer-state/v1";
+
+/// Signature algorithm used by an enclave identity
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    Ed25519,
+    Secp256k1,
+}
+
+enum SigningKey {
+    Ed25519(ed25519_dalek::SigningKey),
+    Secp256k1(k256::ecdsa::SigningKey),
+}
+
+/// Public half of an enclave signing key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EnclaveIdentity {
+    pub scheme: SignatureScheme,
+    /// Raw 32-byte ed25519 key or 33-byte compressed SEC1 secp256k1 key
+    #[serde(with = "crate::serde_hex")]
+    pub public_key: Vec<u8>,
+}
+
+impl EnclaveIdentity {
+    /// Check a signature made by this identity over `message`
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), EnclaveError> {
+        match self.scheme {
+            SignatureScheme::Ed25519 => {
+                let key: [u8; 32] = self
+                    .public_key
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| attestation_failure("ed25519 public key must be 32 bytes"))?;
+                let key = ed25519_dalek::VerifyingKey::from_bytes(&key)
+                    .map_err(|e| attestation_failure(e.to_string()))?;
+                let signature = ed25519_dalek::Signature::from_slice(signature)
+                    .map_err(|e| attestation_failure(e.to_string()))?;
+                key.verify_strict(message, &signature)
+                    .map_err(|_| attestation_failure("signature mismatch"))
+            }
+            SignatureScheme::Secp256k1 => {
+                let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(&self.public_key)
+                    .map_err(|e| attestation_failure(e.to_string()))?;
+                let signature = k256::ecdsa::Signature::from_slice(signature)
+                    .map_err(|e| attestation_failure(e.to_string()))?;
+                key.verify(message, &signature)
+                    .map_err(|_| attestation_failure("signature mismatch"))
+            }
+        }
+    }
+}
+
+/// Private signing key held by the enclave together with its output counter
+pub struct EnclaveSigner {
+    key: SigningKey,
+    sequence: u64,
+}
+
+impl EnclaveSigner {
+    /// Generate a fresh key from the platform RNG
+    pub fn generate(scheme: SignatureScheme) -> Self {
+        let key = match scheme {
+            SignatureScheme::Ed25519 => {
+                SigningKey::Ed25519(ed25519_dalek::SigningKey::generate(&mut OsRng))
+            }
+            SignatureScheme::Secp256k1 => {
+                SigningKey::Secp256k1(k256::ecdsa::SigningKey::random(&mut OsRng))
+            }
+        };
+        Self { key, sequence: 0 }
+    }
+
+    /// Rebuild a signer from 32 bytes of secret key material
+    pub fn from_secret(scheme: SignatureScheme, secret: &[u8; 32]) -> Result<Self, EnclaveError> {
+        let key = match scheme {
+            SignatureScheme::Ed25519 => {
+                SigningKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(secret))
+            }
+            SignatureScheme::Secp256k1 => SigningKey::Secp256k1(
+                k256::ecdsa::SigningKey::from_slice(secret)
+                    .map_err(|e| EnclaveError::invalid_payload(e.to_string()))?,
+            ),
+        };
+        Ok(Self { key, sequence: 0 })
+    }
+
+    /// Signature scheme of this signer
+    pub fn scheme(&self) -> SignatureScheme {
+        match self.key {
+            SigningKey::Ed25519(_) => SignatureScheme::Ed25519,
+            SigningKey::Secp256k1(_) => SignatureScheme::Secp256k1,
+        }
+    }
+
+    /// Public identity that verifiers should pin
+    pub fn identity(&self) -> EnclaveIdentity {
+        let public_key = match &self.key {
+            SigningKey::Ed25519(key) => key.verifying_key().to_bytes().to_vec(),
+            SigningKey::Secp256k1(key) => key
+                .verifying_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec(),
+        };
+        EnclaveIdentity {
+            scheme: self.scheme(),
+            public_key,
+        }
+    }
+
+    /// Sequence number that the next signed output will carry
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Seal the key and the next sequence number for storage by the host
+    ///
+    /// The sealed state is `scheme (u8) || secret (32) || sequence (u64 BE)`.
+    /// A host can hand back an older blob to rewind the sequence number;
+    /// [`crate::state::EnclaveState`] detects that.
+    pub fn seal(&self, sealer: &Sealer, policy: SealPolicy) -> Result<SealedBlob, EnclaveError> {
+        sealer.seal(policy, SIGNER_STATE_AAD, &self.state_bytes())
+    }
+
+    /// Restore a signer, and its sequence number, from [`EnclaveSigner::seal`]
+    pub fn unseal(sealer: &Sealer, blob: &SealedBlob) -> Result<Self, EnclaveError> {
+        if blob.aad != SIGNER_STATE_AAD {
+            return Err(invalid_signer_state());
+        }
+        Self::from_state_bytes(&sealer.unseal(blob)?)
+    }
+
+    /// Encoded size of [`EnclaveSigner::state_bytes`]
+    pub(crate) const STATE_SIZE: usize = 41;
+
+    /// Key and sequence number in their sealed encoding
+    pub(crate) fn state_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut state = Zeroizing::new(Vec::with_capacity(Self::STATE_SIZE));
+        let secret: Zeroizing<[u8; 32]> = match &self.key {
+            SigningKey::Ed25519(key) => {
+                state.push(0);
+                Zeroizing::new(key.to_bytes())
+            }
+            SigningKey::Secp256k1(key) => {
+                state.push(1);
+                Zeroizing::new(key.to_bytes().into())
+            }
+        };
+        state.extend_from_slice(secret.as_ref());
+        state.extend_from_slice(&self.sequence.to_be_bytes());
+        state
+    }
+
+    pub(crate) fn from_state_bytes(state: &[u8]) -> Result<Self, EnclaveError> {
+        if state.len() != Self::STATE_SIZE {
+            return Err(invalid_signer_state());
+        }
+        let scheme = match state[0] {
+            0 => SignatureScheme::Ed25519,
+            1 => SignatureScheme::Secp256k1,
+            _ => return Err(invalid_signer_state()),
+        };
+        let secret: Zeroizing<[u8; 32]> =
+            Zeroizing::new(state[1..33].try_into().expect("length checked"));
+        let mut signer = Self::from_secret(scheme, &secret)?;
+        signer.sequence = u64::from_be_bytes(state[33..].try_into().expect("length checked"));
+        Ok(signer)
+    }
+
+    /// Ethereum account key derived from this signer's key
+    pub(crate) fn ethereum_key(&self) -> EthereumKey {
+        EthereumKey::derive(&self.state_bytes()[..33])
+    }
+
+    /// Sign an arbitrary message with the enclave key
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match &self.key {
+            SigningKey::Ed25519(key) => key.sign(message).to_bytes().to_vec(),
+            SigningKey::Secp256k1(key) => {
+                let signature: k256::ecdsa::Signature = key.sign(message);
+                signature.to_bytes().to_vec()
+            }
+        }
+    }
+
+    /// Sign an output produced for `input`, consuming the next sequence number
+    pub fn sign_output(
+        &mut self,
+        input: &EnclaveInput,
+        output: EnclaveOutput,
+        timestamp: u64,
+    ) -> Result<SignedEnclaveOutput, EnclaveError> {
+        let next = self
+            .sequence
+            .checked_add(1)
+            .ok_or_else(|| EnclaveError::ResourceExhausted {
+                resource: "output sequence number".to_string(),
+            })?;
+
+        let mut signed = SignedEnclaveOutput {
+            input_hash: input.input_hash(),
+            output_hash: output.output_hash(),
+            output,
+            sequence: self.sequence,
+            timestamp,
+            signer: self.identity(),
+            signature: Vec::new(),
+        };
+        signed.signature = self.sign(&signed.signing_message());
+        self.sequence = next;
+
+        Ok(signed)
+    }
+}
+
+impl std::fmt::Debug for EnclaveSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnclaveSigner")
+            .field("identity", &self.identity())
+            .field("sequence", &self.sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An enclave output together with the enclave's signature over it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedEnclaveOutput {
+    pub output: EnclaveOutput,
+    #[serde(with = "crate::serde_hex")]
+    pub input_hash: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub output_hash: [u8; 32],
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub signer: EnclaveIdentity,
+    #[serde(with = "crate::serde_hex")]
+    pub signature: Vec<u8>,
+}
+
+impl SignedEnclaveOutput {
+    /// Bytes covered by the signature
+    pub fn signing_message(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(OUTPUT_SIGNATURE_DOMAIN.len() + 80);
+        message.extend_from_slice(OUTPUT_SIGNATURE_DOMAIN);
+        message.extend_from_slice(&self.input_hash);
+        message.extend_from_slice(&self.output_hash);
+        message.extend_from_slice(&self.sequence.to_be_bytes());
+        message.extend_from_slice(&self.timestamp.to_be_bytes());
+        message
+    }
+
+    /// Check that the output hash matches the embedded output and that the
+    /// signature was made by the embedded signer
+    ///
+    /// Callers must separately check that `signer` is an identity they trust.
+    pub fn verify(&self) -> Result<(), EnclaveError> {
+        if self.output.output_hash() != self.output_hash {
+            return Err(attestation_failure("output hash mismatch"));
+        }
+        self.signer.verify(&self.signing_message(), &self.signature)
+    }
+
+    /// Check that this output was produced for `input`
+    pub fn verify_input(&self, input: &EnclaveInput) -> Result<(), EnclaveError> {
+        if input.input_hash() != self.input_hash {
+            return Err(attestation_failure("input hash mismatch"));
+        }
+        Ok(())
+    }
+}
+
+fn invalid_signer_state() -> EnclaveError {
+    EnclaveError::SealingFailure {
+        reason: "blob does not hold signer state".to_string(),
+    }
+}
+
+fn attestation_failure(reason: impl Into<String>) -> EnclaveError {
+    EnclaveError::AttestationFailure {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_operation, AddPayload, Operation};
+
+    fn signed_add(signer: &mut EnclaveSigner) -> (EnclaveInput, SignedEnclaveOutput) {
+        let input = EnclaveInput::new(Operation::Add(AddPayload {
+            a: 2.into(),
+            b: 3.into(),
+        }));
+        let output = process_operation(input.clone());
+        let signed = signer.sign_output(&input, output, 1_700_000_000).unwrap();
+        (input, signed)
+    }
+
+    #[test]
+    fn test_sign_and_verify_both_schemes() {
+        for scheme in [SignatureScheme::Ed25519, SignatureScheme::Secp256k1] {
+            let mut signer = EnclaveSigner::generate(scheme);
+            let (input, signed) = signed_add(&mut signer);
+
+            assert_eq!(signed.signer, signer.identity());
+            assert!(signed.verify().is_ok());
+            assert!(signed.verify_input(&input).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_tampered_output_is_rejected() {
+        let mut signer = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let (_, mut signed) = signed_add(&mut signer);
+
+        signed.timestamp += 1;
+        assert!(signed.verify().is_err());
+
+        let (_, mut signed) = signed_add(&mut signer);
+        signed.output.version += 1;
+        assert!(signed.verify().is_err());
+    }
+
+    #[test]
+    fn test_sequence_is_monotonic() {
+        let mut signer = EnclaveSigner::generate(SignatureScheme::Secp256k1);
+        let (_, first) = signed_add(&mut signer);
+        let (_, second) = signed_add(&mut signer);
+
+        assert_eq!(first.sequence, 0);
+        assert_eq!(second.sequence, 1);
+        assert_eq!(signer.sequence(), 2);
+    }
+
+    #[test]
+    fn test_from_secret_is_deterministic() {
+        let secret = [7u8; 32];
+        for scheme in [SignatureScheme::Ed25519, SignatureScheme::Secp256k1] {
+            let a = EnclaveSigner::from_secret(scheme, &secret).unwrap();
+            let b = EnclaveSigner::from_secret(scheme, &secret).unwrap();
+            assert_eq!(a.identity(), b.identity());
+        }
+    }
+
+    #[cfg(not(feature = "sgx"))]
+    #[test]
+    fn test_sealed_signer_survives_restart() {
+        let sealer = Sealer::from_root_key([3; 32]);
+        for scheme in [SignatureScheme::Ed25519, SignatureScheme::Secp256k1] {
+            let mut signer = EnclaveSigner::generate(scheme);
+            signed_add(&mut signer);
+            let blob = signer.seal(&sealer, SealPolicy::MrSigner).unwrap();
+
+            let mut restored = EnclaveSigner::unseal(&sealer, &blob).unwrap();
+            assert_eq!(restored.identity(), signer.identity());
+            let (_, signed) = signed_add(&mut restored);
+            assert_eq!(signed.sequence, 1);
+            assert!(signed.verify().is_ok());
+        }
+
+        let other = sealer
+            .seal(SealPolicy::MrSigner, b"other", &[0; 41])
+            .unwrap();
+        assert!(EnclaveSigner::unseal(&sealer, &other).is_err());
+    }
+
+    #[test]
+    fn test_signed_output_json_round_trip() {
+        let mut signer = EnclaveSigner::generate(SignatureScheme::Ed25519);
+        let (_, signed) = signed_add(&mut signer);
+
+        let json = serde_json::to_string(&signed).unwrap();
+        let decoded: SignedEnclaveOutput = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, signed);
+        assert!(decoded.verify().is_ok());
+    }
+}
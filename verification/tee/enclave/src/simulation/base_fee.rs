@@ -0,0 +1,550 @@
+//! Multi-block EIP-1559 base fee simulation.
+//!
+//! The single-block harness treats the base fee as a constant. Here the base
+//! fee evolves block by block per EIP-1559: blocks above the gas target
+//! (`gas_limit / elasticity_multiplier`) raise it and blocks below lower it,
+//! by at most `1 / base_fee_max_change_denominator` per block. Gas demand
+//! follows a seeded random model that shrinks as the base fee rises above a
+//! reference price. Fees are whole wei and gas whole units; demand factors
+//! and the reported ratios are [`Fixed`] decimals, and demand is rounded down
+//! to whole gas.
+//!
+//! The same demand shocks are replayed against the baseline and the proposed
+//! parameters, so differences between the two runs come from the parameter
+//! change alone.
+
+use serde::{Deserialize, Serialize};
+
+use super::rng::DeterministicRng;
+use crate::ethereum::context::{self, BlockContext, BlockRange};
+use crate::fixed::{Fixed, Rounding};
+use crate::EnclaveError;
+
+/// Upper bound on simulated blocks per run
+pub const MAX_SIMULATED_BLOCKS: u32 = 10_000;
+
+const GWEI: u64 = 1_000_000_000;
+
+/// Fee market parameters a governance proposal may change
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Eip1559Parameters {
+    pub gas_limit: u64,
+    pub elasticity_multiplier: u64,
+    pub base_fee_max_change_denominator: u64,
+    /// Base fee of the block preceding the simulation, in wei
+    pub initial_base_fee: u64,
+}
+
+impl Default for Eip1559Parameters {
+    fn default() -> Self {
+        Self {
+            gas_limit: 30_000_000,
+            elasticity_multiplier: 2,
+            base_fee_max_change_denominator: 8,
+            initial_base_fee: 10 * GWEI,
+        }
+    }
+}
+
+impl Eip1559Parameters {
+    /// Gas usage at which the base fee stays unchanged
+    pub fn gas_target(&self) -> u64 {
+        self.gas_limit / self.elasticity_multiplier
+    }
+
+    /// Base fee of the block after one with `parent_base_fee` that used
+    /// `parent_gas_used`, as specified by EIP-1559
+    pub fn next_base_fee(
+        &self,
+        parent_base_fee: u64,
+        parent_gas_used: u64,
+    ) -> Result<u64, EnclaveError> {
+        let target = self.gas_target() as u128;
+        let used = parent_gas_used as u128;
+        let base_fee = parent_base_fee as u128;
+        let denominator = self.base_fee_max_change_denominator as u128;
+
+        let next = if used > target {
+            let delta = (base_fee * (used - target) / target / denominator).max(1);
+            base_fee + delta
+        } else {
+            base_fee - base_fee * (target - used) / target / denominator
+        };
+        u64::try_from(next).map_err(|_| EnclaveError::ResourceExhausted {
+            resource: "base fee exceeds u64 wei".to_string(),
+        })
+    }
+
+    fn validate(&self, name: &str) -> Result<(), EnclaveError> {
+        if self.elasticity_multiplier == 0 || self.base_fee_max_change_denominator == 0 {
+            return Err(EnclaveError::invalid_payload(format!(
+                "{name}: elasticity_multiplier and base_fee_max_change_denominator must be positive"
+            )));
+        }
+        if self.gas_target() == 0 {
+            return Err(EnclaveError::invalid_payload(format!(
+                "{name}: gas_limit must be at least elasticity_multiplier"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Seeded model of the gas that users want included in each block
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct DemandModel {
+    /// Gas demanded per block when the base fee equals `reference_base_fee`
+    pub mean_demand_gas: u64,
+    /// Uniform per-block noise around the mean, as a share of it
+    pub volatility: Fixed,
+    /// Chance that a block sees a demand spike
+    pub spike_probability: Fixed,
+    /// Demand multiplier during a spike
+    pub spike_multiplier: Fixed,
+    /// Base fee in wei at which demand equals its mean
+    pub reference_base_fee: u64,
+    /// Share of demand lost per 100% of base fee above the reference (and
+    /// gained below it), linear and floored at zero
+    pub price_sensitivity: Fixed,
+}
+
+impl Default for DemandModel {
+    fn default() -> Self {
+        Self {
+            mean_demand_gas: 15_000_000,
+            volatility: Fixed::new(2, 1),
+            spike_probability: Fixed::new(5, 2),
+            spike_multiplier: Fixed::new(3, 0),
+            reference_base_fee: 10 * GWEI,
+            price_sensitivity: Fixed::new(5, 1),
+        }
+    }
+}
+
+impl DemandModel {
+    fn validate(&self) -> Result<(), EnclaveError> {
+        if self.volatility.is_negative()
+            || self.spike_multiplier.is_negative()
+            || self.price_sensitivity.is_negative()
+        {
+            return Err(EnclaveError::invalid_payload(
+                "demand: volatility, spike_multiplier and price_sensitivity must not be negative",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Draw the price-independent demand of each block
+    fn sample(&self, rng: &mut DeterministicRng, blocks: u32) -> Result<Vec<u128>, EnclaveError> {
+        let mean = Fixed::from(self.mean_demand_gas);
+        let max_noise = self.volatility.mul(2.into(), Rounding::Down)?;
+        (0..blocks)
+            .map(|_| {
+                let noise = max_noise.mul(rng.fraction(), Rounding::Down)?;
+                let factor = Fixed::ONE
+                    .checked_add(noise)?
+                    .checked_sub(self.volatility)?
+                    .max(Fixed::ZERO);
+                let mut demand = mean.mul(factor, Rounding::Down)?;
+                if rng.chance(self.spike_probability) {
+                    demand = demand.mul(self.spike_multiplier, Rounding::Down)?;
+                }
+                demand.to_u128(Rounding::Down)
+            })
+            .collect()
+    }
+
+    /// Scale sampled demand for the current base fee
+    fn at_base_fee(&self, demand: u128, base_fee: u64) -> Result<u128, EnclaveError> {
+        let reference = Fixed::from(self.reference_base_fee.max(1));
+        let premium = Fixed::from(base_fee)
+            .checked_sub(reference)?
+            .div(reference, Rounding::Down)?;
+        let factor = Fixed::ONE
+            .checked_sub(premium.mul(self.price_sensitivity, Rounding::Down)?)?
+            .max(Fixed::ZERO);
+        Fixed::from(demand)
+            .mul(factor, Rounding::Down)?
+            .to_u128(Rounding::Down)
+    }
+}
+
+/// Payload for the `simulate_base_fee` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BaseFeeSimulationPayload {
+    #[serde(default)]
+    pub proposal_id: Option<String>,
+    /// Seed for the demand model; equal seeds give equal results
+    pub seed: u64,
+    #[serde(default = "default_blocks")]
+    pub blocks: u32,
+    #[serde(default)]
+    pub demand: DemandModel,
+    /// Fee market parameters currently in force
+    #[serde(default)]
+    pub baseline: Eip1559Parameters,
+    /// Fee market parameters after the proposal
+    #[serde(default)]
+    pub proposed: Eip1559Parameters,
+    /// Return the per-block trace of both runs, not only the summaries
+    #[serde(default)]
+    pub include_blocks: bool,
+    /// Verified blocks to start from: the newest block sets the initial base
+    /// fee of both runs and the baseline gas limit, and the mean gas used
+    /// over all of them sets the mean demand
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_context: Option<BlockContext>,
+}
+
+fn default_blocks() -> u32 {
+    100
+}
+
+/// One simulated block
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockOutcome {
+    pub number: u32,
+    pub base_fee: u64,
+    pub gas_used: u64,
+    #[serde(with = "crate::serde_decimal")]
+    pub burned: u128,
+}
+
+/// Fee market behaviour over a whole run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BaseFeeRun {
+    pub parameters: Eip1559Parameters,
+    pub final_base_fee: u64,
+    pub min_base_fee: u64,
+    pub max_base_fee: u64,
+    pub mean_base_fee: u64,
+    /// Mean absolute block-to-block base fee change, relative to the
+    /// earlier block
+    pub base_fee_volatility: Fixed,
+    /// Mean gas used as a share of the gas limit
+    pub mean_fullness: Fixed,
+    /// Blocks whose demand met or exceeded the gas limit
+    pub full_blocks: u32,
+    /// Total base fee burned, in wei
+    #[serde(with = "crate::serde_decimal")]
+    pub total_burned: u128,
+    pub blocks: Vec<BlockOutcome>,
+}
+
+/// Result of the `simulate_base_fee` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BaseFeeSimulationReport {
+    pub proposal_id: Option<String>,
+    pub seed: u64,
+    pub blocks: u32,
+    pub demand: DemandModel,
+    pub baseline: BaseFeeRun,
+    pub proposed: BaseFeeRun,
+    /// Relative change in total burned fees from baseline to proposed
+    pub burned_delta: Fixed,
+    /// Proposed minus baseline base fee volatility
+    pub volatility_delta: Fixed,
+    /// Proposed minus baseline mean block fullness
+    pub fullness_delta: Fixed,
+    /// Blocks the starting point was taken from, for payloads with a block
+    /// context
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_range: Option<BlockRange>,
+}
+
+/// Evolve the base fee under the baseline and proposed parameters
+pub fn simulate(
+    payload: &BaseFeeSimulationPayload,
+) -> Result<BaseFeeSimulationReport, EnclaveError> {
+    if payload.blocks > MAX_SIMULATED_BLOCKS {
+        return Err(EnclaveError::ResourceExhausted {
+            resource: format!(
+                "simulated blocks ({} > {})",
+                payload.blocks, MAX_SIMULATED_BLOCKS
+            ),
+        });
+    }
+    let (baseline, proposed, model, block_range) = match &payload.block_context {
+        None => (
+            payload.baseline.clone(),
+            payload.proposed.clone(),
+            payload.demand.clone(),
+            None,
+        ),
+        Some(context) => {
+            let verified = context.verify()?;
+            let newest = verified.newest();
+            let initial_base_fee = context::base_fee(newest)?;
+            let gas_used = verified.gas_used();
+            let mean_demand_gas = (gas_used.iter().map(|&gas| gas as u128).sum::<u128>()
+                / gas_used.len() as u128) as u64;
+            (
+                Eip1559Parameters {
+                    gas_limit: newest.gas_limit,
+                    initial_base_fee,
+                    ..payload.baseline.clone()
+                },
+                Eip1559Parameters {
+                    initial_base_fee,
+                    ..payload.proposed.clone()
+                },
+                DemandModel {
+                    mean_demand_gas,
+                    ..payload.demand.clone()
+                },
+                Some(verified.range),
+            )
+        }
+    };
+    baseline.validate("baseline")?;
+    proposed.validate("proposed")?;
+    model.validate()?;
+
+    let mut rng = DeterministicRng::new(payload.seed);
+    let demand = model.sample(&mut rng, payload.blocks)?;
+
+    let baseline = run(&baseline, &model, &demand, payload.include_blocks)?;
+    let proposed = run(&proposed, &model, &demand, payload.include_blocks)?;
+
+    Ok(BaseFeeSimulationReport {
+        proposal_id: payload.proposal_id.clone(),
+        seed: payload.seed,
+        blocks: payload.blocks,
+        demand: model,
+        burned_delta: burned_delta(baseline.total_burned, proposed.total_burned)?,
+        volatility_delta: proposed
+            .base_fee_volatility
+            .checked_sub(baseline.base_fee_volatility)?,
+        fullness_delta: proposed.mean_fullness.checked_sub(baseline.mean_fullness)?,
+        baseline,
+        proposed,
+        block_range,
+    })
+}
+
+fn run(
+    parameters: &Eip1559Parameters,
+    model: &DemandModel,
+    demand: &[u128],
+    include_blocks: bool,
+) -> Result<BaseFeeRun, EnclaveError> {
+    let overflow = || EnclaveError::invalid_payload("burned fees overflow u128");
+    let mut base_fee = parameters.initial_base_fee;
+    let mut min_base_fee = base_fee;
+    let mut max_base_fee = base_fee;
+    let mut base_fee_sum = 0u128;
+    let mut change_sum = Fixed::ZERO;
+    let mut fullness_sum = Fixed::ZERO;
+    let mut full_blocks = 0;
+    let mut total_burned = 0u128;
+    let mut blocks = Vec::new();
+
+    for (number, &sampled) in demand.iter().enumerate() {
+        let wanted = model.at_base_fee(sampled, base_fee)?;
+        let gas_used = wanted.min(parameters.gas_limit as u128) as u64;
+        if wanted >= parameters.gas_limit as u128 {
+            full_blocks += 1;
+        }
+        let burned = base_fee as u128 * gas_used as u128;
+
+        min_base_fee = min_base_fee.min(base_fee);
+        max_base_fee = max_base_fee.max(base_fee);
+        base_fee_sum = base_fee_sum
+            .checked_add(base_fee as u128)
+            .ok_or_else(overflow)?;
+        fullness_sum = fullness_sum.checked_add(
+            Fixed::from(gas_used).div(parameters.gas_limit.max(1).into(), Rounding::Down)?,
+        )?;
+        total_burned = total_burned.checked_add(burned).ok_or_else(overflow)?;
+        if include_blocks {
+            blocks.push(BlockOutcome {
+                number: number as u32,
+                base_fee,
+                gas_used,
+                burned,
+            });
+        }
+
+        let next = parameters.next_base_fee(base_fee, gas_used)?;
+        if base_fee > 0 {
+            change_sum = change_sum.checked_add(
+                Fixed::from(next.abs_diff(base_fee)).div(base_fee.into(), Rounding::Down)?,
+            )?;
+        }
+        base_fee = next;
+    }
+
+    let count = demand.len().max(1) as u128;
+    let mean = |sum: Fixed| sum.div(count.into(), Rounding::Down);
+    Ok(BaseFeeRun {
+        parameters: parameters.clone(),
+        final_base_fee: base_fee,
+        min_base_fee,
+        max_base_fee,
+        mean_base_fee: (base_fee_sum / count) as u64,
+        base_fee_volatility: mean(change_sum)?,
+        mean_fullness: mean(fullness_sum)?,
+        full_blocks,
+        total_burned,
+        blocks,
+    })
+}
+
+/// Relative change in burned fees, rounded toward zero; zero when nothing
+/// was burned in the baseline
+fn burned_delta(baseline: u128, proposed: u128) -> Result<Fixed, EnclaveError> {
+    if baseline == 0 {
+        return Ok(Fixed::ZERO);
+    }
+    Fixed::from(proposed)
+        .checked_sub(baseline.into())?
+        .div(baseline.into(), Rounding::Down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(proposed: Eip1559Parameters) -> BaseFeeSimulationPayload {
+        BaseFeeSimulationPayload {
+            proposal_id: None,
+            seed: 11,
+            blocks: 500,
+            demand: DemandModel::default(),
+            baseline: Eip1559Parameters::default(),
+            proposed,
+            include_blocks: true,
+            block_context: None,
+        }
+    }
+
+    #[test]
+    fn test_base_fee_update_rule() {
+        let params = Eip1559Parameters::default();
+        let fee = 8 * GWEI;
+
+        assert_eq!(params.next_base_fee(fee, 15_000_000).unwrap(), fee);
+        assert_eq!(params.next_base_fee(fee, 30_000_000).unwrap(), 9 * GWEI);
+        assert_eq!(params.next_base_fee(fee, 0).unwrap(), 7 * GWEI);
+        // Increases are at least one wei, decreases may round to zero
+        assert_eq!(params.next_base_fee(7, 15_000_001).unwrap(), 8);
+        assert_eq!(params.next_base_fee(7, 14_999_999).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_identical_parameters_give_identical_runs() {
+        let report = simulate(&payload(Eip1559Parameters::default())).unwrap();
+
+        assert_eq!(report.baseline, report.proposed);
+        assert_eq!(report.burned_delta, Fixed::ZERO);
+        assert_eq!(report.baseline.blocks.len(), 500);
+        assert_eq!(
+            report,
+            simulate(&payload(Eip1559Parameters::default())).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_larger_denominator_dampens_volatility() {
+        let report = simulate(&payload(Eip1559Parameters {
+            base_fee_max_change_denominator: 32,
+            ..Eip1559Parameters::default()
+        }))
+        .unwrap();
+
+        assert!(report.volatility_delta.is_negative());
+        assert!(
+            report.proposed.max_base_fee - report.proposed.min_base_fee
+                < report.baseline.max_base_fee - report.baseline.min_base_fee
+        );
+    }
+
+    #[test]
+    fn test_demand_responds_to_base_fee() {
+        let model = DemandModel::default();
+        let demand = 10_000_000;
+
+        assert_eq!(
+            model.at_base_fee(demand, model.reference_base_fee).unwrap(),
+            demand
+        );
+        assert_eq!(
+            model
+                .at_base_fee(demand, 2 * model.reference_base_fee)
+                .unwrap(),
+            5_000_000
+        );
+        assert_eq!(model.at_base_fee(demand, 0).unwrap(), 15_000_000);
+        assert_eq!(
+            model
+                .at_base_fee(demand, 4 * model.reference_base_fee)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_starts_from_verified_context() {
+        let (context, _) = crate::ethereum::context::tests::context(4);
+        let mut payload = payload(Eip1559Parameters {
+            gas_limit: 36_000_000,
+            ..Eip1559Parameters::default()
+        });
+        payload.block_context = Some(context.clone());
+        let report = simulate(&payload).unwrap();
+
+        assert_eq!(report.demand.mean_demand_gas, 11_500_000);
+        assert_eq!(report.baseline.parameters.initial_base_fee, 8_500_000_000);
+        assert_eq!(report.proposed.parameters.initial_base_fee, 8_500_000_000);
+        assert_eq!(report.baseline.parameters.gas_limit, 30_000_000);
+        assert_eq!(report.proposed.parameters.gas_limit, 36_000_000);
+        assert_eq!(report.block_range, Some(context.verify().unwrap().range));
+    }
+
+    #[test]
+    fn test_rejects_invalid_parameters() {
+        let zero_denominator = Eip1559Parameters {
+            base_fee_max_change_denominator: 0,
+            ..Eip1559Parameters::default()
+        };
+        assert_eq!(
+            simulate(&payload(zero_denominator)).unwrap_err().code(),
+            1003
+        );
+
+        for demand in [
+            DemandModel {
+                volatility: Fixed::MAX,
+                ..DemandModel::default()
+            },
+            DemandModel {
+                volatility: Fixed::new(-1, 0),
+                ..DemandModel::default()
+            },
+            DemandModel {
+                spike_probability: Fixed::ONE,
+                spike_multiplier: u64::MAX.into(),
+                mean_demand_gas: u64::MAX,
+                volatility: Fixed::ZERO,
+                reference_base_fee: 100 * GWEI,
+                ..DemandModel::default()
+            },
+        ] {
+            let mut extreme = payload(Eip1559Parameters::default());
+            extreme.demand = demand;
+            assert_eq!(simulate(&extreme).unwrap_err().code(), 1003);
+        }
+        let sensitive = DemandModel {
+            reference_base_fee: 1,
+            price_sensitivity: Fixed::MAX,
+            ..DemandModel::default()
+        };
+        assert!(sensitive.at_base_fee(1, u64::MAX).is_err());
+
+        let mut too_long = payload(Eip1559Parameters::default());
+        too_long.blocks = MAX_SIMULATED_BLOCKS + 1;
+        assert_eq!(simulate(&too_long).unwrap_err().code(), 2001);
+    }
+}
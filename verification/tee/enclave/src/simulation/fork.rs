@@ -0,0 +1,515 @@
+//! Single-block fork simulation.
+//!
+//! Port of `SimulationHarness.run_simulation`: a batch of synthetic
+//! transactions is generated around the block's base fee, the proposal's gas
+//! and fee adjustments are applied to each one, and the totals before and
+//! after are compared. Adjustment factors and relative changes are
+//! [`Fixed`] decimals rather than floats; adjusted gas values and prices are
+//! rounded down to whole gas and wei, and relative changes toward zero.
+
+use serde::{Deserialize, Serialize};
+
+use super::rng::DeterministicRng;
+use crate::canonical::canonical_hash;
+use crate::ethereum::context::{self, BlockContext, BlockRange};
+use crate::fixed::{Fixed, Rounding};
+use crate::EnclaveError;
+
+/// Upper bound on generated transactions per simulation
+pub const MAX_SIMULATED_TRANSACTIONS: u32 = 10_000;
+
+const GWEI: u64 = 1_000_000_000;
+const TRANSFER_GAS: u64 = 21_000;
+const MAX_TRANSACTION_VALUE: u64 = 10 * GWEI * GWEI;
+
+const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const APPROVE_SELECTOR: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+const TRANSFER_FROM_SELECTOR: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+
+/// ERC20 and DEX router selectors used for contract calls
+const FUNCTION_SELECTORS: [[u8; 4]; 8] = [
+    TRANSFER_SELECTOR,
+    APPROVE_SELECTOR,
+    TRANSFER_FROM_SELECTOR,
+    [0x70, 0xa0, 0x82, 0x31], // balanceOf(address)
+    [0x18, 0x16, 0x0d, 0xdd], // totalSupply()
+    [0x7f, 0xf3, 0x6a, 0xb5], // swapExactETHForTokens
+    [0x38, 0xed, 0x17, 0x39], // swapExactTokensForTokens
+    [0x5c, 0x11, 0xd7, 0x95], // swapExactTokensForETH
+];
+
+/// USDT, USDC, DAI, WBTC and WETH on Ethereum mainnet
+const TOKEN_ADDRESSES: [[u8; 20]; 5] = [
+    address(b"dac17f958d2ee523a2206206994597c13d831ec7"),
+    address(b"a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"),
+    address(b"6b175474e89094c44da98b954eedeac495271d0f"),
+    address(b"2260fac5e5542a773aa44fbcfedf7c193bc2c599"),
+    address(b"c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"),
+];
+
+/// Block-level parameters of the simulated chain
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct BlockParameters {
+    pub gas_limit: u64,
+    /// Base fee per gas in wei
+    pub base_fee_per_gas: u64,
+    /// Gas prices vary by up to `base_fee_per_gas / fee_denominator`
+    pub fee_denominator: u64,
+}
+
+impl Default for BlockParameters {
+    fn default() -> Self {
+        Self {
+            gas_limit: 12_500_000,
+            base_fee_per_gas: 10 * GWEI,
+            fee_denominator: 5,
+        }
+    }
+}
+
+/// Changes a proposal makes to transaction gas usage and pricing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ProposalAdjustments {
+    /// Multiplier on gas limits and gas used; 1 leaves them unchanged
+    pub gas_adjustment: Fixed,
+    /// Multiplier on gas prices; 1 leaves them unchanged
+    pub fee_adjustment: Fixed,
+}
+
+impl Default for ProposalAdjustments {
+    fn default() -> Self {
+        Self {
+            gas_adjustment: Fixed::ONE,
+            fee_adjustment: Fixed::ONE,
+        }
+    }
+}
+
+/// Payload for the `simulate_fork` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ForkSimulationPayload {
+    #[serde(default)]
+    pub proposal_id: Option<String>,
+    /// Seed for transaction generation; equal seeds give equal results
+    pub seed: u64,
+    #[serde(default = "default_transaction_count")]
+    pub transaction_count: u32,
+    #[serde(default)]
+    pub block: BlockParameters,
+    #[serde(default)]
+    pub adjustments: ProposalAdjustments,
+    /// Return every simulated transaction, not only the summary
+    #[serde(default)]
+    pub include_transactions: bool,
+    /// Verified blocks whose newest block sets the gas limit and base fee
+    /// of `block`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_context: Option<BlockContext>,
+}
+
+fn default_transaction_count() -> u32 {
+    100
+}
+
+/// A generated transaction and its cost before and after the proposal
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedTransaction {
+    #[serde(with = "crate::serde_hex")]
+    pub hash: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub from: [u8; 20],
+    #[serde(with = "crate::serde_hex")]
+    pub to: [u8; 20],
+    pub value: u64,
+    pub nonce: u64,
+    #[serde(with = "crate::serde_hex")]
+    pub data: Vec<u8>,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    pub gas_price: u64,
+    pub adjusted_gas_limit: u64,
+    pub adjusted_gas_used: u64,
+    pub adjusted_gas_price: u64,
+    #[serde(with = "crate::serde_decimal")]
+    pub original_cost: u128,
+    #[serde(with = "crate::serde_decimal")]
+    pub adjusted_cost: u128,
+}
+
+/// Result of the `simulate_fork` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ForkSimulationReport {
+    pub proposal_id: Option<String>,
+    pub seed: u64,
+    pub transaction_count: u32,
+    pub block: BlockParameters,
+    pub adjustments: ProposalAdjustments,
+    pub total_original_gas: u64,
+    pub total_adjusted_gas: u64,
+    pub avg_original_gas: u64,
+    pub avg_adjusted_gas: u64,
+    #[serde(with = "crate::serde_decimal")]
+    pub total_original_cost: u128,
+    #[serde(with = "crate::serde_decimal")]
+    pub total_adjusted_cost: u128,
+    #[serde(with = "crate::serde_decimal")]
+    pub avg_original_cost: u128,
+    #[serde(with = "crate::serde_decimal")]
+    pub avg_adjusted_cost: u128,
+    #[serde(with = "crate::serde_decimal")]
+    pub cost_difference: i128,
+    /// `cost_difference` relative to the original total cost
+    pub relative_cost_difference: Fixed,
+    /// Relative change in total gas used
+    pub gas_delta: Fixed,
+    /// Relative change in gas prices, `fee_adjustment - 1`
+    pub fee_growth: Fixed,
+    /// Canonical hash of the full transaction set, whether or not it is
+    /// included below
+    #[serde(with = "crate::serde_hex")]
+    pub transactions_hash: [u8; 32],
+    pub transactions: Vec<SimulatedTransaction>,
+    /// Blocks `block` was taken from, for payloads with a block context
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_range: Option<BlockRange>,
+}
+
+/// Generate a seeded transaction set and apply the proposal's adjustments
+pub fn simulate(payload: &ForkSimulationPayload) -> Result<ForkSimulationReport, EnclaveError> {
+    if payload.transaction_count > MAX_SIMULATED_TRANSACTIONS {
+        return Err(EnclaveError::ResourceExhausted {
+            resource: format!(
+                "simulated transactions ({} > {})",
+                payload.transaction_count, MAX_SIMULATED_TRANSACTIONS
+            ),
+        });
+    }
+    if payload.block.fee_denominator == 0 {
+        return Err(EnclaveError::invalid_payload(
+            "fee_denominator must be positive",
+        ));
+    }
+    let adjustments = &payload.adjustments;
+    if adjustments.gas_adjustment.is_negative() || adjustments.fee_adjustment.is_negative() {
+        return Err(EnclaveError::invalid_payload(
+            "adjustments must not be negative",
+        ));
+    }
+    let (block, block_range) = match &payload.block_context {
+        None => (payload.block.clone(), None),
+        Some(context) => {
+            let verified = context.verify()?;
+            let newest = verified.newest();
+            let block = BlockParameters {
+                gas_limit: newest.gas_limit,
+                base_fee_per_gas: context::base_fee(newest)?,
+                ..payload.block.clone()
+            };
+            (block, Some(verified.range))
+        }
+    };
+
+    let mut rng = DeterministicRng::new(payload.seed);
+    let transactions = (0..payload.transaction_count)
+        .map(|_| {
+            let tx = generate_transaction(&mut rng, &block)?;
+            apply_adjustments(tx, adjustments)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let count = transactions.len().max(1) as u128;
+    let total_original_gas = sum_gas(transactions.iter().map(|tx| tx.gas_used))?;
+    let total_adjusted_gas = sum_gas(transactions.iter().map(|tx| tx.adjusted_gas_used))?;
+    let total_original_cost = sum_cost(transactions.iter().map(|tx| tx.original_cost))?;
+    let total_adjusted_cost = sum_cost(transactions.iter().map(|tx| tx.adjusted_cost))?;
+    let cost_difference = signed(total_adjusted_cost)? - signed(total_original_cost)?;
+
+    Ok(ForkSimulationReport {
+        proposal_id: payload.proposal_id.clone(),
+        seed: payload.seed,
+        transaction_count: payload.transaction_count,
+        block,
+        adjustments: adjustments.clone(),
+        total_original_gas,
+        total_adjusted_gas,
+        avg_original_gas: (total_original_gas as u128 / count) as u64,
+        avg_adjusted_gas: (total_adjusted_gas as u128 / count) as u64,
+        total_original_cost,
+        total_adjusted_cost,
+        avg_original_cost: total_original_cost / count,
+        avg_adjusted_cost: total_adjusted_cost / count,
+        cost_difference,
+        relative_cost_difference: relative_change(total_original_cost, total_adjusted_cost)?,
+        gas_delta: relative_change(total_original_gas as u128, total_adjusted_gas as u128)?,
+        fee_growth: adjustments.fee_adjustment.checked_sub(Fixed::ONE)?,
+        transactions_hash: canonical_hash(&transactions)?,
+        transactions: if payload.include_transactions {
+            transactions
+        } else {
+            Vec::new()
+        },
+        block_range,
+    })
+}
+
+fn generate_transaction(
+    rng: &mut DeterministicRng,
+    block: &BlockParameters,
+) -> Result<SimulatedTransaction, EnclaveError> {
+    let overflow = || EnclaveError::invalid_payload("simulated gas price overflows u64");
+    let from = random_address(rng);
+
+    let (to, gas_limit, data) = if rng.chance(Fixed::new(7, 1)) {
+        let to = *rng.choose(&TOKEN_ADDRESSES);
+        let gas_limit = rng.range_inclusive(50_000, 300_000);
+        (to, gas_limit, call_data(rng))
+    } else {
+        (random_address(rng), TRANSFER_GAS, Vec::new())
+    };
+
+    let volatility = block.base_fee_per_gas / block.fee_denominator;
+    let variation = rng.range_inclusive(0, volatility.checked_mul(2).ok_or_else(overflow)?);
+    let base_price = (block.base_fee_per_gas.checked_add(variation))
+        .ok_or_else(overflow)?
+        .saturating_sub(volatility)
+        .max(1);
+    let gas_price = base_price
+        .checked_add(rng.range_inclusive(GWEI, 3 * GWEI))
+        .ok_or_else(overflow)?;
+    let gas_used = rng.range_inclusive(gas_limit / 2, gas_limit);
+
+    let value = rng.range_inclusive(0, MAX_TRANSACTION_VALUE);
+    let nonce = rng.range_inclusive(0, 1_000);
+    let mut hash = [0u8; 32];
+    rng.fill(&mut hash);
+
+    Ok(SimulatedTransaction {
+        hash,
+        from,
+        to,
+        value,
+        nonce,
+        data,
+        gas_limit,
+        gas_used,
+        gas_price,
+        adjusted_gas_limit: gas_limit,
+        adjusted_gas_used: gas_used,
+        adjusted_gas_price: gas_price,
+        original_cost: gas_used as u128 * gas_price as u128,
+        adjusted_cost: gas_used as u128 * gas_price as u128,
+    })
+}
+
+/// ABI-shaped call data; 30% of contract calls carry none
+fn call_data(rng: &mut DeterministicRng) -> Vec<u8> {
+    if rng.chance(Fixed::new(3, 1)) {
+        return Vec::new();
+    }
+
+    let selector = *rng.choose(&FUNCTION_SELECTORS);
+    let mut data = selector.to_vec();
+    let push_word = |data: &mut Vec<u8>, bytes: &[u8]| {
+        data.extend(std::iter::repeat_n(0, 32 - bytes.len()));
+        data.extend_from_slice(bytes);
+    };
+
+    match selector {
+        TRANSFER_SELECTOR | APPROVE_SELECTOR => {
+            push_word(&mut data, &random_address(rng));
+            push_word(
+                &mut data,
+                &rng.range_inclusive(0, MAX_TRANSACTION_VALUE).to_be_bytes(),
+            );
+        }
+        TRANSFER_FROM_SELECTOR => {
+            push_word(&mut data, &random_address(rng));
+            push_word(&mut data, &random_address(rng));
+            push_word(
+                &mut data,
+                &rng.range_inclusive(0, MAX_TRANSACTION_VALUE).to_be_bytes(),
+            );
+        }
+        _ => push_word(&mut data, &[]),
+    }
+    data
+}
+
+fn apply_adjustments(
+    mut tx: SimulatedTransaction,
+    adjustments: &ProposalAdjustments,
+) -> Result<SimulatedTransaction, EnclaveError> {
+    let scale = |value: u64, factor: Fixed| {
+        Fixed::from(value)
+            .mul(factor, Rounding::Down)?
+            .to_u64(Rounding::Down)
+            .map_err(|_| EnclaveError::invalid_payload("adjusted gas value overflows u64"))
+    };
+
+    tx.adjusted_gas_limit = scale(tx.gas_limit, adjustments.gas_adjustment)?;
+    tx.adjusted_gas_used =
+        scale(tx.gas_used, adjustments.gas_adjustment)?.min(tx.adjusted_gas_limit);
+    tx.adjusted_gas_price = scale(tx.gas_price, adjustments.fee_adjustment)?;
+    tx.adjusted_cost = tx.adjusted_gas_used as u128 * tx.adjusted_gas_price as u128;
+    Ok(tx)
+}
+
+fn sum_gas(mut gas: impl Iterator<Item = u64>) -> Result<u64, EnclaveError> {
+    gas.try_fold(0u64, u64::checked_add)
+        .ok_or_else(|| EnclaveError::invalid_payload("total gas overflows u64"))
+}
+
+fn sum_cost(mut costs: impl Iterator<Item = u128>) -> Result<u128, EnclaveError> {
+    costs
+        .try_fold(0u128, u128::checked_add)
+        .ok_or_else(|| EnclaveError::invalid_payload("total cost overflows u128"))
+}
+
+fn signed(value: u128) -> Result<i128, EnclaveError> {
+    i128::try_from(value).map_err(|_| EnclaveError::invalid_payload("total cost overflows i128"))
+}
+
+/// Relative change from `before` to `after`, rounded toward zero; zero
+/// when `before` is zero
+fn relative_change(before: u128, after: u128) -> Result<Fixed, EnclaveError> {
+    if before == 0 {
+        return Ok(Fixed::ZERO);
+    }
+    Fixed::from(after)
+        .checked_sub(before.into())?
+        .div(before.into(), Rounding::Down)
+}
+
+fn random_address(rng: &mut DeterministicRng) -> [u8; 20] {
+    let mut address = [0u8; 20];
+    rng.fill(&mut address);
+    address
+}
+
+const fn address(hex: &[u8; 40]) -> [u8; 20] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            _ => panic!("invalid hex digit"),
+        }
+    }
+
+    let mut out = [0u8; 20];
+    let mut i = 0;
+    while i < 20 {
+        out[i] = nibble(hex[2 * i]) << 4 | nibble(hex[2 * i + 1]);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(seed: u64, gas_adjustment: Fixed, fee_adjustment: Fixed) -> ForkSimulationPayload {
+        ForkSimulationPayload {
+            proposal_id: Some("p".to_string()),
+            seed,
+            transaction_count: 100,
+            block: BlockParameters::default(),
+            adjustments: ProposalAdjustments {
+                gas_adjustment,
+                fee_adjustment,
+            },
+            include_transactions: true,
+            block_context: None,
+        }
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_result() {
+        let a = simulate(&payload(7, Fixed::new(11, 1), Fixed::new(12, 1))).unwrap();
+        let b = simulate(&payload(7, Fixed::new(11, 1), Fixed::new(12, 1))).unwrap();
+        let c = simulate(&payload(8, Fixed::new(11, 1), Fixed::new(12, 1))).unwrap();
+
+        assert_eq!(a, b);
+        assert_ne!(a.transactions_hash, c.transactions_hash);
+    }
+
+    #[test]
+    fn test_neutral_adjustments_change_nothing() {
+        let report = simulate(&payload(1, Fixed::ONE, Fixed::ONE)).unwrap();
+
+        assert_eq!(report.total_original_gas, report.total_adjusted_gas);
+        assert_eq!(report.cost_difference, 0);
+        assert_eq!(report.gas_delta, Fixed::ZERO);
+        assert_eq!(report.fee_growth, Fixed::ZERO);
+    }
+
+    #[test]
+    fn test_adjustments_are_reflected_in_summary() {
+        let report = simulate(&payload(1, Fixed::new(11, 1), Fixed::new(12, 1))).unwrap();
+
+        // Flooring per transaction keeps the deltas just under the factors
+        assert!((Fixed::new(99, 3)..=Fixed::new(1, 1)).contains(&report.gas_delta));
+        assert!((Fixed::new(318, 3)..=Fixed::new(32, 2)).contains(&report.relative_cost_difference));
+        assert_eq!(report.fee_growth, Fixed::new(2, 1));
+        assert!(report.cost_difference > 0);
+
+        let cheaper = simulate(&payload(1, Fixed::ONE, Fixed::new(8, 1))).unwrap();
+        assert_eq!(cheaper.fee_growth, Fixed::new(-2, 1));
+        assert!(cheaper.cost_difference < 0);
+
+        let negative = payload(1, Fixed::new(-1, 0), Fixed::ONE);
+        assert_eq!(simulate(&negative).unwrap_err().code(), 1003);
+    }
+
+    #[test]
+    fn test_generated_transactions_are_well_formed() {
+        let report = simulate(&payload(3, Fixed::ONE, Fixed::ONE)).unwrap();
+        let block = BlockParameters::default();
+        let min_price = block.base_fee_per_gas - block.base_fee_per_gas / 5 + GWEI;
+        let max_price = block.base_fee_per_gas + block.base_fee_per_gas / 5 + 3 * GWEI;
+
+        for tx in &report.transactions {
+            assert!(tx.gas_used >= tx.gas_limit / 2 && tx.gas_used <= tx.gas_limit);
+            assert!((min_price..=max_price).contains(&tx.gas_price));
+            assert!(matches!(tx.data.len(), 0 | 36 | 68 | 100));
+            if tx.gas_limit == TRANSFER_GAS {
+                assert!(tx.data.is_empty());
+            } else {
+                assert!(TOKEN_ADDRESSES.contains(&tx.to));
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_from_verified_context() {
+        let (context, _) = crate::ethereum::context::tests::context(4);
+        let mut payload = payload(1, Fixed::ONE, Fixed::ONE);
+        payload.block_context = Some(context.clone());
+        let report = simulate(&payload).unwrap();
+
+        assert_eq!(report.block.base_fee_per_gas, 8_500_000_000);
+        assert_eq!(report.block.gas_limit, 30_000_000);
+        assert_eq!(report.block.fee_denominator, 5);
+        assert_eq!(report.block_range, Some(context.verify().unwrap().range));
+    }
+
+    #[test]
+    fn test_extreme_block_parameters_are_rejected() {
+        let mut payload = payload(0, Fixed::ONE, Fixed::ONE);
+        payload.block.base_fee_per_gas = u64::MAX;
+        payload.block.fee_denominator = 1;
+        assert_eq!(simulate(&payload).unwrap_err().code(), 1003);
+
+        payload.block.fee_denominator = 5;
+        assert_eq!(simulate(&payload).unwrap_err().code(), 1003);
+    }
+
+    #[test]
+    fn test_transaction_limit() {
+        let mut payload = payload(0, Fixed::ONE, Fixed::ONE);
+        payload.transaction_count = MAX_SIMULATED_TRANSACTIONS + 1;
+        assert_eq!(simulate(&payload).unwrap_err().code(), 2001);
+    }
+}
@@ -0,0 +1,13 @@
+//! Deterministic simulations of proposed chain parameter changes.
+//!
+//! [`fork`] ports `simulation/sim_harness.py` and [`base_fee`] extends it to
+//! multi-block EIP-1559 fee dynamics, so that simulation results can be
+//! attested like any other governance output. All randomness comes from
+//! [`rng::DeterministicRng`] seeded by the caller. Wei, gas and counts are
+//! integers and ratios are [`Fixed`](crate::fixed::Fixed) decimals, so
+//! re-running a simulation with the same payload reproduces the same result
+//! on every platform.
+
+pub mod base_fee;
+pub mod fork;
+pub mod rng;
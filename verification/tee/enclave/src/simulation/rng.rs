@@ -0,0 +1,145 @@
+//! Seeded pseudo-random number generator for simulations.
+//!
+//! xoshiro256** seeded through SplitMix64, as specified by its authors at
+//! <https://prng.di.unimi.it/>. It is not cryptographically secure; it only
+//! needs to be fast, well distributed and identical on every platform.
+
+use crate::fixed::{Fixed, DECIMALS};
+
+/// Number of distinct values [`DeterministicRng::fraction`] draws from
+const FRACTION_UNITS: u64 = 1_000_000_000_000_000_000;
+
+/// Reproducible random number generator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeterministicRng {
+    state: [u64; 4],
+}
+
+impl DeterministicRng {
+    /// Create a generator whose entire output is determined by `seed`
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Self {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    /// Next 64 uniformly distributed bits
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    /// Uniform value in `0..bound`, without modulo bias
+    ///
+    /// Panics if `bound` is zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0, "empty range");
+        // Lemire's multiply-shift with rejection of the biased low zone
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let product = self.next_u64() as u128 * bound as u128;
+            if (product as u64) >= threshold {
+                return (product >> 64) as u64;
+            }
+        }
+    }
+
+    /// Uniform value in `low..=high`
+    pub fn range_inclusive(&mut self, low: u64, high: u64) -> u64 {
+        assert!(low <= high, "empty range");
+        match (high - low).checked_add(1) {
+            Some(span) => low + self.below(span),
+            None => self.next_u64(),
+        }
+    }
+
+    /// Uniform value in `[0, 1)` with 18 decimals
+    pub fn fraction(&mut self) -> Fixed {
+        Fixed::new(self.below(FRACTION_UNITS) as i128, DECIMALS)
+    }
+
+    /// True with probability `probability`; values outside `[0, 1]` act as
+    /// the nearer bound
+    pub fn chance(&mut self, probability: Fixed) -> bool {
+        self.fraction() < probability
+    }
+
+    /// Fill `bytes` with random data
+    pub fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let word = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+
+    /// Uniformly chosen element of a non-empty slice
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_vector() {
+        // Seed 0 expanded with SplitMix64, then three xoshiro256** steps
+        let mut rng = DeterministicRng::new(0);
+        let first: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+        assert_eq!(
+            first,
+            [
+                0x99ec_5f36_cb75_f2b4,
+                0xbf6e_1f78_4956_452a,
+                0x1a5f_849d_4933_e6e0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ranges_are_inclusive_and_bounded() {
+        let mut rng = DeterministicRng::new(42);
+        let mut seen = [false; 3];
+        for _ in 0..1_000 {
+            let v = rng.range_inclusive(5, 7);
+            assert!((5..=7).contains(&v));
+            seen[(v - 5) as usize] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+        assert_eq!(DeterministicRng::new(1).range_inclusive(9, 9), 9);
+    }
+
+    #[test]
+    fn test_fractions_and_chances() {
+        let mut rng = DeterministicRng::new(42);
+        let hits = (0..1_000)
+            .filter(|_| {
+                let fraction = rng.fraction();
+                assert!(!fraction.is_negative() && fraction < Fixed::ONE);
+                rng.chance(Fixed::new(3, 1))
+            })
+            .count();
+        assert!((250..=350).contains(&hits), "{} hits", hits);
+        assert!(!rng.chance(Fixed::ZERO));
+        assert!(rng.chance(Fixed::ONE));
+    }
+}
@@ -0,0 +1,492 @@
+//! Persistent enclave state with rollback detection.
+//!
+//! [`EnclaveState`] is what a long-running enclave keeps between requests:
+//! its [`EnclaveSigner`], whose sequence number every signed output carries,
+//! the [`EthereumKey`] derived from it, the [`EnclaveConfig`] it was created
+//! with, a [`ReplayGuard`] of client nonces, the last epoch whose Merkle
+//! root it signed and the [`AgencyLedger`] of receipt chain heads. [`EnclaveState::process`] refuses replayed requests
+//! before signing anything, and is the only way to run operations that act
+//! with the enclave's keys.
+//!
+//! Sealing alone does not stop a host from handing back an older blob and so
+//! rewinding both the sequence number and the spent nonces. Each sealed state
+//! therefore records the value one above a [`MonotonicCounter`], and the
+//! host saves the blob before it increments the counter. Unsealing accepts a
+//! blob at the counter's current value, or at the next one when the host
+//! saved the blob but stopped before incrementing; any older blob is
+//! refused. Whichever step fails, the store holds a state that unseals. The
+//! host must persist each new blob before it releases the output that
+//! produced it: an enclave whose latest blob is lost cannot be restored.
+//!
+//! The simulation build counts in a local file, see [`FileCounter`], which
+//! guards against stale blobs but not against a host that also rewinds the
+//! file. The SGX build's ECALLs take the counter value from the host as
+//! well, with the same limitation, until a counter the host cannot rewind
+//! is available.
+
+use zeroize::Zeroizing;
+
+use crate::agency::AgencyLedger;
+use crate::config::EnclaveConfig;
+use crate::ethereum::{Address, EthereumKey};
+use crate::merkle::MerkleBatch;
+use crate::replay::ReplayGuard;
+use crate::sealing::{SealPolicy, SealedBlob, Sealer};
+use crate::signing::{EnclaveSigner, SignedEnclaveOutput};
+use crate::{run, unix_time, EnclaveContext, EnclaveError, EnclaveInput};
+
+/// Additional data of sealed enclave state
+pub const ENCLAVE_STATE_AAD: &[u8] = b"chaoschain-enclave/state/v2";
+
+/// Counter that can only move forward
+pub trait MonotonicCounter {
+    /// Current value
+    fn read(&self) -> Result<u64, EnclaveError>;
+
+    /// Advance by one and return the new value
+    fn increment(&mut self) -> Result<u64, EnclaveError>;
+}
+
+/// Monotonic counter kept in a local file
+///
+/// The file holds the value as 8 big-endian bytes and is replaced
+/// atomically on every increment. A missing file reads as zero.
+#[cfg(not(feature = "sgx"))]
+#[derive(Debug, Clone)]
+pub struct FileCounter {
+    path: std::path::PathBuf,
+}
+
+#[cfg(not(feature = "sgx"))]
+impl FileCounter {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn io_error(&self, error: std::io::Error) -> EnclaveError {
+        EnclaveError::SealingFailure {
+            reason: format!("{}: {}", self.path.display(), error),
+        }
+    }
+}
+
+#[cfg(not(feature = "sgx"))]
+impl MonotonicCounter for FileCounter {
+    fn read(&self) -> Result<u64, EnclaveError> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => {
+                let bytes: [u8; 8] =
+                    bytes
+                        .as_slice()
+                        .try_into()
+                        .map_err(|_| EnclaveError::SealingFailure {
+                            reason: format!("{}: counter must be 8 bytes", self.path.display()),
+                        })?;
+                Ok(u64::from_be_bytes(bytes))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(self.io_error(e)),
+        }
+    }
+
+    fn increment(&mut self) -> Result<u64, EnclaveError> {
+        use std::io::Write;
+
+        let next = self
+            .read()?
+            .checked_add(1)
+            .ok_or_else(|| EnclaveError::ResourceExhausted {
+                resource: "monotonic counter".to_string(),
+            })?;
+
+        let staging = self.path.with_extension("tmp");
+        std::fs::File::create(&staging)
+            .and_then(|mut file| {
+                file.write_all(&next.to_be_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&staging, &self.path))
+            .map_err(|e| self.io_error(e))?;
+        Ok(next)
+    }
+}
+
+/// Signing key, settings and spent nonces of a running enclave
+#[derive(Debug)]
+pub struct EnclaveState {
+    signer: EnclaveSigner,
+    ethereum: EthereumKey,
+    config: EnclaveConfig,
+    replay: ReplayGuard,
+    last_epoch: Option<u64>,
+    agency: AgencyLedger,
+}
+
+impl EnclaveState {
+    /// Fresh state for `signer` with the default config, with no nonces
+    /// spent
+    pub fn new(signer: EnclaveSigner) -> Self {
+        Self::with_config(signer, EnclaveConfig::default())
+    }
+
+    /// Fresh state for `signer` that will keep `config` for its lifetime
+    pub fn with_config(signer: EnclaveSigner, config: EnclaveConfig) -> Self {
+        Self {
+            ethereum: signer.ethereum_key(),
+            signer,
+            config,
+            replay: ReplayGuard::new(),
+            last_epoch: None,
+            agency: AgencyLedger::new(),
+        }
+    }
+
+    pub fn signer(&self) -> &EnclaveSigner {
+        &self.signer
+    }
+
+    pub fn config(&self) -> &EnclaveConfig {
+        &self.config
+    }
+
+    /// Address of the enclave's Ethereum account
+    pub fn ethereum_address(&self) -> Address {
+        self.ethereum.address()
+    }
+
+    pub fn replay_guard(&self) -> &ReplayGuard {
+        &self.replay
+    }
+
+    /// Last epoch whose Merkle root this enclave signed
+    pub fn last_epoch(&self) -> Option<u64> {
+        self.last_epoch
+    }
+
+    pub fn agency_ledger(&self) -> &AgencyLedger {
+        &self.agency
+    }
+
+    /// Process and sign `input`, which must carry a fresh client nonce
+    ///
+    /// The nonce is spent even if the operation itself fails, since its
+    /// failure is signed like any other output.
+    pub fn process(&mut self, input: EnclaveInput) -> Result<SignedEnclaveOutput, EnclaveError> {
+        self.replay.check(&input)?;
+        let mut context = EnclaveContext {
+            signer: &self.signer,
+            ethereum: &self.ethereum,
+            config: &self.config,
+            last_epoch: &mut self.last_epoch,
+            agency: &mut self.agency,
+        };
+        let output = run(input.clone(), Some(&mut context));
+        let signed = self.signer.sign_output(&input, output, unix_time())?;
+        self.replay.accept(&input)?;
+        Ok(signed)
+    }
+
+    /// Empty Merkle batch for outputs signed by this enclave
+    pub fn merkle_batch(&self) -> MerkleBatch {
+        MerkleBatch::new(self.signer.identity())
+    }
+
+    /// Seal this state at the value after `counter`'s current one
+    ///
+    /// The caller saves the blob and only then increments `counter`. The
+    /// sealed state is `counter (u64 BE) || signer state (41)` followed by
+    /// the config as JSON, the replay state, the last signed epoch (u64 BE,
+    /// or nothing before the first) and the agency ledger, each prefixed by
+    /// its length (u32 BE).
+    pub fn seal(
+        &self,
+        sealer: &Sealer,
+        policy: SealPolicy,
+        counter: &dyn MonotonicCounter,
+    ) -> Result<SealedBlob, EnclaveError> {
+        let value =
+            counter
+                .read()?
+                .checked_add(1)
+                .ok_or_else(|| EnclaveError::ResourceExhausted {
+                    resource: "monotonic counter".to_string(),
+                })?;
+
+        let config = serde_json::to_vec(&self.config).map_err(|e| EnclaveError::Internal {
+            reason: e.to_string(),
+        })?;
+
+        let mut state = Zeroizing::new(value.to_be_bytes().to_vec());
+        state.extend_from_slice(&self.signer.state_bytes());
+        let last_epoch = self
+            .last_epoch
+            .map(|epoch| epoch.to_be_bytes().to_vec())
+            .unwrap_or_default();
+        let sections = [
+            config,
+            self.replay.to_bytes(),
+            last_epoch,
+            self.agency.to_bytes(),
+        ];
+        for section in sections {
+            state.extend_from_slice(&(section.len() as u32).to_be_bytes());
+            state.extend_from_slice(&section);
+        }
+        sealer.seal(policy, ENCLAVE_STATE_AAD, &state)
+    }
+
+    /// Restore state sealed by [`EnclaveState::seal`], provided it is the
+    /// latest state sealed against `counter`
+    ///
+    /// A blob sealed at the value after the counter's is one whose increment
+    /// never happened; the counter is incremented now, so that no older blob
+    /// unseals again.
+    pub fn unseal(
+        sealer: &Sealer,
+        blob: &SealedBlob,
+        counter: &mut dyn MonotonicCounter,
+    ) -> Result<Self, EnclaveError> {
+        if blob.aad != ENCLAVE_STATE_AAD {
+            return Err(EnclaveError::SealingFailure {
+                reason: "blob does not hold enclave state".to_string(),
+            });
+        }
+        let state = sealer.unseal(blob)?;
+        let signer_end = 8 + EnclaveSigner::STATE_SIZE;
+        if state.len() < signer_end {
+            return Err(EnclaveError::SealingFailure {
+                reason: "enclave state is truncated".to_string(),
+            });
+        }
+
+        let sealed = u64::from_be_bytes(state[..8].try_into().expect("length checked"));
+        let current = counter.read()?;
+        if sealed == current.wrapping_add(1) {
+            counter.increment()?;
+        } else if sealed != current {
+            return Err(EnclaveError::RollbackDetected { sealed, current });
+        }
+
+        let signer = EnclaveSigner::from_state_bytes(&state[8..signer_end])?;
+        let mut sections = &state[signer_end..];
+        let config = serde_json::from_slice(take_section(&mut sections)?).map_err(|e| {
+            EnclaveError::SealingFailure {
+                reason: format!("sealed config: {}", e),
+            }
+        })?;
+        let replay = ReplayGuard::from_bytes(take_section(&mut sections)?)?;
+        let last_epoch = match take_section(&mut sections)? {
+            [] => None,
+            epoch => Some(u64::from_be_bytes(epoch.try_into().map_err(|_| {
+                EnclaveError::SealingFailure {
+                    reason: "sealed epoch is not 8 bytes".to_string(),
+                }
+            })?)),
+        };
+        let agency = AgencyLedger::from_bytes(take_section(&mut sections)?)?;
+        if !sections.is_empty() {
+            return Err(EnclaveError::SealingFailure {
+                reason: "trailing bytes after enclave state".to_string(),
+            });
+        }
+
+        Ok(Self {
+            ethereum: signer.ethereum_key(),
+            signer,
+            config,
+            replay,
+            last_epoch,
+            agency,
+        })
+    }
+}
+
+/// Split the next length-prefixed section off `bytes`
+fn take_section<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], EnclaveError> {
+    let truncated = || EnclaveError::SealingFailure {
+        reason: "enclave state is truncated".to_string(),
+    };
+    let (length, rest) = bytes.split_first_chunk::<4>().ok_or_else(truncated)?;
+    let length = u32::from_be_bytes(*length) as usize;
+    if rest.len() < length {
+        return Err(truncated());
+    }
+    let (section, rest) = rest.split_at(length);
+    *bytes = rest;
+    Ok(section)
+}
+
+#[cfg(all(test, not(feature = "sgx")))]
+mod tests {
+    use super::*;
+    use crate::ethereum::transaction::EthereumAccount;
+    use crate::merkle::SignMerkleRootPayload;
+    use crate::{AddPayload, Operation, OperationResult, SignatureScheme};
+
+    fn add(client: &str, nonce: u64) -> EnclaveInput {
+        EnclaveInput::new(Operation::Add(AddPayload {
+            a: 2.into(),
+            b: 3.into(),
+        }))
+        .with_nonce(client, nonce)
+    }
+
+    fn counter_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "chaoschain-counter-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_process_rejects_replays() {
+        let mut state = EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Ed25519));
+        let first = state.process(add("dao-a", 1)).unwrap();
+        let second = state.process(add("dao-a", 2)).unwrap();
+        assert_eq!((first.sequence, second.sequence), (0, 1));
+
+        let error = state.process(add("dao-a", 2)).unwrap_err();
+        assert_eq!(error.code(), 3003);
+        assert_eq!(state.signer().sequence(), 2);
+
+        let bare = EnclaveInput::new(Operation::Add(AddPayload {
+            a: 2.into(),
+            b: 3.into(),
+        }));
+        assert!(state.process(bare).is_err());
+    }
+
+    #[test]
+    fn test_ethereum_operations_need_state() {
+        let mut state = EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Ed25519));
+        let input = EnclaveInput::new(Operation::EthereumAddress).with_nonce("dao-a", 1);
+        let signed = state.process(input.clone()).unwrap();
+        assert_eq!(
+            signed.output.result,
+            Ok(OperationResult::EthereumAddress(EthereumAccount {
+                address: state.ethereum_address()
+            }))
+        );
+
+        let error = crate::process_operation(input).result.unwrap_err();
+        assert_eq!(error.code(), 1001);
+    }
+
+    #[test]
+    fn test_sign_root_of_own_outputs() {
+        let mut state = EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Ed25519));
+        let outputs = vec![
+            state.process(add("dao-a", 1)).unwrap(),
+            state.process(add("dao-a", 2)).unwrap(),
+        ];
+        let sign_root = |epoch, nonce| {
+            EnclaveInput::new(Operation::SignMerkleRoot(SignMerkleRootPayload {
+                epoch,
+                outputs: outputs.clone(),
+            }))
+            .with_nonce("dao-a", nonce)
+        };
+
+        let signed = state.process(sign_root(3, 3)).unwrap();
+        let Ok(OperationResult::SignMerkleRoot(root)) = signed.output.result else {
+            panic!("unexpected output {:?}", signed.output);
+        };
+        assert_eq!(root.signer, state.signer().identity());
+        assert_eq!(root.leaf_count, 2);
+        assert!(root.verify().is_ok());
+        assert_eq!(state.last_epoch(), Some(3));
+
+        // An epoch is signed once, and epochs only move forward
+        for (epoch, nonce) in [(3, 4), (2, 5)] {
+            let signed = state.process(sign_root(epoch, nonce)).unwrap();
+            assert_eq!(signed.output.result.unwrap_err().code(), 1003);
+        }
+        assert_eq!(state.last_epoch(), Some(3));
+
+        let mut other = EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Ed25519));
+        let signed = other.process(sign_root(1, 1)).unwrap();
+        assert_eq!(signed.output.result.unwrap_err().code(), 3001);
+        assert_eq!(other.last_epoch(), None);
+    }
+
+    #[test]
+    fn test_file_counter_is_monotonic() {
+        let path = counter_path("monotonic");
+        let mut counter = FileCounter::new(&path);
+        assert_eq!(counter.read().unwrap(), 0);
+        assert_eq!(counter.increment().unwrap(), 1);
+        assert_eq!(counter.increment().unwrap(), 2);
+        assert_eq!(FileCounter::new(&path).read().unwrap(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sealed_state_detects_rollback() {
+        let sealer = Sealer::from_root_key([5; 32]);
+        let path = counter_path("rollback");
+        let mut counter = FileCounter::new(&path);
+        let config = EnclaveConfig {
+            chain_id: 5,
+            endpoint: Some(Address([0x35; 20])),
+            ..EnclaveConfig::default()
+        };
+
+        let mut state = EnclaveState::with_config(
+            EnclaveSigner::generate(SignatureScheme::Secp256k1),
+            config.clone(),
+        );
+        let first = state.process(add("dao-a", 1)).unwrap();
+        let stale = state.seal(&sealer, SealPolicy::MrSigner, &counter).unwrap();
+        counter.increment().unwrap();
+        let root = EnclaveInput::new(Operation::SignMerkleRoot(SignMerkleRootPayload {
+            epoch: 7,
+            outputs: vec![first],
+        }));
+        state.process(root.with_nonce("dao-a", 2)).unwrap();
+        let latest = state.seal(&sealer, SealPolicy::MrSigner, &counter).unwrap();
+        counter.increment().unwrap();
+
+        let error = EnclaveState::unseal(&sealer, &stale, &mut counter).unwrap_err();
+        assert_eq!(
+            error,
+            EnclaveError::RollbackDetected {
+                sealed: 1,
+                current: 2
+            }
+        );
+
+        let mut restored = EnclaveState::unseal(&sealer, &latest, &mut counter).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.signer().identity(), state.signer().identity());
+        assert_eq!(restored.ethereum_address(), state.ethereum_address());
+        assert_eq!(restored.config(), &config);
+        assert_eq!(restored.last_epoch(), Some(7));
+        assert_eq!(restored.replay_guard().last_nonce("dao-a"), Some(2));
+        assert!(restored.process(add("dao-a", 2)).is_err());
+        assert_eq!(restored.process(add("dao-a", 3)).unwrap().sequence, 2);
+    }
+
+    #[test]
+    fn test_blob_saved_before_increment_unseals() {
+        let sealer = Sealer::from_root_key([5; 32]);
+        let path = counter_path("increment");
+        let mut counter = FileCounter::new(&path);
+
+        let mut state = EnclaveState::new(EnclaveSigner::generate(SignatureScheme::Secp256k1));
+        let previous = state.seal(&sealer, SealPolicy::MrSigner, &counter).unwrap();
+        counter.increment().unwrap();
+        state.process(add("dao-a", 1)).unwrap();
+        // The host saved this blob, then stopped before incrementing
+        let saved = state.seal(&sealer, SealPolicy::MrSigner, &counter).unwrap();
+
+        let restored = EnclaveState::unseal(&sealer, &saved, &mut counter).unwrap();
+        assert_eq!(restored.replay_guard().last_nonce("dao-a"), Some(1));
+        assert_eq!(counter.read().unwrap(), 2);
+        let error = EnclaveState::unseal(&sealer, &previous, &mut counter).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.code(), 3004);
+    }
+}
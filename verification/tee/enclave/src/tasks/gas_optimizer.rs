@@ -0,0 +1,422 @@
+//! Gas parameter optimization.
+//!
+//! Port of `agent/tasks/gas_parameter_optimizer.py`. The Python task works
+//! in floats; here prices and factors are [`Fixed`] decimals, so fractional
+//! gwei prices are exact and only the final results are rounded: prices and
+//! costs down to wei precision, and the gas limit down to a whole unit, where
+//! the Python code truncates with `int(...)`. The two agree except when
+//! float rounding pushes a product across a boundary, and except that the
+//! Python task also truncates prices to whole gwei.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ethereum::context::{BlockContext, BlockRange};
+use crate::fixed::{self, Fixed, Rounding};
+use crate::EnclaveError;
+
+/// Share of the recommended gas price suggested as priority fee
+const PRIORITY_FEE_SHARE: Fixed = Fixed::new(15, 2);
+
+/// Gwei amounts in results are rounded down to whole wei
+const GWEI_DECIMALS: u32 = 9;
+
+/// Relative standard deviation bounds (gas used, gas price) for each grade
+const HIGH_QUALITY_RSD: (Fixed, Fixed) = (Fixed::new(1, 1), Fixed::new(2, 1));
+const MEDIUM_QUALITY_RSD: (Fixed, Fixed) = (Fixed::new(3, 1), Fixed::new(5, 1));
+
+/// Kind of governance proposal the gas parameters are for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalType {
+    #[default]
+    Standard,
+    Complex,
+    Upgrade,
+}
+
+impl ProposalType {
+    /// Gas limit multiplier
+    pub fn gas_multiplier(self) -> Fixed {
+        match self {
+            ProposalType::Standard => Fixed::ONE,
+            ProposalType::Complex => Fixed::new(15, 1),
+            ProposalType::Upgrade => Fixed::new(2, 0),
+        }
+    }
+}
+
+/// Tunables mirroring the Python task's default `parameters`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct GasOptimizerConfig {
+    pub sample_size: usize,
+    /// Percentile of observed prices used as the recommendation (0-100)
+    pub percentile_base: u8,
+    /// Multiplier applied to the recommended price to get the max price
+    pub volatility_factor: Fixed,
+    pub min_gas_limit: u64,
+    pub max_recommendation_age_blocks: u64,
+}
+
+impl Default for GasOptimizerConfig {
+    fn default() -> Self {
+        Self {
+            sample_size: 200,
+            percentile_base: 75,
+            volatility_factor: Fixed::new(12, 1),
+            min_gas_limit: 100_000,
+            max_recommendation_age_blocks: 10,
+        }
+    }
+}
+
+/// Payload for the `optimize_gas_parameters` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GasOptimizationPayload {
+    /// Gas used by each recent block, newest first
+    #[serde(default)]
+    pub gas_used: Vec<u64>,
+    /// Recent gas prices in gwei, aligned with `gas_used`
+    #[serde(default)]
+    pub gas_prices_gwei: Vec<Fixed>,
+    /// Verified blocks to take both series from instead, with the base fee
+    /// of each block as its gas price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_context: Option<BlockContext>,
+    #[serde(default)]
+    pub proposal_type: ProposalType,
+    /// Network congestion from 0 (idle) to 1 (saturated)
+    #[serde(default = "default_congestion")]
+    pub network_congestion: Fixed,
+    #[serde(default)]
+    pub config: GasOptimizerConfig,
+}
+
+fn default_congestion() -> Fixed {
+    Fixed::new(5, 1)
+}
+
+/// Confidence grade derived from how consistent the samples are
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecommendationQuality {
+    High,
+    Medium,
+    Low,
+}
+
+/// Result of the `optimize_gas_parameters` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GasRecommendation {
+    pub recommended_gas_price_gwei: Fixed,
+    pub max_gas_price_gwei: Fixed,
+    pub gas_limit: u64,
+    /// `gas_limit * recommended_gas_price_gwei`; divide by 1e9 for ETH
+    pub estimated_cost_gwei: Fixed,
+    pub priority_fee_gwei: Fixed,
+    pub recommendation_quality: RecommendationQuality,
+    pub proposal_type: ProposalType,
+    pub validity_blocks: u64,
+    pub analyzed_blocks: u64,
+    /// Blocks the samples came from, for payloads with a block context
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_range: Option<BlockRange>,
+}
+
+/// Compute gas parameter recommendations from recent block samples
+pub fn optimize(payload: &GasOptimizationPayload) -> Result<GasRecommendation, EnclaveError> {
+    let config = &payload.config;
+    if config.percentile_base > 100 {
+        return Err(EnclaveError::invalid_payload(
+            "percentile_base must be between 0 and 100",
+        ));
+    }
+    if payload.network_congestion.is_negative() || payload.network_congestion > Fixed::ONE {
+        return Err(EnclaveError::invalid_payload(
+            "network_congestion must be between 0 and 1",
+        ));
+    }
+    if payload
+        .gas_prices_gwei
+        .iter()
+        .any(|price| price.is_negative())
+    {
+        return Err(EnclaveError::invalid_payload(
+            "gas prices must not be negative",
+        ));
+    }
+
+    let (all_gas_used, all_gas_prices, block_range) = match &payload.block_context {
+        None => (
+            payload.gas_used.clone(),
+            payload.gas_prices_gwei.clone(),
+            None,
+        ),
+        Some(_) if !payload.gas_used.is_empty() || !payload.gas_prices_gwei.is_empty() => {
+            return Err(EnclaveError::invalid_payload(
+                "gas samples come either from the block context or from the payload",
+            ))
+        }
+        Some(context) => {
+            let verified = context.verify()?;
+            (
+                verified.gas_used(),
+                verified.base_fees_gwei()?,
+                Some(verified.range),
+            )
+        }
+    };
+
+    // The Python task truncates both series to the gas-used sample size
+    let sample_size = all_gas_used.len().min(config.sample_size);
+    let gas_used: Vec<Fixed> = all_gas_used[..sample_size]
+        .iter()
+        .map(|&gas| gas.into())
+        .collect();
+    let gas_prices = &all_gas_prices[..sample_size.min(all_gas_prices.len())];
+    if gas_used.is_empty() || gas_prices.is_empty() {
+        return Err(EnclaveError::invalid_payload(
+            "insufficient gas data for analysis",
+        ));
+    }
+
+    let to_wei = |gwei: Fixed| gwei.round(GWEI_DECIMALS, Rounding::Down);
+    let recommended = fixed::percentile(gas_prices, config.percentile_base)?;
+    let max_price = recommended.mul(config.volatility_factor, Rounding::Down)?;
+    let priority_fee = recommended.mul(PRIORITY_FEE_SHARE, Rounding::Down)?;
+
+    // gas_limit_base = max(mean(gas_used) * 1.5, min_gas_limit); the mean's
+    // division comes last so the limit is only rounded once
+    let n = Fixed::from(gas_used.len() as u64);
+    let scaled_sum = fixed::sum(&gas_used)?.mul(Fixed::new(15, 1), Rounding::Down)?;
+    let min_gas_limit = Fixed::from(config.min_gas_limit);
+    let (base, divisor) = if scaled_sum > min_gas_limit.mul(n, Rounding::Down)? {
+        (scaled_sum, n)
+    } else {
+        (min_gas_limit, Fixed::ONE)
+    };
+    let congestion_factor = payload
+        .network_congestion
+        .mul(Fixed::new(5, 1), Rounding::Down)?
+        .checked_add(Fixed::ONE)?;
+    let gas_limit = base
+        .mul(payload.proposal_type.gas_multiplier(), Rounding::Down)?
+        .mul(congestion_factor, Rounding::Down)?
+        .div(divisor, Rounding::Down)?
+        .to_u64(Rounding::Down)?;
+
+    let estimated_cost = recommended.mul(gas_limit.into(), Rounding::Down)?;
+
+    Ok(GasRecommendation {
+        recommended_gas_price_gwei: to_wei(recommended)?,
+        max_gas_price_gwei: to_wei(max_price)?,
+        gas_limit,
+        estimated_cost_gwei: to_wei(estimated_cost)?,
+        priority_fee_gwei: to_wei(priority_fee)?,
+        recommendation_quality: recommendation_quality(&gas_used, gas_prices)?,
+        proposal_type: payload.proposal_type,
+        validity_blocks: config.max_recommendation_age_blocks,
+        analyzed_blocks: all_gas_used.len() as u64,
+        block_range,
+    })
+}
+
+fn recommendation_quality(
+    gas_used: &[Fixed],
+    gas_prices: &[Fixed],
+) -> Result<RecommendationQuality, EnclaveError> {
+    // statistics.stdev needs two samples; the Python task grades that as low
+    if gas_used.len() < 2 || gas_prices.len() < 2 {
+        return Ok(RecommendationQuality::Low);
+    }
+
+    let grade = |(used_bound, price_bound): (Fixed, Fixed)| -> Result<bool, EnclaveError> {
+        Ok(rsd_below(gas_used, used_bound)? && rsd_below(gas_prices, price_bound)?)
+    };
+    if grade(HIGH_QUALITY_RSD)? {
+        Ok(RecommendationQuality::High)
+    } else if grade(MEDIUM_QUALITY_RSD)? {
+        Ok(RecommendationQuality::Medium)
+    } else {
+        Ok(RecommendationQuality::Low)
+    }
+}
+
+/// Whether the relative standard deviation `stdev / mean` is below `bound`
+///
+/// Compared as `stdev < bound * mean`; a zero mean makes the ratio infinite,
+/// which is never below the bound.
+fn rsd_below(values: &[Fixed], bound: Fixed) -> Result<bool, EnclaveError> {
+    let mean = fixed::mean(values, Rounding::HalfEven)?;
+    if mean.is_zero() {
+        return Ok(false);
+    }
+    let stdev = fixed::stdev(values, Rounding::HalfEven)?;
+    Ok(stdev < bound.mul(mean, Rounding::HalfEven)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(s: &str) -> Fixed {
+        s.parse().unwrap()
+    }
+
+    fn payload(
+        gas_used: &[u64],
+        gas_prices_gwei: &[u64],
+        proposal_type: ProposalType,
+        network_congestion: &str,
+    ) -> GasOptimizationPayload {
+        GasOptimizationPayload {
+            gas_used: gas_used.to_vec(),
+            gas_prices_gwei: gas_prices_gwei.iter().map(|&p| p.into()).collect(),
+            block_context: None,
+            proposal_type,
+            network_congestion: fixed(network_congestion),
+            config: GasOptimizerConfig::default(),
+        }
+    }
+
+    // Expected values below match
+    // GasParameterOptimizer()._calculate_recommendations on the same inputs,
+    // except that max and priority fees keep the fractional gwei the Python
+    // task truncates away.
+
+    #[test]
+    fn test_golden_standard_steady_network() {
+        let result = optimize(&payload(
+            &[
+                12_500_000, 13_000_000, 12_000_000, 12_800_000, 12_600_000, 12_400_000, 12_700_000,
+                12_300_000, 12_900_000, 12_550_000,
+            ],
+            &[20, 22, 25, 21, 24, 23, 26, 22, 21, 30],
+            ProposalType::Standard,
+            "0.5",
+        ))
+        .unwrap();
+
+        assert_eq!(result.recommended_gas_price_gwei, fixed("25"));
+        assert_eq!(result.max_gas_price_gwei, fixed("30"));
+        assert_eq!(result.gas_limit, 23_578_125);
+        assert_eq!(result.estimated_cost_gwei, fixed("589453125"));
+        assert_eq!(result.priority_fee_gwei, fixed("3.75"));
+        assert_eq!(result.recommendation_quality, RecommendationQuality::High);
+        assert_eq!(result.validity_blocks, 10);
+    }
+
+    #[test]
+    fn test_golden_complex_volatile_network() {
+        let result = optimize(&payload(
+            &[
+                15_000_000, 9_000_000, 20_000_000, 11_000_000, 14_000_000, 18_000_000,
+            ],
+            &[30, 45, 28, 60, 33, 40],
+            ProposalType::Complex,
+            "0.8",
+        ))
+        .unwrap();
+
+        assert_eq!(result.recommended_gas_price_gwei, fixed("45"));
+        assert_eq!(result.max_gas_price_gwei, fixed("54"));
+        assert_eq!(result.gas_limit, 45_675_000);
+        assert_eq!(result.estimated_cost_gwei, fixed("2055375000"));
+        assert_eq!(result.priority_fee_gwei, fixed("6.75"));
+        assert_eq!(result.recommendation_quality, RecommendationQuality::Medium);
+    }
+
+    #[test]
+    fn test_golden_upgrade_min_gas_limit() {
+        let result = optimize(&payload(
+            &[50_000, 60_000, 40_000],
+            &[100, 10, 50],
+            ProposalType::Upgrade,
+            "0",
+        ))
+        .unwrap();
+
+        assert_eq!(result.recommended_gas_price_gwei, fixed("100"));
+        assert_eq!(result.max_gas_price_gwei, fixed("120"));
+        assert_eq!(result.gas_limit, 200_000);
+        assert_eq!(result.estimated_cost_gwei, fixed("20000000"));
+        assert_eq!(result.priority_fee_gwei, fixed("15"));
+        assert_eq!(result.recommendation_quality, RecommendationQuality::Low);
+    }
+
+    #[test]
+    fn test_golden_upgrade_saturated_network() {
+        let result = optimize(&payload(
+            &[29_000_000, 29_500_000, 28_800_000, 29_900_000],
+            &[40, 41, 42, 40],
+            ProposalType::Upgrade,
+            "1",
+        ))
+        .unwrap();
+
+        assert_eq!(result.recommended_gas_price_gwei, fixed("42"));
+        assert_eq!(result.max_gas_price_gwei, fixed("50.4"));
+        assert_eq!(result.gas_limit, 131_850_000);
+        assert_eq!(result.estimated_cost_gwei, fixed("5537700000"));
+        assert_eq!(result.priority_fee_gwei, fixed("6.3"));
+        assert_eq!(result.recommendation_quality, RecommendationQuality::High);
+    }
+
+    #[test]
+    fn test_fractional_prices_round_to_wei() {
+        let mut fractional = payload(
+            &[12_000_000, 12_500_000, 13_000_000],
+            &[],
+            ProposalType::Standard,
+            "0.25",
+        );
+        fractional.gas_prices_gwei = vec![fixed("0.1234567891"), fixed("0.1"), fixed("0.2")];
+        let result = optimize(&fractional).unwrap();
+
+        // 0.1234567891 is below wei precision and rounds down
+        assert_eq!(result.recommended_gas_price_gwei, fixed("0.2"));
+        assert_eq!(result.max_gas_price_gwei, fixed("0.24"));
+        assert_eq!(result.priority_fee_gwei, fixed("0.03"));
+        assert_eq!(result.gas_limit, 21_093_750);
+        assert_eq!(result.estimated_cost_gwei, fixed("4218750"));
+
+        fractional.gas_prices_gwei = vec![fixed("0.1234567891")];
+        let result = optimize(&fractional).unwrap();
+        assert_eq!(result.recommended_gas_price_gwei, fixed("0.123456789"));
+    }
+
+    #[test]
+    fn test_rejects_empty_and_out_of_range_input() {
+        assert!(optimize(&payload(&[], &[20], ProposalType::Standard, "0")).is_err());
+        assert!(optimize(&payload(&[1], &[20], ProposalType::Standard, "1.01")).is_err());
+        assert!(optimize(&payload(&[1], &[20], ProposalType::Standard, "-0.1")).is_err());
+        let mut negative = payload(&[1], &[], ProposalType::Standard, "0");
+        negative.gas_prices_gwei = vec![fixed("-1")];
+        assert!(optimize(&negative).is_err());
+    }
+
+    #[test]
+    fn test_samples_from_verified_blocks() {
+        let (context, _) = crate::ethereum::context::tests::context(4);
+        let mut from_context = payload(&[], &[], ProposalType::Standard, "0.5");
+        from_context.block_context = Some(context.clone());
+        let result = optimize(&from_context).unwrap();
+
+        let mut plain = payload(
+            &[13_000_000, 12_000_000, 11_000_000, 10_000_000],
+            &[],
+            ProposalType::Standard,
+            "0.5",
+        );
+        plain.gas_prices_gwei = ["8.5", "8", "7.5", "7"].iter().map(|p| fixed(p)).collect();
+        let plain = optimize(&plain).unwrap();
+        assert_eq!(result.recommended_gas_price_gwei, fixed("8.5"));
+        assert_eq!(result.gas_limit, plain.gas_limit);
+        assert_eq!(result.analyzed_blocks, 4);
+        assert_eq!(result.block_range, Some(context.verify().unwrap().range));
+        assert_eq!(plain.block_range, None);
+
+        from_context.gas_used = vec![1];
+        assert!(optimize(&from_context).is_err());
+    }
+}
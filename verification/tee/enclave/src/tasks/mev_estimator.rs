@@ -0,0 +1,796 @@
+//! MEV cost estimation.
+//!
+//! Port of `agent/tasks/mev_cost_estimator.py`. Four extraction vectors
+//! (sandwich attacks, frontrunning, liquidations and arbitrage) are scored
+//! independently and combined with the same weights as
+//! `_calculate_weighted_risk`. Ratios, scores and currency amounts
+//! (liquidity, volume, transaction values, estimated costs) are [`Fixed`]
+//! decimals, currency in whatever unit the caller uses, and every product
+//! and quotient is rounded down to 18 decimals. Counts of transactions and
+//! positions stay integers; exposed positions are rounded down to whole
+//! ones.
+//!
+//! Which vectors a proposal touches is decided, as in Python, by the names
+//! of the parameters it changes (for instance `slippage_tolerance` or
+//! `oracle_update_frequency`).
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::RiskLevel;
+use crate::fixed::{self, Fixed, Rounding};
+use crate::EnclaveError;
+
+const SLIPPAGE_PARAMETERS: &[&str] = &["slippage_tolerance", "max_slippage", "min_output_amount"];
+const FEE_PARAMETERS: &[&str] = &["fee", "commission", "tax_rate", "protocol_fee"];
+const LIQUIDATION_PARAMETERS: &[&str] = &[
+    "liquidation_threshold",
+    "collateral_factor",
+    "loan_to_value",
+    "debt_ceiling",
+];
+const ORACLE_PARAMETERS: &[&str] = &["price_oracle", "oracle_update_frequency", "price_feed"];
+const MARKET_MAKING_PARAMETERS: &[&str] =
+    &["curve_parameters", "k_value", "fee_tier", "pool_weights"];
+
+/// Vector weights (sandwich, frontrunning, liquidations, arbitrage)
+const VECTOR_WEIGHTS: [Fixed; 4] = [
+    Fixed::new(3, 1),
+    Fixed::new(2, 1),
+    Fixed::new(3, 1),
+    Fixed::new(2, 1),
+];
+
+/// Mempool size at which frontrunning density saturates
+const MEMPOOL_SATURATION_TX_COUNT: u64 = 5_000;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Trading pair exposed to the proposal
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TradingPair {
+    pub id: String,
+    #[serde(default = "default_volatility")]
+    pub volatility: Fixed,
+    /// Typical slippage tolerance used by traders on this pair
+    #[serde(default = "default_avg_slippage")]
+    pub avg_slippage: Fixed,
+    /// Pool liquidity in currency units
+    pub liquidity: Fixed,
+    /// Daily volume in currency units
+    pub volume: Fixed,
+}
+
+fn default_volatility() -> Fixed {
+    Fixed::new(1, 1)
+}
+
+fn default_avg_slippage() -> Fixed {
+    Fixed::new(1, 2)
+}
+
+/// Snapshot of pending transactions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MempoolSnapshot {
+    pub transaction_count: u64,
+    /// Mean value of a pending transaction in currency units
+    pub average_transaction_value: Fixed,
+}
+
+impl Default for MempoolSnapshot {
+    fn default() -> Self {
+        Self {
+            transaction_count: 1_000,
+            average_transaction_value: Fixed::ZERO,
+        }
+    }
+}
+
+/// Searcher bot observed on the network
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ActiveBot {
+    pub id: String,
+    #[serde(default)]
+    pub frontrunning: bool,
+}
+
+/// Lending positions that liquidation-related changes could expose
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LendingExposure {
+    pub positions_at_risk: u64,
+    /// Collateral value of those positions in currency units
+    pub value_at_risk: Fixed,
+    pub avg_liquidation_discount: Fixed,
+}
+
+impl Default for LendingExposure {
+    fn default() -> Self {
+        Self {
+            positions_at_risk: 120,
+            value_at_risk: 5_000_000.into(),
+            avg_liquidation_discount: Fixed::new(5, 2),
+        }
+    }
+}
+
+/// Tunables mirroring the Python task's default `parameters`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct MevConfig {
+    pub block_time_seconds: u64,
+    pub mev_estimation_blocks: u64,
+    pub liquidation_risk_threshold: Fixed,
+    pub sandwich_attack_sensitivity: Fixed,
+    pub volume_impact_factor: Fixed,
+    pub max_slippage_tolerance: Fixed,
+}
+
+impl Default for MevConfig {
+    fn default() -> Self {
+        Self {
+            block_time_seconds: 12,
+            mev_estimation_blocks: 100,
+            liquidation_risk_threshold: Fixed::new(2, 1),
+            sandwich_attack_sensitivity: Fixed::new(5, 1),
+            volume_impact_factor: Fixed::new(65, 2),
+            max_slippage_tolerance: Fixed::new(3, 2),
+        }
+    }
+}
+
+/// Payload for the `estimate_mev_cost` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MevEstimationPayload {
+    #[serde(default)]
+    pub proposal_id: String,
+    /// Parameters the proposal changes, with their new values in protocol units
+    #[serde(default)]
+    pub parameter_changes: BTreeMap<String, Fixed>,
+    #[serde(default)]
+    pub trading_pairs: Vec<TradingPair>,
+    #[serde(default)]
+    pub mempool: MempoolSnapshot,
+    #[serde(default)]
+    pub gas_prices_gwei: Vec<Fixed>,
+    #[serde(default)]
+    pub active_bots: Vec<ActiveBot>,
+    #[serde(default)]
+    pub lending: LendingExposure,
+    #[serde(default)]
+    pub config: MevConfig,
+}
+
+/// Sandwich risk contributed by one trading pair
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PairRisk {
+    pub pair: String,
+    pub risk_score: Fixed,
+    pub potential_mev: Fixed,
+    pub volume: Fixed,
+    pub liquidity: Fixed,
+}
+
+/// Sandwich attack vector
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SandwichRisk {
+    pub risk_score: Fixed,
+    pub estimated_cost: Fixed,
+    pub affected_pairs: u64,
+    /// Up to three pairs, riskiest first
+    pub highest_risk_pairs: Vec<PairRisk>,
+    pub slippage_parameter_changes: bool,
+}
+
+/// Frontrunning vector
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FrontrunningRisk {
+    pub risk_score: Fixed,
+    pub estimated_cost: Fixed,
+    pub mempool_density: Fixed,
+    pub gas_price_volatility: Fixed,
+    pub frontrunning_bot_prevalence: Fixed,
+    pub fee_parameter_changes: bool,
+}
+
+/// Liquidation vector
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LiquidationRisk {
+    pub risk_score: Fixed,
+    pub estimated_cost: Fixed,
+    pub positions_at_risk: u64,
+    pub value_at_risk: Fixed,
+    pub liquidation_parameter_changes: bool,
+}
+
+/// Arbitrage vector
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArbitrageRisk {
+    pub risk_score: Fixed,
+    pub estimated_cost: Fixed,
+    pub oracle_parameter_changes: bool,
+    pub market_making_parameter_changes: bool,
+    pub daily_volume: Fixed,
+    /// Share of the daily volume exposed to arbitrage
+    pub affected_volume: Fixed,
+}
+
+/// Suggested countermeasure, identified by a stable code
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mitigation {
+    AntiSandwichProtection,
+    MinimumOutputAmount,
+    ReviewSlippageChanges,
+    CommitReveal,
+    BatchAuctions,
+    PhaseInFeeChanges,
+    DutchAuctionLiquidations,
+    GradualLiquidationChanges,
+    TwapOracles,
+    CircuitBreakers,
+    LowActivityScheduling,
+    Timelock,
+    PrivateMempool,
+    MonitorNetworkActivity,
+    PhasedImplementation,
+}
+
+impl Mitigation {
+    /// Human readable suggestion, as worded by the Python task
+    pub fn description(self) -> &'static str {
+        match self {
+            Mitigation::AntiSandwichProtection => {
+                "Consider implementing anti-sandwich protection like Uniswap's"
+            }
+            Mitigation::MinimumOutputAmount => "Add minimum output amount requirements for swaps",
+            Mitigation::ReviewSlippageChanges => {
+                "Carefully review slippage parameter changes for sandwich attack vectors"
+            }
+            Mitigation::CommitReveal => {
+                "Consider implementing a commit-reveal scheme to prevent frontrunning"
+            }
+            Mitigation::BatchAuctions => "Implement batch auctions for high-value transactions",
+            Mitigation::PhaseInFeeChanges => {
+                "Phase in fee changes gradually to reduce frontrunning opportunities"
+            }
+            Mitigation::DutchAuctionLiquidations => "Implement Dutch auctions for liquidations",
+            Mitigation::GradualLiquidationChanges => {
+                "Consider gradual changes to liquidation parameters"
+            }
+            Mitigation::TwapOracles => {
+                "Use time-weighted average prices (TWAPs) to reduce oracle manipulation"
+            }
+            Mitigation::CircuitBreakers => "Implement circuit breakers for extreme price movements",
+            Mitigation::LowActivityScheduling => {
+                "Consider scheduling parameter changes during periods of low network activity"
+            }
+            Mitigation::Timelock => {
+                "Apply timelock to all parameter changes to allow users to adjust positions"
+            }
+            Mitigation::PrivateMempool => {
+                "Employ private mempool solutions for critical transactions"
+            }
+            Mitigation::MonitorNetworkActivity => {
+                "Monitor network activity during and after parameter changes"
+            }
+            Mitigation::PhasedImplementation => {
+                "Consider phased implementation of parameter changes"
+            }
+        }
+    }
+}
+
+/// Result of the `estimate_mev_cost` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MevEstimate {
+    pub proposal_id: String,
+    pub risk_level: RiskLevel,
+    /// Weighted risk across all vectors, from 0 to 1
+    pub risk_score: Fixed,
+    pub estimated_total_mev_cost: Fixed,
+    pub estimated_cost_per_block: Fixed,
+    pub sandwich_attacks: SandwichRisk,
+    pub frontrunning: FrontrunningRisk,
+    pub liquidations: LiquidationRisk,
+    pub arbitrage: ArbitrageRisk,
+    pub mitigations: Vec<Mitigation>,
+    pub estimation_horizon_blocks: u64,
+}
+
+/// Estimate the MEV a proposal exposes users to over the configured horizon
+pub fn estimate(payload: &MevEstimationPayload) -> Result<MevEstimate, EnclaveError> {
+    if payload.config.mev_estimation_blocks == 0 {
+        return Err(EnclaveError::invalid_payload(
+            "mev_estimation_blocks must be positive",
+        ));
+    }
+
+    let sandwich = analyze_sandwich(payload)?;
+    let frontrunning = analyze_frontrunning(payload)?;
+    let liquidations = analyze_liquidations(payload)?;
+    let arbitrage = analyze_arbitrage(payload)?;
+
+    let scores = [
+        sandwich.score,
+        frontrunning.score,
+        liquidations.score,
+        arbitrage.score,
+    ];
+    let weighted = scores
+        .iter()
+        .zip(VECTOR_WEIGHTS)
+        .map(|(score, weight)| score.mul(weight, Rounding::Down))
+        .collect::<Result<Vec<_>, _>>()?;
+    let weighted = fixed::sum(&weighted)?;
+    let risk_level = if weighted > Fixed::new(7, 1) {
+        RiskLevel::High
+    } else if weighted > Fixed::new(3, 1) {
+        RiskLevel::Medium
+    } else {
+        RiskLevel::Low
+    };
+
+    let total_cost = fixed::sum(&[
+        sandwich.risk.estimated_cost,
+        frontrunning.risk.estimated_cost,
+        liquidations.risk.estimated_cost,
+        arbitrage.risk.estimated_cost,
+    ])?;
+    let blocks = Fixed::from(payload.config.mev_estimation_blocks);
+
+    let mitigations = mitigations(
+        &sandwich.risk,
+        &frontrunning.risk,
+        &liquidations.risk,
+        &arbitrage.risk,
+        risk_level,
+    );
+
+    Ok(MevEstimate {
+        proposal_id: payload.proposal_id.clone(),
+        risk_level,
+        risk_score: weighted,
+        estimated_total_mev_cost: total_cost,
+        estimated_cost_per_block: total_cost.div(blocks, Rounding::Down)?,
+        sandwich_attacks: sandwich.risk,
+        frontrunning: frontrunning.risk,
+        liquidations: liquidations.risk,
+        arbitrage: arbitrage.risk,
+        mitigations,
+        estimation_horizon_blocks: payload.config.mev_estimation_blocks,
+    })
+}
+
+/// A vector's public report plus the score it contributes to the total
+struct Vector<T> {
+    risk: T,
+    /// Risk score capped at 1
+    score: Fixed,
+}
+
+fn changes_any(payload: &MevEstimationPayload, names: &[&str]) -> bool {
+    names
+        .iter()
+        .any(|name| payload.parameter_changes.contains_key(*name))
+}
+
+fn analyze_sandwich(payload: &MevEstimationPayload) -> Result<Vector<SandwichRisk>, EnclaveError> {
+    let config = &payload.config;
+    let slippage_changes = changes_any(payload, SLIPPAGE_PARAMETERS);
+
+    let mut sensitivity = non_negative(
+        config.sandwich_attack_sensitivity,
+        "sandwich_attack_sensitivity",
+    )?;
+    if slippage_changes {
+        sensitivity = sensitivity.mul(Fixed::new(15, 1), Rounding::Down)?;
+    }
+    let volume_impact = non_negative(config.volume_impact_factor, "volume_impact_factor")?;
+
+    let mut pairs = Vec::new();
+    let mut total_risk = Fixed::ZERO;
+    let mut total_cost = Fixed::ZERO;
+    for pair in &payload.trading_pairs {
+        let liquidity = non_negative(pair.liquidity, "liquidity")?;
+        let volume = non_negative(pair.volume, "volume")?;
+        if liquidity.is_zero() || volume.is_zero() {
+            continue;
+        }
+
+        let sqrt_turnover = volume
+            .div(liquidity, Rounding::Down)?
+            .sqrt(Rounding::Down)?;
+        let risk = sensitivity
+            .mul(non_negative(pair.volatility, "volatility")?, Rounding::Down)?
+            .mul(sqrt_turnover, Rounding::Down)?
+            .mul(volume_impact, Rounding::Down)?;
+        let slippage = non_negative(
+            config.max_slippage_tolerance.min(pair.avg_slippage),
+            "slippage",
+        )?;
+        let potential = volume
+            .mul(risk, Rounding::Down)?
+            .mul(slippage, Rounding::Down)?;
+
+        total_risk = total_risk.checked_add(risk)?;
+        total_cost = total_cost.checked_add(potential)?;
+        pairs.push(PairRisk {
+            pair: pair.id.clone(),
+            risk_score: risk,
+            potential_mev: potential,
+            volume,
+            liquidity,
+        });
+    }
+
+    let score = if pairs.is_empty() {
+        Fixed::ZERO
+    } else {
+        total_risk
+            .div(Fixed::from(pairs.len() as u64), Rounding::Down)?
+            .min(Fixed::ONE)
+    };
+    let affected_pairs = pairs.len() as u64;
+    pairs.sort_by_key(|pair| Reverse(pair.risk_score));
+    pairs.truncate(3);
+
+    Ok(Vector {
+        risk: SandwichRisk {
+            risk_score: score,
+            estimated_cost: total_cost,
+            affected_pairs,
+            highest_risk_pairs: pairs,
+            slippage_parameter_changes: slippage_changes,
+        },
+        score,
+    })
+}
+
+fn analyze_frontrunning(
+    payload: &MevEstimationPayload,
+) -> Result<Vector<FrontrunningRisk>, EnclaveError> {
+    let fee_changes = changes_any(payload, FEE_PARAMETERS);
+
+    let prices = &payload.gas_prices_gwei;
+    let gas_volatility = if prices.len() > 1 {
+        let changes = prices
+            .windows(2)
+            .map(|window| {
+                if window[0] <= Fixed::ZERO {
+                    return Err(EnclaveError::invalid_payload("gas prices must be positive"));
+                }
+                window[1]
+                    .checked_sub(window[0])?
+                    .abs()
+                    .div(window[0], Rounding::Down)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        fixed::mean(&changes, Rounding::Down)?
+    } else {
+        Fixed::ZERO
+    };
+
+    let bots = &payload.active_bots;
+    let frontrunning_bots = bots.iter().filter(|bot| bot.frontrunning).count() as u64;
+    let prevalence =
+        Fixed::from(frontrunning_bots).div((bots.len() as u64).max(1).into(), Rounding::Down)?;
+
+    let tx_count = Fixed::from(payload.mempool.transaction_count);
+    let density = tx_count
+        .div(MEMPOOL_SATURATION_TX_COUNT.into(), Rounding::Down)?
+        .min(Fixed::ONE);
+
+    let mut risk = fixed::sum(&[
+        density.mul(Fixed::new(4, 1), Rounding::Down)?,
+        gas_volatility.mul(Fixed::new(3, 1), Rounding::Down)?,
+        prevalence.mul(Fixed::new(3, 1), Rounding::Down)?,
+    ])?;
+    if fee_changes {
+        risk = risk.mul(Fixed::new(13, 1), Rounding::Down)?;
+    }
+
+    // The uncapped score drives the cost, as in the Python model; 0.5% extraction rate
+    let average_value = non_negative(
+        payload.mempool.average_transaction_value,
+        "average_transaction_value",
+    )?;
+    let cost = tx_count
+        .mul(average_value, Rounding::Down)?
+        .mul(risk, Rounding::Down)?
+        .mul(Fixed::new(5, 3), Rounding::Down)?;
+    let score = risk.min(Fixed::ONE);
+
+    Ok(Vector {
+        risk: FrontrunningRisk {
+            risk_score: score,
+            estimated_cost: cost,
+            mempool_density: density,
+            gas_price_volatility: gas_volatility,
+            frontrunning_bot_prevalence: prevalence,
+            fee_parameter_changes: fee_changes,
+        },
+        score,
+    })
+}
+
+fn analyze_liquidations(
+    payload: &MevEstimationPayload,
+) -> Result<Vector<LiquidationRisk>, EnclaveError> {
+    let lending = &payload.lending;
+    let liquidation_changes = changes_any(payload, LIQUIDATION_PARAMETERS);
+
+    let (mut risk, affected) = if liquidation_changes {
+        (Fixed::new(7, 1), Fixed::new(15, 2))
+    } else {
+        (Fixed::new(2, 1), Fixed::new(5, 2))
+    };
+    if risk < payload.config.liquidation_risk_threshold {
+        risk = risk.mul(Fixed::new(7, 1), Rounding::Down)?;
+    }
+
+    let value_at_risk =
+        non_negative(lending.value_at_risk, "value_at_risk")?.mul(affected, Rounding::Down)?;
+    let cost = value_at_risk.mul(
+        non_negative(lending.avg_liquidation_discount, "avg_liquidation_discount")?,
+        Rounding::Down,
+    )?;
+    let positions_at_risk = Fixed::from(lending.positions_at_risk)
+        .mul(affected, Rounding::Down)?
+        .to_u64(Rounding::Down)?;
+    let score = risk.min(Fixed::ONE);
+
+    Ok(Vector {
+        risk: LiquidationRisk {
+            risk_score: score,
+            estimated_cost: cost,
+            positions_at_risk,
+            value_at_risk,
+            liquidation_parameter_changes: liquidation_changes,
+        },
+        score,
+    })
+}
+
+fn analyze_arbitrage(
+    payload: &MevEstimationPayload,
+) -> Result<Vector<ArbitrageRisk>, EnclaveError> {
+    let config = &payload.config;
+    let oracle_changes = changes_any(payload, ORACLE_PARAMETERS);
+    let market_making_changes = changes_any(payload, MARKET_MAKING_PARAMETERS);
+
+    let base_risk = if oracle_changes {
+        Fixed::new(8, 1)
+    } else if market_making_changes {
+        Fixed::new(6, 1)
+    } else {
+        Fixed::new(3, 1)
+    };
+
+    let volumes = payload
+        .trading_pairs
+        .iter()
+        .map(|pair| non_negative(pair.volume, "volume"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let daily_volume = fixed::sum(&volumes)?;
+
+    // Up to 10% of volume is affected; each arbitrage captures 0.2% (0.5% on oracle changes)
+    let affected = base_risk.div(10.into(), Rounding::Down)?;
+    let profit = if oracle_changes {
+        Fixed::new(5, 3)
+    } else {
+        Fixed::new(2, 3)
+    };
+    let horizon_seconds = Fixed::from(config.mev_estimation_blocks)
+        .mul(config.block_time_seconds.into(), Rounding::Down)?;
+    let cost = daily_volume
+        .mul(affected, Rounding::Down)?
+        .mul(profit, Rounding::Down)?
+        .mul(horizon_seconds, Rounding::Down)?
+        .div(SECONDS_PER_DAY.into(), Rounding::Down)?;
+
+    Ok(Vector {
+        risk: ArbitrageRisk {
+            risk_score: base_risk,
+            estimated_cost: cost,
+            oracle_parameter_changes: oracle_changes,
+            market_making_parameter_changes: market_making_changes,
+            daily_volume,
+            affected_volume: affected,
+        },
+        score: base_risk,
+    })
+}
+
+fn mitigations(
+    sandwich: &SandwichRisk,
+    frontrunning: &FrontrunningRisk,
+    liquidations: &LiquidationRisk,
+    arbitrage: &ArbitrageRisk,
+    risk_level: RiskLevel,
+) -> Vec<Mitigation> {
+    let mut mitigations = Vec::new();
+
+    if sandwich.risk_score > Fixed::new(6, 1) {
+        mitigations.push(Mitigation::AntiSandwichProtection);
+        mitigations.push(Mitigation::MinimumOutputAmount);
+    }
+    if sandwich.slippage_parameter_changes {
+        mitigations.push(Mitigation::ReviewSlippageChanges);
+    }
+    if frontrunning.risk_score > Fixed::new(5, 1) {
+        mitigations.push(Mitigation::CommitReveal);
+        mitigations.push(Mitigation::BatchAuctions);
+    }
+    if frontrunning.fee_parameter_changes {
+        mitigations.push(Mitigation::PhaseInFeeChanges);
+    }
+    if liquidations.risk_score > Fixed::new(4, 1) {
+        mitigations.push(Mitigation::DutchAuctionLiquidations);
+        mitigations.push(Mitigation::GradualLiquidationChanges);
+    }
+    if arbitrage.oracle_parameter_changes {
+        mitigations.push(Mitigation::TwapOracles);
+        mitigations.push(Mitigation::CircuitBreakers);
+    }
+
+    match risk_level {
+        RiskLevel::High => {
+            mitigations.push(Mitigation::LowActivityScheduling);
+            mitigations.push(Mitigation::Timelock);
+            mitigations.push(Mitigation::PrivateMempool);
+        }
+        RiskLevel::Medium => {
+            mitigations.push(Mitigation::MonitorNetworkActivity);
+            mitigations.push(Mitigation::PhasedImplementation);
+        }
+        RiskLevel::Low => {}
+    }
+
+    mitigations
+}
+
+/// `value`, which must not be negative
+fn non_negative(value: Fixed, name: &str) -> Result<Fixed, EnclaveError> {
+    if value.is_negative() {
+        return Err(EnclaveError::invalid_payload(format!(
+            "{} must not be negative",
+            name
+        )));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(s: &str) -> Fixed {
+        s.parse().unwrap()
+    }
+
+    /// `value` to four decimals, the precision of the Python reference values
+    fn approx(value: Fixed) -> Fixed {
+        value.round(4, Rounding::HalfUp).unwrap()
+    }
+
+    /// Mirrors the mock context of the Python task
+    fn payload(parameter_changes: &[&str]) -> MevEstimationPayload {
+        let pair = |id: &str, volatility, avg_slippage, liquidity: u64, volume: u64| TradingPair {
+            id: id.to_string(),
+            volatility: fixed(volatility),
+            avg_slippage: fixed(avg_slippage),
+            liquidity: liquidity.into(),
+            volume: volume.into(),
+        };
+        MevEstimationPayload {
+            proposal_id: "p".to_string(),
+            parameter_changes: parameter_changes
+                .iter()
+                .map(|name| (name.to_string(), Fixed::ONE))
+                .collect(),
+            trading_pairs: vec![
+                pair("ETH/USDC", "0.15", "0.005", 1_000_000, 500_000),
+                pair("WBTC/ETH", "0.12", "0.008", 750_000, 300_000),
+                pair("DAI/USDC", "0.01", "0.001", 2_000_000, 600_000),
+            ],
+            mempool: MempoolSnapshot {
+                transaction_count: 1_000,
+                average_transaction_value: fixed("0.5"),
+            },
+            gas_prices_gwei: ["25", "30", "27", "27"].iter().map(|p| fixed(p)).collect(),
+            active_bots: [true, false, true, true]
+                .iter()
+                .enumerate()
+                .map(|(i, &frontrunning)| ActiveBot {
+                    id: format!("bot{}", i + 1),
+                    frontrunning,
+                })
+                .collect(),
+            lending: LendingExposure::default(),
+            config: MevConfig::default(),
+        }
+    }
+
+    #[test]
+    fn test_oracle_and_fee_changes_match_python() {
+        let estimate = estimate(&payload(&[
+            "fee",
+            "slippage_tolerance",
+            "oracle_update_frequency",
+        ]))
+        .unwrap();
+
+        assert_eq!(estimate.risk_level, RiskLevel::Medium);
+        assert_eq!(approx(estimate.risk_score), fixed("0.3162"));
+        assert_eq!(
+            approx(estimate.estimated_total_mev_cost),
+            fixed("12728.5333")
+        );
+        assert_eq!(approx(estimate.estimated_cost_per_block), fixed("127.2853"));
+
+        let sandwich = &estimate.sandwich_attacks;
+        assert_eq!(approx(sandwich.risk_score), fixed("0.0305"));
+        assert_eq!(approx(sandwich.estimated_cost), fixed("219.6668"));
+        assert_eq!(sandwich.highest_risk_pairs[0].pair, "ETH/USDC");
+        assert_eq!(
+            approx(sandwich.highest_risk_pairs[0].potential_mev),
+            fixed("129.268")
+        );
+
+        assert_eq!(estimate.frontrunning.risk_score, fixed("0.4355"));
+        assert_eq!(estimate.frontrunning.estimated_cost, fixed("1.08875"));
+        assert_eq!(estimate.frontrunning.gas_price_volatility, fixed("0.1"));
+        assert_eq!(estimate.liquidations.estimated_cost, 12_500.into());
+        assert_eq!(estimate.liquidations.positions_at_risk, 6);
+        assert_eq!(estimate.arbitrage.risk_score, fixed("0.8"));
+        assert_eq!(approx(estimate.arbitrage.estimated_cost), fixed("7.7778"));
+
+        assert_eq!(
+            estimate.mitigations,
+            vec![
+                Mitigation::ReviewSlippageChanges,
+                Mitigation::PhaseInFeeChanges,
+                Mitigation::TwapOracles,
+                Mitigation::CircuitBreakers,
+                Mitigation::MonitorNetworkActivity,
+                Mitigation::PhasedImplementation,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_liquidation_change_matches_python() {
+        let estimate = estimate(&payload(&["collateral_factor"])).unwrap();
+
+        assert_eq!(approx(estimate.risk_score), fixed("0.3431"));
+        assert_eq!(
+            approx(estimate.estimated_total_mev_cost),
+            fixed("37648.4487")
+        );
+        assert_eq!(estimate.liquidations.risk_score, fixed("0.7"));
+        assert_eq!(estimate.liquidations.positions_at_risk, 18);
+        assert_eq!(estimate.liquidations.value_at_risk, 750_000.into());
+        assert_eq!(
+            estimate.mitigations,
+            vec![
+                Mitigation::DutchAuctionLiquidations,
+                Mitigation::GradualLiquidationChanges,
+                Mitigation::MonitorNetworkActivity,
+                Mitigation::PhasedImplementation,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_inputs() {
+        let mut zero_price = payload(&[]);
+        zero_price.gas_prices_gwei = vec![Fixed::ZERO, 10.into()];
+        assert_eq!(estimate(&zero_price).unwrap_err().code(), 1003);
+
+        let mut negative_volume = payload(&[]);
+        negative_volume.trading_pairs[1].volume = fixed("-1");
+        assert_eq!(estimate(&negative_volume).unwrap_err().code(), 1003);
+    }
+}
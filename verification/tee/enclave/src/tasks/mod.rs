@@ -0,0 +1,21 @@
+//! Governance analysis tasks executed inside the enclave.
+//!
+//! Each submodule ports one of the Python agent tasks under `agent/tasks`
+//! to deterministic [`Fixed`](crate::fixed::Fixed) decimal arithmetic, so
+//! that the same inputs produce bit-for-bit identical, attestable outputs on
+//! every platform.
+
+pub mod gas_optimizer;
+pub mod mev_estimator;
+pub mod proposal_scanner;
+
+use serde::{Deserialize, Serialize};
+
+/// Three-level grade shared by task risk levels and finding severities
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
@@ -0,0 +1,776 @@
+//! Proposal sanity scanning.
+//!
+//! Port of `agent/tasks/proposal_sanity_scanner.py`. The scanner runs five
+//! checks (size, code vulnerabilities, parameter ranges, author history and
+//! bytecode similarity), weighs every finding by severity and grades the
+//! proposal. Protocol parameters are [`Fixed`] decimals in whatever unit the
+//! protocol uses (a fee of 0.3% might be `"0.003"` or `3000` parts per
+//! million); the scanner only compares them with each other, so any
+//! consistent scale works. Scores, rates and thresholds are fractions from
+//! 0 to 1, and the percentages quoted in findings are rounded half up to
+//! one decimal. Maps are
+//! ordered by key, so findings for parameters and contracts are reported in
+//! key order rather than in the caller's insertion order.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::RiskLevel;
+use crate::fixed::{self, Fixed, Rounding};
+use crate::EnclaveError;
+
+/// Upper bound on the compiled size of a single vulnerability pattern
+const PATTERN_SIZE_LIMIT: usize = 1 << 20;
+
+/// Bytecode similarity is Jaccard similarity over overlapping chunks
+const SIMILARITY_CHUNK_SIZE: usize = 16;
+const SIMILARITY_CHUNK_STRIDE: usize = 4;
+
+/// Divisor turning the weighted issue count into a 0-1 risk score
+const RISK_SCALE_FACTOR: u64 = 10;
+
+/// Proposal under review
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ProposalData {
+    pub id: String,
+    pub calldata: String,
+    pub code: String,
+    pub signature: String,
+    pub author: Option<String>,
+    /// Proposed parameter values, in protocol units
+    pub parameters: BTreeMap<String, Fixed>,
+    /// Hex bytecode of a contract the proposal would deploy
+    pub bytecode: Option<String>,
+}
+
+/// Current value and accepted range of a protocol parameter
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ProtocolParameter {
+    pub current_value: Option<Fixed>,
+    /// Inclusive `[min, max]` range
+    pub safe_range: Option<(Fixed, Fixed)>,
+}
+
+/// Known vulnerability matched as a literal substring
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KnownVulnerability {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default = "default_vulnerability_severity")]
+    pub severity: RiskLevel,
+    #[serde(default)]
+    pub cve: Option<String>,
+    #[serde(default)]
+    pub mitigation: Option<String>,
+}
+
+fn default_vulnerability_severity() -> RiskLevel {
+    RiskLevel::High
+}
+
+/// On-chain history of a proposal author
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct AccountHistory {
+    pub age_in_blocks: u64,
+    pub proposals: Vec<PastProposal>,
+}
+
+/// Outcome of an earlier proposal by the same author
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PastProposal {
+    pub id: String,
+    pub status: String,
+}
+
+/// Tunables mirroring the Python task's default `parameters`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScannerConfig {
+    pub risk_threshold_high: Fixed,
+    pub risk_threshold_medium: Fixed,
+    pub skip_historical_check: bool,
+    pub check_bytecode_similarity: bool,
+    pub max_proposal_size_bytes: u64,
+    /// Case-insensitive regular expressions searched in code, calldata and signature
+    pub vulnerability_patterns: Vec<String>,
+    pub new_account_age_blocks: u64,
+    pub high_rejection_rate: Fixed,
+    /// Relative change from the current value that counts as large
+    pub large_change: Fixed,
+    pub high_similarity: Fixed,
+    pub moderate_similarity: Fixed,
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        Self {
+            risk_threshold_high: Fixed::new(7, 1),
+            risk_threshold_medium: Fixed::new(4, 1),
+            skip_historical_check: false,
+            check_bytecode_similarity: true,
+            max_proposal_size_bytes: 1024 * 1024,
+            vulnerability_patterns: vec![
+                "selfdestruct".to_string(),
+                "delegatecall".to_string(),
+                r"transfer.*\(address\([a-zA-Z0-9]*\)\)".to_string(),
+                r"approve\(address\([a-zA-Z0-9]*\), uint256\([0-9]+\)\)".to_string(),
+            ],
+            new_account_age_blocks: 1_000,
+            high_rejection_rate: Fixed::new(7, 1),
+            large_change: Fixed::new(5, 1),
+            high_similarity: Fixed::new(9, 1),
+            moderate_similarity: Fixed::new(7, 1),
+        }
+    }
+}
+
+/// Payload for the `scan_proposal` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProposalScanPayload {
+    pub proposal: ProposalData,
+    #[serde(default)]
+    pub protocol_parameters: BTreeMap<String, ProtocolParameter>,
+    #[serde(default)]
+    pub known_vulnerabilities: Vec<KnownVulnerability>,
+    /// Author histories keyed by address
+    #[serde(default)]
+    pub account_history: BTreeMap<String, AccountHistory>,
+    /// Hex bytecode of existing contracts keyed by name
+    #[serde(default)]
+    pub contract_bytecode: BTreeMap<String, String>,
+    #[serde(default)]
+    pub config: ScannerConfig,
+}
+
+/// Individual check run by the scanner
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanCheck {
+    ProposalSize,
+    CodeVulnerabilities,
+    Parameters,
+    AuthorHistory,
+    BytecodeSimilarity,
+}
+
+/// Category of a scanner finding
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    SizeLimit,
+    CodeVulnerability,
+    KnownVulnerability,
+    ParameterOutOfRange,
+    LargeParameterChange,
+    NewAccount,
+    HighRejectionRate,
+    HighBytecodeSimilarity,
+    ModerateBytecodeSimilarity,
+}
+
+/// Issue reported by a check
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub severity: RiskLevel,
+    pub description: String,
+    pub recommendation: Option<String>,
+    /// Text matched by a vulnerability pattern
+    pub matches: Vec<String>,
+    pub cve: Option<String>,
+}
+
+impl Finding {
+    fn new(
+        kind: FindingKind,
+        severity: RiskLevel,
+        description: String,
+        recommendation: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            severity,
+            description,
+            recommendation: Some(recommendation.into()),
+            matches: Vec::new(),
+            cve: None,
+        }
+    }
+}
+
+/// Outcome of one check
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub check: ScanCheck,
+    pub passed: bool,
+    pub findings: Vec<Finding>,
+}
+
+impl CheckResult {
+    fn from_findings(check: ScanCheck, findings: Vec<Finding>) -> Self {
+        Self {
+            check,
+            passed: findings.is_empty(),
+            findings,
+        }
+    }
+}
+
+/// Result of the `scan_proposal` operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProposalScanReport {
+    pub proposal_id: String,
+    pub risk_level: RiskLevel,
+    /// Weighted risk score from 0 to 1
+    pub risk_score: Fixed,
+    pub checks: Vec<CheckResult>,
+    pub checks_passed: u64,
+    pub checks_failed: u64,
+    pub recommendations: Vec<String>,
+}
+
+/// Run every scanner check against a proposal and grade it
+pub fn scan(payload: &ProposalScanPayload) -> Result<ProposalScanReport, EnclaveError> {
+    let config = &payload.config;
+    let patterns = compile_patterns(&config.vulnerability_patterns)?;
+    let proposal = &payload.proposal;
+
+    let checks = vec![
+        check_proposal_size(proposal, config),
+        check_code_vulnerabilities(proposal, &patterns, &payload.known_vulnerabilities),
+        validate_parameters(proposal, &payload.protocol_parameters, config)?,
+        check_author_history(proposal, &payload.account_history, config)?,
+        if config.check_bytecode_similarity {
+            check_bytecode_similarity(proposal, &payload.contract_bytecode, config)?
+        } else {
+            CheckResult::from_findings(ScanCheck::BytecodeSimilarity, Vec::new())
+        },
+    ];
+
+    let risk_score = risk_score(&checks)?;
+    let risk_level = if risk_score >= config.risk_threshold_high {
+        RiskLevel::High
+    } else if risk_score >= config.risk_threshold_medium {
+        RiskLevel::Medium
+    } else {
+        RiskLevel::Low
+    };
+    let checks_passed = checks.iter().filter(|check| check.passed).count() as u64;
+
+    Ok(ProposalScanReport {
+        proposal_id: proposal.id.clone(),
+        risk_level,
+        risk_score,
+        recommendations: recommendations(&checks, risk_level),
+        checks_failed: checks.len() as u64 - checks_passed,
+        checks_passed,
+        checks,
+    })
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Regex>, EnclaveError> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .size_limit(PATTERN_SIZE_LIMIT)
+                .build()
+                .map_err(|e| {
+                    EnclaveError::invalid_payload(format!(
+                        "invalid vulnerability pattern '{}': {}",
+                        pattern, e
+                    ))
+                })
+        })
+        .collect()
+}
+
+fn check_proposal_size(proposal: &ProposalData, config: &ScannerConfig) -> CheckResult {
+    let size = proposal.calldata.len() as u64;
+    let max_size = config.max_proposal_size_bytes;
+
+    let mut findings = Vec::new();
+    if size > max_size {
+        findings.push(Finding::new(
+            FindingKind::SizeLimit,
+            RiskLevel::Medium,
+            format!(
+                "Proposal size ({} bytes) exceeds maximum recommended size ({} bytes)",
+                size, max_size
+            ),
+            "Break down the proposal into smaller, separate proposals",
+        ));
+    }
+    CheckResult::from_findings(ScanCheck::ProposalSize, findings)
+}
+
+fn check_code_vulnerabilities(
+    proposal: &ProposalData,
+    patterns: &[Regex],
+    known_vulnerabilities: &[KnownVulnerability],
+) -> CheckResult {
+    let text = format!(
+        "{} {} {}",
+        proposal.code, proposal.calldata, proposal.signature
+    );
+
+    let mut findings = Vec::new();
+    for pattern in patterns {
+        let matches: Vec<String> = pattern
+            .find_iter(&text)
+            .map(|m| m.as_str().to_string())
+            .collect();
+        if !matches.is_empty() {
+            findings.push(Finding {
+                matches,
+                ..Finding::new(
+                    FindingKind::CodeVulnerability,
+                    RiskLevel::High,
+                    format!("Potential vulnerability detected: {}", pattern.as_str()),
+                    "Review and secure the code against this vulnerability",
+                )
+            });
+        }
+    }
+
+    for vulnerability in known_vulnerabilities {
+        if text.contains(&vulnerability.pattern) {
+            findings.push(Finding {
+                kind: FindingKind::KnownVulnerability,
+                severity: vulnerability.severity,
+                description: format!("Known vulnerability detected: {}", vulnerability.name),
+                recommendation: vulnerability.mitigation.clone(),
+                matches: Vec::new(),
+                cve: vulnerability.cve.clone(),
+            });
+        }
+    }
+
+    CheckResult::from_findings(ScanCheck::CodeVulnerabilities, findings)
+}
+
+fn validate_parameters(
+    proposal: &ProposalData,
+    protocol_parameters: &BTreeMap<String, ProtocolParameter>,
+    config: &ScannerConfig,
+) -> Result<CheckResult, EnclaveError> {
+    let mut findings = Vec::new();
+
+    for (name, &value) in &proposal.parameters {
+        let Some(parameter) = protocol_parameters.get(name) else {
+            continue;
+        };
+
+        if let Some((min, max)) = parameter.safe_range {
+            if value < min || value > max {
+                findings.push(Finding::new(
+                    FindingKind::ParameterOutOfRange,
+                    RiskLevel::Medium,
+                    format!(
+                        "Parameter '{}' value {} is outside safe range ({}, {})",
+                        name, value, min, max
+                    ),
+                    "Adjust parameter to be within the safe range",
+                ));
+            }
+        }
+
+        if let Some(current) = parameter.current_value.filter(|current| !current.is_zero()) {
+            let change = value
+                .checked_sub(current)?
+                .abs()
+                .div(current.abs(), Rounding::Down)?;
+            if change > config.large_change {
+                findings.push(Finding::new(
+                    FindingKind::LargeParameterChange,
+                    RiskLevel::Medium,
+                    format!(
+                        "Large change ({}%) for parameter '{}': {} -> {}",
+                        format_percent(change)?,
+                        name,
+                        current,
+                        value
+                    ),
+                    "Consider a more gradual parameter change",
+                ));
+            }
+        }
+    }
+
+    Ok(CheckResult::from_findings(ScanCheck::Parameters, findings))
+}
+
+fn check_author_history(
+    proposal: &ProposalData,
+    account_history: &BTreeMap<String, AccountHistory>,
+    config: &ScannerConfig,
+) -> Result<CheckResult, EnclaveError> {
+    let mut findings = Vec::new();
+    let author = match &proposal.author {
+        Some(author) if !config.skip_historical_check && !author.is_empty() => author,
+        _ => {
+            return Ok(CheckResult::from_findings(
+                ScanCheck::AuthorHistory,
+                findings,
+            ))
+        }
+    };
+    let history = account_history.get(author).cloned().unwrap_or_default();
+
+    if history.age_in_blocks < config.new_account_age_blocks {
+        findings.push(Finding::new(
+            FindingKind::NewAccount,
+            RiskLevel::Low,
+            format!(
+                "Proposal author account is relatively new ({} blocks old)",
+                history.age_in_blocks
+            ),
+            "Verify author's reputation in the community",
+        ));
+    }
+
+    let total = history.proposals.len() as u64;
+    let rejected = history
+        .proposals
+        .iter()
+        .filter(|proposal| proposal.status == "rejected")
+        .count() as u64;
+    if total > 0
+        && Fixed::from(rejected).div(total.into(), Rounding::Down)? > config.high_rejection_rate
+    {
+        findings.push(Finding::new(
+            FindingKind::HighRejectionRate,
+            RiskLevel::Medium,
+            format!(
+                "Author has high proposal rejection rate ({}/{})",
+                rejected, total
+            ),
+            "Review author's previous proposals to understand rejection patterns",
+        ));
+    }
+
+    Ok(CheckResult::from_findings(
+        ScanCheck::AuthorHistory,
+        findings,
+    ))
+}
+
+fn check_bytecode_similarity(
+    proposal: &ProposalData,
+    contract_bytecode: &BTreeMap<String, String>,
+    config: &ScannerConfig,
+) -> Result<CheckResult, EnclaveError> {
+    let mut findings = Vec::new();
+    let new_bytecode = match &proposal.bytecode {
+        Some(bytecode) if !bytecode.is_empty() => bytecode,
+        _ => {
+            return Ok(CheckResult::from_findings(
+                ScanCheck::BytecodeSimilarity,
+                findings,
+            ))
+        }
+    };
+
+    for (name, bytecode) in contract_bytecode {
+        let (shared, total) = bytecode_similarity(new_bytecode.as_bytes(), bytecode.as_bytes());
+        if total == 0 {
+            continue;
+        }
+        let similarity = Fixed::from(shared).div(total.into(), Rounding::Down)?;
+        let percent = format_percent(similarity)?;
+        if similarity > config.high_similarity {
+            findings.push(Finding::new(
+                FindingKind::HighBytecodeSimilarity,
+                RiskLevel::Medium,
+                format!(
+                    "New contract is very similar to existing contract '{}' ({}% match)",
+                    name, percent
+                ),
+                "Verify that this is not a duplicate or malicious variation of an existing contract",
+            ));
+        } else if similarity > config.moderate_similarity {
+            findings.push(Finding::new(
+                FindingKind::ModerateBytecodeSimilarity,
+                RiskLevel::Low,
+                format!(
+                    "New contract has similarities with existing contract '{}' ({}% match)",
+                    name, percent
+                ),
+                "Review the contract code to understand the similarities",
+            ));
+        }
+    }
+
+    Ok(CheckResult::from_findings(
+        ScanCheck::BytecodeSimilarity,
+        findings,
+    ))
+}
+
+/// Jaccard similarity of two byte strings as `(intersection, union)` chunk counts
+fn bytecode_similarity(a: &[u8], b: &[u8]) -> (u64, u64) {
+    fn chunks(bytes: &[u8]) -> BTreeSet<&[u8]> {
+        if bytes.len() < SIMILARITY_CHUNK_SIZE {
+            return BTreeSet::new();
+        }
+        (0..=bytes.len() - SIMILARITY_CHUNK_SIZE)
+            .step_by(SIMILARITY_CHUNK_STRIDE)
+            .map(|i| &bytes[i..i + SIMILARITY_CHUNK_SIZE])
+            .collect()
+    }
+
+    let (a, b) = (chunks(a), chunks(b));
+    (
+        a.intersection(&b).count() as u64,
+        a.union(&b).count() as u64,
+    )
+}
+
+/// Weighted issue count scaled to a score capped at 1
+///
+/// High findings weigh 1.0, medium 0.5 and low 0.2; ten high findings
+/// saturate the score.
+fn risk_score(checks: &[CheckResult]) -> Result<Fixed, EnclaveError> {
+    let weights: Vec<Fixed> = checks
+        .iter()
+        .flat_map(|check| &check.findings)
+        .map(|finding| match finding.severity {
+            RiskLevel::High => Fixed::ONE,
+            RiskLevel::Medium => Fixed::new(5, 1),
+            RiskLevel::Low => Fixed::new(2, 1),
+        })
+        .collect();
+    Ok(fixed::sum(&weights)?
+        .div(RISK_SCALE_FACTOR.into(), Rounding::Down)?
+        .min(Fixed::ONE))
+}
+
+fn recommendations(checks: &[CheckResult], risk_level: RiskLevel) -> Vec<String> {
+    let mut recommendations: Vec<String> = checks
+        .iter()
+        .flat_map(|check| &check.findings)
+        .filter_map(|finding| finding.recommendation.clone())
+        .collect();
+
+    match risk_level {
+        RiskLevel::High => {
+            recommendations.push(
+                "Consider rejecting this proposal until security issues are addressed".to_string(),
+            );
+            recommendations.push("Request a formal security audit for this proposal".to_string());
+        }
+        RiskLevel::Medium => {
+            recommendations.push(
+                "Request more documentation and justification for the proposed changes".to_string(),
+            );
+            recommendations
+                .push("Consider a peer review by at least two community members".to_string());
+        }
+        RiskLevel::Low if recommendations.is_empty() => {
+            recommendations.push(
+                "No significant issues found. Standard review procedures recommended.".to_string(),
+            );
+        }
+        RiskLevel::Low => {}
+    }
+
+    let mut unique = Vec::with_capacity(recommendations.len());
+    for recommendation in recommendations {
+        if !unique.contains(&recommendation) {
+            unique.push(recommendation);
+        }
+    }
+    unique
+}
+
+/// Render a ratio as a percentage with one decimal, as Python's `:.1f`
+fn format_percent(ratio: Fixed) -> Result<String, EnclaveError> {
+    let percent = ratio
+        .mul(100.into(), Rounding::HalfUp)?
+        .round(1, Rounding::HalfUp)?
+        .to_string();
+    Ok(if percent.contains('.') {
+        percent
+    } else {
+        format!("{}.0", percent)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(s: &str) -> Fixed {
+        s.parse().unwrap()
+    }
+
+    const BYTECODE: &str = "6080604052348015600f57600080fd5b506004361060285760003560e01c8063a9059cbb14602d575b600080fd";
+
+    /// Python reference: the same proposal scored 0.42 ("medium") with
+    /// checks_passed == 1 and seven issues.
+    fn risky_payload() -> ProposalScanPayload {
+        ProposalScanPayload {
+            proposal: ProposalData {
+                id: "p-42".to_string(),
+                calldata: format!("0x{}", "ab".repeat(10)),
+                code: "function drain() { selfdestruct(owner); target.delegatecall(data); x.call.value(1)(); }".to_string(),
+                signature: "drain()".to_string(),
+                author: Some("0xabc".to_string()),
+                parameters: BTreeMap::from([
+                    ("feePercentage".to_string(), 20_000.into()),
+                    ("maxSlippage".to_string(), 6_000.into()),
+                ]),
+                bytecode: Some(BYTECODE.to_string()),
+            },
+            protocol_parameters: BTreeMap::from([
+                (
+                    "feePercentage".to_string(),
+                    ProtocolParameter {
+                        current_value: Some(2_000.into()),
+                        safe_range: Some((100.into(), 10_000.into())),
+                    },
+                ),
+                (
+                    "maxSlippage".to_string(),
+                    ProtocolParameter {
+                        current_value: Some(5_000.into()),
+                        safe_range: Some((1_000.into(), 50_000.into())),
+                    },
+                ),
+            ]),
+            known_vulnerabilities: vec![KnownVulnerability {
+                name: "Reentrancy".to_string(),
+                pattern: r"call.value\(".to_string(),
+                severity: RiskLevel::High,
+                cve: Some("CVE-2018-12056".to_string()),
+                mitigation: Some("Use ReentrancyGuard".to_string()),
+            }],
+            account_history: BTreeMap::from([(
+                "0xabc".to_string(),
+                AccountHistory {
+                    age_in_blocks: 500,
+                    proposals: ["rejected", "rejected", "rejected", "accepted"]
+                        .iter()
+                        .map(|status| PastProposal {
+                            id: String::new(),
+                            status: status.to_string(),
+                        })
+                        .collect(),
+                },
+            )]),
+            contract_bytecode: BTreeMap::from([("Token".to_string(), BYTECODE.to_string())]),
+            config: ScannerConfig::default(),
+        }
+    }
+
+    #[test]
+    fn test_scan_matches_python_reference() {
+        let report = scan(&risky_payload()).unwrap();
+
+        assert_eq!(report.risk_score, Fixed::new(42, 2));
+        assert_eq!(report.risk_level, RiskLevel::Medium);
+        assert_eq!(report.checks_passed, 1);
+        assert_eq!(report.checks_failed, 4);
+
+        let kinds: Vec<FindingKind> = report
+            .checks
+            .iter()
+            .flat_map(|check| check.findings.iter().map(|finding| finding.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::CodeVulnerability,
+                FindingKind::CodeVulnerability,
+                FindingKind::ParameterOutOfRange,
+                FindingKind::LargeParameterChange,
+                FindingKind::NewAccount,
+                FindingKind::HighRejectionRate,
+                FindingKind::HighBytecodeSimilarity,
+            ]
+        );
+        assert_eq!(
+            report.checks[2].findings[1].description,
+            "Large change (900.0%) for parameter 'feePercentage': 2000 -> 20000"
+        );
+        assert_eq!(report.recommendations.len(), 8);
+    }
+
+    #[test]
+    fn test_clean_proposal_is_low_risk() {
+        let payload = ProposalScanPayload {
+            proposal: ProposalData {
+                id: "p-1".to_string(),
+                code: "function setFee(uint256 fee) public onlyOwner { feePercentage = fee; }"
+                    .to_string(),
+                ..ProposalData::default()
+            },
+            protocol_parameters: BTreeMap::new(),
+            known_vulnerabilities: Vec::new(),
+            account_history: BTreeMap::new(),
+            contract_bytecode: BTreeMap::new(),
+            config: ScannerConfig::default(),
+        };
+
+        let report = scan(&payload).unwrap();
+        assert_eq!(report.risk_score, Fixed::ZERO);
+        assert_eq!(report.risk_level, RiskLevel::Low);
+        assert_eq!(report.checks_passed, 5);
+        assert_eq!(
+            report.recommendations,
+            vec!["No significant issues found. Standard review procedures recommended."]
+        );
+    }
+
+    #[test]
+    fn test_custom_patterns_and_thresholds() {
+        let mut payload = risky_payload();
+        payload.config.vulnerability_patterns = vec!["CALL\\.VALUE".to_string()];
+        payload.config.risk_threshold_medium = Fixed::new(5, 1);
+
+        let report = scan(&payload).unwrap();
+        assert_eq!(report.checks[1].findings[0].matches, vec!["call.value"]);
+        assert_eq!(report.risk_level, RiskLevel::Low);
+
+        payload.config.vulnerability_patterns = vec!["(unclosed".to_string()];
+        assert!(matches!(
+            scan(&payload),
+            Err(EnclaveError::InvalidPayload { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fractional_parameters() {
+        let mut payload = risky_payload();
+        payload.proposal.parameters =
+            BTreeMap::from([("feePercentage".to_string(), fixed("0.0031"))]);
+        payload.protocol_parameters = BTreeMap::from([(
+            "feePercentage".to_string(),
+            ProtocolParameter {
+                current_value: Some(fixed("0.002")),
+                safe_range: Some((fixed("0.001"), fixed("0.003"))),
+            },
+        )]);
+
+        let report = scan(&payload).unwrap();
+        let descriptions: Vec<&str> = report.checks[2]
+            .findings
+            .iter()
+            .map(|finding| finding.description.as_str())
+            .collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                "Parameter 'feePercentage' value 0.0031 is outside safe range (0.001, 0.003)",
+                "Large change (55.0%) for parameter 'feePercentage': 0.002 -> 0.0031",
+            ]
+        );
+    }
+}
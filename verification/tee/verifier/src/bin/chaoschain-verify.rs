@@ -0,0 +1,120 @@
+//! ChaosChain quote verifier
+//!
+//! ```text
+//! chaoschain-verify --root <root.pem> --collateral <collateral.json>
+//!     [--policy <policy.json>] [--deployment-policy <policy.toml|json>]
+//! ```
+//!
+//! Reads `{"quote": "<hex>", "output": <EnclaveOutput, optional>,
+//! "signed_output": <SignedEnclaveOutput, optional>, "attested_at": <unix
+//! seconds, optional>}` from stdin and writes
+//! `{"verified": true, "quote": <VerifiedQuote>}` or
+//! `{"verified": false, "error": <VerifyError>}` to stdout. With a
+//! deployment policy, the response also carries its `"policy"` report and
+//! `verified` is true only if the policy passed; the quote policy then
+//! defaults to permissive. Rejected quotes still exit with status 0; status
+//! 2 means the invocation itself was bad.
+
+use std::io::Read;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use chaoschain_enclave::{EnclaveOutput, SignedEnclaveOutput};
+use chaoschain_verifier::{now, Collateral, Evidence, Policy, QuotePolicy, Verifier};
+
+#[derive(Deserialize)]
+struct Request {
+    quote: String,
+    #[serde(default)]
+    output: Option<EnclaveOutput>,
+    #[serde(default)]
+    signed_output: Option<SignedEnclaveOutput>,
+    #[serde(default)]
+    attested_at: Option<u64>,
+}
+
+fn main() {
+    let (verifier, deployment_policy) = configure().unwrap_or_else(|error| fail(&error));
+
+    let mut input = String::new();
+    if let Err(error) = std::io::stdin().read_to_string(&mut input) {
+        fail(&format!("failed to read stdin: {}", error));
+    }
+    let request: Request = serde_json::from_str(&input)
+        .unwrap_or_else(|error| fail(&format!("invalid request: {}", error)));
+    let quote = hex::decode(request.quote.strip_prefix("0x").unwrap_or(&request.quote))
+        .unwrap_or_else(|error| fail(&format!("quote is not hex: {}", error)));
+
+    let now = now();
+    let result = match (&request.output, &request.signed_output) {
+        (Some(_), Some(_)) => fail("give either output or signed_output"),
+        (Some(output), None) => verifier.verify_output(&quote, output, now),
+        (None, Some(signed)) => verifier.verify_signed_output(&quote, signed, now),
+        (None, None) => verifier.verify(&quote, now),
+    };
+    let output = request
+        .output
+        .as_ref()
+        .or(request.signed_output.as_ref().map(|signed| &signed.output));
+    let response = match (result, deployment_policy) {
+        (Ok(verified), Some(policy)) => {
+            let evidence = Evidence {
+                quote: &verified,
+                output,
+                attested_at: request.attested_at.map(Duration::from_secs),
+            };
+            let report = policy.evaluate(&evidence, now);
+            json!({ "verified": report.passed, "quote": verified, "policy": report })
+        }
+        (Ok(verified), None) => json!({ "verified": true, "quote": verified }),
+        (Err(error), _) => {
+            json!({ "verified": false, "error": error, "message": error.to_string() })
+        }
+    };
+    println!("{}", response);
+}
+
+fn configure() -> Result<(Verifier, Option<Policy>), String> {
+    let (mut root, mut collateral, mut policy) = (None, None, None);
+    let mut deployment_policy = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        if flag == "--deployment-policy" {
+            deployment_policy = Some(Policy::load(&value).map_err(|error| error.to_string())?);
+            continue;
+        }
+        let contents = std::fs::read(&value)
+            .map_err(|error| format!("failed to read {}: {}", value, error))?;
+        match flag.as_str() {
+            "--root" => root = Some(contents),
+            "--collateral" => collateral = Some(contents),
+            "--policy" => policy = Some(contents),
+            _ => return Err(format!("unknown argument {}", flag)),
+        }
+    }
+
+    let root = root.ok_or("--root is required")?;
+    let root = String::from_utf8(root).map_err(|_| "root certificate is not PEM".to_string())?;
+    let collateral = Collateral::from_json(&collateral.ok_or("--collateral is required")?)
+        .map_err(|error| error.to_string())?;
+    let policy: QuotePolicy = match policy {
+        Some(policy) => {
+            serde_json::from_slice(&policy).map_err(|error| format!("invalid policy: {}", error))?
+        }
+        None if deployment_policy.is_some() => QuotePolicy::permissive(),
+        None => QuotePolicy::default(),
+    };
+    let verifier =
+        Verifier::from_pem(&root, collateral, policy).map_err(|error| error.to_string())?;
+    Ok((verifier, deployment_policy))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("chaoschain-verify: {}", message);
+    std::process::exit(2);
+}
@@ -0,0 +1,132 @@
+//! PCK certificate chain validation.
+//!
+//! Quotes carry their certification chain as concatenated PEM, leaf first:
+//! the PCK certificate, the platform (or processor) CA, and usually the root.
+//! The chain is accepted when every certificate is signed by the next one,
+//! all intermediates are CAs, every certificate is within its validity
+//! period, and the chain ends at (or is issued directly by) the trusted
+//! root. Only ECDSA P-256 with SHA-256 is accepted, which is all Intel's SGX
+//! PKI uses.
+//!
+//! Revocation is not checked here: CRLs belong in the collateral bundle and
+//! are left to a later change.
+
+use std::time::Duration;
+
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{DerSignature, VerifyingKey};
+use x509_cert::der::oid::ObjectIdentifier;
+use x509_cert::der::{DecodePem, Encode};
+use x509_cert::ext::pkix::BasicConstraints;
+use x509_cert::Certificate;
+
+use crate::error::VerifyError;
+
+/// Signature algorithm of every certificate in an SGX PCK chain
+pub const ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+
+/// Parse a PEM certificate to trust as the chain root
+pub fn parse_root(pem: &str) -> Result<Certificate, VerifyError> {
+    Certificate::from_pem(pem).map_err(|e| chain_error(format!("trusted root: {}", e)))
+}
+
+/// Validate `chain_pem` at time `now` (since the Unix epoch) and return its
+/// leaf certificate
+pub fn verify_chain(
+    chain_pem: &[u8],
+    trusted_root: &Certificate,
+    now: Duration,
+) -> Result<Certificate, VerifyError> {
+    let mut chain = Certificate::load_pem_chain(chain_pem).map_err(chain_error)?;
+    if chain.is_empty() {
+        return Err(chain_error("certificate chain is empty"));
+    }
+
+    let ends_at_root = chain.last().map(der_bytes).transpose()? == Some(der_bytes(trusted_root)?);
+    if !ends_at_root {
+        chain.push(trusted_root.clone());
+    }
+
+    for certificate in &chain {
+        check_validity(certificate, now)?;
+    }
+    for pair in chain.windows(2) {
+        let (certificate, issuer) = (&pair[0], &pair[1]);
+        if certificate.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+            return Err(chain_error(format!(
+                "{} is not issued by {}",
+                certificate.tbs_certificate.subject, issuer.tbs_certificate.subject
+            )));
+        }
+        check_is_ca(issuer)?;
+        check_signed_by(certificate, issuer)?;
+    }
+
+    Ok(chain.swap_remove(0))
+}
+
+/// Uncompressed SEC1 P-256 key of a certificate's subject
+pub fn public_key(certificate: &Certificate) -> Result<VerifyingKey, VerifyError> {
+    let key = &certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .subject_public_key;
+    VerifyingKey::from_sec1_bytes(key.raw_bytes()).map_err(|_| {
+        chain_error(format!(
+            "{} does not hold a P-256 key",
+            certificate.tbs_certificate.subject
+        ))
+    })
+}
+
+fn check_validity(certificate: &Certificate, now: Duration) -> Result<(), VerifyError> {
+    let validity = &certificate.tbs_certificate.validity;
+    if now < validity.not_before.to_unix_duration() || now > validity.not_after.to_unix_duration() {
+        return Err(chain_error(format!(
+            "{} is not valid at the verification time",
+            certificate.tbs_certificate.subject
+        )));
+    }
+    Ok(())
+}
+
+fn check_is_ca(certificate: &Certificate) -> Result<(), VerifyError> {
+    match certificate.tbs_certificate.get::<BasicConstraints>() {
+        Ok(Some((_, constraints))) if constraints.ca => Ok(()),
+        _ => Err(chain_error(format!(
+            "{} is not a CA",
+            certificate.tbs_certificate.subject
+        ))),
+    }
+}
+
+fn check_signed_by(certificate: &Certificate, issuer: &Certificate) -> Result<(), VerifyError> {
+    let subject = &certificate.tbs_certificate.subject;
+    if certificate.signature_algorithm.oid != ECDSA_WITH_SHA256 {
+        return Err(chain_error(format!(
+            "{} is not signed with ECDSA P-256/SHA-256",
+            subject
+        )));
+    }
+
+    let signature = certificate
+        .signature
+        .as_bytes()
+        .and_then(|bytes| DerSignature::try_from(bytes).ok())
+        .ok_or_else(|| chain_error(format!("{} has a malformed signature", subject)))?;
+    let tbs = certificate.tbs_certificate.to_der().map_err(chain_error)?;
+
+    public_key(issuer)?
+        .verify(&tbs, &signature)
+        .map_err(|_| chain_error(format!("signature on {} does not verify", subject)))
+}
+
+fn der_bytes(certificate: &Certificate) -> Result<Vec<u8>, VerifyError> {
+    certificate.to_der().map_err(chain_error)
+}
+
+fn chain_error(reason: impl ToString) -> VerifyError {
+    VerifyError::CertificateChain {
+        reason: reason.to_string(),
+    }
+}
@@ -0,0 +1,267 @@
+//! Locally supplied verification collateral.
+//!
+//! Intel publishes, per platform family (FMSPC), a TCB info document listing
+//! TCB levels and their status, and a QE identity describing the genuine
+//! quoting enclave. A [`Collateral`] bundle holds both in Intel's v3 JSON
+//! layout (the `tcbInfo` and `enclaveIdentity` bodies, without the envelope
+//! signatures), so bundles can be fetched once from the PCCS, reviewed, and
+//! shipped alongside the verifier; nothing is fetched at verification time.
+//!
+//! The bundle is trusted as supplied: whoever provides it decides which TCB
+//! levels are acceptable, so it must come from the same trusted source as
+//! the root certificate.
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::VerifyError;
+use crate::pck::PckInfo;
+
+/// TCB info and QE identity used to appraise a quote
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Collateral {
+    pub tcb_info: TcbInfo,
+    #[serde(rename = "enclaveIdentity")]
+    pub qe_identity: QeIdentity,
+}
+
+/// TCB levels of one platform family
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbInfo {
+    pub version: u32,
+    pub issue_date: String,
+    pub next_update: String,
+    #[serde(with = "crate::serde_hex")]
+    pub fmspc: [u8; 6],
+    #[serde(with = "crate::serde_hex")]
+    pub pce_id: [u8; 2],
+    /// Levels in descending order, as Intel publishes them
+    pub tcb_levels: Vec<TcbLevel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TcbLevel {
+    pub tcb: Tcb,
+    pub tcb_date: String,
+    pub tcb_status: TcbStatus,
+    #[serde(default, rename = "advisoryIDs")]
+    pub advisory_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tcb {
+    pub sgxtcbcomponents: Vec<TcbComponent>,
+    pub pcesvn: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcbComponent {
+    pub svn: u8,
+}
+
+/// Identity of the genuine quoting enclave
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QeIdentity {
+    pub version: u32,
+    pub issue_date: String,
+    pub next_update: String,
+    #[serde(with = "crate::serde_hex")]
+    pub mrsigner: [u8; 32],
+    pub isvprodid: u16,
+    /// Levels in descending ISV SVN order
+    pub tcb_levels: Vec<QeTcbLevel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QeTcbLevel {
+    pub tcb: QeTcb,
+    pub tcb_date: String,
+    pub tcb_status: TcbStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QeTcb {
+    pub isvsvn: u16,
+}
+
+/// Status Intel assigns to a TCB level
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TcbStatus {
+    UpToDate,
+    #[serde(rename = "SWHardeningNeeded")]
+    SwHardeningNeeded,
+    ConfigurationNeeded,
+    #[serde(rename = "ConfigurationAndSWHardeningNeeded")]
+    ConfigurationAndSwHardeningNeeded,
+    OutOfDate,
+    OutOfDateConfigurationNeeded,
+    Revoked,
+}
+
+impl fmt::Display for TcbStatus {
+    /// Intel's name for the status
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TcbStatus::UpToDate => "UpToDate",
+            TcbStatus::SwHardeningNeeded => "SWHardeningNeeded",
+            TcbStatus::ConfigurationNeeded => "ConfigurationNeeded",
+            TcbStatus::ConfigurationAndSwHardeningNeeded => "ConfigurationAndSWHardeningNeeded",
+            TcbStatus::OutOfDate => "OutOfDate",
+            TcbStatus::OutOfDateConfigurationNeeded => "OutOfDateConfigurationNeeded",
+            TcbStatus::Revoked => "Revoked",
+        })
+    }
+}
+
+impl Collateral {
+    /// Parse a bundle from JSON
+    pub fn from_json(json: &[u8]) -> Result<Self, VerifyError> {
+        serde_json::from_slice(json).map_err(collateral_error)
+    }
+
+    /// Check the bundle is current at `now` and describes `pck`'s platform
+    pub fn check_applies(&self, pck: &PckInfo, now: Duration) -> Result<(), VerifyError> {
+        for (what, next_update) in [
+            ("TCB info", &self.tcb_info.next_update),
+            ("QE identity", &self.qe_identity.next_update),
+        ] {
+            if now > parse_timestamp(next_update)? {
+                return Err(collateral_error(format!(
+                    "{} expired at {}",
+                    what, next_update
+                )));
+            }
+        }
+        if self.tcb_info.fmspc != pck.fmspc || self.tcb_info.pce_id != pck.pce_id {
+            return Err(collateral_error(format!(
+                "TCB info is for FMSPC {} PCE ID {}, platform is FMSPC {} PCE ID {}",
+                hex::encode(self.tcb_info.fmspc),
+                hex::encode(self.tcb_info.pce_id),
+                hex::encode(pck.fmspc),
+                hex::encode(pck.pce_id)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Highest TCB level the platform meets
+    pub fn platform_tcb_level(&self, pck: &PckInfo) -> Result<&TcbLevel, VerifyError> {
+        self.tcb_info
+            .tcb_levels
+            .iter()
+            .find(|level| {
+                level.tcb.sgxtcbcomponents.len() == pck.tcb_components.len()
+                    && level
+                        .tcb
+                        .sgxtcbcomponents
+                        .iter()
+                        .zip(pck.tcb_components)
+                        .all(|(required, actual)| actual >= required.svn)
+                    && pck.pce_svn >= level.tcb.pcesvn
+            })
+            .ok_or(VerifyError::TcbLevelNotFound)
+    }
+
+    /// Highest QE TCB level the quoting enclave at `isv_svn` meets
+    pub fn qe_tcb_level(&self, isv_svn: u16) -> Result<&QeTcbLevel, VerifyError> {
+        self.qe_identity
+            .tcb_levels
+            .iter()
+            .find(|level| isv_svn >= level.tcb.isvsvn)
+            .ok_or(VerifyError::TcbLevelNotFound)
+    }
+}
+
+/// Seconds since the Unix epoch of an RFC 3339 UTC timestamp such as
+/// `2024-05-01T00:00:00Z`, the only form Intel's collateral uses
+pub fn parse_timestamp(timestamp: &str) -> Result<Duration, VerifyError> {
+    let invalid = || collateral_error(format!("invalid timestamp {:?}", timestamp));
+    let bytes = timestamp.as_bytes();
+    if bytes.len() != 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || bytes[10] != b'T'
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+        || bytes[19] != b'Z'
+    {
+        return Err(invalid());
+    }
+    let field = |range: std::ops::Range<usize>| -> Result<i64, VerifyError> {
+        let digits = &timestamp[range];
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        digits.parse().map_err(|_| invalid())
+    };
+
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(invalid());
+    }
+
+    // Days from civil, Howard Hinnant's algorithm
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    u64::try_from(seconds)
+        .map(Duration::from_secs)
+        .map_err(|_| invalid())
+}
+
+fn collateral_error(reason: impl ToString) -> VerifyError {
+    VerifyError::Collateral {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp("1970-01-01T00:00:00Z").unwrap().as_secs(),
+            0
+        );
+        assert_eq!(
+            parse_timestamp("2020-01-01T00:00:00Z").unwrap().as_secs(),
+            1_577_836_800
+        );
+        assert_eq!(
+            parse_timestamp("2049-12-31T23:59:59Z").unwrap().as_secs(),
+            2_524_607_999
+        );
+        assert!(parse_timestamp("2024-13-01T00:00:00Z").is_err());
+        assert!(parse_timestamp("2024-01-01 00:00:00").is_err());
+    }
+
+    #[test]
+    fn test_tcb_status_names() {
+        let status: TcbStatus = serde_json::from_str("\"SWHardeningNeeded\"").unwrap();
+        assert_eq!(status, TcbStatus::SwHardeningNeeded);
+        assert_eq!(
+            serde_json::to_string(&TcbStatus::OutOfDate).unwrap(),
+            "\"OutOfDate\""
+        );
+    }
+}
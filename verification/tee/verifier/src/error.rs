@@ -0,0 +1,76 @@
+//! Reasons attestation evidence is rejected.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Why a quote failed verification
+///
+/// Serializes as an object tagged by `kind`, e.g.
+/// `{"kind": "invalid_signature", "signature": "qe_report"}`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VerifyError {
+    /// The quote bytes do not decode as a DCAP quote
+    MalformedQuote { reason: String },
+    /// The quote decodes but uses a format this verifier does not handle
+    UnsupportedQuote { reason: String },
+    /// The PCK certificate chain does not lead to the trusted root
+    CertificateChain { reason: String },
+    /// The PCK certificate lacks a well-formed SGX extension
+    PckExtension { reason: String },
+    /// A signature in the quote does not verify
+    InvalidSignature { signature: String },
+    /// The QE report does not commit to the attestation key
+    AttestationKeyNotBound,
+    /// The collateral bundle is malformed, stale or for another platform
+    Collateral { reason: String },
+    /// No TCB level in the collateral matches the platform
+    TcbLevelNotFound,
+    /// The report data does not commit to the expected output
+    OutputNotBound,
+    /// The report data does not commit to the identity that signed the output
+    SignerNotBound,
+    /// The signed output does not verify under its signer
+    InvalidOutputSignature { reason: String },
+    /// Verification succeeded but the quote is not acceptable under policy
+    PolicyViolation { reason: String },
+    /// A policy file could not be loaded
+    InvalidPolicy { reason: String },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::MalformedQuote { reason } => write!(f, "malformed quote: {}", reason),
+            VerifyError::UnsupportedQuote { reason } => write!(f, "unsupported quote: {}", reason),
+            VerifyError::CertificateChain { reason } => {
+                write!(f, "invalid PCK certificate chain: {}", reason)
+            }
+            VerifyError::PckExtension { reason } => {
+                write!(f, "invalid PCK SGX extension: {}", reason)
+            }
+            VerifyError::InvalidSignature { signature } => {
+                write!(f, "{} signature does not verify", signature)
+            }
+            VerifyError::AttestationKeyNotBound => {
+                write!(f, "QE report data does not commit to the attestation key")
+            }
+            VerifyError::Collateral { reason } => write!(f, "unusable collateral: {}", reason),
+            VerifyError::TcbLevelNotFound => write!(f, "no TCB level matches the platform"),
+            VerifyError::OutputNotBound => {
+                write!(f, "report data does not commit to the expected output")
+            }
+            VerifyError::SignerNotBound => {
+                write!(f, "report data does not commit to the output's signer")
+            }
+            VerifyError::InvalidOutputSignature { reason } => {
+                write!(f, "signed output does not verify: {}", reason)
+            }
+            VerifyError::PolicyViolation { reason } => write!(f, "policy violation: {}", reason),
+            VerifyError::InvalidPolicy { reason } => write!(f, "invalid policy: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
@@ -0,0 +1,49 @@
+//! ChaosChain attestation verifier
+//!
+//! Offline verification of the DCAP quotes produced for ChaosChain enclaves,
+//! usable anywhere outside the enclave: the API gateway, auditors, tests.
+//! Nothing is fetched over the network. The caller supplies:
+//!
+//! - the root certificate to trust (Intel's SGX root CA in production, or
+//!   the simulated quote generator's test root in development);
+//! - a [`Collateral`] bundle with the platform's TCB info and the quoting
+//!   enclave's identity;
+//! - a [`QuotePolicy`] saying which enclaves are acceptable.
+//!
+//! [`Verifier::verify`] checks the quote's certificate chain and signatures,
+//! maps the platform to a TCB status, and enforces the policy, returning a
+//! [`VerifiedQuote`] or the [`VerifyError`] that rejected it.
+//! [`Verifier::verify_output`] additionally checks that the quote's report
+//! data commits to a given [`chaoschain_enclave::EnclaveOutput`], and
+//! [`Verifier::verify_signed_output`] that it commits to the enclave identity
+//! that signed a [`chaoschain_enclave::SignedEnclaveOutput`].
+//!
+//! Deployments with their own trust requirements express them as a
+//! [`Policy`], loaded from TOML or JSON and evaluated against verified
+//! evidence into a [`PolicyReport`] tracing every check.
+//!
+//! The `chaoschain-verify` binary wraps the same checks behind a JSON
+//! stdin/stdout interface for callers outside Rust.
+
+pub mod chain;
+pub mod collateral;
+pub mod error;
+pub mod pck;
+pub mod policy;
+mod serde_hex;
+pub mod verify;
+
+pub use collateral::{Collateral, TcbStatus};
+pub use error::VerifyError;
+pub use pck::PckInfo;
+pub use policy::{Evidence, Policy, PolicyReport};
+pub use verify::{QuotePolicy, VerifiedQuote, Verifier};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Current time as used for certificate and collateral validity
+pub fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
@@ -0,0 +1,201 @@
+//! Platform details carried in a PCK certificate's SGX extension.
+//!
+//! Intel encodes them as a DER `SEQUENCE` of `(OID, value)` pairs under
+//! `1.2.840.113741.1.13.1`: the PPID, the platform's TCB (sixteen CPU
+//! component SVNs, the PCE SVN and the raw CPUSVN), the PCE ID and the
+//! FMSPC identifying the platform family. The TCB recorded here is what the
+//! collateral's TCB levels are matched against.
+
+use serde::Serialize;
+use x509_cert::der::oid::ObjectIdentifier;
+use x509_cert::Certificate;
+
+use crate::error::VerifyError;
+
+/// OID of the SGX extension
+pub const SGX_EXTENSION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1");
+
+const PPID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.1");
+const TCB: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.2");
+const PCE_ID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.3");
+const FMSPC: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.4");
+const PCESVN: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.2.17");
+const CPUSVN: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.2.18");
+
+const SEQUENCE: u8 = 0x30;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+
+/// Platform identity and TCB certified by a PCK certificate
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PckInfo {
+    #[serde(serialize_with = "crate::serde_hex::serialize")]
+    pub ppid: [u8; 16],
+    /// SVNs of the sixteen SGX TCB components
+    pub tcb_components: [u8; 16],
+    pub pce_svn: u16,
+    #[serde(serialize_with = "crate::serde_hex::serialize")]
+    pub cpu_svn: [u8; 16],
+    #[serde(serialize_with = "crate::serde_hex::serialize")]
+    pub pce_id: [u8; 2],
+    #[serde(serialize_with = "crate::serde_hex::serialize")]
+    pub fmspc: [u8; 6],
+}
+
+impl PckInfo {
+    /// Read the SGX extension of `certificate`
+    pub fn from_certificate(certificate: &Certificate) -> Result<Self, VerifyError> {
+        let extension = certificate
+            .tbs_certificate
+            .extensions
+            .iter()
+            .flatten()
+            .find(|extension| extension.extn_id == SGX_EXTENSION)
+            .ok_or_else(|| pck_error("certificate has no SGX extension"))?;
+        Self::from_der(extension.extn_value.as_bytes())
+    }
+
+    /// Decode the DER value of the SGX extension
+    pub fn from_der(der: &[u8]) -> Result<Self, VerifyError> {
+        let mut input = der;
+        let mut fields = expect(&mut input, SEQUENCE)?;
+
+        let (mut ppid, mut tcb, mut pce_id, mut fmspc) = (None, None, None, None);
+        for (oid, tag, value) in pairs(&mut fields)? {
+            match oid {
+                PPID if tag == OCTET_STRING => ppid = Some(fixed(value)?),
+                TCB if tag == SEQUENCE => tcb = Some(value),
+                PCE_ID if tag == OCTET_STRING => pce_id = Some(fixed(value)?),
+                FMSPC if tag == OCTET_STRING => fmspc = Some(fixed(value)?),
+                _ => {}
+            }
+        }
+
+        let mut tcb = tcb.ok_or_else(|| pck_error("missing TCB"))?;
+        let mut tcb_components = [None; 16];
+        let (mut pce_svn, mut cpu_svn) = (None, None);
+        for (oid, tag, value) in pairs(&mut tcb)? {
+            match (oid, tag) {
+                (PCESVN, INTEGER) => pce_svn = Some(integer(value)?),
+                (CPUSVN, OCTET_STRING) => cpu_svn = Some(fixed(value)?),
+                (oid, INTEGER) if oid.parent() == Some(TCB) => {
+                    let arc = oid.arcs().last().unwrap_or(0) as usize;
+                    if (1..=16).contains(&arc) {
+                        let svn = integer(value)?;
+                        tcb_components[arc - 1] =
+                            Some(u8::try_from(svn).map_err(|_| pck_error("SVN out of range"))?);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut components = [0u8; 16];
+        for (slot, component) in components.iter_mut().zip(tcb_components) {
+            *slot = component.ok_or_else(|| pck_error("missing TCB component SVN"))?;
+        }
+
+        Ok(Self {
+            ppid: ppid.ok_or_else(|| pck_error("missing PPID"))?,
+            tcb_components: components,
+            pce_svn: pce_svn.ok_or_else(|| pck_error("missing PCESVN"))?,
+            cpu_svn: cpu_svn.ok_or_else(|| pck_error("missing CPUSVN"))?,
+            pce_id: pce_id.ok_or_else(|| pck_error("missing PCE ID"))?,
+            fmspc: fmspc.ok_or_else(|| pck_error("missing FMSPC"))?,
+        })
+    }
+}
+
+/// `(OID, value tag, value)` of one extension field
+type Field<'a> = (ObjectIdentifier, u8, &'a [u8]);
+
+/// Every `SEQUENCE { OID, value }` in `input`
+fn pairs<'a>(input: &mut &'a [u8]) -> Result<Vec<Field<'a>>, VerifyError> {
+    let mut pairs = Vec::new();
+    while !input.is_empty() {
+        let mut pair = expect(input, SEQUENCE)?;
+        let oid = ObjectIdentifier::from_bytes(expect(&mut pair, OID)?)
+            .map_err(|_| pck_error("malformed OID"))?;
+        let (tag, value) = tlv(&mut pair)?;
+        pairs.push((oid, tag, value));
+    }
+    Ok(pairs)
+}
+
+fn expect<'a>(input: &mut &'a [u8], expected: u8) -> Result<&'a [u8], VerifyError> {
+    match tlv(input)? {
+        (tag, value) if tag == expected => Ok(value),
+        (tag, _) => Err(pck_error(format!(
+            "expected tag {:#04x}, found {:#04x}",
+            expected, tag
+        ))),
+    }
+}
+
+fn tlv<'a>(input: &mut &'a [u8]) -> Result<(u8, &'a [u8]), VerifyError> {
+    let truncated = || pck_error("truncated DER");
+    let (&tag, rest) = input.split_first().ok_or_else(truncated)?;
+    let (&first, mut rest) = rest.split_first().ok_or_else(truncated)?;
+
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err(pck_error("unsupported DER length"));
+        }
+        let (bytes, tail) = rest.split_at(count);
+        rest = tail;
+        bytes.iter().fold(0usize, |len, &b| (len << 8) | b as usize)
+    };
+
+    if rest.len() < len {
+        return Err(truncated());
+    }
+    let (value, tail) = rest.split_at(len);
+    *input = tail;
+    Ok((tag, value))
+}
+
+fn integer(value: &[u8]) -> Result<u16, VerifyError> {
+    match value {
+        [b] if b & 0x80 == 0 => Ok(*b as u16),
+        [0, b] if b & 0x80 != 0 => Ok(*b as u16),
+        [hi, lo] if hi & 0x80 == 0 && *hi != 0 => Ok(u16::from_be_bytes([*hi, *lo])),
+        [0, hi, lo] if hi & 0x80 != 0 => Ok(u16::from_be_bytes([*hi, *lo])),
+        _ => Err(pck_error("INTEGER is not a 16-bit SVN")),
+    }
+}
+
+fn fixed<const N: usize>(value: &[u8]) -> Result<[u8; N], VerifyError> {
+    value
+        .try_into()
+        .map_err(|_| pck_error(format!("expected {} bytes, found {}", N, value.len())))
+}
+
+fn pck_error(reason: impl Into<String>) -> VerifyError {
+    VerifyError::PckExtension {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_encodings() {
+        assert_eq!(integer(&[0x0d]).unwrap(), 13);
+        assert_eq!(integer(&[0x00, 0xff]).unwrap(), 255);
+        assert_eq!(integer(&[0x01, 0x00]).unwrap(), 256);
+        assert!(integer(&[0xff]).is_err());
+        assert!(integer(&[0x00, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_truncated_extension_is_rejected() {
+        assert!(PckInfo::from_der(&[0x30, 0x05, 0x30]).is_err());
+        assert!(PckInfo::from_der(&[0x30, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+//! Deployment trust policies.
+//!
+//! A [`QuotePolicy`](crate::QuotePolicy) answers "is this a genuine enclave";
+//! a [`Policy`] answers "is this enclave, and this output, good enough for a
+//! given deployment". Each DAO we serve gets its own policy file, in TOML or
+//! JSON:
+//!
+//! ```toml
+//! name = "example-dao-production"
+//! allow_debug = false
+//! min_isv_svn = 2
+//! allowed_tcb_statuses = ["UpToDate", "SWHardeningNeeded"]
+//! max_attestation_age_secs = 86400
+//!
+//! [[measurements]]
+//! name = "v1.4.0"
+//! mr_enclave = "6e71f4ba..."
+//! mr_signer = "38f5e0f5..."
+//!
+//! [operations.simulate_fork]
+//! min_version = 1
+//! ```
+//!
+//! [`Policy::evaluate`] runs every check, without stopping at the first
+//! failure, and returns a [`PolicyReport`] tracing each one.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use chaoschain_enclave::EnclaveOutput;
+
+use crate::collateral::TcbStatus;
+use crate::error::VerifyError;
+use crate::verify::VerifiedQuote;
+
+/// Trust requirements of one deployment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub name: String,
+    /// Enclave builds accepted; at least one must match. Empty accepts none.
+    #[serde(default)]
+    pub measurements: Vec<MeasurementSet>,
+    #[serde(default)]
+    pub min_isv_svn: u16,
+    #[serde(default)]
+    pub allow_debug: bool,
+    /// Platform and QE TCB statuses accepted
+    #[serde(default = "default_tcb_statuses")]
+    pub allowed_tcb_statuses: Vec<TcbStatus>,
+    /// Oldest acceptable attestation, in seconds; unlimited if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attestation_age_secs: Option<u64>,
+    /// Requirements on the operation that produced the attested output
+    #[serde(default)]
+    pub operations: BTreeMap<String, OperationRequirement>,
+    /// Reject outputs of operations not listed in `operations`
+    #[serde(default)]
+    pub require_listed_operations: bool,
+}
+
+/// One accepted enclave build
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MeasurementSet {
+    pub name: String,
+    #[serde(with = "crate::serde_hex")]
+    pub mr_enclave: [u8; 32],
+    #[serde(with = "crate::serde_hex")]
+    pub mr_signer: [u8; 32],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isv_prod_id: Option<u16>,
+}
+
+/// Constraint on one registered operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct OperationRequirement {
+    #[serde(default)]
+    pub min_version: u32,
+    /// Exact code measurement required, if pinned
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "optional_hex"
+    )]
+    pub measurement: Option<[u8; 32]>,
+}
+
+fn default_tcb_statuses() -> Vec<TcbStatus> {
+    vec![TcbStatus::UpToDate]
+}
+
+/// Attested facts a policy is evaluated against
+#[derive(Debug, Clone, Copy)]
+pub struct Evidence<'a> {
+    pub quote: &'a VerifiedQuote,
+    /// Output the quote was verified to be bound to
+    pub output: Option<&'a EnclaveOutput>,
+    /// When the quote was produced, since the Unix epoch
+    pub attested_at: Option<Duration>,
+}
+
+/// Outcome of one policy check
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub check: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Outcome of evaluating a policy
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyReport {
+    pub policy: String,
+    pub passed: bool,
+    pub checks: Vec<CheckResult>,
+}
+
+impl PolicyReport {
+    /// Checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+}
+
+impl Policy {
+    pub fn from_toml(toml: &str) -> Result<Self, VerifyError> {
+        toml::from_str(toml).map_err(invalid_policy)
+    }
+
+    pub fn from_json(json: &[u8]) -> Result<Self, VerifyError> {
+        serde_json::from_slice(json).map_err(invalid_policy)
+    }
+
+    /// Load a policy file, choosing the format by its `.toml` or `.json`
+    /// extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, VerifyError> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)
+            .map_err(|e| invalid_policy(format!("{}: {}", path.display(), e)))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => {
+                let contents = String::from_utf8(contents).map_err(invalid_policy)?;
+                Self::from_toml(&contents)
+            }
+            Some("json") => Self::from_json(&contents),
+            _ => Err(invalid_policy(format!(
+                "{}: expected a .toml or .json file",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Evaluate every check against `evidence` at time `now`
+    pub fn evaluate(&self, evidence: &Evidence<'_>, now: Duration) -> PolicyReport {
+        let report = &evidence.quote.report;
+        let mut checks = Vec::new();
+        let mut check = |check: &'static str, passed: bool, detail: String| {
+            checks.push(CheckResult {
+                check,
+                passed,
+                detail,
+            })
+        };
+
+        let matched = self.measurements.iter().find(|set| {
+            set.mr_enclave == report.mr_enclave
+                && set.mr_signer == report.mr_signer
+                && set.isv_prod_id.is_none_or(|id| id == report.isv_prod_id)
+        });
+        check(
+            "measurement",
+            matched.is_some(),
+            match matched {
+                Some(set) => format!("matches {}", set.name),
+                None => format!(
+                    "MRENCLAVE {} / MRSIGNER {} matches no allowed measurement set",
+                    hex::encode(report.mr_enclave),
+                    hex::encode(report.mr_signer)
+                ),
+            },
+        );
+
+        check(
+            "isv_svn",
+            report.isv_svn >= self.min_isv_svn,
+            format!("ISV SVN {}, minimum {}", report.isv_svn, self.min_isv_svn),
+        );
+
+        let debug = report.attributes.is_debug();
+        check(
+            "debug",
+            !debug || self.allow_debug,
+            match (debug, self.allow_debug) {
+                (false, _) => "production enclave".to_string(),
+                (true, true) => "debug enclave, allowed".to_string(),
+                (true, false) => "debug enclave".to_string(),
+            },
+        );
+
+        for (name, status) in [
+            ("platform_tcb", evidence.quote.tcb_status),
+            ("qe_tcb", evidence.quote.qe_tcb_status),
+        ] {
+            check(
+                name,
+                self.allowed_tcb_statuses.contains(&status),
+                status.to_string(),
+            );
+        }
+
+        if let Some(max_age) = self.max_attestation_age_secs {
+            match evidence.attested_at {
+                Some(attested_at) => {
+                    let age = now.saturating_sub(attested_at).as_secs();
+                    check(
+                        "attestation_age",
+                        age <= max_age && attested_at <= now,
+                        if attested_at > now {
+                            "attested in the future".to_string()
+                        } else {
+                            format!("{}s old, maximum {}s", age, max_age)
+                        },
+                    );
+                }
+                None => check("attestation_age", false, "attestation time unknown".into()),
+            }
+        }
+
+        if !self.operations.is_empty() || self.require_listed_operations {
+            let (passed, detail) = self.check_operation(evidence.output);
+            check("operation", passed, detail);
+        }
+
+        PolicyReport {
+            policy: self.name.clone(),
+            passed: checks.iter().all(|check| check.passed),
+            checks,
+        }
+    }
+
+    fn check_operation(&self, output: Option<&EnclaveOutput>) -> (bool, String) {
+        let Some(operation) = output.and_then(|output| output.operation.as_ref()) else {
+            return (false, "no attested output names its operation".to_string());
+        };
+
+        match self.operations.get(&operation.name) {
+            Some(requirement) => {
+                if operation.version < requirement.min_version {
+                    return (
+                        false,
+                        format!(
+                            "{} version {}, minimum {}",
+                            operation.name, operation.version, requirement.min_version
+                        ),
+                    );
+                }
+                if let Some(measurement) = requirement.measurement {
+                    if operation.measurement != measurement {
+                        return (
+                            false,
+                            format!(
+                                "{} measurement {} is not the pinned {}",
+                                operation.name,
+                                hex::encode(operation.measurement),
+                                hex::encode(measurement)
+                            ),
+                        );
+                    }
+                }
+                (
+                    true,
+                    format!("{} version {}", operation.name, operation.version),
+                )
+            }
+            None if self.require_listed_operations => (
+                false,
+                format!("{} is not an allowed operation", operation.name),
+            ),
+            None => (true, format!("{} is unconstrained", operation.name)),
+        }
+    }
+}
+
+mod optional_hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<[u8; 32]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(bytes) => crate::serde_hex::serialize(bytes, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<[u8; 32]>, D::Error> {
+        #[derive(Deserialize)]
+        struct Hex(#[serde(with = "crate::serde_hex")] [u8; 32]);
+
+        Ok(Option::<Hex>::deserialize(deserializer)?.map(|Hex(bytes)| bytes))
+    }
+}
+
+fn invalid_policy(reason: impl ToString) -> VerifyError {
+    VerifyError::InvalidPolicy {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Collateral, QuotePolicy, Verifier};
+
+    const ROOT: &str = include_str!("../tests/fixtures/simulated_root.pem");
+    const QUOTE: &str = include_str!("../tests/fixtures/simulated_quote.hex");
+    const OUTPUT: &str = include_str!("../tests/fixtures/simulated_output.json");
+    const COLLATERAL: &str = include_str!("../tests/fixtures/simulated_collateral.json");
+    const POLICY: &str = include_str!("../tests/fixtures/simulated_policy.toml");
+
+    /// 2030-01-01T00:00:00Z
+    const NOW: Duration = Duration::from_secs(1_893_456_000);
+
+    fn verified() -> (VerifiedQuote, EnclaveOutput) {
+        let collateral = Collateral::from_json(COLLATERAL.as_bytes()).unwrap();
+        let verifier = Verifier::from_pem(ROOT, collateral, QuotePolicy::permissive()).unwrap();
+        let output: EnclaveOutput = serde_json::from_str(OUTPUT).unwrap();
+        let quote = hex::decode(QUOTE.trim()).unwrap();
+        (
+            verifier.verify_output(&quote, &output, NOW).unwrap(),
+            output,
+        )
+    }
+
+    fn failed_checks(report: &PolicyReport) -> Vec<&'static str> {
+        report.failures().map(|check| check.check).collect()
+    }
+
+    #[test]
+    fn test_fixture_policy_passes() {
+        let (quote, output) = verified();
+        let evidence = Evidence {
+            quote: &quote,
+            output: Some(&output),
+            attested_at: Some(NOW - Duration::from_secs(60)),
+        };
+        let report = Policy::from_toml(POLICY).unwrap().evaluate(&evidence, NOW);
+
+        assert!(report.passed, "{:?}", report);
+        assert_eq!(report.checks.len(), 7);
+        assert_eq!(report.checks[0].detail, "matches simulated");
+    }
+
+    #[test]
+    fn test_every_failure_is_traced() {
+        let (quote, output) = verified();
+        let mut policy = Policy::from_toml(POLICY).unwrap();
+        policy.measurements.clear();
+        policy.allow_debug = false;
+        policy.min_isv_svn = 2;
+        policy.operations.get_mut("add").unwrap().min_version = 3;
+        let evidence = Evidence {
+            quote: &quote,
+            output: Some(&output),
+            attested_at: Some(NOW - Duration::from_secs(7200)),
+        };
+
+        let report = policy.evaluate(&evidence, NOW);
+        assert!(!report.passed);
+        assert_eq!(
+            failed_checks(&report),
+            vec![
+                "measurement",
+                "isv_svn",
+                "debug",
+                "attestation_age",
+                "operation"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operation_requirements() {
+        let (quote, output) = verified();
+        let mut policy = Policy::from_toml(POLICY).unwrap();
+        policy.max_attestation_age_secs = None;
+        let evidence = |output| Evidence {
+            quote: &quote,
+            output,
+            attested_at: None,
+        };
+
+        assert_eq!(
+            failed_checks(&policy.evaluate(&evidence(None), NOW)),
+            vec!["operation"]
+        );
+
+        policy.operations.clear();
+        policy.require_listed_operations = true;
+        assert_eq!(
+            failed_checks(&policy.evaluate(&evidence(Some(&output)), NOW)),
+            vec!["operation"]
+        );
+
+        policy.operations.insert(
+            "add".to_string(),
+            OperationRequirement {
+                min_version: 1,
+                measurement: output.operation.as_ref().map(|op| op.measurement),
+            },
+        );
+        assert!(policy.evaluate(&evidence(Some(&output)), NOW).passed);
+    }
+
+    #[test]
+    fn test_json_and_toml_agree() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+        let json = serde_json::to_vec(&policy).unwrap();
+
+        assert_eq!(Policy::from_json(&json).unwrap(), policy);
+        assert!(Policy::from_toml("name = \"x\"\nunknown = 1").is_err());
+    }
+}
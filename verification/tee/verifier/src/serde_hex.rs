@@ -0,0 +1,61 @@
+//! Serde helpers encoding byte strings as lowercase hex.
+//!
+//! Same encoding as the enclave's: `0x` prefixes are accepted on input and
+//! never produced. [`list`] handles sequences of fixed-size digests.
+
+use hex::FromHex;
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]>,
+    S: Serializer,
+{
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromHex,
+    <T as FromHex>::Error: std::fmt::Display,
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    decode(&s).map_err(serde::de::Error::custom)
+}
+
+fn decode<T>(s: &str) -> Result<T, T::Error>
+where
+    T: FromHex,
+{
+    T::from_hex(s.strip_prefix("0x").unwrap_or(s))
+}
+
+pub mod list {
+    use super::*;
+    use serde::ser::SerializeSeq;
+
+    pub fn serialize<T, S>(items: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for item in items {
+            seq.serialize_element(&hex::encode(item))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: FromHex,
+        <T as FromHex>::Error: std::fmt::Display,
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| decode(s).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
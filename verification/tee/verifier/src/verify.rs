@@ -0,0 +1,503 @@
+//! Quote verification.
+//!
+//! [`Verifier::verify`] runs the DCAP checks in order, stopping at the first
+//! failure:
+//!
+//! 1. the quote decodes as a version 3 ECDSA-P256 SGX quote from Intel's QE;
+//! 2. its PCK certificate chain leads to the trusted root;
+//! 3. the PCK key signed the QE report, and the QE report commits to the
+//!    attestation key;
+//! 4. the attestation key signed the header and enclave report;
+//! 5. the collateral is current and describes this platform, the QE matches
+//!    the collateral's QE identity, and both the platform and QE TCB map to
+//!    a level with a known status;
+//! 6. the enclave report satisfies the [`QuotePolicy`].
+
+use std::time::Duration;
+
+use p256::ecdsa::signature::Verifier as _;
+use p256::ecdsa::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use x509_cert::Certificate;
+
+use chaoschain_enclave::attestation::{
+    AttestationReport, Quote, CERT_DATA_PCK_CERT_CHAIN, INTEL_QE_VENDOR_ID, TEE_TYPE_SGX,
+};
+use chaoschain_enclave::{EnclaveOutput, SignedEnclaveOutput};
+
+use crate::chain::{parse_root, public_key, verify_chain};
+use crate::collateral::{Collateral, TcbStatus};
+use crate::error::VerifyError;
+use crate::pck::PckInfo;
+
+/// Requirements on the attested enclave itself
+///
+/// Empty measurement lists accept any value.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuotePolicy {
+    #[serde(default, with = "crate::serde_hex::list")]
+    pub mr_enclave: Vec<[u8; 32]>,
+    #[serde(default, with = "crate::serde_hex::list")]
+    pub mr_signer: Vec<[u8; 32]>,
+    #[serde(default)]
+    pub isv_prod_id: Option<u16>,
+    #[serde(default)]
+    pub min_isv_svn: u16,
+    /// Accept enclaves launched in debug mode, whose memory the host can read
+    #[serde(default)]
+    pub allow_debug: bool,
+    /// Platform and QE TCB statuses to accept
+    #[serde(default = "default_tcb_statuses")]
+    pub allowed_tcb_statuses: Vec<TcbStatus>,
+}
+
+impl Default for QuotePolicy {
+    fn default() -> Self {
+        Self {
+            mr_enclave: Vec::new(),
+            mr_signer: Vec::new(),
+            isv_prod_id: None,
+            min_isv_svn: 0,
+            allow_debug: false,
+            allowed_tcb_statuses: default_tcb_statuses(),
+        }
+    }
+}
+
+impl QuotePolicy {
+    /// Accept any genuine enclave, leaving appraisal to a deployment
+    /// [`Policy`](crate::Policy)
+    pub fn permissive() -> Self {
+        Self {
+            allow_debug: true,
+            allowed_tcb_statuses: vec![
+                TcbStatus::UpToDate,
+                TcbStatus::SwHardeningNeeded,
+                TcbStatus::ConfigurationNeeded,
+                TcbStatus::ConfigurationAndSwHardeningNeeded,
+                TcbStatus::OutOfDate,
+                TcbStatus::OutOfDateConfigurationNeeded,
+                TcbStatus::Revoked,
+            ],
+            ..Self::default()
+        }
+    }
+}
+
+fn default_tcb_statuses() -> Vec<TcbStatus> {
+    vec![TcbStatus::UpToDate]
+}
+
+/// What a successfully verified quote attests to
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedQuote {
+    /// The attested enclave's report
+    pub report: AttestationReport,
+    /// Platform identity and TCB from the PCK certificate
+    pub pck: PckInfo,
+    pub tcb_status: TcbStatus,
+    pub qe_tcb_status: TcbStatus,
+    pub advisory_ids: Vec<String>,
+}
+
+/// Verifies quotes against a trusted root, collateral and policy
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    root: Certificate,
+    collateral: Collateral,
+    policy: QuotePolicy,
+}
+
+impl Verifier {
+    /// Trust quotes certified by `root` as appraised by `collateral`
+    pub fn new(root: Certificate, collateral: Collateral, policy: QuotePolicy) -> Self {
+        Self {
+            root,
+            collateral,
+            policy,
+        }
+    }
+
+    /// Like [`Verifier::new`], with the root given as PEM
+    pub fn from_pem(
+        root_pem: &str,
+        collateral: Collateral,
+        policy: QuotePolicy,
+    ) -> Result<Self, VerifyError> {
+        Ok(Self::new(parse_root(root_pem)?, collateral, policy))
+    }
+
+    pub fn policy(&self) -> &QuotePolicy {
+        &self.policy
+    }
+
+    /// Verify `quote` at time `now` (since the Unix epoch)
+    pub fn verify(&self, quote: &[u8], now: Duration) -> Result<VerifiedQuote, VerifyError> {
+        let quote = Quote::from_bytes(quote).map_err(|error| VerifyError::MalformedQuote {
+            reason: error.to_string(),
+        })?;
+        check_header(&quote)?;
+
+        let certification = &quote.signature.certification_data;
+        if certification.cert_type != CERT_DATA_PCK_CERT_CHAIN {
+            return Err(unsupported(format!(
+                "certification data type {}",
+                certification.cert_type
+            )));
+        }
+        let pck_certificate = verify_chain(&certification.data, &self.root, now)?;
+        let pck = PckInfo::from_certificate(&pck_certificate)?;
+
+        let signature = &quote.signature;
+        check_signature(
+            &public_key(&pck_certificate)?,
+            &signature.qe_report.to_bytes(),
+            &signature.qe_report_signature,
+            "qe_report",
+        )?;
+        if signature.qe_report.report_data
+            != Quote::expected_qe_report_data(&signature.attestation_key, &signature.qe_auth_data)
+        {
+            return Err(VerifyError::AttestationKeyNotBound);
+        }
+        check_signature(
+            &attestation_key(&signature.attestation_key)?,
+            &quote.signed_bytes(),
+            &signature.report_signature,
+            "report",
+        )?;
+
+        let collateral = &self.collateral;
+        collateral.check_applies(&pck, now)?;
+        let qe_identity = &collateral.qe_identity;
+        if signature.qe_report.mr_signer != qe_identity.mrsigner
+            || signature.qe_report.isv_prod_id != qe_identity.isvprodid
+        {
+            return Err(VerifyError::Collateral {
+                reason: "quoting enclave does not match the QE identity".to_string(),
+            });
+        }
+        let platform_level = collateral.platform_tcb_level(&pck)?;
+        let qe_level = collateral.qe_tcb_level(signature.qe_report.isv_svn)?;
+
+        let verified = VerifiedQuote {
+            report: quote.report,
+            pck,
+            tcb_status: platform_level.tcb_status,
+            qe_tcb_status: qe_level.tcb_status,
+            advisory_ids: platform_level.advisory_ids.clone(),
+        };
+        self.check_policy(&verified)?;
+        Ok(verified)
+    }
+
+    /// Verify `quote` and check that it attests to `output`
+    pub fn verify_output(
+        &self,
+        quote: &[u8],
+        output: &EnclaveOutput,
+        now: Duration,
+    ) -> Result<VerifiedQuote, VerifyError> {
+        let verified = self.verify(quote, now)?;
+        if !verified.report.binds_output(output) {
+            return Err(VerifyError::OutputNotBound);
+        }
+        Ok(verified)
+    }
+
+    /// Verify `quote` and check that it binds the identity that signed
+    /// `output`, and that the signature holds
+    pub fn verify_signed_output(
+        &self,
+        quote: &[u8],
+        output: &SignedEnclaveOutput,
+        now: Duration,
+    ) -> Result<VerifiedQuote, VerifyError> {
+        let verified = self.verify(quote, now)?;
+        if !verified.report.binds_identity(&output.signer) {
+            return Err(VerifyError::SignerNotBound);
+        }
+        output
+            .verify()
+            .map_err(|e| VerifyError::InvalidOutputSignature {
+                reason: e.to_string(),
+            })?;
+        Ok(verified)
+    }
+
+    fn check_policy(&self, verified: &VerifiedQuote) -> Result<(), VerifyError> {
+        let policy = &self.policy;
+        let report = &verified.report;
+
+        if !policy.mr_enclave.is_empty() && !policy.mr_enclave.contains(&report.mr_enclave) {
+            return Err(violation(format!(
+                "MRENCLAVE {} is not allowed",
+                hex::encode(report.mr_enclave)
+            )));
+        }
+        if !policy.mr_signer.is_empty() && !policy.mr_signer.contains(&report.mr_signer) {
+            return Err(violation(format!(
+                "MRSIGNER {} is not allowed",
+                hex::encode(report.mr_signer)
+            )));
+        }
+        if let Some(isv_prod_id) = policy.isv_prod_id {
+            if report.isv_prod_id != isv_prod_id {
+                return Err(violation(format!(
+                    "ISV product ID {} is not {}",
+                    report.isv_prod_id, isv_prod_id
+                )));
+            }
+        }
+        if report.isv_svn < policy.min_isv_svn {
+            return Err(violation(format!(
+                "ISV SVN {} is below {}",
+                report.isv_svn, policy.min_isv_svn
+            )));
+        }
+        if report.attributes.is_debug() && !policy.allow_debug {
+            return Err(violation("enclave runs in debug mode"));
+        }
+        for (what, status) in [
+            ("platform", verified.tcb_status),
+            ("QE", verified.qe_tcb_status),
+        ] {
+            if !policy.allowed_tcb_statuses.contains(&status) {
+                return Err(violation(format!("{} TCB status is {}", what, status)));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_header(quote: &Quote) -> Result<(), VerifyError> {
+    if quote.header.tee_type != TEE_TYPE_SGX {
+        return Err(unsupported(format!("TEE type {}", quote.header.tee_type)));
+    }
+    if quote.header.qe_vendor_id != INTEL_QE_VENDOR_ID {
+        return Err(unsupported(format!(
+            "QE vendor {}",
+            hex::encode(quote.header.qe_vendor_id)
+        )));
+    }
+    Ok(())
+}
+
+fn attestation_key(raw: &[u8; 64]) -> Result<VerifyingKey, VerifyError> {
+    let mut sec1 = [0u8; 65];
+    sec1[0] = 0x04;
+    sec1[1..].copy_from_slice(raw);
+    VerifyingKey::from_sec1_bytes(&sec1).map_err(|_| VerifyError::MalformedQuote {
+        reason: "attestation key is not a P-256 point".to_string(),
+    })
+}
+
+fn check_signature(
+    key: &VerifyingKey,
+    message: &[u8],
+    signature: &[u8; 64],
+    what: &str,
+) -> Result<(), VerifyError> {
+    let invalid = || VerifyError::InvalidSignature {
+        signature: what.to_string(),
+    };
+    let signature = Signature::from_slice(signature).map_err(|_| invalid())?;
+    key.verify(message, &signature).map_err(|_| invalid())
+}
+
+fn unsupported(reason: impl Into<String>) -> VerifyError {
+    VerifyError::UnsupportedQuote {
+        reason: reason.into(),
+    }
+}
+
+fn violation(reason: impl Into<String>) -> VerifyError {
+    VerifyError::PolicyViolation {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Produced by `SimulatedQuoteGenerator::from_seed([7; 32])` in the host
+    // crate, quoting the output of `add` with a = 2, b = 3.
+    const ROOT: &str = include_str!("../tests/fixtures/simulated_root.pem");
+    const QUOTE: &str = include_str!("../tests/fixtures/simulated_quote.hex");
+    const OUTPUT: &str = include_str!("../tests/fixtures/simulated_output.json");
+    const COLLATERAL: &str = include_str!("../tests/fixtures/simulated_collateral.json");
+    // Same generator, quoting the identity of `EnclaveSigner::from_secret`
+    // with an ed25519 secret of [9; 32], which signed the output of `add`
+    // with a = 2, b = 3.
+    const IDENTITY_QUOTE: &str = include_str!("../tests/fixtures/simulated_identity_quote.hex");
+    const SIGNED_OUTPUT: &str = include_str!("../tests/fixtures/simulated_signed_output.json");
+
+    /// 2030-01-01T00:00:00Z
+    const NOW: Duration = Duration::from_secs(1_893_456_000);
+
+    fn quote() -> Vec<u8> {
+        hex::decode(QUOTE.trim()).unwrap()
+    }
+
+    fn collateral() -> Collateral {
+        Collateral::from_json(COLLATERAL.as_bytes()).unwrap()
+    }
+
+    fn verifier(policy: QuotePolicy) -> Verifier {
+        Verifier::from_pem(ROOT, collateral(), policy).unwrap()
+    }
+
+    fn debug_policy() -> QuotePolicy {
+        QuotePolicy {
+            allow_debug: true,
+            ..QuotePolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_verifies_fixture_quote() {
+        let output: EnclaveOutput = serde_json::from_str(OUTPUT).unwrap();
+        let verified = verifier(debug_policy())
+            .verify_output(&quote(), &output, NOW)
+            .unwrap();
+
+        assert_eq!(verified.tcb_status, TcbStatus::UpToDate);
+        assert_eq!(verified.qe_tcb_status, TcbStatus::UpToDate);
+        assert_eq!(verified.pck.fmspc, [0x00, 0x90, 0x6e, 0xa1, 0x00, 0x00]);
+        assert_eq!(verified.pck.pce_svn, 13);
+    }
+
+    #[test]
+    fn test_rejects_other_output() {
+        let mut output: EnclaveOutput = serde_json::from_str(OUTPUT).unwrap();
+        output.version += 1;
+
+        assert_eq!(
+            verifier(debug_policy()).verify_output(&quote(), &output, NOW),
+            Err(VerifyError::OutputNotBound)
+        );
+    }
+
+    #[test]
+    fn test_verifies_signed_output() {
+        let identity_quote = hex::decode(IDENTITY_QUOTE.trim()).unwrap();
+        let signed: SignedEnclaveOutput = serde_json::from_str(SIGNED_OUTPUT).unwrap();
+        let verifier = verifier(debug_policy());
+        assert!(verifier
+            .verify_signed_output(&identity_quote, &signed, NOW)
+            .is_ok());
+
+        let mut forged = signed.clone();
+        forged.timestamp += 1;
+        assert!(matches!(
+            verifier.verify_signed_output(&identity_quote, &forged, NOW),
+            Err(VerifyError::InvalidOutputSignature { .. })
+        ));
+
+        let unbound = verifier.verify_signed_output(&quote(), &signed, NOW);
+        assert_eq!(unbound, Err(VerifyError::SignerNotBound));
+    }
+
+    #[test]
+    fn test_rejects_tampered_report() {
+        let mut quote = quote();
+        // First byte of the report data
+        quote[48 + 320] ^= 1;
+
+        assert_eq!(
+            verifier(debug_policy()).verify(&quote, NOW),
+            Err(VerifyError::InvalidSignature {
+                signature: "report".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_untrusted_root() {
+        // The fixture chain ends at its own root, so trusting a different
+        // certificate (here, the fixture PCK's issuer) must fail.
+        let chain = Quote::from_bytes(&quote())
+            .unwrap()
+            .signature
+            .certification_data
+            .data;
+        let certificates = Certificate::load_pem_chain(&chain).unwrap();
+        let verifier = Verifier::new(certificates[1].clone(), collateral(), debug_policy());
+
+        assert!(matches!(
+            verifier.verify(&quote(), NOW),
+            Err(VerifyError::CertificateChain { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_expired_collateral() {
+        let after_expiry = Duration::from_secs(2_524_608_000);
+        assert!(matches!(
+            verifier(debug_policy()).verify(&quote(), after_expiry),
+            Err(VerifyError::CertificateChain { .. } | VerifyError::Collateral { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tcb_status_from_collateral() {
+        let mut collateral = collateral();
+        collateral.tcb_info.tcb_levels.remove(0);
+        let verifier = Verifier::from_pem(ROOT, collateral, debug_policy()).unwrap();
+
+        assert!(matches!(
+            verifier.verify(&quote(), NOW),
+            Err(VerifyError::PolicyViolation { .. })
+        ));
+
+        let mut collateral = verifier.collateral.clone();
+        let policy = QuotePolicy {
+            allowed_tcb_statuses: vec![TcbStatus::UpToDate, TcbStatus::OutOfDate],
+            ..debug_policy()
+        };
+        let verified = Verifier::from_pem(ROOT, collateral.clone(), policy)
+            .unwrap()
+            .verify(&quote(), NOW)
+            .unwrap();
+        assert_eq!(verified.tcb_status, TcbStatus::OutOfDate);
+        assert_eq!(verified.advisory_ids, vec!["INTEL-SA-00000"]);
+
+        collateral.tcb_info.tcb_levels.clear();
+        assert_eq!(
+            Verifier::from_pem(ROOT, collateral, debug_policy())
+                .unwrap()
+                .verify(&quote(), NOW),
+            Err(VerifyError::TcbLevelNotFound)
+        );
+    }
+
+    #[test]
+    fn test_policy_checks_report() {
+        assert_eq!(
+            verifier(QuotePolicy::default()).verify(&quote(), NOW),
+            Err(VerifyError::PolicyViolation {
+                reason: "enclave runs in debug mode".to_string()
+            })
+        );
+
+        let report = Quote::from_bytes(&quote()).unwrap().report;
+        let policy = QuotePolicy {
+            mr_enclave: vec![report.mr_enclave],
+            mr_signer: vec![report.mr_signer],
+            isv_prod_id: Some(report.isv_prod_id),
+            min_isv_svn: report.isv_svn,
+            ..debug_policy()
+        };
+        assert!(verifier(policy.clone()).verify(&quote(), NOW).is_ok());
+
+        let policy = QuotePolicy {
+            mr_enclave: vec![[0; 32]],
+            ..policy
+        };
+        assert!(matches!(
+            verifier(policy).verify(&quote(), NOW),
+            Err(VerifyError::PolicyViolation { .. })
+        ));
+    }
+}